@@ -0,0 +1,867 @@
+mod runtime;
+#[cfg(test)]
+mod test;
+
+use cb_parse::{
+    Ast, Atom, Call, CallId, Expr, ExprId, Loop, LoopId, LoopKind, Op, SideTable, Stmt, Symbol,
+};
+use cb_prelude::Intrinsic;
+use cb_typeck::Type;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+type CResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+const INDENT: &str = "    ";
+
+/// Transpiles a program into a single C99 translation unit, given the types
+/// that [`cb_typeck::check`] found for its expressions. The generated
+/// `main` prints the value of every top level expression on its own line.
+/// Of the prelude, `print` and `println` of integers and string literals,
+/// `exit` and `assert` are supported. Strings are handled by the functions
+/// of the [`runtime`] module, which is included as far as it is used.
+///
+/// Variables hold numbers or strings, and each `let` declares a C variable
+/// of its own, named after it and numbered so that shadowing needs no
+/// nested scopes. Loops become C loops, and a `break` or `continue` that
+/// leaves more than the innermost one becomes a `goto`. A function bound by
+/// a top level `let` becomes a `static` C function, as long as it only
+/// reads its parameters and other such functions.
+pub fn compile(ast: &Ast, types: &SideTable<ExprId, Type>) -> CResult<String> {
+    let mut c = CodeGen {
+        ast,
+        types,
+        out: String::new(),
+        depth: 1,
+        exits: false,
+        helpers: BTreeSet::new(),
+        temps: vec![],
+        vars: vec![],
+        declared: HashMap::new(),
+        loops: vec![],
+        loop_count: 0,
+        functions: String::new(),
+        boundary: 0,
+    };
+    for &stmt in ast.program() {
+        // A struct or enum declaration only names a type, so it generates no
+        // code.
+        let Stmt::Expr(expr) = ast[stmt] else {
+            continue;
+        };
+        match ast[expr] {
+            Expr::Let(name, value) if matches!(ast[value], Expr::Lambda(_)) => {
+                c.function(name, value)?
+            }
+            _ => c.statement(expr, Sink::Print)?,
+        }
+    }
+    c.drop_vars(0);
+    c.line("return 0;");
+    let body = std::mem::take(&mut c.out);
+    let helpers: Vec<_> = runtime::HELPERS
+        .into_iter()
+        .filter(|name| c.helpers.contains(name))
+        .map(runtime::helper)
+        .collect();
+    let mut headers = BTreeSet::from(["inttypes.h", "stdint.h", "stdio.h"]);
+    if c.exits {
+        headers.insert("stdlib.h");
+    }
+    headers.extend(helpers.iter().flat_map(|helper| helper.headers));
+    c.depth = 0;
+    for header in headers {
+        c.line(&format!("#include <{header}>"));
+    }
+    c.line("");
+    for helper in helpers {
+        c.line(helper.c);
+        c.line("");
+    }
+    let functions = std::mem::take(&mut c.functions);
+    c.out.push_str(&functions);
+    c.line("int main(void) {");
+    c.depth = 1;
+    for (i, ty) in std::mem::take(&mut c.temps).into_iter().enumerate() {
+        c.line(&format!("{ty} t{i};"));
+    }
+    c.out.push_str(&body);
+    c.depth = 0;
+    c.line("}");
+    Ok(c.out)
+}
+
+#[derive(Debug)]
+enum CodeGenError {
+    Unit,
+    /// A feature only the interpreter runs so far, named in the plural.
+    Unsupported(&'static str),
+}
+
+impl fmt::Display for CodeGenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unit => write!(f, "statement without a value used as a value"),
+            Self::Unsupported(what) => write!(f, "{what} are only supported by the interpreter"),
+        }
+    }
+}
+
+impl std::error::Error for CodeGenError {}
+
+struct CodeGen<'a> {
+    ast: &'a Ast,
+    /// The type the checker gave each expression.
+    types: &'a SideTable<ExprId, Type>,
+    out: String,
+    depth: usize,
+    /// Whether the program calls `exit` or `assert`, and so needs `stdlib.h`.
+    exits: bool,
+    /// The [`runtime`] functions the program calls.
+    helpers: BTreeSet<&'static str>,
+    /// The types of the temporaries `t0`, `t1` and so on, declared at the
+    /// start of `main`.
+    temps: Vec<&'static str>,
+    /// Variables in scope, innermost last.
+    vars: Vec<Var>,
+    /// How many variables of each name have been declared so far.
+    declared: HashMap<Symbol, usize>,
+    /// The loops being generated, innermost last.
+    loops: Vec<Frame>,
+    /// How many loops have been generated so far, which numbers their
+    /// labels.
+    loop_count: usize,
+    /// The C functions generated so far, which come before `main`.
+    functions: String,
+    /// How many of `vars` are outside the function being generated, which
+    /// can only call those that are functions themselves.
+    boundary: usize,
+}
+
+/// Where [`CodeGen::statement`] sends the value of the C it writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sink {
+    /// It is printed, as the value of a top level expression is.
+    Print,
+    /// It is dropped, as the value of a statement in a block other than the
+    /// last is.
+    Drop,
+    /// It is returned, as the value of the body of a function is.
+    Return,
+}
+
+struct Var {
+    name: Symbol,
+    /// The C variable, as the name is not unique once shadowed.
+    c: String,
+    /// Whether the variable is read, without which C compilers warn.
+    used: bool,
+    /// Whether `c` names a C function rather than a variable.
+    function: bool,
+}
+
+/// A loop, and whether a jump from an inner loop needs a label to leave it
+/// or to go on with its next iteration.
+struct Frame {
+    label: Option<Symbol>,
+    index: usize,
+    breaks: bool,
+    continues: bool,
+}
+
+impl CodeGen<'_> {
+    fn line(&mut self, line: &str) {
+        if !line.is_empty() {
+            self.out.push_str(&INDENT.repeat(self.depth));
+        }
+        self.out.push_str(line);
+        self.out.push('\n');
+    }
+
+    fn statement(&mut self, expr: ExprId, sink: Sink) -> CResult<()> {
+        match self.ast[expr] {
+            Expr::If(c, b) => {
+                let c = self.expression(c)?;
+                self.line(&format!("if ({c}) {{"));
+                self.block(b, sink)?;
+                self.line("}");
+            }
+            Expr::Block(block) => {
+                let depth = self.vars.len();
+                let stmts = &self.ast[block];
+                let mut start = self.out.len();
+                for (i, &stmt) in stmts.iter().enumerate() {
+                    let sink = if i + 1 == stmts.len() {
+                        start = self.out.len();
+                        sink
+                    } else {
+                        Sink::Drop
+                    };
+                    self.statement(stmt, sink)?;
+                }
+                // Unused variables are marked before the last statement,
+                // which returns.
+                let tail = match sink {
+                    Sink::Return => self.out.split_off(start),
+                    _ => String::new(),
+                };
+                self.drop_vars(depth);
+                self.out.push_str(&tail);
+            }
+            Expr::Let(name, value) => {
+                let ty = self.c_type(value)?;
+                let value = self.expression(value)?;
+                let c = self.declare(name);
+                self.line(&format!("{ty} {c} = {value};"));
+            }
+            Expr::Assign(target, value) => self.assign(target, value)?,
+            Expr::Loop(id) => self.run_loop(id)?,
+            Expr::Break(_, Some(_)) => {
+                return Err(Box::new(CodeGenError::Unsupported("loop values")))
+            }
+            Expr::Break(label, None) => self.jump("break", label),
+            Expr::Continue(label) => self.jump("continue", label),
+            Expr::Call(id) if self.is_unit(expr) => {
+                let call = &self.ast[id];
+                match self.intrinsic(call.callee) {
+                    Some(intrinsic) => self.intrinsic_call(intrinsic, &call.args)?,
+                    None => {
+                        let call = self.call(id)?;
+                        self.line(&format!("{call};"));
+                    }
+                }
+            }
+            _ if sink == Sink::Drop && !is_if(self.ast, expr) => {
+                let value = self.operand(expr)?;
+                self.line(&format!("(void){value};"));
+            }
+            _ if sink == Sink::Return && (!is_if(self.ast, expr) || self.is_ternary(expr)) => {
+                let value = self.expression(expr)?;
+                self.line(&format!("return {value};"));
+            }
+            Expr::Atom(Atom::Str(text)) => self.write(&format!("{}\n", &self.ast[text])),
+            _ if sink == Sink::Print && self.types.get(expr) == Some(&Type::String) => {
+                let text = self.expression(expr)?;
+                self.helper("write");
+                self.line(&format!("cb_write({text});"));
+                self.write("\n");
+            }
+            Expr::IfElse(..) if sink == Sink::Print && self.is_ternary(expr) => self.print(expr)?,
+            Expr::IfElse(c, b1, b2) => {
+                let c = self.expression(c)?;
+                self.line(&format!("if ({c}) {{"));
+                self.block(b1, sink)?;
+                self.else_chain(b2, sink)?;
+            }
+            _ => self.print(expr)?,
+        }
+        Ok(())
+    }
+
+    fn else_chain(&mut self, expr: ExprId, sink: Sink) -> CResult<()> {
+        match self.ast[expr] {
+            Expr::If(c, b) => {
+                let c = self.expression(c)?;
+                self.line(&format!("}} else if ({c}) {{"));
+                self.block(b, sink)?;
+                self.line("}");
+                Ok(())
+            }
+            Expr::IfElse(c, b1, b2) => {
+                let c = self.expression(c)?;
+                self.line(&format!("}} else if ({c}) {{"));
+                self.block(b1, sink)?;
+                self.else_chain(b2, sink)
+            }
+            _ => {
+                self.line("} else {");
+                self.block(expr, sink)?;
+                self.line("}");
+                Ok(())
+            }
+        }
+    }
+
+    fn block(&mut self, expr: ExprId, sink: Sink) -> CResult<()> {
+        self.depth += 1;
+        self.statement(expr, sink)?;
+        self.depth -= 1;
+        Ok(())
+    }
+
+    /// Declares a variable, returning its name in C.
+    fn declare(&mut self, name: Symbol) -> String {
+        let count = self.declared.entry(name).or_default();
+        let c = format!("{}_{count}", &self.ast[name]);
+        *count += 1;
+        self.vars.push(Var {
+            name,
+            c: c.clone(),
+            used: false,
+            function: false,
+        });
+        c
+    }
+
+    /// Writes the lambda `value`, bound to `name` at the top level, as a
+    /// `static` C function. Its body is generated like `main`, with its own
+    /// temporaries, and returns the value of its last statement.
+    fn function(&mut self, name: Symbol, value: ExprId) -> CResult<()> {
+        let Expr::Lambda(lambda) = self.ast[value] else {
+            unreachable!("only lambdas become functions");
+        };
+        let Some(Type::Fn(params, ret)) = self.types.get(value) else {
+            unreachable!("the type checker gives a lambda a function type");
+        };
+        let ret = match **ret {
+            Type::Unit => "void",
+            ref ty => c_type(ty)?,
+        };
+        let out = std::mem::take(&mut self.out);
+        let temps = std::mem::take(&mut self.temps);
+        let loops = std::mem::take(&mut self.loops);
+        let depth = self.vars.len();
+        self.boundary = depth;
+        let mut signature = vec![];
+        for (param, ty) in self.ast[lambda].params.iter().zip(params) {
+            let ty = c_type(ty)?;
+            signature.push(format!("{ty} {}", self.declare(param.name)));
+        }
+        let sink = if ret == "void" {
+            Sink::Drop
+        } else {
+            Sink::Return
+        };
+        let indent = std::mem::replace(&mut self.depth, 0);
+        let body = self.block(self.ast[lambda].body, sink);
+        self.boundary = 0;
+        self.loops = loops;
+        body?;
+        // Unused parameters are marked before the body, which may return.
+        let body = std::mem::take(&mut self.out);
+        self.depth = 1;
+        self.drop_vars(depth);
+        self.depth = indent;
+        self.out.push_str(&body);
+        let body = std::mem::replace(&mut self.out, out);
+        let temps = std::mem::replace(&mut self.temps, temps);
+        let c = self.declare(name);
+        self.vars.last_mut().expect("it was declared").function = true;
+        if signature.is_empty() {
+            signature.push("void".to_string());
+        }
+        let function = &mut self.functions;
+        function.push_str(&format!("static {ret} {c}({}) {{\n", signature.join(", ")));
+        for (i, ty) in temps.into_iter().enumerate() {
+            function.push_str(&format!("{INDENT}{ty} t{i};\n"));
+        }
+        function.push_str(&body);
+        function.push_str("}\n\n");
+        Ok(())
+    }
+
+    /// Ends the scope of the variables declared since there were `depth`,
+    /// marking those never read as used.
+    fn drop_vars(&mut self, depth: usize) {
+        for var in self.vars.split_off(depth) {
+            if !var.used {
+                self.line(&format!("(void){};", var.c));
+            }
+        }
+    }
+
+    fn lookup(&self, name: Symbol) -> Option<&Var> {
+        self.vars.iter().rev().find(|var| var.name == name)
+    }
+
+    /// The variable `name` names, which a function can only read if it is
+    /// its own or another function.
+    fn var(&mut self, name: Symbol) -> CResult<Option<&mut Var>> {
+        let boundary = self.boundary;
+        let Some(i) = self.vars.iter().rposition(|var| var.name == name) else {
+            return Ok(None);
+        };
+        let var = &mut self.vars[i];
+        if i < boundary && !var.function {
+            return Err(Box::new(CodeGenError::Unsupported("closures")));
+        }
+        Ok(Some(var))
+    }
+
+    fn assign(&mut self, target: ExprId, value: ExprId) -> CResult<()> {
+        let name = match self.ast[target] {
+            Expr::Atom(Atom::Id(name)) => name,
+            Expr::Index(..) => return Err(Box::new(CodeGenError::Unsupported("arrays"))),
+            Expr::Field(..) => return Err(Box::new(CodeGenError::Unsupported("structs"))),
+            _ => unreachable!("the parser only allows places to be assigned to"),
+        };
+        let value = self.expression(value)?;
+        let Some(var) = self.var(name)? else {
+            unreachable!("the type checker binds '{}'", &self.ast[name]);
+        };
+        let c = var.c.clone();
+        self.line(&format!("{c} = {value};"));
+        Ok(())
+    }
+
+    /// Writes a loop. The bounds of a `for` are kept in temporaries, and
+    /// its variable is a copy of the counter so that assigning to it only
+    /// changes the current iteration, as in the interpreter.
+    fn run_loop(&mut self, id: LoopId) -> CResult<()> {
+        let Loop { label, kind, body } = self.ast[id];
+        let depth = self.vars.len();
+        match kind {
+            LoopKind::Loop => self.line("for (;;) {"),
+            LoopKind::While(c) => {
+                let c = self.expression(c)?;
+                self.line(&format!("while ({c}) {{"));
+            }
+            LoopKind::For(name, from, to) => {
+                let (from, to) = (self.expression(from)?, self.expression(to)?);
+                let (i, end) = (self.temp("int32_t"), self.temp("int32_t"));
+                self.line(&format!(
+                    "for ({i} = {from}, {end} = {to}; {i} < {end}; {i}++) {{"
+                ));
+                let c = self.declare(name);
+                self.depth += 1;
+                self.line(&format!("int32_t {c} = {i};"));
+                self.depth -= 1;
+            }
+        }
+        let index = self.loop_count;
+        self.loop_count += 1;
+        self.loops.push(Frame {
+            label,
+            index,
+            breaks: false,
+            continues: false,
+        });
+        let body = self.block(body, Sink::Drop);
+        let frame = self.loops.pop().expect("the loop was pushed");
+        body?;
+        self.depth += 1;
+        self.drop_vars(depth);
+        self.depth -= 1;
+        if frame.continues {
+            self.line(&format!("{INDENT}continue_{index}:;"));
+        }
+        self.line("}");
+        if frame.breaks {
+            self.line(&format!("break_{index}:;"));
+        }
+        Ok(())
+    }
+
+    /// Writes a `break` or `continue` of the innermost loop or the one with
+    /// `label`, which the parser made sure exists.
+    fn jump(&mut self, keyword: &str, label: Option<Symbol>) {
+        let target = match label {
+            Some(label) => self.loops.iter().rposition(|l| l.label == Some(label)),
+            None => self.loops.len().checked_sub(1),
+        };
+        let target = target.expect("the parser only allows jumps inside loops");
+        if target + 1 == self.loops.len() {
+            self.line(&format!("{keyword};"));
+            return;
+        }
+        let frame = &mut self.loops[target];
+        match keyword {
+            "break" => frame.breaks = true,
+            _ => frame.continues = true,
+        }
+        let index = frame.index;
+        self.line(&format!("goto {keyword}_{index};"));
+    }
+
+    /// A new temporary of type `ty`.
+    fn temp(&mut self, ty: &'static str) -> String {
+        self.temps.push(ty);
+        format!("t{}", self.temps.len() - 1)
+    }
+
+    fn print(&mut self, expr: ExprId) -> CResult<()> {
+        let value = self.expression(expr)?;
+        self.line(&format!(
+            "printf(\"%\" PRId32 \"\\n\", (int32_t)({value}));"
+        ));
+        Ok(())
+    }
+
+    /// Writes a call to `print`, `println`, `exit` or `assert`.
+    fn intrinsic_call(&mut self, intrinsic: Intrinsic, args: &[ExprId]) -> CResult<()> {
+        let &[arg] = args else {
+            return Err(Box::new(CodeGenError::Unsupported("functions")));
+        };
+        match (intrinsic, self.ast[arg]) {
+            (Intrinsic::Print, Expr::Atom(Atom::Str(text))) => self.write(&self.ast[text]),
+            (Intrinsic::Println, Expr::Atom(Atom::Str(text))) => {
+                self.write(&format!("{}\n", &self.ast[text]))
+            }
+            (Intrinsic::Print | Intrinsic::Println, _)
+                if self.types.get(arg) == Some(&Type::String) =>
+            {
+                let text = self.expression(arg)?;
+                self.helper("write");
+                self.line(&format!("cb_write({text});"));
+                if intrinsic == Intrinsic::Println {
+                    self.write("\n");
+                }
+            }
+            (Intrinsic::Print | Intrinsic::Println, _) => {
+                let value = self.expression(arg)?;
+                let newline = if intrinsic == Intrinsic::Println {
+                    " \"\\n\""
+                } else {
+                    ""
+                };
+                self.line(&format!(
+                    "printf(\"%\" PRId32{newline}, (int32_t)({value}));"
+                ));
+            }
+            (Intrinsic::Exit, _) => {
+                let code = self.expression(arg)?;
+                self.exits = true;
+                self.line(&format!("exit({code});"));
+            }
+            (Intrinsic::Assert, _) => {
+                let c = self.operand(arg)?;
+                self.exits = true;
+                self.line(&format!("if (!{c}) {{"));
+                self.depth += 1;
+                let message = c_string(&format!("{}\n", cb_prelude::ASSERTION_FAILED));
+                self.line(&format!("fputs({message}, stderr);"));
+                self.line("exit(3);");
+                self.depth -= 1;
+                self.line("}");
+            }
+            _ => unreachable!("'{intrinsic}' has a value"),
+        }
+        Ok(())
+    }
+
+    /// Writes `text` to stdout. `fputs` stops at a NUL, so text with one is
+    /// written with `fwrite` instead.
+    fn write(&mut self, text: &str) {
+        let literal = c_string(text);
+        match text.contains('\0') {
+            true => self.line(&format!("fwrite({literal}, 1, {}, stdout);", text.len())),
+            false => self.line(&format!("fputs({literal}, stdout);")),
+        }
+    }
+
+    /// Includes a [`runtime`] function and those it calls.
+    fn helper(&mut self, name: &'static str) {
+        let mut names = vec![name];
+        while let Some(name) = names.pop() {
+            if self.helpers.insert(name) {
+                names.extend(runtime::helper(name).needs);
+            }
+        }
+    }
+
+    /// The operands of an operator or a call. C leaves the order they are
+    /// evaluated in unspecified, so when more than one of them has an
+    /// effect they are stored in temporaries, left to right, by the prefix
+    /// returned with them.
+    fn operands(&mut self, exprs: &[ExprId]) -> CResult<(String, Vec<String>)> {
+        let mut prefix = String::new();
+        let mut operands = vec![];
+        for &expr in exprs {
+            if !self.sequenced(exprs) {
+                operands.push(self.operand(expr)?);
+                continue;
+            }
+            let operand = self.expression(expr)?;
+            let temp = self.temp(self.c_type(expr)?);
+            prefix.push_str(&format!("{temp} = {operand}, "));
+            operands.push(temp);
+        }
+        Ok((prefix, operands))
+    }
+
+    fn expression(&mut self, expr: ExprId) -> CResult<String> {
+        let ast = self.ast;
+        let code = match ast[expr] {
+            // `-2147483648` in C negates a literal too large for an `int`.
+            Expr::Atom(Atom::Int(i32::MIN)) => "(-2147483647 - 1)".to_string(),
+            Expr::Atom(Atom::Int(i)) => i.to_string(),
+            Expr::Atom(Atom::Id(id)) => match self.var(id)? {
+                Some(var) if var.function => {
+                    return Err(Box::new(CodeGenError::Unsupported("functions")))
+                }
+                Some(var) => {
+                    var.used = true;
+                    var.c.clone()
+                }
+                None if Intrinsic::lookup(&ast[id]).is_some() => {
+                    return Err(Box::new(CodeGenError::Unsupported("functions")))
+                }
+                None => unreachable!("the type checker binds '{}'", &ast[id]),
+            },
+            Expr::Atom(Atom::Str(text)) => {
+                self.helper("str");
+                format!("(cb_str){{{}, {}}}", c_string(&ast[text]), ast[text].len())
+            }
+            Expr::Call(call) => {
+                let (callee, args) = (ast[call].callee, &ast[call].args);
+                let error = match (self.intrinsic(callee), ast[callee]) {
+                    (Some(Intrinsic::Len), _)
+                        if args.first().and_then(|&arg| self.types.get(arg))
+                            != Some(&Type::String) =>
+                    {
+                        CodeGenError::Unsupported("arrays")
+                    }
+                    (Some(i), _) if i.is_unit() => CodeGenError::Unit,
+                    (Some(i), _) if args.len() != i.arity() => {
+                        CodeGenError::Unsupported("functions")
+                    }
+                    (Some(Intrinsic::Len), _) => {
+                        return Ok(format!("{}.len", self.operand(args[0])?));
+                    }
+                    (Some(i), _) => {
+                        let name = match i {
+                            Intrinsic::ToString => "to_string",
+                            Intrinsic::ParseInt => "parse_int",
+                            Intrinsic::ReadLine => "read_line",
+                            Intrinsic::Slice => "slice",
+                            _ => unreachable!("'{i}' has no value"),
+                        };
+                        self.helper(name);
+                        let (prefix, args) = self.operands(args)?;
+                        let call = format!("cb_{name}({})", args.join(", "));
+                        return Ok(match prefix.is_empty() {
+                            true => call,
+                            false => format!("({prefix}{call})"),
+                        });
+                    }
+                    (None, _) if self.is_unit(expr) => CodeGenError::Unit,
+                    (None, _) => return self.call(call),
+                };
+                return Err(Box::new(error));
+            }
+            // Every literal but `i32::MIN` is positive, so negating one cannot
+            // overflow.
+            Expr::Unary(Op::Minus, rhs) if matches!(ast[rhs], Expr::Atom(Atom::Int(0..))) => {
+                format!("-{}", self.expression(rhs)?)
+            }
+            Expr::Unary(Op::Minus, rhs) => {
+                self.helper("neg");
+                format!("cb_neg({})", self.expression(rhs)?)
+            }
+            Expr::Unary(op, _) => unreachable!("'{op}' is not a unary operator"),
+            Expr::Binary(op, lhs, rhs) => {
+                let strings = self.types.get(lhs) == Some(&Type::String);
+                let (prefix, operands) = self.operands(&[lhs, rhs])?;
+                let [lhs, rhs] = &operands[..] else {
+                    unreachable!("two operands");
+                };
+                let code = match (strings, op) {
+                    (false, Op::Plus | Op::Minus | Op::Mult | Op::Div) => {
+                        let name = match op {
+                            Op::Plus => "add",
+                            Op::Minus => "sub",
+                            Op::Mult => "mul",
+                            _ => "div",
+                        };
+                        self.helper(name);
+                        format!("cb_{name}({lhs}, {rhs})")
+                    }
+                    (false, _) => format!("{lhs} {op} {rhs}"),
+                    (true, Op::Plus) => {
+                        self.helper("concat");
+                        format!("cb_concat({lhs}, {rhs})")
+                    }
+                    (true, _) => {
+                        self.helper("equal");
+                        let not = if op == Op::Ne { "!" } else { "" };
+                        format!("{not}cb_equal({lhs}, {rhs})")
+                    }
+                };
+                match prefix.is_empty() {
+                    true => code,
+                    false => format!("({prefix}{code})"),
+                }
+            }
+            Expr::IfElse(c, b1, b2) => format!(
+                "{} ? {} : {}",
+                self.operand(c)?,
+                self.operand(b1)?,
+                self.operand(b2)?
+            ),
+            Expr::If(..) => {
+                unreachable!("the type checker rejects an 'if' without 'else' as a value")
+            }
+            Expr::Lambda(_) => return Err(Box::new(CodeGenError::Unsupported("functions"))),
+            Expr::Array(_) | Expr::Repeat(..) | Expr::Index(..) => {
+                return Err(Box::new(CodeGenError::Unsupported("arrays")))
+            }
+            Expr::Assign(..) => return Err(Box::new(CodeGenError::Unit)),
+            Expr::Block(_) => return Err(Box::new(CodeGenError::Unsupported("block values"))),
+            Expr::Loop(_) => return Err(Box::new(CodeGenError::Unsupported("loop values"))),
+            Expr::Let(..) | Expr::Break(..) | Expr::Continue(_) => {
+                return Err(Box::new(CodeGenError::Unit))
+            }
+            Expr::StructLit(_) | Expr::Field(..) => {
+                return Err(Box::new(CodeGenError::Unsupported("structs")))
+            }
+            Expr::VariantLit(_) | Expr::Match(_) => {
+                return Err(Box::new(CodeGenError::Unsupported("enums")))
+            }
+        };
+        Ok(code)
+    }
+
+    /// A call to a function bound by a top level `let`.
+    fn call(&mut self, id: CallId) -> CResult<String> {
+        let ast = self.ast;
+        let Call { callee, ref args } = ast[id];
+        let var = match ast[callee] {
+            Expr::Atom(Atom::Id(name)) => self.var(name)?,
+            _ => None,
+        };
+        let c = match var {
+            Some(var) if var.function => {
+                var.used = true;
+                var.c.clone()
+            }
+            _ => return Err(Box::new(CodeGenError::Unsupported("functions"))),
+        };
+        let (prefix, args) = self.operands(args)?;
+        let call = format!("{c}({})", args.join(", "));
+        Ok(match prefix.is_empty() {
+            true => call,
+            false => format!("({prefix}{call})"),
+        })
+    }
+
+    /// The C type of the value of `expr`.
+    fn c_type(&self, expr: ExprId) -> CResult<&'static str> {
+        self.types.get(expr).map_or(Ok("int32_t"), c_type)
+    }
+
+    /// An expression that is safe to use as an operand. A call is already
+    /// one, as is arithmetic, which is done by calls, and so are operands
+    /// stored in temporaries, which come in parentheses.
+    fn operand(&mut self, expr: ExprId) -> CResult<String> {
+        let code = self.expression(expr)?;
+        match self.ast[expr] {
+            Expr::Atom(_) | Expr::Call(_) => Ok(code),
+            Expr::Binary(Op::Plus | Op::Minus | Op::Mult | Op::Div, ..) => Ok(code),
+            Expr::Binary(_, lhs, rhs) if self.sequenced(&[lhs, rhs]) => Ok(code),
+            _ => Ok(format!("({code})")),
+        }
+    }
+
+    /// An `if`/`else` whose branches are plain expressions reads better as a
+    /// conditional expression than as a statement.
+    fn is_ternary(&self, expr: ExprId) -> bool {
+        let plain = |b| !is_if(self.ast, b) && !is_statement(self.ast, b) && !self.is_unit(b);
+        match self.ast[expr] {
+            Expr::IfElse(_, b1, b2) => plain(b1) && (plain(b2) || self.is_ternary(b2)),
+            _ => false,
+        }
+    }
+
+    /// The function from the prelude that `callee` names, unless a variable
+    /// shadows it.
+    fn intrinsic(&self, callee: ExprId) -> Option<Intrinsic> {
+        match self.ast[callee] {
+            Expr::Atom(Atom::Id(id)) if self.lookup(id).is_none() => {
+                Intrinsic::lookup(&self.ast[id])
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether more than one of the operands `exprs` has an effect, so they
+    /// must be stored in temporaries to be evaluated in order.
+    fn sequenced(&self, exprs: &[ExprId]) -> bool {
+        exprs.iter().filter(|&&expr| self.has_effect(expr)).count() > 1
+    }
+
+    /// Whether evaluating `expr` reads input or can stop the program.
+    fn has_effect(&self, expr: ExprId) -> bool {
+        let ast = self.ast;
+        match ast[expr] {
+            Expr::Atom(_) => false,
+            Expr::Unary(_, rhs) => self.has_effect(rhs),
+            Expr::Binary(_, lhs, rhs) => self.has_effect(lhs) || self.has_effect(rhs),
+            Expr::IfElse(c, b1, b2) => [c, b1, b2].into_iter().any(|e| self.has_effect(e)),
+            Expr::Call(call) => {
+                let call = &ast[call];
+                // A function of the program may do anything.
+                let effect = matches!(
+                    self.intrinsic(call.callee),
+                    None | Some(Intrinsic::ReadLine | Intrinsic::ParseInt | Intrinsic::Slice)
+                );
+                effect || call.args.iter().any(|&arg| self.has_effect(arg))
+            }
+            _ => true,
+        }
+    }
+
+    /// Whether `expr` is a call that only has an effect, which becomes a
+    /// statement of its own.
+    fn is_unit(&self, expr: ExprId) -> bool {
+        match self.ast[expr] {
+            Expr::Call(call) => match self.intrinsic(self.ast[call].callee) {
+                Some(intrinsic) => intrinsic.is_unit(),
+                None => self.types.get(expr) == Some(&Type::Unit),
+            },
+            _ => false,
+        }
+    }
+}
+
+/// The fixed width C type of values of type `ty`. A type nothing pins down
+/// is never looked at, so any will do.
+fn c_type(ty: &Type) -> CResult<&'static str> {
+    match ty {
+        Type::Int | Type::Var(_) => Ok("int32_t"),
+        Type::String => Ok("cb_str"),
+        Type::Array(..) => Err(Box::new(CodeGenError::Unsupported("arrays"))),
+        Type::Struct(_) => Err(Box::new(CodeGenError::Unsupported("structs"))),
+        Type::Enum(_) => Err(Box::new(CodeGenError::Unsupported("enums"))),
+        Type::Fn(..) => Err(Box::new(CodeGenError::Unsupported("functions"))),
+        Type::Unit | Type::Size(_) => Err(Box::new(CodeGenError::Unit)),
+    }
+}
+
+/// Whether `expr` can only be a statement: it runs for its effect on
+/// variables or on the flow of control.
+fn is_statement(ast: &Ast, expr: ExprId) -> bool {
+    matches!(
+        ast[expr],
+        Expr::Block(_)
+            | Expr::Let(..)
+            | Expr::Assign(..)
+            | Expr::Loop(_)
+            | Expr::Break(..)
+            | Expr::Continue(_)
+    )
+}
+
+/// A C string literal holding `text`. Other control characters are written
+/// in octal and `?` is escaped so that no trigraph can form.
+fn c_string(text: &str) -> String {
+    let mut literal = String::from('"');
+    for c in text.chars() {
+        match c {
+            '\n' => literal.push_str("\\n"),
+            '\t' => literal.push_str("\\t"),
+            '\r' => literal.push_str("\\r"),
+            '\\' | '"' | '?' => {
+                literal.push('\\');
+                literal.push(c);
+            }
+            c if c.is_ascii_control() => literal.push_str(&format!("\\{:03o}", c as u8)),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+fn is_if(ast: &Ast, expr: ExprId) -> bool {
+    matches!(ast[expr], Expr::If(..) | Expr::IfElse(..))
+}
@@ -0,0 +1,182 @@
+//! C functions that the generated code calls for arithmetic and strings.
+//!
+//! Arithmetic wraps on overflow as it does in the interpreter. Signed
+//! overflow is undefined in C, so it is done on `uint32_t` and converted
+//! back by `cb_wrap`, which avoids the implementation defined conversion of
+//! an out of range value to `int32_t`. Dividing by zero writes an error to
+//! stderr and exits with code 3.
+//!
+//! A string is a `cb_str`, a pointer and a length in bytes. New strings are
+//! allocated with `malloc` and never freed: a program's strings live until
+//! it exits. A failed `slice` or `parse_int` writes its error to stderr and
+//! exits with code 3, like a failed `assert`.
+
+/// A helper, the helpers it calls and the headers it needs.
+pub(crate) struct Helper {
+    pub(crate) needs: &'static [&'static str],
+    pub(crate) headers: &'static [&'static str],
+    pub(crate) c: &'static str,
+}
+
+/// Every helper, each after the ones it calls.
+pub(crate) const HELPERS: [&str; 16] = [
+    "wrap",
+    "add",
+    "sub",
+    "mul",
+    "neg",
+    "div",
+    "str",
+    "alloc",
+    "concat",
+    "equal",
+    "boundary",
+    "slice",
+    "to_string",
+    "parse_int",
+    "read_line",
+    "write",
+];
+
+pub(crate) fn helper(name: &str) -> Helper {
+    let (needs, headers, c): (&[&str], &[&str], _) = match name {
+        "wrap" => (&[], &[], WRAP),
+        "add" => (&["wrap"], &[], ADD),
+        "sub" => (&["wrap"], &[], SUB),
+        "mul" => (&["wrap"], &[], MUL),
+        "neg" => (&["wrap"], &[], NEG),
+        "div" => (&["neg"], &["stdlib.h"], DIV),
+        "str" => (&[], &[], STR),
+        "alloc" => (&[], &["stdlib.h"], ALLOC),
+        "concat" => (&["str", "alloc"], &["string.h"], CONCAT),
+        "equal" => (&["str"], &["string.h"], EQUAL),
+        "boundary" => (&["str"], &[], BOUNDARY),
+        "slice" => (&["str", "boundary"], &["stdlib.h"], SLICE),
+        "to_string" => (&["str", "alloc"], &[], TO_STRING),
+        "parse_int" => (&["str"], &["stdlib.h"], PARSE_INT),
+        "read_line" => (&["str", "alloc"], &[], READ_LINE),
+        "write" => (&["str"], &[], WRITE),
+        _ => unreachable!("no helper is called '{name}'"),
+    };
+    Helper { needs, headers, c }
+}
+
+const WRAP: &str = "static int32_t cb_wrap(uint32_t u) {
+    return u <= INT32_MAX ? (int32_t)u : (int32_t)(u - INT32_MAX - 1) + INT32_MIN;
+}";
+
+const ADD: &str = "static int32_t cb_add(int32_t a, int32_t b) {
+    return cb_wrap((uint32_t)a + (uint32_t)b);
+}";
+
+const SUB: &str = "static int32_t cb_sub(int32_t a, int32_t b) {
+    return cb_wrap((uint32_t)a - (uint32_t)b);
+}";
+
+const MUL: &str = "static int32_t cb_mul(int32_t a, int32_t b) {
+    return cb_wrap((uint32_t)a * (uint32_t)b);
+}";
+
+const NEG: &str = "static int32_t cb_neg(int32_t a) {
+    return cb_wrap(0u - (uint32_t)a);
+}";
+
+/// `INT32_MIN / -1` overflows, so dividing by `-1` negates instead.
+const DIV: &str = r#"static int32_t cb_div(int32_t a, int32_t b) {
+    if (b == 0) {
+        fputs("attempt to divide by zero\n", stderr);
+        exit(3);
+    }
+    return b == -1 ? cb_neg(a) : a / b;
+}"#;
+
+const STR: &str = "typedef struct {
+    const char *ptr;
+    int32_t len;
+} cb_str;";
+
+/// Grows `ptr`, or allocates when it is `NULL`.
+const ALLOC: &str = r#"static char *cb_alloc(char *ptr, int32_t len) {
+    ptr = realloc(ptr, len > 0 ? len : 1);
+    if (!ptr) {
+        fputs("out of memory\n", stderr);
+        exit(3);
+    }
+    return ptr;
+}"#;
+
+const CONCAT: &str = "static cb_str cb_concat(cb_str a, cb_str b) {
+    char *ptr = cb_alloc(NULL, a.len + b.len);
+    memcpy(ptr, a.ptr, a.len);
+    memcpy(ptr + a.len, b.ptr, b.len);
+    return (cb_str){ptr, a.len + b.len};
+}";
+
+const EQUAL: &str = "static int32_t cb_equal(cb_str a, cb_str b) {
+    return a.len == b.len && memcmp(a.ptr, b.ptr, a.len) == 0;
+}";
+
+/// Whether byte `i` of `s` starts a character, or is its end.
+const BOUNDARY: &str = "static int cb_boundary(cb_str s, int32_t i) {
+    return i == s.len || ((unsigned char)s.ptr[i] & 0xC0) != 0x80;
+}";
+
+/// Strings never change, so a slice shares the bytes of its string.
+const SLICE: &str = r#"static cb_str cb_slice(cb_str s, int32_t from, int32_t to) {
+    if (from < 0 || from > to || to > s.len) {
+        fprintf(stderr, "slice out of bounds: the length is %" PRId32 " but the range is %" PRId32 "..%" PRId32 "\n", s.len, from, to);
+        exit(3);
+    }
+    if (!cb_boundary(s, from) || !cb_boundary(s, to)) {
+        fprintf(stderr, "byte index %" PRId32 " is not a char boundary\n", cb_boundary(s, from) ? to : from);
+        exit(3);
+    }
+    return (cb_str){s.ptr + from, to - from};
+}"#;
+
+const TO_STRING: &str = r#"static cb_str cb_to_string(int32_t i) {
+    char *ptr = cb_alloc(NULL, 12);
+    return (cb_str){ptr, sprintf(ptr, "%" PRId32, i)};
+}"#;
+
+/// Accepts what Rust's `i32::from_str` does: a sign, then at least one
+/// digit.
+const PARSE_INT: &str = r#"static int32_t cb_parse_int(cb_str s) {
+    int32_t i = s.len > 0 && (s.ptr[0] == '-' || s.ptr[0] == '+');
+    int ok = i < s.len;
+    int64_t n = 0;
+    for (; ok && i < s.len; i++) {
+        ok = s.ptr[i] >= '0' && s.ptr[i] <= '9' && n * 10 + (s.ptr[i] - '0') <= 2147483648;
+        n = n * 10 + (s.ptr[i] - '0');
+    }
+    if (s.len > 0 && s.ptr[0] == '-') {
+        n = -n;
+    }
+    if (!ok || n > INT32_MAX) {
+        fprintf(stderr, "cannot parse \"%.*s\" as an integer\n", (int)s.len, s.ptr);
+        exit(3);
+    }
+    return (int32_t)n;
+}"#;
+
+/// Reads up to a newline, which is dropped along with a `\r` before it.
+const READ_LINE: &str = r#"static cb_str cb_read_line(void) {
+    int32_t len = 0, cap = 16;
+    char *ptr = cb_alloc(NULL, cap);
+    int c;
+    while ((c = getchar()) != EOF && c != '\n') {
+        if (len == cap) {
+            cap *= 2;
+            ptr = cb_alloc(ptr, cap);
+        }
+        ptr[len++] = (char)c;
+    }
+    if (c == '\n' && len > 0 && ptr[len - 1] == '\r') {
+        len--;
+    }
+    return (cb_str){ptr, len};
+}"#;
+
+const WRITE: &str = "static void cb_write(cb_str s) {
+    fwrite(s.ptr, 1, s.len, stdout);
+}";
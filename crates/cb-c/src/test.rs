@@ -0,0 +1,395 @@
+use super::compile;
+use cb_parse::{parse, Ast, ExprId, SideTable};
+use cb_prelude::{Capture, Exit};
+use cb_typeck::Type;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+/// Parses and type checks a program, which [`compile`] expects.
+fn check(src: &str) -> (Ast, SideTable<ExprId, Type>) {
+    let ast = parse(src).unwrap();
+    let types = cb_typeck::check(&ast).unwrap();
+    (ast, types)
+}
+
+/// Compiles the generated C with the host `cc` and returns what the binary
+/// printed, or `None` when no C compiler is installed.
+fn run_c(name: &str, code: &str) -> Option<Vec<i32>> {
+    let run = exec_c(name, code)?;
+    assert!(run.status.success());
+    let stdout = String::from_utf8(run.stdout).unwrap();
+    Some(stdout.lines().map(|l| l.parse().unwrap()).collect())
+}
+
+/// Compiles and runs the generated C, or returns `None` when no C compiler
+/// is installed.
+fn exec_c(name: &str, code: &str) -> Option<Output> {
+    exec_c_with(name, code, "")
+}
+
+/// Like [`exec_c`], with `input` as the binary's stdin.
+fn exec_c_with(name: &str, code: &str, input: &str) -> Option<Output> {
+    let dir = std::env::temp_dir().join(format!("cb-c-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let src = dir.join(format!("{name}.c"));
+    let exe: PathBuf = dir.join(name);
+    std::fs::write(&src, code).unwrap();
+    let cc = Command::new("cc")
+        .args(["-std=c99", "-pedantic", "-Wall", "-Werror", "-o"])
+        .arg(&exe)
+        .arg(&src)
+        .output();
+    let Ok(cc) = cc else {
+        eprintln!("skipping '{name}': no host C compiler");
+        return None;
+    };
+    assert!(
+        cc.status.success(),
+        "{code}\n{}",
+        String::from_utf8_lossy(&cc.stderr)
+    );
+    let mut run = Command::new(&exe)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    run.stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    Some(run.wait_with_output().unwrap())
+}
+
+macro_rules! setup_test {
+    ($name:ident, $input:expr $(,)?) => {
+        #[test]
+        fn $name() {
+            let (ast, types) = check($input);
+            let code = compile(&ast, &types).unwrap();
+            let expected = cb_interp::run(&ast).unwrap();
+            if let Some(output) = run_c(stringify!($name), &code) {
+                assert_eq!(output, expected, "{code}");
+            }
+        }
+    };
+}
+
+setup_test!(c_atom, "1");
+setup_test!(c_unary, "-1 - -(2 * 3)");
+setup_test!(c_precedence, "-7 / 2 1 + 2 * 3 (1 + 2) * 3 10 - 4 - 3");
+setup_test!(c_wrapping_division, "-2147483648 / -1");
+setup_test!(
+    c_wrapping,
+    "2147483647 + 1 (0 - 2147483647) - 2 65536 * 65536 + 7 -(0 - 2147483647 - 1) 7 / -1"
+);
+setup_test!(c_comparison, "1 > 2 1 < 2");
+setup_test!(c_if, "if 1 > 3 { 1 } if 1 < 3 { 2 }");
+setup_test!(c_if_else, "if 1 > 3 { 1 } else { 2 }");
+setup_test!(
+    c_if_else_chain,
+    "if 3 < 1 { 1 } else if 1 > 2 { 2 } else if 2 > 1 { if 1 > 0 { 3 } } else { 4 }"
+);
+
+setup_test!(
+    c_variables,
+    "let x = 2 let y = x * 3 x = x + y x let x = x - 1 x if x > 0 { let z = x z } else { x }"
+);
+setup_test!(
+    c_while,
+    "let i = 0 let s = 0 while i < 5 { s = s + i i = i + 1 } s"
+);
+setup_test!(
+    c_for,
+    "let s = 0 for i in 0..4 { i = i * 10 s = s + i } s\n\
+     'outer: for i in 1..5 { for j in 0..9 { if j > i { continue 'outer } if i == 4 { break 'outer } s = s + 1 } } s\n\
+     loop { s = s - 7 if s < 0 { break } } s for i in 3..1 { s = 0 } s"
+);
+
+#[test]
+fn c_readable_output() {
+    let (ast, types) = check("1 + 2 * 3\nif 1 > 3 { 1 } else { 2 }\nif 1 > 3 { if 2 > 1 { 3 } }");
+    assert_eq!(
+        compile(&ast, &types).unwrap(),
+        r#"#include <inttypes.h>
+#include <stdint.h>
+#include <stdio.h>
+
+static int32_t cb_wrap(uint32_t u) {
+    return u <= INT32_MAX ? (int32_t)u : (int32_t)(u - INT32_MAX - 1) + INT32_MIN;
+}
+
+static int32_t cb_add(int32_t a, int32_t b) {
+    return cb_wrap((uint32_t)a + (uint32_t)b);
+}
+
+static int32_t cb_mul(int32_t a, int32_t b) {
+    return cb_wrap((uint32_t)a * (uint32_t)b);
+}
+
+int main(void) {
+    printf("%" PRId32 "\n", (int32_t)(cb_add(1, cb_mul(2, 3))));
+    printf("%" PRId32 "\n", (int32_t)((1 > 3) ? 1 : 2));
+    if (1 > 3) {
+        if (2 > 1) {
+            printf("%" PRId32 "\n", (int32_t)(3));
+        }
+    }
+    return 0;
+}
+"#
+    );
+}
+
+#[test]
+fn c_functions() {
+    check_output(
+        "c_functions",
+        "let double = fn(x: i32) -> i32 { x * 2 }\n\
+         let greet = fn(name: String) { println(\"hi \" + name) }\n\
+         let sign = fn(x) { if x < 0 { let s = \"-\" s } else { \"+\" } }\n\
+         let unused = fn(x, y) { 0 }\n\
+         double(double(3)) greet(\"you\") sign(-1) + sign(double(1))\n\
+         let quad = fn(x) { let d = double(x) double(d) } quad(read_line() == \"\" + 5)",
+    );
+}
+
+#[test]
+fn c_function_output() {
+    let (ast, types) =
+        check("let inc = fn(x: i32, unused: i32) -> i32 { let y = 1 x + y } inc(1, 2)");
+    assert_eq!(
+        compile(&ast, &types).unwrap(),
+        r#"#include <inttypes.h>
+#include <stdint.h>
+#include <stdio.h>
+
+static int32_t cb_wrap(uint32_t u) {
+    return u <= INT32_MAX ? (int32_t)u : (int32_t)(u - INT32_MAX - 1) + INT32_MIN;
+}
+
+static int32_t cb_add(int32_t a, int32_t b) {
+    return cb_wrap((uint32_t)a + (uint32_t)b);
+}
+
+static int32_t inc_0(int32_t x_0, int32_t unused_0) {
+    (void)unused_0;
+    int32_t y_0 = 1;
+    return cb_add(x_0, y_0);
+}
+
+int main(void) {
+    printf("%" PRId32 "\n", (int32_t)(inc_0(1, 2)));
+    return 0;
+}
+"#
+    );
+}
+
+#[test]
+fn c_unsupported() {
+    for (src, what) in [
+        ("fn(x) { x }(1)", "functions"),
+        ("let f = fn(x) { x } let g = f g(1)", "functions"),
+        ("if 1 { let f = fn(x) { x } f(1) }", "functions"),
+        ("let y = 1 let f = fn(x) { x + y } f(1)", "closures"),
+        ("let p = println 1", "functions"),
+        ("[1, 2][0]", "arrays"),
+        ("let a = [1] a[0]", "arrays"),
+        ("let a = [1] a[0] = 2", "arrays"),
+        ("struct P { x: i32 } P { x: 1 }.x", "structs"),
+        ("enum E { A } match E::A { E::A => 1 }", "enums"),
+        ("loop { break 1 }", "loop values"),
+        ("let x = loop { break 1 } x", "loop values"),
+        ("1 + if 1 { let y = 2 y } else { 3 }", "block values"),
+    ] {
+        let (ast, types) = check(src);
+        let err = compile(&ast, &types).unwrap_err();
+        let message = format!("{what} are only supported by the interpreter");
+        assert_eq!(err.to_string(), message, "{src}");
+    }
+}
+
+/// Checks that the binary writes what the interpreter does and exits with
+/// the code the program passed to `exit`, if any.
+fn check_output(name: &str, src: &str) {
+    check_output_with(name, src, "");
+}
+
+fn check_output_with(name: &str, src: &str, input: &str) {
+    let (ast, types) = check(src);
+    let code = compile(&ast, &types).unwrap();
+    let mut host = Capture::new(input);
+    let exit = match cb_interp::run_with(&ast, &mut host) {
+        Ok(()) => 0,
+        Err(e) => e.downcast_ref::<Exit>().unwrap().0,
+    };
+    if let Some(run) = exec_c_with(name, &code, input) {
+        assert_eq!(
+            String::from_utf8(run.stdout).unwrap(),
+            host.output,
+            "{code}"
+        );
+        assert_eq!(run.status.code(), Some(exit), "{code}");
+    }
+}
+
+#[test]
+fn c_prelude() {
+    check_output(
+        "c_prelude",
+        "print(1 + 2) println(-4) print(\"a\\t\\\"b\\\" ??= \") println(\"é\") \"s\"\n\
+         if 1 > 2 { println(1) } else { print(\"\\0x\\n\") } assert(1)",
+    );
+    check_output("c_exit", "1 if 2 > 1 { exit(2) } 3");
+    let (ast, types) = check("1 / (1 - 1)");
+    if let Some(run) = exec_c("c_divide_by_zero", &compile(&ast, &types).unwrap()) {
+        assert_eq!(run.status.code(), Some(3));
+        let stderr = String::from_utf8(run.stderr).unwrap();
+        assert_eq!(stderr, "attempt to divide by zero\n");
+    }
+    let (ast, types) = check("assert(0 > 1)");
+    if let Some(run) = exec_c("c_assert", &compile(&ast, &types).unwrap()) {
+        assert_eq!(run.status.code(), Some(3));
+        assert_eq!(String::from_utf8(run.stderr).unwrap(), "assertion failed\n");
+    }
+}
+
+#[test]
+fn c_prelude_output() {
+    let (ast, types) = check("println(\"hi?\") if 1 { exit(1 + 1) }");
+    assert_eq!(
+        compile(&ast, &types).unwrap(),
+        r#"#include <inttypes.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+static int32_t cb_wrap(uint32_t u) {
+    return u <= INT32_MAX ? (int32_t)u : (int32_t)(u - INT32_MAX - 1) + INT32_MIN;
+}
+
+static int32_t cb_add(int32_t a, int32_t b) {
+    return cb_wrap((uint32_t)a + (uint32_t)b);
+}
+
+int main(void) {
+    fputs("hi\?\n", stdout);
+    if (1) {
+        exit(cb_add(1, 1));
+    }
+    return 0;
+}
+"#
+    );
+}
+
+setup_test!(
+    c_equality,
+    "(1 == 1) (1 != 1) (\"ab\" == (\"a\" + \"b\")) (\"a\" != \"b\") len(\"hé\")"
+);
+
+#[test]
+fn c_strings() {
+    check_output(
+        "c_strings",
+        "println(\"n = \" + to_string(parse_int(\"-42\") * 2)) slice(\"héllo\", 1, 3)\n\
+         if len(\"abc\") > 2 { \"long\" } else { \"short\" } print(slice(\"ab\", 1, 2) + \"\\n\")\n\
+         println(to_string(0 - 2147483647 - 1) + to_string(0)) parse_int(\"+7\") parse_int(\"-2147483648\")",
+    );
+    check_output_with(
+        "c_read_line",
+        "println(to_string(parse_int(read_line()) - parse_int(read_line())))\n\
+         read_line() + read_line() read_line()",
+        "7\n2\r\na\nb\n",
+    );
+}
+
+#[test]
+fn c_string_errors() {
+    for (name, src, error) in [
+        (
+            "c_slice_boundary",
+            "slice(\"é\", 0, 1)",
+            "byte index 1 is not a char boundary",
+        ),
+        (
+            "c_slice_bounds",
+            "slice(\"abc\", 0 - 1, 1)",
+            "slice out of bounds: the length is 3 but the range is -1..1",
+        ),
+        (
+            "c_parse_empty",
+            "parse_int(\"\")",
+            "cannot parse \"\" as an integer",
+        ),
+        (
+            "c_parse_sign",
+            "parse_int(\"-\")",
+            "cannot parse \"-\" as an integer",
+        ),
+        (
+            "c_parse_overflow",
+            "parse_int(\"2147483648\")",
+            "cannot parse \"2147483648\" as an integer",
+        ),
+    ] {
+        let (ast, types) = check(src);
+        let interp = cb_interp::run(&ast).unwrap_err().to_string();
+        assert!(interp.ends_with(error), "{interp}");
+        if let Some(run) = exec_c(name, &compile(&ast, &types).unwrap()) {
+            assert_eq!(run.status.code(), Some(3));
+            assert_eq!(String::from_utf8(run.stderr).unwrap(), format!("{error}\n"));
+        }
+    }
+}
+
+#[test]
+fn c_string_variables() {
+    check_output(
+        "c_string_variables",
+        "let s = \"a\" for i in 0..3 { s = s + to_string(i) } println(s) s let s = len(s) s",
+    );
+}
+
+#[test]
+fn c_loops_output() {
+    let (ast, types) = check("let n = 0 'a: for i in 0..2 { while 1 { n = n + i continue 'a } }");
+    let code = compile(&ast, &types).unwrap();
+    assert!(
+        code.ends_with(
+            "int main(void) {
+    int32_t t0;
+    int32_t t1;
+    int32_t n_0 = 0;
+    for (t0 = 0, t1 = 2; t0 < t1; t0++) {
+        int32_t i_0 = t0;
+        while (1) {
+            n_0 = cb_add(n_0, i_0);
+            goto continue_0;
+        }
+        continue_0:;
+    }
+    return 0;
+}
+"
+        ),
+        "{code}"
+    );
+}
+
+#[test]
+fn c_strings_output() {
+    let (ast, types) = check("len(read_line() + read_line())");
+    let code = compile(&ast, &types).unwrap();
+    assert!(code.contains("#include <string.h>\n"), "{code}");
+    assert!(
+        code.contains(
+            "    cb_str t0;\n    cb_str t1;\n    printf(\"%\" PRId32 \"\\n\", \
+             (int32_t)((t0 = cb_read_line(), t1 = cb_read_line(), cb_concat(t0, t1)).len));\n"
+        ),
+        "{code}"
+    );
+}
@@ -0,0 +1,276 @@
+#[cfg(test)]
+mod test;
+
+use cb_lexer::{Scanner, TokenKind};
+use cb_parse::syntax::{self, SyntaxElement, SyntaxKind, SyntaxNode};
+use std::collections::{HashMap, HashSet};
+
+type CResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+const INDENT: &str = "    ";
+
+/// Re-prints a program in the canonical style. Only whitespace changes, so
+/// comments are kept and the program parses to the same AST. Source that does
+/// not parse is rejected rather than guessed at.
+pub fn format(src: &str) -> CResult<String> {
+    let parse = syntax::parse(src);
+    parse.lower()?;
+    let root = parse.syntax();
+    let starts = statement_starts(&root);
+    let fields = field_braces(&root);
+    let mut f = Formatter::default();
+    let mut end = 0;
+    for token in Scanner::new(src) {
+        if token.is_eof() {
+            break;
+        }
+        f.gap(&src[end..token.span.start]);
+        let statement = starts.contains(&token.span.start);
+        let brace = fields.get(&token.span.start).copied();
+        f.token(token.kind, token.text(src), statement, brace);
+        end = token.span.end;
+    }
+    f.gap(&src[end..]);
+    Ok(f.finish())
+}
+
+/// The offsets of the statements of the program and of every block in it,
+/// including the `pub` before a declaration.
+fn statement_starts(root: &SyntaxNode) -> HashSet<usize> {
+    let mut starts = HashSet::new();
+    for node in root.descendants() {
+        if !matches!(node.kind(), SyntaxKind::Root | SyntaxKind::Block) {
+            continue;
+        }
+        let mut public = false;
+        for element in node.children_with_tokens() {
+            let start = element.text_range().start().into();
+            match element {
+                SyntaxElement::Token(t) if t.kind() == SyntaxKind::KeyWord && t.text() == "pub" => {
+                    starts.insert(start);
+                    public = true;
+                }
+                SyntaxElement::Node(_) if !public => {
+                    starts.insert(start);
+                }
+                SyntaxElement::Node(_) => public = false,
+                SyntaxElement::Token(_) => {}
+            }
+        }
+    }
+    starts
+}
+
+/// The offsets of the `{` that open the fields of a struct literal or of a
+/// struct or enum declaration, with what they open.
+fn field_braces(root: &SyntaxNode) -> HashMap<usize, Brace> {
+    let mut braces = HashMap::new();
+    for node in root.descendants() {
+        let brace = match node.kind() {
+            SyntaxKind::Struct | SyntaxKind::Enum => Brace::Struct,
+            SyntaxKind::StructLit => Brace::Literal,
+            _ => continue,
+        };
+        let open = node
+            .children_with_tokens()
+            .filter_map(|e| e.into_token())
+            .find(|t| t.text() == "{");
+        if let Some(open) = open {
+            braces.insert(open.text_range().start().into(), brace);
+        }
+    }
+    braces
+}
+
+/// What a `{` opened. Lambdas are kept on one line, since they are
+/// operands, and so is every block inside them. Struct and enum declarations
+/// and struct literals are lists of fields and stay on one line too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Brace {
+    Block,
+    Lambda,
+    Inline,
+    Struct,
+    Literal,
+}
+
+#[derive(Default)]
+struct Formatter {
+    out: String,
+    depth: usize,
+    parens: usize,
+    /// Line breaks owed before the next token, at most two.
+    newlines: usize,
+    previous: Option<TokenKind>,
+    /// Whether `previous` was a prefix operator, which hugs its operand.
+    unary: bool,
+    /// Whether a line comment was written since the last token.
+    comment: bool,
+    /// Whether the next token directly follows the previous one in the
+    /// source, which decides whether a `(` starts a call and a `[` an index.
+    adjacent: bool,
+    /// Whether a `λ` or `fn` has been seen whose body has not started yet.
+    lambda: bool,
+    /// The braces that are open, innermost last.
+    braces: Vec<Brace>,
+    /// Whether `previous` closed a lambda or a struct literal, which ends an
+    /// operand.
+    lambda_end: bool,
+}
+
+impl Formatter {
+    /// Handles the whitespace and comments between two tokens. Line breaks
+    /// outside of parentheses are kept, with runs of blank lines collapsed.
+    fn gap(&mut self, gap: &str) {
+        self.adjacent = gap.is_empty();
+        let mut rest = gap;
+        while let Some(start) = rest.find("//") {
+            let before = &rest[..start];
+            let end = rest[start..].find('\n').map_or(rest.len(), |i| start + i);
+            let comment = rest[start..end].trim_end();
+            let breaks = before.matches('\n').count();
+            if breaks > 0 || self.out.is_empty() {
+                self.newlines = self.newlines.max(breaks.min(2));
+                self.start_token();
+            } else {
+                self.out.push(' ');
+            }
+            self.out.push_str(comment);
+            self.newlines = 1;
+            self.comment = true;
+            rest = &rest[end..];
+        }
+        if self.parens == 0 && !self.inline() {
+            let breaks = rest.matches('\n').count().min(2);
+            self.newlines = self.newlines.max(breaks);
+        }
+    }
+
+    fn inline(&self) -> bool {
+        matches!(
+            self.braces.last(),
+            Some(Brace::Lambda | Brace::Inline | Brace::Struct | Brace::Literal)
+        )
+    }
+
+    /// Writes a token, on a new line if it starts a statement outside of a
+    /// lambda. A `{` that opens a list of fields comes with its `brace`.
+    fn token(&mut self, kind: TokenKind, text: &str, statement: bool, brace: Option<Brace>) {
+        let after_brace = self.previous == Some(TokenKind::LBrace);
+        match kind {
+            _ if self.inline() => {}
+            TokenKind::RBrace => {
+                self.depth = self.depth.saturating_sub(1);
+                self.newlines = usize::from(!after_brace);
+            }
+            TokenKind::Else | TokenKind::LBrace | TokenKind::Comma => self.newlines = 0,
+            _ if after_brace => self.newlines = self.newlines.min(1),
+            _ if statement => self.newlines = self.newlines.max(1),
+            _ => {}
+        }
+        if self.comment {
+            self.newlines = self.newlines.max(1);
+            self.comment = false;
+        }
+        let line_start = self.start_token();
+        if !line_start && self.space_before(kind) {
+            self.out.push(' ');
+        }
+        self.out.push_str(text);
+
+        self.unary = matches!(kind, TokenKind::Minus | TokenKind::Bang) && self.is_prefix();
+        self.lambda_end = false;
+        match kind {
+            TokenKind::Lambda | TokenKind::Fn => self.lambda = true,
+            TokenKind::LBrace if brace.is_some() => self.braces.extend(brace),
+            TokenKind::LBrace if self.lambda => {
+                self.braces.push(Brace::Lambda);
+                self.lambda = false;
+            }
+            TokenKind::LBrace if self.inline() => self.braces.push(Brace::Inline),
+            TokenKind::LBrace => {
+                self.braces.push(Brace::Block);
+                self.depth += 1;
+                self.newlines = 1;
+            }
+            // Whatever follows a block is on the same line unless it starts
+            // a statement, as in `(loop { break 1 }) + 1`.
+            TokenKind::RBrace => match self.braces.pop() {
+                Some(Brace::Lambda | Brace::Literal) => self.lambda_end = true,
+                Some(Brace::Block) => self.newlines = 0,
+                _ => {}
+            },
+            // Outside of parentheses, only the arms of a `match` are
+            // separated by commas in a block, and each gets its own line.
+            TokenKind::Comma if self.parens == 0 && !self.inline() => self.newlines = 1,
+            TokenKind::LParen | TokenKind::LBracket => self.parens += 1,
+            TokenKind::RParen | TokenKind::RBracket => self.parens = self.parens.saturating_sub(1),
+            _ => {}
+        }
+        self.previous = Some(kind);
+    }
+
+    /// Writes owed line breaks and indentation, returning whether the next
+    /// token starts a line.
+    fn start_token(&mut self) -> bool {
+        if self.out.is_empty() {
+            self.newlines = 0;
+            return true;
+        }
+        if self.newlines == 0 {
+            return false;
+        }
+        for _ in 0..self.newlines {
+            self.out.push('\n');
+        }
+        self.newlines = 0;
+        self.out.push_str(&INDENT.repeat(self.depth));
+        true
+    }
+
+    fn space_before(&self, kind: TokenKind) -> bool {
+        use TokenKind::*;
+        if self.unary
+            || matches!(
+                kind,
+                RParen | RBracket | Comma | Semicolon | Colon | DotDot | Dot | ColonColon
+            )
+        {
+            return false;
+        }
+        if kind == RBrace && self.previous == Some(LBrace) {
+            return false;
+        }
+        if matches!(kind, LParen | LBracket) {
+            match self.previous {
+                Some(Lambda | Fn) if kind == LParen => return false,
+                Some(Id | Int | RParen | RBracket) if self.adjacent => return false,
+                Some(RBrace) if self.adjacent && self.lambda_end => return false,
+                _ => {}
+            }
+        }
+        !matches!(
+            self.previous,
+            Some(LParen | LBracket | DotDot | Dot | ColonColon)
+        )
+    }
+
+    /// An operator is a prefix operator unless it follows something that
+    /// ends an operand.
+    fn is_prefix(&self) -> bool {
+        use TokenKind::*;
+        match self.previous {
+            None => true,
+            Some(RParen | RBracket | True | False) => false,
+            Some(RBrace) if self.lambda_end => false,
+            Some(kind) => kind.is_op() || kind.is_keyword(),
+        }
+    }
+
+    fn finish(mut self) -> String {
+        if !self.out.is_empty() {
+            self.out.push('\n');
+        }
+        self.out
+    }
+}
@@ -0,0 +1,132 @@
+use super::format;
+use cb_parse::parse;
+
+macro_rules! setup_test {
+    ($name:ident, $input:expr, $output:expr $(,)?) => {
+        #[test]
+        fn $name() {
+            let formatted = format($input).unwrap();
+            assert_eq!(formatted, $output);
+            assert_eq!(format(&formatted).unwrap(), formatted, "not idempotent");
+            assert_eq!(parse(&formatted).unwrap(), parse($input).unwrap());
+        }
+    };
+}
+
+setup_test!(binary_spacing, "1+2*  3", "1 + 2 * 3\n");
+setup_test!(unary_minus, "- 1 -  -2", "-1 - -2\n");
+setup_test!(parens, "( 1 + 2 )*3", "(1 + 2) * 3\n");
+setup_test!(newlines_in_parens, "(1 +\n 2)\n\n\n\n3", "(1 + 2)\n\n3\n");
+setup_test!(if_block, "if(1>3){a+b}", "if (1 > 3) {\n    a + b\n}\n");
+setup_test!(
+    if_else_chain,
+    "if 1 > 3 {\n\n  1\n\n}\nelse if 2 < 3 { 2 } else\n{ 3 }",
+    "if 1 > 3 {\n    1\n} else if 2 < 3 {\n    2\n} else {\n    3\n}\n",
+);
+setup_test!(
+    nested_blocks,
+    "if 1 { if 2 { 3 } }",
+    "if 1 {\n    if 2 {\n        3\n    }\n}\n",
+);
+setup_test!(
+    comments,
+    "// leading   \n1+2 // trailing\n\n\n  // own line\n3",
+    "// leading\n1 + 2 // trailing\n\n// own line\n3\n",
+);
+setup_test!(
+    comments_in_blocks,
+    "if 1 { // why\n// what\n2 } // done\nelse { 3 }",
+    "if 1 { // why\n    // what\n    2\n} // done\nelse {\n    3\n}\n",
+);
+setup_test!(empty, "  \n\n", "");
+setup_test!(
+    lambda,
+    "λ (x : i32,y)->i32 {\n  x+y\n}( 1 ,2 )",
+    "λ(x: i32, y) -> i32 { x + y }(1, 2)\n",
+);
+setup_test!(
+    calls,
+    "f(1)(g)\n(2)(h)  fn(){if 1 {2} else {3}}() -1",
+    "f(1)(g)\n(2)(h)\nfn() { if 1 { 2 } else { 3 } }() - 1\n",
+);
+setup_test!(
+    lambda_in_block,
+    "if 1 { fn(f: fn() -> i32) { f() }(fn() { 2 }) }",
+    "if 1 {\n    fn(f: fn() -> i32) { f() }(fn() { 2 })\n}\n",
+);
+setup_test!(
+    arrays,
+    "[ 1,2 ][0]  a [ 0 ] [x;3][i] [[ 1 ]][0][ 0 ]",
+    "[1, 2][0]\na\n[0]\n[x; 3][i]\n[[1]][0][0]\n",
+);
+setup_test!(
+    assign,
+    "if 1 {m[ i ][j]=fn(a:[i32;2]){a[1]}([1,2])}",
+    "if 1 {\n    m[i][j] = fn(a: [i32; 2]) { a[1] }([1, 2])\n}\n",
+);
+setup_test!(
+    enums,
+    "enum S{C(i32),E}\nmatch S :: C( -1 ){S::C(n)if n>0=>{n}\n,S::C(_)=>match S::E{x=>0},S::E=>{let y=1 y}}",
+    "enum S { C(i32), E }\nmatch S::C(-1) {\n    S::C(n) if n > 0 => {\n        n\n    },\n    S::C(_) => match S::E {\n        x => 0\n    },\n    S::E => {\n        let y = 1\n        y\n    }\n}\n",
+);
+setup_test!(
+    modules,
+    "pub  mod a ;use a :: b;\npub let x=b\npub struct P{x:i32}",
+    "pub mod a;\nuse a::b;\npub let x = b\npub struct P { x: i32 }\n",
+);
+setup_test!(
+    imported_struct_literal,
+    "use m::P; P{x: 1, y: 2}.x",
+    "use m::P;\nP { x: 1, y: 2 }.x\n",
+);
+setup_test!(
+    loops,
+    "let s=0\n'outer :for i in 0 .. n{ while s>i { s=s-1 continue 'outer }\nbreak }\nloop{break s}",
+    "let s = 0\n'outer: for i in 0..n {\n    while s > i {\n        s = s - 1\n        continue 'outer\n    }\n    break\n}\nloop {\n    break s\n}\n",
+);
+setup_test!(
+    empty_blocks,
+    "while 1 {  }  fn ( ) { }",
+    "while 1 {}\nfn() {}\n",
+);
+setup_test!(
+    structs,
+    "struct P{x:i32,\ny:i32} struct E {  }\nlet p=P{\nx:1,y:2} p . x=P{x:3,y:4}.y-1\nif p.x>0 { E{} }",
+    "struct P { x: i32, y: i32 }\nstruct E {}\nlet p = P { x: 1, y: 2 }\np.x = P { x: 3, y: 4 }.y - 1\nif p.x > 0 {\n    E {}\n}\n",
+);
+setup_test!(
+    statements,
+    "1 2\nlet x = 3 x = x + 1 if x { 4 } -5",
+    "1\n2\nlet x = 3\nx = x + 1\nif x {\n    4\n}\n-5\n",
+);
+setup_test!(
+    block_like_operands,
+    "let x = loop { break 5 } (loop {break 1}) + 1\nlet y = if x { 1 } else { 2 } * 3",
+    "let x = loop {\n    break 5\n}\n(loop {\n    break 1\n}) + 1\nlet y = if x {\n    1\n} else {\n    2\n} * 3\n",
+);
+
+#[test]
+fn rejects_invalid_source() {
+    assert!(format("1 +").is_err());
+}
+
+#[test]
+fn samples_are_idempotent() {
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../../samples");
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|e| e != "cb") {
+            continue;
+        }
+        let src = std::fs::read_to_string(&path).unwrap();
+        let once = format(&src).unwrap();
+        let twice = format(&once).unwrap();
+        assert_eq!(once, twice, "{}", path.display());
+        assert_eq!(
+            parse(&once).unwrap(),
+            parse(&src).unwrap(),
+            "{}",
+            path.display()
+        );
+    }
+}
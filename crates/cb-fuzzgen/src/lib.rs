@@ -0,0 +1,564 @@
+//! Random C Flat programs for property tests of the whole pipeline.
+//!
+//! Programs are generated from a model of the grammar as lists of tokens,
+//! then laid out with random whitespace and comments between them.
+//! [`program`] only produces source that parses, and [`malformed`] breaks
+//! such source with a few random edits.
+
+#[cfg(test)]
+mod test;
+
+use proptest::prelude::*;
+
+/// What a generated program may contain.
+#[derive(Debug, Clone, Copy)]
+pub struct Options {
+    /// Whether identifiers appear. Every identifier is unbound, so programs
+    /// without them are the ones that can run to completion.
+    pub identifiers: bool,
+    /// Whether lambdas and calls appear. Only the interpreter runs them.
+    pub functions: bool,
+    /// Whether array literals, indexing and `len` appear. Only the
+    /// interpreter runs them.
+    pub arrays: bool,
+    /// Whether `let`, assignments, `for`, `while` and `loop` appear. Every
+    /// loop stops after a few iterations. Such programs start by binding
+    /// `x`, which they read and assign.
+    pub loops: bool,
+    /// Whether string literals, `+` and `==` of strings, and the prelude's
+    /// string functions appear. Strings never stand where a number does, so
+    /// programs still type check; with `loops`, programs also bind `s` to a
+    /// string.
+    pub strings: bool,
+    /// Whether a struct is declared, built and has its fields read. Only
+    /// the interpreter runs them.
+    pub structs: bool,
+    /// Whether an enum is declared and its variants built and matched on.
+    /// Only the interpreter runs them.
+    pub enums: bool,
+    /// How deeply expressions nest.
+    pub depth: u32,
+    /// How deeply `if` statements nest.
+    pub blocks: u32,
+    /// The most top level statements.
+    pub statements: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            identifiers: false,
+            functions: false,
+            arrays: false,
+            loops: false,
+            strings: false,
+            structs: false,
+            enums: false,
+            depth: 5,
+            blocks: 3,
+            statements: 6,
+        }
+    }
+}
+
+const IDENTIFIERS: [&str; 5] = ["a", "b", "x", "foo", "bar_1"];
+
+const OPERATORS: [&str; 6] = ["+", "-", "*", "/", "<", ">"];
+
+/// String literals as written, with escapes and characters of more than one
+/// byte, so that `slice` can fail.
+const STRINGS: [&str; 6] = [
+    r#""""#,
+    r#""a""#,
+    r#""hello""#,
+    r#""tab\t\"q\"""#,
+    r#""é λ""#,
+    r#""line\n""#,
+];
+
+/// The declarations that programs with structs and enums start with.
+const STRUCT: &str = "struct P { a: i32, b: i32 }";
+const ENUM: &str = "enum E { A, B(i32) }";
+
+/// Parameter lists, with and without types.
+const PARAMS: [&[&str]; 5] = [
+    &[],
+    &["x"],
+    &["x", ":", "i32"],
+    &["a", ",", "b", ":", "i32"],
+    &["f", ":", "fn", "(", "i32", ")", "->", "i32", ",", "y"],
+];
+
+/// Tokens that [`malformed`] splices into a program: stray delimiters and
+/// keywords, literals the parser rejects, and characters no token starts with.
+const JUNK: [&str; 19] = [
+    "(",
+    ")",
+    "{",
+    "}",
+    "if",
+    "else",
+    "fn",
+    "while",
+    "break",
+    "..",
+    "->",
+    "==",
+    "1.5",
+    "2147483648",
+    "99999999999999999999",
+    "@",
+    "λ",
+    "é",
+    "\"",
+];
+
+/// A well-formed program.
+pub fn program(options: Options) -> impl Strategy<Value = String> {
+    tokens(options).prop_flat_map(layout)
+}
+
+/// A program that has been broken by deleting, duplicating, swapping or
+/// inserting tokens, so it most likely does not parse.
+pub fn malformed(options: Options) -> impl Strategy<Value = String> {
+    let edits = prop::collection::vec(edit(), 1..4);
+    (tokens(options), edits)
+        .prop_map(|(mut tokens, edits)| {
+            for edit in edits {
+                edit.apply(&mut tokens);
+            }
+            tokens
+        })
+        .prop_flat_map(layout)
+}
+
+fn int() -> impl Strategy<Value = String> {
+    prop_oneof![
+        4 => (0..10i32).prop_map(|i| i.to_string()),
+        1 => (0..=i32::MAX).prop_map(|i| i.to_string()),
+        1 => (0..1_000_000i32).prop_map(|i| {
+            let digits = i.to_string();
+            let split = digits.len() / 2;
+            format!("{}_{}", &digits[..split.max(1)], &digits[split.max(1)..])
+        }),
+    ]
+}
+
+fn expression(options: Options) -> BoxedStrategy<Vec<String>> {
+    let leaf = match options.identifiers {
+        true => prop_oneof![
+            3 => int(),
+            1 => prop::sample::select(&IDENTIFIERS[..]).prop_map(String::from),
+        ]
+        .boxed(),
+        false => int().boxed(),
+    };
+    let leaf = match options.loops {
+        true => prop_oneof![4 => leaf, 1 => Just("x".to_string())].boxed(),
+        false => leaf,
+    };
+    let leaf = leaf.prop_map(|token| vec![token]);
+    leaf.prop_recursive(options.depth, 64, 2, move |inner| {
+        let operators = prop_oneof![
+            inner.clone().prop_map(|e| wrap("(", e, ")")),
+            inner.clone().prop_map(|e| wrap("-", e, "")),
+            (
+                inner.clone(),
+                prop::sample::select(&OPERATORS[..]),
+                inner.clone()
+            )
+                .prop_map(|(mut lhs, op, rhs)| {
+                    lhs.push(op.into());
+                    lhs.extend(rhs);
+                    lhs
+                }),
+        ];
+        let operators = match options.functions {
+            true => prop_oneof![3 => operators, 1 => call(inner.clone())].boxed(),
+            false => operators.boxed(),
+        };
+        let operators = match options.strings {
+            true => prop_oneof![4 => operators, 1 => string_int(options, inner.clone())].boxed(),
+            false => operators,
+        };
+        let operators = match options.structs {
+            true => prop_oneof![4 => operators, 1 => field(inner.clone())].boxed(),
+            false => operators,
+        };
+        let operators = match options.enums {
+            true => prop_oneof![4 => operators, 1 => matches(inner.clone())].boxed(),
+            false => operators,
+        };
+        match options.arrays {
+            true => prop_oneof![3 => operators, 1 => array(inner)].boxed(),
+            false => operators,
+        }
+    })
+    .boxed()
+}
+
+/// A string, made of literals and of numbers from `int`.
+fn string(options: Options, int: BoxedStrategy<Vec<String>>) -> BoxedStrategy<Vec<String>> {
+    let literal = prop::sample::select(&STRINGS[..]).prop_map(|s| vec![s.to_string()]);
+    let leaf = match options.loops {
+        true => prop_oneof![3 => literal, 1 => Just(vec!["s".to_string()])].boxed(),
+        false => literal.boxed(),
+    };
+    let leaf = prop_oneof![
+        3 => leaf,
+        1 => int.clone().prop_map(|i| wrap("to_string(", i, ")")),
+    ];
+    leaf.prop_recursive(2, 8, 3, move |inner| {
+        prop_oneof![
+            (inner.clone(), inner.clone()).prop_map(|(mut lhs, rhs)| {
+                lhs.push("+".into());
+                lhs.extend(rhs);
+                lhs
+            }),
+            (inner, int.clone(), int.clone()).prop_map(|(text, from, to)| {
+                let mut tokens = wrap("slice(", text, ",");
+                tokens.extend(from);
+                tokens.push(",".into());
+                tokens.extend(to);
+                tokens.push(")".into());
+                tokens
+            }),
+        ]
+    })
+    .boxed()
+}
+
+/// A number from strings: a length or a comparison. Comparisons bind
+/// tighter than `+` and looser than a unary `-`, so both the comparison and
+/// the strings compared are in parentheses.
+fn string_int(
+    options: Options,
+    inner: BoxedStrategy<Vec<String>>,
+) -> impl Strategy<Value = Vec<String>> {
+    let text = string(options, inner);
+    prop_oneof![
+        text.clone().prop_map(|s| wrap("len(", s, ")")),
+        (text.clone(), prop::sample::select(&["==", "!="][..]), text).prop_map(|(lhs, op, rhs)| {
+            let mut tokens = wrap("(", lhs, ")");
+            tokens.push(op.into());
+            tokens.extend(wrap("(", rhs, ")"));
+            wrap("(", tokens, ")")
+        }),
+    ]
+}
+
+/// A field of a literal of the struct `P`. The `.` is glued to the literal,
+/// as the `(` of a call is.
+fn field(inner: BoxedStrategy<Vec<String>>) -> impl Strategy<Value = Vec<String>> {
+    (
+        inner.clone(),
+        inner,
+        prop::sample::select(&["}.a", "}.b"][..]),
+    )
+        .prop_map(|(a, b, field)| {
+            let mut tokens = ["P", "{", "a", ":"].map(String::from).to_vec();
+            tokens.extend(a);
+            tokens.extend([",".into(), "b".into(), ":".into()]);
+            tokens.extend(b);
+            tokens.push(field.into());
+            tokens
+        })
+}
+
+/// A `match` of a variant of the enum `E`, with an arm for each variant.
+/// A `match` that starts a statement or an arm ends it, so the `match` and
+/// its arms are in parentheses.
+fn matches(inner: BoxedStrategy<Vec<String>>) -> impl Strategy<Value = Vec<String>> {
+    let scrutinee = prop_oneof![
+        Just(vec!["E::A".to_string()]),
+        inner.clone().prop_map(|e| wrap("E::B(", e, ")")),
+    ];
+    (scrutinee, inner.clone(), inner).prop_map(|(scrutinee, a, b)| {
+        let mut tokens = wrap("(", wrap("match", scrutinee, "{"), "");
+        tokens.extend(["E::A".into(), "=>".into()]);
+        tokens.extend(wrap("(", a, ")"));
+        tokens.extend([
+            ",".into(),
+            "E::B(v)".into(),
+            "=>".into(),
+            "v".into(),
+            "-".into(),
+        ]);
+        tokens.extend(wrap("(", b, ")"));
+        tokens.extend(["}".into(), ")".into()]);
+        tokens
+    })
+}
+
+/// A call of a lambda or a name. The `(` of the arguments is glued to the
+/// callee, since a gap would make them separate statements.
+fn call(inner: BoxedStrategy<Vec<String>>) -> impl Strategy<Value = Vec<String>> {
+    let lambda = (
+        prop::sample::select(&["λ", "fn"][..]),
+        prop::sample::select(&PARAMS[..]),
+        any::<bool>(),
+        inner.clone(),
+    )
+        .prop_map(|(keyword, params, typed, body)| {
+            let mut tokens = vec![keyword.to_string(), "(".into()];
+            tokens.extend(params.iter().map(|t| t.to_string()));
+            tokens.push(")".into());
+            if typed {
+                tokens.extend(["->".into(), "i32".into()]);
+            }
+            tokens.push("{".into());
+            tokens.extend(body);
+            tokens.push("}(".into());
+            tokens
+        });
+    let name = prop::sample::select(&IDENTIFIERS[..]).prop_map(|name| vec![format!("{name}(")]);
+    let args = prop::collection::vec(inner, 0..3);
+    (prop_oneof![lambda, name], args).prop_map(|(mut tokens, args)| {
+        for (i, arg) in args.into_iter().enumerate() {
+            if i > 0 {
+                tokens.push(",".into());
+            }
+            tokens.extend(arg);
+        }
+        tokens.push(")".into());
+        tokens
+    })
+}
+
+/// An array literal, either indexed or passed to `len` so that the result is
+/// an operand like any other. As with calls, the `[` of an index is glued to
+/// the array.
+fn array(inner: BoxedStrategy<Vec<String>>) -> impl Strategy<Value = Vec<String>> {
+    let list = prop::collection::vec(inner.clone(), 0..4).prop_map(|elems| {
+        let mut tokens = vec!["[".to_string()];
+        for (i, elem) in elems.into_iter().enumerate() {
+            if i > 0 {
+                tokens.push(",".into());
+            }
+            tokens.extend(elem);
+        }
+        tokens
+    });
+    let repeat = (inner.clone(), 0..4u32).prop_map(|(elem, len)| {
+        let mut tokens = wrap("[", elem, ";");
+        tokens.push(len.to_string());
+        tokens
+    });
+    let index = prop_oneof![Just(None), inner.prop_map(Some)];
+    (prop_oneof![list, repeat], index).prop_map(|(mut tokens, index)| match index {
+        Some(index) => {
+            tokens.push("][".into());
+            tokens.extend(index);
+            tokens.push("]".into());
+            tokens
+        }
+        None => {
+            tokens.push("]".into());
+            wrap("len(", tokens, ")")
+        }
+    })
+}
+
+fn statement(options: Options) -> BoxedStrategy<Vec<String>> {
+    let expr = expression(options);
+    // A statement that starts with `-` would continue the one before it,
+    // which may be a string, so it is put in parentheses.
+    let value = expr.clone().prop_map(|e| match e[0] == "-" {
+        true => wrap("(", e, ")"),
+        false => e,
+    });
+    let leaf = match options.loops {
+        true => prop_oneof![
+            3 => value.clone(),
+            1 => expr.clone().prop_map(|value| {
+                let mut tokens = vec!["let".into(), "x".into(), "=".into()];
+                tokens.extend(value);
+                tokens
+            }),
+            1 => expr.clone().prop_map(|value| wrap("x", wrap("=", value, ""), "")),
+        ]
+        .boxed(),
+        false => value.clone().boxed(),
+    };
+    let leaf = match options.strings {
+        true => prop_oneof![3 => leaf, 1 => string_statement(options, expr.clone())].boxed(),
+        false => leaf,
+    };
+    leaf.prop_recursive(options.blocks, 16, 3, move |inner| {
+        let block = inner.clone().prop_map(|s| wrap("{", s, "}"));
+        // The blocks of an `if` with an `else` must have the same type, so
+        // both end with a number.
+        let typed = (inner.clone(), value.clone()).prop_map(|(mut s, tail)| {
+            s.extend(tail);
+            wrap("{", s, "}")
+        });
+        let if_statement = prop_oneof![
+            (expr.clone(), block.clone()).prop_map(|(cond, then)| {
+                let mut tokens = wrap("if", cond, "");
+                tokens.extend(then);
+                tokens
+            }),
+            (expr.clone(), typed.clone(), typed).prop_map(|(cond, then, otherwise)| {
+                let mut tokens = wrap("if", cond, "");
+                tokens.extend(then);
+                tokens.extend(wrap("else", otherwise, ""));
+                tokens
+            }),
+        ];
+        if !options.loops {
+            return if_statement.boxed();
+        }
+        prop_oneof![
+            2 => if_statement,
+            1 => loops(inner, block),
+        ]
+        .boxed()
+    })
+    .boxed()
+}
+
+/// A string printed as the value of a statement or by the prelude, or, with
+/// `loops`, bound or assigned to `s`.
+fn string_statement(
+    options: Options,
+    int: BoxedStrategy<Vec<String>>,
+) -> impl Strategy<Value = Vec<String>> {
+    let text = string(options, int);
+    let prefixes: &[&[&str]] = match options.loops {
+        true => &[&[], &["let", "s", "="], &["s", "="]],
+        false => &[&[]],
+    };
+    let statement = (prop::sample::select(prefixes), text.clone()).prop_map(|(prefix, text)| {
+        let mut tokens: Vec<String> = prefix.iter().map(|t| t.to_string()).collect();
+        tokens.extend(text);
+        tokens
+    });
+    let print = (prop::sample::select(&["print(", "println("][..]), text)
+        .prop_map(|(print, text)| wrap(print, text, ")"));
+    prop_oneof![statement, print]
+}
+
+/// A `for` loop over a short range, a `while` loop that counts to a few,
+/// or a `loop` whose body ends in `break`. The `while` loop counts with `n`,
+/// which nothing else assigns, before its body runs, so that the body may
+/// shadow `n`.
+fn loops(
+    inner: BoxedStrategy<Vec<String>>,
+    block: impl Strategy<Value = Vec<String>>,
+) -> impl Strategy<Value = Vec<String>> {
+    let while_loop = (0..4u32, inner.clone()).prop_map(|(n, body)| {
+        let mut tokens = ["let", "n", "=", "0", "while", "n", "<", &n.to_string()]
+            .map(String::from)
+            .to_vec();
+        tokens.extend(["{", "n", "=", "n", "+", "1"].map(String::from));
+        tokens.extend(body);
+        tokens.push("}".into());
+        tokens
+    });
+    let for_loop = (0..4u32, block).prop_map(|(n, body)| {
+        let mut tokens = ["for", "x", "in", "0", "..", &n.to_string()]
+            .map(String::from)
+            .to_vec();
+        tokens.extend(body);
+        tokens
+    });
+    let loop_break = inner.prop_map(|body| {
+        let mut tokens = wrap("loop", wrap("{", body, "break"), "}");
+        tokens.insert(0, "'l".into());
+        tokens.insert(1, ":".into());
+        tokens
+    });
+    prop_oneof![for_loop, while_loop, loop_break]
+}
+
+/// The statements of a program, after the declarations and bindings that
+/// `options` call for.
+fn tokens(options: Options) -> impl Strategy<Value = Vec<String>> {
+    let mut prelude = vec![];
+    if options.structs {
+        prelude.push(STRUCT);
+    }
+    if options.enums {
+        prelude.push(ENUM);
+    }
+    if options.loops {
+        prelude.push("let x = 0");
+        if options.strings {
+            prelude.push(r#"let s = "s""#);
+        }
+    }
+    let prelude: Vec<String> = prelude
+        .iter()
+        .flat_map(|line| line.split(' '))
+        .map(String::from)
+        .collect();
+    prop::collection::vec(statement(options), 0..=options.statements).prop_map(move |statements| {
+        let mut tokens = prelude.clone();
+        tokens.extend(statements.concat());
+        tokens
+    })
+}
+
+/// Joins tokens with whitespace and comments, at least one space or line
+/// break between each pair so that neighbours never merge into one token.
+fn layout(tokens: Vec<String>) -> impl Strategy<Value = String> {
+    let gap = prop_oneof![
+        8 => Just(" "),
+        2 => Just("\n"),
+        1 => Just("\t"),
+        1 => Just("\r\n\n"),
+        1 => Just(" // note\n"),
+        1 => Just("\n// {( if\n"),
+    ];
+    let gaps = prop::collection::vec(gap, tokens.len() + 1);
+    gaps.prop_map(move |gaps| {
+        let mut src = gaps[0].to_string();
+        for (token, gap) in tokens.iter().zip(&gaps[1..]) {
+            src.push_str(token);
+            src.push_str(gap);
+        }
+        src
+    })
+}
+
+fn wrap(open: &str, mut tokens: Vec<String>, close: &str) -> Vec<String> {
+    tokens.insert(0, open.into());
+    if !close.is_empty() {
+        tokens.push(close.into());
+    }
+    tokens
+}
+
+/// One change to the tokens of a program. Positions are taken modulo the
+/// length, since the length is not known when the edit is generated.
+#[derive(Debug, Clone)]
+enum Edit {
+    Delete(usize),
+    Duplicate(usize),
+    Swap(usize, usize),
+    Insert(usize, &'static str),
+}
+
+fn edit() -> impl Strategy<Value = Edit> {
+    prop_oneof![
+        any::<usize>().prop_map(Edit::Delete),
+        any::<usize>().prop_map(Edit::Duplicate),
+        (any::<usize>(), any::<usize>()).prop_map(|(a, b)| Edit::Swap(a, b)),
+        (any::<usize>(), prop::sample::select(&JUNK[..])).prop_map(|(i, t)| Edit::Insert(i, t)),
+    ]
+}
+
+impl Edit {
+    fn apply(self, tokens: &mut Vec<String>) {
+        let len = tokens.len();
+        match self {
+            Self::Insert(i, token) => tokens.insert(i % (len + 1), token.into()),
+            _ if len == 0 => {}
+            Self::Delete(i) => {
+                tokens.remove(i % len);
+            }
+            Self::Duplicate(i) => tokens.insert(i % len, tokens[i % len].clone()),
+            Self::Swap(a, b) => tokens.swap(a % len, b % len),
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use super::{malformed, program, Options};
+use cb_interp::RuntimeError;
+use cb_lexer::Scanner;
+use cb_prelude::{Capture, Host};
+use proptest::prelude::*;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use wasmi::{Caller, Engine, Extern, Linker, Module, Store};
+
+fn with_identifiers() -> Options {
+    Options {
+        identifiers: true,
+        functions: true,
+        arrays: true,
+        loops: true,
+        strings: true,
+        structs: true,
+        enums: true,
+        ..Options::default()
+    }
+}
+
+/// What every backend compiles.
+fn compiled() -> Options {
+    Options {
+        loops: true,
+        strings: true,
+        ..Options::default()
+    }
+}
+
+/// Scans to the end of `src`, which must come after at most one token per
+/// byte.
+fn scan(src: &str) {
+    let mut scanner = Scanner::new(src);
+    for _ in 0..=src.len() {
+        if scanner.next().is_some_and(|t| t.is_eof()) {
+            return;
+        }
+    }
+    panic!("no end of input in {src:?}");
+}
+
+/// Runs the module with `wasmi` and returns what it wrote, and whether it
+/// ran to the end rather than trapping.
+fn run_wasm(wasm: &[u8]) -> (String, bool) {
+    let engine = Engine::default();
+    let module = Module::new(&engine, wasm).unwrap();
+    let mut store = Store::new(&engine, Capture::default());
+    let mut linker = <Linker<Capture>>::new(&engine);
+    linker
+        .func_wrap(
+            "env",
+            "print",
+            |mut caller: Caller<'_, Capture>, value: i32| {
+                caller.data_mut().value(value);
+            },
+        )
+        .unwrap()
+        .func_wrap(
+            "env",
+            "write_int",
+            |mut caller: Caller<'_, Capture>, value: i32| {
+                caller.data_mut().write(&value.to_string());
+            },
+        )
+        .unwrap()
+        .func_wrap(
+            "env",
+            "write",
+            |mut caller: Caller<'_, Capture>, ptr: i32, len: i32| {
+                let Some(Extern::Memory(memory)) = caller.get_export("memory") else {
+                    panic!("a module that writes exports its memory");
+                };
+                let mut bytes = vec![0; len as usize];
+                memory.read(&caller, ptr as usize, &mut bytes).unwrap();
+                caller.data_mut().write(&String::from_utf8(bytes).unwrap());
+            },
+        )
+        .unwrap();
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .unwrap()
+        .start(&mut store)
+        .unwrap();
+    let main = instance.get_typed_func::<(), ()>(&store, "main").unwrap();
+    let ok = main.call(&mut store, ()).is_ok();
+    (store.into_data().output, ok)
+}
+
+/// Compiles the C with the host `cc` and returns what the binary wrote, and
+/// whether it exited successfully, or `None` when no C compiler is
+/// installed.
+fn run_c(code: &str) -> Option<(String, bool)> {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let name = format!(
+        "cb-fuzzgen-{}-{}",
+        std::process::id(),
+        COUNT.fetch_add(1, Ordering::Relaxed)
+    );
+    let (src, exe) = (
+        std::env::temp_dir().join(format!("{name}.c")),
+        std::env::temp_dir().join(name),
+    );
+    std::fs::write(&src, code).unwrap();
+    // Unlike the backend's own tests, warnings are allowed: they come from
+    // what the program says, such as `x < x`.
+    let cc = Command::new("cc")
+        .args(["-std=c99", "-w", "-o"])
+        .arg(&exe)
+        .arg(&src)
+        .output()
+        .ok()?;
+    let _ = std::fs::remove_file(&src);
+    assert!(
+        cc.status.success(),
+        "{code}\n{}",
+        String::from_utf8_lossy(&cc.stderr)
+    );
+    let run = Command::new(&exe).output().unwrap();
+    let _ = std::fs::remove_file(&exe);
+    Some((String::from_utf8(run.stdout).unwrap(), run.status.success()))
+}
+
+proptest! {
+    #[test]
+    fn scanner_never_panics(src in any::<String>()) {
+        scan(&src);
+    }
+
+    #[test]
+    fn scanner_never_panics_on_programs(src in malformed(with_identifiers())) {
+        scan(&src);
+    }
+
+    #[test]
+    fn programs_parse(src in program(with_identifiers())) {
+        let parsed = cb_parse::parse(&src);
+        prop_assert!(parsed.is_ok(), "{}\n{}", parsed.unwrap_err(), src);
+        let ast = parsed.unwrap();
+        prop_assert_eq!(cb_parse::parse(&cb_parse::print(&ast)).unwrap(), ast);
+        let formatted = cb_fmt::format(&src).unwrap();
+        prop_assert_eq!(cb_fmt::format(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn parsers_never_panic(src in malformed(with_identifiers())) {
+        let parsed = cb_parse::syntax::parse(&src);
+        prop_assert_eq!(parsed.syntax().to_string(), src.clone());
+        if parsed.lower().is_err() {
+            prop_assert!(cb_fmt::format(&src).is_err());
+        }
+    }
+
+    /// The interpreter checks types as it runs, so a program the type checker
+    /// accepts can only fail on values.
+    #[test]
+    fn checked_programs_run(src in program(with_identifiers())) {
+        let ast = cb_parse::parse(&src).unwrap();
+        if cb_typeck::check(&ast).is_ok() {
+            if let Err(e) = cb_interp::run(&ast) {
+                let e = e.to_string();
+                prop_assert!(
+                    e.ends_with("attempt to divide by zero")
+                        || e.contains("calls nested")
+                        || e.contains("index out of bounds")
+                        || e.contains("slice out of bounds")
+                        || e.contains("is not a char boundary"),
+                    "{}\n{}",
+                    e,
+                    src
+                );
+            }
+        }
+    }
+
+    /// Programs of what every backend compiles type check and compile, so
+    /// the backends must write the same output and either all run to the
+    /// end or all fail at the same point. The C backend runs whenever a host
+    /// compiler is installed.
+    #[test]
+    fn backends_agree(src in program(compiled())) {
+        let ast = cb_parse::parse(&src).unwrap();
+        let checked = cb_typeck::check(&ast);
+        prop_assert!(checked.is_ok(), "{}\n{}", checked.unwrap_err(), src);
+        let types = checked.unwrap();
+        let mut expected = Capture::default();
+        // The JIT does not know where an error happened, so only the
+        // interpreter's message is compared.
+        let result = cb_interp::run_with(&ast, &mut expected).map_err(|e| {
+            match e.downcast::<RuntimeError>() {
+                Ok(e) => e.message,
+                Err(e) => e.to_string(),
+            }
+        });
+        let mut jit = Capture::default();
+        let jit_result = cb_jit::run_with(&ast, &types, &mut jit).map_err(|e| e.to_string());
+        prop_assert_eq!(&jit_result, &result, "{}", src);
+        prop_assert_eq!(&jit.output, &expected.output, "{}", src);
+        let wasm = run_wasm(&cb_wasm::compile(&ast, &types).unwrap());
+        prop_assert_eq!(wasm, (expected.output.clone(), result.is_ok()), "{}", src);
+        if let Some(c) = run_c(&cb_c::compile(&ast, &types).unwrap()) {
+            prop_assert_eq!(c, (expected.output.clone(), result.is_ok()), "{}", src);
+        }
+    }
+}
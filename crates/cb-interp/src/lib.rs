@@ -0,0 +1,1109 @@
+use cb_lexer::{escape, Span};
+use cb_parse::decision::{self, Case, Decision, Path};
+use cb_parse::{
+    Ast, Atom, Expr, ExprId, LambdaId, Loop, LoopId, LoopKind, Op, SideTable, Stmt, StmtId, Symbol,
+};
+use cb_prelude::{Capture, Exit, Host, Intrinsic, SliceError};
+use std::fmt;
+use std::rc::Rc;
+
+type CResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+/// How deeply calls may nest before the program is stopped.
+const MAX_DEPTH: usize = 256;
+
+/// Runs a program and returns the value of every top level expression in
+/// order. An `if` without an `else` whose condition is false produces no value,
+/// and neither does an assignment, a `let` or a loop that is not left by a
+/// `break` with a value. What the program writes is dropped, and it reads
+/// no input.
+pub fn run(ast: &Ast) -> CResult<Vec<i32>> {
+    let mut host = Capture::default();
+    run_with(ast, &mut host)?;
+    Ok(host.values)
+}
+
+/// Runs a program against `host`, which is given the value of every top
+/// level expression and does the program's input and output. A top level
+/// string is written on its own line. A call to `exit` stops the program
+/// with an [`Exit`] error.
+pub fn run_with(ast: &Ast, host: &mut dyn Host) -> CResult<()> {
+    let mut interp = Interp {
+        ast,
+        host,
+        depth: 0,
+        scope: vec![],
+        decisions: SideTable::new(),
+    };
+    interp.program(ast.program())
+}
+
+/// Runs a program a piece at a time, as a REPL does, keeping its variables
+/// from one piece to the next.
+///
+/// Each run is given the whole program so far and runs the statements that
+/// are new. The program must be parsed afresh from the source so far, so
+/// that the nodes of the statements that already ran keep their ids.
+#[derive(Default)]
+pub struct Session {
+    /// The variables declared by the statements that ran.
+    scope: Vec<(Symbol, Value)>,
+    decisions: SideTable<ExprId, Rc<Decision>>,
+    /// How many statements of the program have run.
+    done: usize,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs the statements of `ast` that have not run yet, like
+    /// [`run_with`]. If one fails, the session goes back to how it was
+    /// before, so that the new statements can be dropped from the program.
+    pub fn run(&mut self, ast: &Ast, host: &mut dyn Host) -> CResult<()> {
+        let mut interp = Interp {
+            ast,
+            host,
+            depth: 0,
+            scope: self.scope.clone(),
+            decisions: std::mem::take(&mut self.decisions),
+        };
+        interp.program(&ast.program()[self.done..])?;
+        self.scope = interp.scope;
+        self.decisions = interp.decisions;
+        self.done = ast.program().len();
+        Ok(())
+    }
+}
+
+/// An error while running, at the expression that failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeError {
+    pub span: Span,
+    pub message: String,
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{} {}", self.span.start, self.span.end, self.message)
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+/// What went wrong, before it is given the span of the expression that was
+/// running, unless it knows a narrower one.
+#[derive(Debug)]
+enum Fault {
+    Unbound(Rc<str>),
+    DivideByZero,
+    NoValue,
+    Unit,
+    AssignValue,
+    NotAFunction,
+    ExpectedNumber(&'static str),
+    ExpectedString(&'static str),
+    ExpectedArray(&'static str),
+    ExpectedStruct(&'static str),
+    ExpectedEnum(&'static str),
+    NoField(Rc<str>, Rc<str>),
+    NoMatch,
+    Arity(usize, usize),
+    Print(&'static str),
+    StackOverflow,
+    OutOfBounds { span: Span, len: usize, index: i32 },
+    ParseInt(Rc<str>),
+    Assert,
+    Slice(SliceError),
+    ExpectedSized(&'static str),
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unbound(id) => write!(f, "unbound identifier '{id}'"),
+            Self::DivideByZero => write!(f, "attempt to divide by zero"),
+            Self::NoValue => write!(f, "'if' without 'else' used as a value"),
+            Self::Unit => write!(f, "statement without a value used as a value"),
+            Self::AssignValue => write!(f, "assignment used as a value"),
+            Self::NotAFunction => write!(f, "called a number as a function"),
+            Self::ExpectedNumber(found) => write!(f, "expected a number but found {found}"),
+            Self::ExpectedString(found) => write!(f, "expected a string but found {found}"),
+            Self::ExpectedArray(found) => write!(f, "expected an array but found {found}"),
+            Self::ExpectedStruct(found) => write!(f, "expected a struct but found {found}"),
+            Self::ExpectedEnum(found) => write!(f, "expected an enum but found {found}"),
+            Self::NoField(name, field) => write!(f, "struct '{name}' has no field '{field}'"),
+            Self::NoMatch => write!(f, "no arm of a 'match' matched"),
+            Self::Arity(expected, found) => {
+                write!(f, "expected {expected} arguments but found {found}")
+            }
+            Self::Print(kind) => write!(f, "cannot print {kind}"),
+            Self::StackOverflow => write!(f, "calls nested more than {MAX_DEPTH} deep"),
+            Self::OutOfBounds { len, index, .. } => write!(
+                f,
+                "index out of bounds: the length is {len} but the index is {index}"
+            ),
+            Self::ParseInt(text) => write!(f, "cannot parse {} as an integer", escape(text)),
+            Self::Assert => write!(f, "{}", cb_prelude::ASSERTION_FAILED),
+            Self::Slice(error) => write!(f, "{error}"),
+            Self::ExpectedSized(found) => {
+                write!(f, "expected an array or a string but found {found}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Fault {}
+
+/// Gives a [`Fault`] the span of the expression at `span` that failed, or
+/// of the index that was out of bounds. Errors that already have a span, or
+/// that are not faults, go on as they are.
+fn locate(error: Box<dyn std::error::Error>, span: Span) -> Box<dyn std::error::Error> {
+    match error.downcast::<Fault>() {
+        Ok(fault) => {
+            let span = match *fault {
+                Fault::OutOfBounds { ref span, .. } => span.clone(),
+                _ => span,
+            };
+            let message = fault.to_string();
+            Box::new(RuntimeError { span, message })
+        }
+        Err(error) => error,
+    }
+}
+
+/// Leaves the loop with the label, or the innermost loop if there is none.
+/// It travels as an error until a loop catches it, and the parser makes sure
+/// one always does.
+#[derive(Debug)]
+enum Jump {
+    Break(Option<Symbol>, Option<Value>),
+    Continue(Option<Symbol>),
+}
+
+impl fmt::Display for Jump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Break(..) => write!(f, "'break' outside of a loop"),
+            Self::Continue(_) => write!(f, "'continue' outside of a loop"),
+        }
+    }
+}
+
+impl std::error::Error for Jump {}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Int(i32),
+    /// Strings are immutable, so values share them and the last one to go
+    /// frees them.
+    Str(Rc<str>),
+    /// What a call that only has an effect, like `print(x)`, returns.
+    Unit,
+    Fn(Rc<Closure>),
+    /// Arrays are values: assigning to an element of a shared array copies
+    /// it first.
+    Array(Rc<Vec<Value>>),
+    /// A struct and its fields in the order the literal gave them. Like
+    /// arrays, structs are copied when a shared one is assigned into.
+    Struct(Symbol, Rc<Vec<(Symbol, Value)>>),
+    /// The enum, the variant and the values it carries.
+    Variant(Symbol, Symbol, Rc<Vec<Value>>),
+    /// A function from the prelude, in scope unless shadowed.
+    Intrinsic(Intrinsic),
+}
+
+impl Value {
+    fn int(self) -> CResult<i32> {
+        match self {
+            Self::Int(i) => Ok(i),
+            _ => Err(Box::new(Fault::ExpectedNumber(self.kind()))),
+        }
+    }
+
+    fn str(self) -> CResult<Rc<str>> {
+        match self {
+            Self::Str(text) => Ok(text),
+            _ => Err(Box::new(Fault::ExpectedString(self.kind()))),
+        }
+    }
+
+    /// What sort of value this is, for error messages.
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Int(_) => "a number",
+            Self::Str(_) => "a string",
+            Self::Unit => "no value",
+            Self::Fn(_) | Self::Intrinsic(_) => "a function",
+            Self::Array(_) => "an array",
+            Self::Struct(..) => "a struct",
+            Self::Variant(..) => "an enum",
+        }
+    }
+}
+
+/// The value at `path` inside the values of nested variants.
+fn at<'v>(value: &'v Value, path: &Path) -> CResult<&'v Value> {
+    path.iter().try_fold(value, |value, &i| match value {
+        Value::Variant(_, _, values) => Ok(&values[i]),
+        _ => Err(Box::new(Fault::ExpectedEnum(value.kind())).into()),
+    })
+}
+
+/// Finds the field `name` of a struct.
+fn field<'v>(ast: &Ast, value: &'v mut Value, name: Symbol) -> CResult<&'v mut Value> {
+    let Value::Struct(ty, fields) = value else {
+        return Err(Box::new(Fault::ExpectedStruct(value.kind())));
+    };
+    let ty = *ty;
+    match Rc::make_mut(fields).iter_mut().find(|(n, _)| *n == name) {
+        Some((_, value)) => Ok(value),
+        None => Err(Box::new(Fault::NoField(ast[ty].into(), ast[name].into()))),
+    }
+}
+
+/// A lambda along with copies of the variables in scope when it was
+/// evaluated, so later assignments are not seen by the closure.
+#[derive(Debug)]
+struct Closure {
+    lambda: LambdaId,
+    captured: Vec<(Symbol, Value)>,
+}
+
+struct Interp<'a> {
+    ast: &'a Ast,
+    host: &'a mut dyn Host,
+    depth: usize,
+    /// Variables in scope, innermost last.
+    scope: Vec<(Symbol, Value)>,
+    /// The decision tree of each `match` and `if` chain that has run.
+    decisions: SideTable<ExprId, Rc<Decision>>,
+}
+
+impl Interp<'_> {
+    /// Runs top level statements, giving the value of each to the host.
+    fn program(&mut self, stmts: &[StmtId]) -> CResult<()> {
+        for &stmt in stmts {
+            let Stmt::Expr(expr) = self.ast[stmt] else {
+                continue;
+            };
+            match self.statement(expr)? {
+                Some(Value::Int(value)) => self.host.value(value),
+                Some(Value::Str(text)) => self.host.write(&format!("{text}\n")),
+                Some(Value::Unit) | None => {}
+                Some(value) => {
+                    let fault = Box::new(Fault::Print(value.kind()));
+                    return Err(locate(fault, self.ast.span(expr)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, expr: ExprId) -> CResult<Option<Value>> {
+        self.run_statement(expr)
+            .map_err(|e| locate(e, self.ast.span(expr)))
+    }
+
+    fn run_statement(&mut self, expr: ExprId) -> CResult<Option<Value>> {
+        match self.ast[expr] {
+            Expr::If(..) | Expr::IfElse(..) => {
+                let decision = self.decision(expr);
+                self.decide(&decision, &Value::Unit)
+            }
+            Expr::Assign(target, value) => {
+                let value = self.eval(value)?;
+                self.assign(target, value)?;
+                Ok(None)
+            }
+            Expr::Block(block) => {
+                let ast = self.ast;
+                let depth = self.scope.len();
+                let mut value = None;
+                for &stmt in &ast[block] {
+                    value = self.statement(stmt)?;
+                }
+                self.scope.truncate(depth);
+                Ok(value)
+            }
+            Expr::Let(name, value) => {
+                let value = self.eval(value)?;
+                self.scope.push((name, value));
+                Ok(None)
+            }
+            Expr::Loop(id) => self.run_loop(id),
+            Expr::Break(label, value) => {
+                let value = value.map(|value| self.eval(value)).transpose()?;
+                Err(Box::new(Jump::Break(label, value)))
+            }
+            Expr::Continue(label) => Err(Box::new(Jump::Continue(label))),
+            Expr::Match(m) => {
+                let value = self.eval(self.ast[m].scrutinee)?;
+                let decision = self.decision(expr);
+                self.decide(&decision, &value)
+            }
+            _ => self.eval(expr).map(Some),
+        }
+    }
+
+    /// The decision tree of a `match` or an `if` chain, lowered the first
+    /// time it runs.
+    fn decision(&mut self, expr: ExprId) -> Rc<Decision> {
+        if let Some(decision) = self.decisions.get(expr) {
+            return decision.clone();
+        }
+        let decision = Rc::new(match self.ast[expr] {
+            Expr::Match(m) => decision::lower_match(self.ast, m),
+            _ => decision::lower_if(self.ast, expr),
+        });
+        self.decisions.insert(expr, decision.clone());
+        decision
+    }
+
+    /// Follows `decision` for `value` and runs the body it reaches, with
+    /// the names bound on the way in scope.
+    fn decide(&mut self, decision: &Decision, value: &Value) -> CResult<Option<Value>> {
+        match decision {
+            Decision::Fail => Err(Box::new(Fault::NoMatch)),
+            Decision::Leaf {
+                bindings,
+                guard,
+                body,
+                otherwise,
+            } => {
+                let depth = self.scope.len();
+                for (name, path) in bindings {
+                    let value = at(value, path)?.clone();
+                    self.scope.push((*name, value));
+                }
+                let result = match guard {
+                    Some(guard) if self.eval(*guard)?.int()? == 0 => {
+                        self.scope.truncate(depth);
+                        return self.decide(otherwise, value);
+                    }
+                    _ => body.map(|body| self.statement(body)).transpose(),
+                };
+                self.scope.truncate(depth);
+                Ok(result?.flatten())
+            }
+            Decision::Switch {
+                path,
+                cases,
+                default,
+            } => {
+                let tested = at(value, path)?;
+                for (case, decision) in cases {
+                    let equal = match (case, tested) {
+                        (Case::Int(i), tested) => tested.clone().int()? == *i,
+                        (Case::Variant(ty, variant), Value::Variant(t, v, _)) => {
+                            (t, v) == (ty, variant)
+                        }
+                        (Case::Variant(..), tested) => {
+                            return Err(Box::new(Fault::ExpectedEnum(tested.kind())));
+                        }
+                    };
+                    if equal {
+                        return self.decide(decision, value);
+                    }
+                }
+                self.decide(default, value)
+            }
+        }
+    }
+
+    /// Runs a loop, whose value is that of the `break` that leaves it.
+    fn run_loop(&mut self, id: LoopId) -> CResult<Option<Value>> {
+        let Loop { label, kind, body } = self.ast[id];
+        match kind {
+            LoopKind::Loop => loop {
+                if let Some(value) = self.iteration(label, body)? {
+                    return Ok(value);
+                }
+            },
+            LoopKind::While(cond) => {
+                while self.eval(cond)?.int()? != 0 {
+                    if self.iteration(label, body)?.is_some() {
+                        break;
+                    }
+                }
+            }
+            LoopKind::For(name, from, to) => {
+                let from = self.eval(from)?.int()?;
+                let to = self.eval(to)?.int()?;
+                for i in from..to {
+                    self.scope.push((name, Value::Int(i)));
+                    let exit = self.iteration(label, body);
+                    self.scope.pop();
+                    if exit?.is_some() {
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Runs the body of a loop once, returning the value of a `break` that
+    /// leaves the loop.
+    fn iteration(&mut self, label: Option<Symbol>, body: ExprId) -> CResult<Option<Option<Value>>> {
+        let depth = self.scope.len();
+        let result = self.statement(body);
+        self.scope.truncate(depth);
+        let Err(error) = result else {
+            return Ok(None);
+        };
+        let targets = |l: &Option<Symbol>| l.is_none() || *l == label;
+        match error.downcast::<Jump>() {
+            Ok(jump) => match *jump {
+                Jump::Break(l, value) if targets(&l) => Ok(Some(value)),
+                Jump::Continue(l) if targets(&l) => Ok(None),
+                jump => Err(Box::new(jump)),
+            },
+            Err(error) => Err(error),
+        }
+    }
+
+    fn lookup(&self, name: Symbol) -> CResult<Value> {
+        match self.scope.iter().rev().find(|(n, _)| *n == name) {
+            Some((_, value)) => Ok(value.clone()),
+            None => match Intrinsic::lookup(&self.ast[name]) {
+                Some(intrinsic) => Ok(Value::Intrinsic(intrinsic)),
+                None => Err(Box::new(Fault::Unbound(self.ast[name].into()))),
+            },
+        }
+    }
+
+    fn eval(&mut self, expr: ExprId) -> CResult<Value> {
+        self.evaluate(expr)
+            .map_err(|e| locate(e, self.ast.span(expr)))
+    }
+
+    fn evaluate(&mut self, expr: ExprId) -> CResult<Value> {
+        let value = match self.ast[expr] {
+            Expr::Atom(Atom::Int(i)) => Value::Int(i),
+            Expr::Atom(Atom::Str(s)) => Value::Str(self.ast[s].into()),
+            Expr::Atom(Atom::Id(id)) => self.lookup(id)?,
+            Expr::Unary(Op::Minus, rhs) => Value::Int(self.eval(rhs)?.int()?.wrapping_neg()),
+            Expr::Unary(op, _) => unreachable!("'{op}' is not a unary operator"),
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = self.eval(lhs)?;
+                let rhs = self.eval(rhs)?;
+                match (op, lhs) {
+                    (Op::Plus, Value::Str(lhs)) => {
+                        Value::Str(format!("{lhs}{}", rhs.str()?).into())
+                    }
+                    (Op::Eq | Op::Ne, Value::Str(lhs)) => {
+                        Value::Int(((lhs == rhs.str()?) == (op == Op::Eq)) as i32)
+                    }
+                    (op, lhs) => Value::Int(binary(op, lhs.int()?, rhs.int()?)?),
+                }
+            }
+            Expr::Assign(..) => {
+                self.statement(expr)?;
+                return Err(Box::new(Fault::AssignValue));
+            }
+            Expr::If(..) | Expr::IfElse(..) => self
+                .statement(expr)?
+                .ok_or_else(|| Box::new(Fault::NoValue))?,
+            Expr::Block(_)
+            | Expr::Let(..)
+            | Expr::Match(_)
+            | Expr::Loop(_)
+            | Expr::Break(..)
+            | Expr::Continue(_) => self.statement(expr)?.ok_or_else(|| Box::new(Fault::Unit))?,
+            Expr::Lambda(lambda) => Value::Fn(Rc::new(Closure {
+                lambda,
+                captured: self.scope.clone(),
+            })),
+            Expr::Call(call) => {
+                let call = &self.ast[call];
+                let callee = self.eval(call.callee)?;
+                let args = call
+                    .args
+                    .iter()
+                    .map(|&arg| self.eval(arg))
+                    .collect::<CResult<Vec<_>>>()?;
+                match callee {
+                    Value::Fn(closure) => self.call(&closure, args)?,
+                    Value::Intrinsic(intrinsic) => self.intrinsic(intrinsic, args)?,
+                    _ => return Err(Box::new(Fault::NotAFunction)),
+                }
+            }
+            Expr::Array(array) => {
+                let elems = self.ast[array]
+                    .iter()
+                    .map(|&elem| self.eval(elem))
+                    .collect::<CResult<Vec<_>>>()?;
+                Value::Array(Rc::new(elems))
+            }
+            Expr::Repeat(elem, len) => {
+                let elem = self.eval(elem)?;
+                Value::Array(Rc::new(vec![elem; len as usize]))
+            }
+            Expr::Index(array, index) => {
+                let array = self.eval(array)?;
+                let index = self.eval(index)?.int()?;
+                let Value::Array(elems) = array else {
+                    return Err(Box::new(Fault::ExpectedArray(array.kind())));
+                };
+                elems[bounds(self.ast.span(expr), elems.len(), index)?].clone()
+            }
+            Expr::StructLit(lit) => {
+                let lit = &self.ast[lit];
+                let fields = lit
+                    .fields
+                    .iter()
+                    .map(|&(name, value)| Ok((name, self.eval(value)?)))
+                    .collect::<CResult<Vec<_>>>()?;
+                Value::Struct(lit.name, Rc::new(fields))
+            }
+            Expr::Field(value, name) => {
+                let mut value = self.eval(value)?;
+                field(self.ast, &mut value, name)?.clone()
+            }
+            Expr::VariantLit(lit) => {
+                let lit = &self.ast[lit];
+                let args = lit
+                    .args
+                    .iter()
+                    .map(|&arg| self.eval(arg))
+                    .collect::<CResult<Vec<_>>>()?;
+                Value::Variant(lit.ty, lit.variant, Rc::new(args))
+            }
+        };
+        Ok(value)
+    }
+
+    /// Stores `value` in the variable, element or field `target` names. The
+    /// indices are all evaluated, left to right, before anything is changed.
+    fn assign(&mut self, target: ExprId, value: Value) -> CResult<()> {
+        let mut places = vec![];
+        let mut place = target;
+        while let Expr::Index(base, _) | Expr::Field(base, _) = self.ast[place] {
+            places.push(place);
+            place = base;
+        }
+        let Expr::Atom(Atom::Id(name)) = self.ast[place] else {
+            unreachable!("the parser only allows places to be assigned to");
+        };
+        let mut path = vec![];
+        for &place in places.iter().rev() {
+            let step = match self.ast[place] {
+                Expr::Index(_, index) => Step::Index(self.eval(index)?.int()?),
+                Expr::Field(_, name) => Step::Field(name),
+                _ => unreachable!(),
+            };
+            path.push((place, step));
+        }
+        let Some(slot) = self.scope.iter().rposition(|(n, _)| *n == name) else {
+            return Err(Box::new(Fault::Unbound(self.ast[name].into())));
+        };
+        let ast = self.ast;
+        let mut slot = &mut self.scope[slot].1;
+        for (place, step) in path {
+            slot = match step {
+                Step::Index(index) => {
+                    let Value::Array(elems) = slot else {
+                        return Err(Box::new(Fault::ExpectedArray(slot.kind())));
+                    };
+                    let elems = Rc::make_mut(elems);
+                    let i = bounds(ast.span(place), elems.len(), index)?;
+                    &mut elems[i]
+                }
+                Step::Field(name) => field(ast, slot, name)?,
+            };
+        }
+        *slot = value;
+        Ok(())
+    }
+
+    fn call(&mut self, closure: &Closure, args: Vec<Value>) -> CResult<Value> {
+        let lambda = &self.ast[closure.lambda];
+        if lambda.params.len() != args.len() {
+            let arity = Fault::Arity(lambda.params.len(), args.len());
+            return Err(Box::new(arity));
+        }
+        if self.depth == MAX_DEPTH {
+            return Err(Box::new(Fault::StackOverflow));
+        }
+        let mut scope = closure.captured.clone();
+        scope.extend(lambda.params.iter().map(|p| p.name).zip(args));
+        let caller = std::mem::replace(&mut self.scope, scope);
+        self.depth += 1;
+        let value = self.eval(lambda.body);
+        self.depth -= 1;
+        self.scope = caller;
+        value
+    }
+
+    /// Calls a function from the prelude.
+    fn intrinsic(&mut self, intrinsic: Intrinsic, args: Vec<Value>) -> CResult<Value> {
+        let arity = intrinsic.arity();
+        if args.len() != arity {
+            return Err(Box::new(Fault::Arity(arity, args.len())));
+        }
+        let mut args = args.into_iter();
+        let mut arg = || args.next().expect("checked the arity above");
+        let value = match intrinsic {
+            Intrinsic::Len => match arg() {
+                Value::Array(elems) => Value::Int(elems.len() as i32),
+                Value::Str(text) => Value::Int(text.len() as i32),
+                value => return Err(Box::new(Fault::ExpectedSized(value.kind()))),
+            },
+            Intrinsic::Print | Intrinsic::Println => {
+                let mut text = match arg() {
+                    Value::Int(i) => i.to_string(),
+                    Value::Str(text) => text.to_string(),
+                    value => return Err(Box::new(Fault::Print(value.kind()))),
+                };
+                if intrinsic == Intrinsic::Println {
+                    text.push('\n');
+                }
+                self.host.write(&text);
+                Value::Unit
+            }
+            Intrinsic::ReadLine => Value::Str(self.host.read_line().unwrap_or_default().into()),
+            Intrinsic::Exit => return Err(Box::new(Exit(arg().int()?))),
+            Intrinsic::Assert if arg().int()? == 0 => return Err(Box::new(Fault::Assert)),
+            Intrinsic::Assert => Value::Unit,
+            Intrinsic::ToString => Value::Str(arg().int()?.to_string().into()),
+            Intrinsic::ParseInt => {
+                let text = arg().str()?;
+                match text.parse() {
+                    Ok(i) => Value::Int(i),
+                    Err(_) => return Err(Box::new(Fault::ParseInt(text))),
+                }
+            }
+            Intrinsic::Slice => {
+                let text = arg().str()?;
+                let (from, to) = (arg().int()?, arg().int()?);
+                match cb_prelude::slice(&text, from, to) {
+                    Ok(slice) => Value::Str(slice.into()),
+                    Err(error) => return Err(Box::new(Fault::Slice(error))),
+                }
+            }
+        };
+        Ok(value)
+    }
+}
+
+/// One step from a variable towards the place an assignment stores into.
+enum Step {
+    Index(i32),
+    Field(Symbol),
+}
+
+/// Checks `index` against the length of the array indexed at `span`.
+fn bounds(span: Span, len: usize, index: i32) -> CResult<usize> {
+    match usize::try_from(index) {
+        Ok(i) if i < len => Ok(i),
+        _ => Err(Box::new(Fault::OutOfBounds { span, len, index })),
+    }
+}
+
+fn binary(op: Op, lhs: i32, rhs: i32) -> CResult<i32> {
+    let value = match op {
+        Op::Plus => lhs.wrapping_add(rhs),
+        Op::Minus => lhs.wrapping_sub(rhs),
+        Op::Mult => lhs.wrapping_mul(rhs),
+        Op::Div if rhs == 0 => return Err(Box::new(Fault::DivideByZero)),
+        Op::Div => lhs.wrapping_div(rhs),
+        Op::Grt => (lhs > rhs) as i32,
+        Op::Les => (lhs < rhs) as i32,
+        Op::Eq => (lhs == rhs) as i32,
+        Op::Ne => (lhs != rhs) as i32,
+    };
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cb_parse::parse;
+
+    fn trun(src: &str) -> Vec<i32> {
+        let ast = parse(src).unwrap();
+        run(&ast).unwrap()
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(trun("1 + 2 * 3"), vec![7]);
+        assert_eq!(trun("(1 + 2) * 3"), vec![9]);
+        assert_eq!(trun("10 - 4 - 3"), vec![3]);
+        assert_eq!(trun("-7 / 2"), vec![-3]);
+    }
+
+    #[test]
+    fn if_else() {
+        assert_eq!(trun("if 1 > 3 { 1 }"), vec![]);
+        assert_eq!(trun("if 1 < 3 { 1 } else { 2 }"), vec![1]);
+        assert_eq!(
+            trun("if 1 > 3 { 1 } else if 2 > 1 { 2 } else { 3 }"),
+            vec![2]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let ast = parse("a + 1").unwrap();
+        assert_eq!(
+            run(&ast).unwrap_err().to_string(),
+            "0:1 unbound identifier 'a'"
+        );
+        let ast = parse("1 / 0").unwrap();
+        assert_eq!(
+            run(&ast).unwrap_err().to_string(),
+            "0:5 attempt to divide by zero"
+        );
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(trun("λ(x: i32) -> i32 { x + 1 }(41)"), vec![42]);
+        assert_eq!(trun("fn(f, x) { f(f(x)) }(fn(y) { y * 3 }, 2)"), vec![18]);
+        assert_eq!(
+            trun("fn(x) { fn(y) { x - y } }(10)(3) fn(x) { fn(x) { x } }(1)(2)"),
+            vec![7, 2]
+        );
+        assert_eq!(trun("fn(b) { if b { 1 } else { 2 } }(0)"), vec![2]);
+    }
+
+    #[test]
+    fn closures_capture_by_value() {
+        let src = "fn(x) { fn(add) { fn(x) { add(x) }(100) }(fn(y) { x + y }) }(1)";
+        assert_eq!(trun(src), vec![101]);
+    }
+
+    #[test]
+    fn function_errors() {
+        let error = |src| run(&parse(src).unwrap()).unwrap_err().to_string();
+        assert_eq!(error("1(2)"), "0:4 called a number as a function");
+        assert_eq!(
+            error("fn(x) { x }(1, 2)"),
+            "0:17 expected 1 arguments but found 2"
+        );
+        assert_eq!(error("fn(x) { x }"), "0:11 cannot print a function");
+        assert_eq!(
+            error("fn(x) { x }(fn() { 1 }) + 1"),
+            "0:27 expected a number but found a function"
+        );
+        assert_eq!(
+            error("fn(f) { f(f) }(fn(f) { f(f) })"),
+            "23:27 calls nested more than 256 deep"
+        );
+    }
+
+    #[test]
+    fn arrays() {
+        assert_eq!(trun("[1, 2, 3][1]"), vec![2]);
+        assert_eq!(trun("[7; 4][3] len([0; 16]) len([])"), vec![7, 16, 0]);
+        assert_eq!(trun("[[1, 2], [3, 4]][1][0]"), vec![3]);
+        assert_eq!(trun("fn(a) { a[0] }([fn(x) { x * 2 }(4)])"), vec![8]);
+        assert_eq!(trun("fn(len) { len }(5)"), vec![5]);
+    }
+
+    #[test]
+    fn assignment() {
+        assert_eq!(
+            trun("let a = [1, 2] let b = a b[1] = 5 a[1] b[1]"),
+            vec![2, 5]
+        );
+        assert_eq!(trun("let x = 1 x = x + 1 x"), vec![2]);
+        assert_eq!(
+            trun("let m = [[0; 2]; 2] m[1][0] = 3 m[1][0] + m[0][0]"),
+            vec![3]
+        );
+        assert_eq!(trun("let f = fn() { x } let x = 1 fn(x) { x }(2)"), vec![2]);
+    }
+
+    #[test]
+    fn loops() {
+        assert_eq!(trun("let s = 0 for i in 0..5 { s = s + i } s"), vec![10]);
+        assert_eq!(trun("let n = 0 while n < 100 { n = n + 7 } n"), vec![105]);
+        assert_eq!(
+            trun("let i = 0 loop { i = i + 1 if i > 3 { break i * 10 } }"),
+            vec![40]
+        );
+        assert_eq!(
+            trun("let s = 0 for i in 0..10 { if (i / 2 * 2) < i { continue } s = s + i } s"),
+            vec![20]
+        );
+        let src = "let n = 0
+            'rows: for i in 0..5 {
+                for j in 0..5 {
+                    if j > i { continue 'rows }
+                    if i > 3 { break 'rows }
+                    n = n + 1
+                }
+            }
+            n";
+        assert_eq!(trun(src), vec![10]);
+        assert_eq!(trun("for i in 3..0 { i } while 0 {}"), vec![]);
+        assert_eq!(trun("let i = 5 for i in 0..2 {} i"), vec![5]);
+        assert_eq!(
+            trun("fn(n) { let a = 1 for i in 0..n { a = a * 2 } a }(10)"),
+            vec![1024]
+        );
+        assert_eq!(
+            trun("let x = loop { break 5 } x (loop { break 1 }) + 1 let y = if x > 3 { 1 } else { 2 } * 3 y"),
+            vec![5, 2, 3]
+        );
+    }
+
+    #[test]
+    fn loop_errors() {
+        let error = |src| run(&parse(src).unwrap()).unwrap_err().to_string();
+        assert_eq!(error("loop { 1 / 0 }"), "7:12 attempt to divide by zero");
+        assert_eq!(
+            error("fn() { while 0 {} }() + 1"),
+            "statement without a value used as a value"
+        );
+        assert_eq!(error("loop { break [1] }"), "0:18 cannot print an array");
+        assert_eq!(
+            error("for i in 0..[1] {}"),
+            "0:18 expected a number but found an array"
+        );
+    }
+
+    #[test]
+    fn structs() {
+        let point = "struct P { x: i32, y: i32 } struct L { a: P, b: [P; 2] }";
+        assert_eq!(
+            trun(&format!("{point} let p = P {{ y: 2, x: 1 }} p.x p . y")),
+            vec![1, 2]
+        );
+        assert_eq!(
+            trun(&format!(
+                "{point} let p = P {{ x: 1, y: 2 }} let l = L {{ a: p, b: [p; 2] }} \
+                 l.b[1].y = 5 l.a.x = 3 l.a.x l.b[0].y l.b[1].y p.x"
+            )),
+            vec![3, 2, 5, 1]
+        );
+        assert_eq!(
+            trun(&format!(
+                "{point} fn(p: P) -> i32 {{ p.x = 9 p.x }}(P {{ x: 1, y: 2 }})"
+            )),
+            vec![9]
+        );
+    }
+
+    #[test]
+    fn struct_errors() {
+        let error = |src: &str| run(&parse(src).unwrap()).unwrap_err().to_string();
+        let point = "struct P { x: i32 }";
+        assert_eq!(
+            error(&format!("{point} P {{ x: 1 }}")),
+            "20:30 cannot print a struct"
+        );
+        assert_eq!(
+            error(&format!("{point} P {{ x: 1 }}.y")),
+            "20:32 struct 'P' has no field 'y'"
+        );
+        assert_eq!(error("[1].x"), "0:5 expected a struct but found an array");
+        assert_eq!(
+            error("let a = [1] a.x = 2"),
+            "12:19 expected a struct but found an array"
+        );
+    }
+
+    #[test]
+    fn enums() {
+        let shape = "enum S { C(i32), R(i32, i32), E }";
+        let area = "let area = fn(s: S) -> i32 { match s { S::C(r) => 3 * r * r, S::R(w, h) => w * h, S::E => 0 } }";
+        assert_eq!(
+            trun(&format!(
+                "{shape} {area} area(S::C(2)) area(S::R(2, 5)) area(S::E)"
+            )),
+            vec![12, 10, 0]
+        );
+        let src = "enum O { N, S(i32) } enum L { E, C(O, i32) } \
+            let f = fn(l) { match l { L::C(O::S(x), y) if x > y => x, L::C(O::S(-1), y) => y, L::C(_, y) => -y, L::E => 0 } } \
+            f(L::C(O::S(5), 1)) f(L::C(O::S(-1), 7)) f(L::C(O::N, 3)) f(L::E)";
+        assert_eq!(trun(src), vec![5, 7, -3, 0]);
+        assert_eq!(
+            trun("let x = 1 for i in 0..4 { match i { 0 => continue, 3 => break, n => x = x * 10 + n } } x"),
+            vec![112]
+        );
+        assert_eq!(
+            trun("let y = 5 match 2 { y if y > 3 => 0, y => y } y"),
+            vec![2, 5]
+        );
+        assert_eq!(
+            trun(&format!(
+                "{shape} let s = S::R(3, 4) let x = match s {{ S::R(w, 4) => w, _ => 0 }} + 1 x \
+                 let f = fn(n) {{ n * 10 }} f(match S::E {{ S::E => 2, _ => 3 }}) match 7 {{ 7 if 0 => 1, _ => 2 }}"
+            )),
+            vec![4, 20, 2]
+        );
+    }
+
+    #[test]
+    fn enum_errors() {
+        let error = |src: &str| run(&parse(src).unwrap()).unwrap_err().to_string();
+        assert_eq!(
+            error("match 3 { 1 => 1, 2 => 2 }"),
+            "0:26 no arm of a 'match' matched"
+        );
+        assert_eq!(error("enum E { A } E::A"), "13:17 cannot print an enum");
+        assert_eq!(
+            error("match 1 { E::A => 1 }"),
+            "0:21 expected an enum but found a number"
+        );
+        assert_eq!(
+            error("fn() { match 1 { 1 => let x = 1 } }() + 1"),
+            "statement without a value used as a value"
+        );
+    }
+
+    #[test]
+    fn array_errors() {
+        let error = |src| run(&parse(src).unwrap()).unwrap_err().to_string();
+        assert_eq!(
+            error("[1, 2, 3][5]"),
+            "0:12 index out of bounds: the length is 3 but the index is 5"
+        );
+        assert_eq!(
+            error("fn(a) { a[0][-1] = 1 }([[2]])"),
+            "8:16 index out of bounds: the length is 1 but the index is -1"
+        );
+        assert_eq!(error("[1]"), "0:3 cannot print an array");
+        assert_eq!(error("1[0]"), "0:4 expected an array but found a number");
+        assert_eq!(error("len(1, 2)"), "0:9 expected 1 arguments but found 2");
+        assert_eq!(
+            error("fn(a) { a[0] = 1 }(1)"),
+            "8:16 expected an array but found a number"
+        );
+        assert_eq!(error("[1] + 1"), "0:7 expected a number but found an array");
+        assert_eq!(
+            error("fn(a) { a[0] = 1 }([2])"),
+            "8:16 assignment used as a value"
+        );
+    }
+
+    /// Everything the program writes, given `input` to read from.
+    fn transcript(src: &str, input: &str) -> CResult<String> {
+        let mut host = Capture::new(input);
+        run_with(&parse(src).unwrap(), &mut host)?;
+        Ok(host.output)
+    }
+
+    #[test]
+    fn prelude() {
+        assert_eq!(
+            transcript("print(1) println(\"a\\tb\") 2 \"s\"", "").unwrap(),
+            "1a\tb\n2\ns\n"
+        );
+        assert_eq!(
+            transcript(
+                "let n = parse_int(read_line()) println(to_string(n * 2)) read_line() read_line()",
+                "21\nend"
+            )
+            .unwrap(),
+            "42\nend\n\n"
+        );
+        assert_eq!(
+            transcript("assert(1) println(1) exit(7) println(2)", "")
+                .unwrap_err()
+                .to_string(),
+            "exited with code 7"
+        );
+        assert_eq!(trun("let println = fn(x) { x } println(3)"), vec![3]);
+        assert_eq!(trun("println(1) fn() { print(2) }()"), vec![]);
+    }
+
+    #[test]
+    fn prelude_errors() {
+        let error = |src| run(&parse(src).unwrap()).unwrap_err().to_string();
+        assert_eq!(error("1 assert(1 > 2)"), "2:15 assertion failed");
+        assert_eq!(
+            error("parse_int(\"1\\n\")"),
+            "0:16 cannot parse \"1\\n\" as an integer"
+        );
+        assert_eq!(error("print([1])"), "0:10 cannot print an array");
+        assert_eq!(
+            error("print(1, 2)"),
+            "0:11 expected 1 arguments but found 2"
+        );
+        assert_eq!(
+            error("1 + print(1)"),
+            "0:12 expected a number but found no value"
+        );
+        let exit = run(&parse("exit(3)").unwrap()).unwrap_err();
+        assert_eq!(exit.downcast_ref::<Exit>(), Some(&Exit(3)));
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(
+            transcript(
+                "let s = \"hé\" + to_string(4 + 2) println(s) len(s) slice(s, 1, 3) slice(s, 4, 4) + \"!\"",
+                ""
+            )
+            .unwrap(),
+            "hé6\n4\né\n!\n"
+        );
+        assert_eq!(
+            transcript(
+                "loop { break \"a\" } loop { if 0 { break \"b\" } break \"c\" }",
+                ""
+            )
+            .unwrap(),
+            "a\nc\n"
+        );
+        assert_eq!(
+            trun("(\"ab\" == (\"a\" + \"b\")) (\"a\" != \"b\") (\"a\" == \"b\") (1 == 1) (1 != 1)"),
+            vec![1, 1, 0, 1, 0]
+        );
+        assert_eq!(
+            transcript("let line = read_line() len(line) line == \"\"", "").unwrap(),
+            "0\n1\n"
+        );
+    }
+
+    #[test]
+    fn string_errors() {
+        let error = |src| run(&parse(src).unwrap()).unwrap_err().to_string();
+        assert_eq!(
+            error("slice(\"abc\", 2, 4)"),
+            "0:18 slice out of bounds: the length is 3 but the range is 2..4"
+        );
+        assert_eq!(
+            error("slice(\"é\", 0, 1)"),
+            "0:17 byte index 1 is not a char boundary"
+        );
+        assert_eq!(
+            error("\"a\" + 1"),
+            "0:7 expected a string but found a number"
+        );
+        assert_eq!(
+            error("len(1)"),
+            "0:6 expected an array or a string but found a number"
+        );
+    }
+
+    #[test]
+    fn session() {
+        let mut session = Session::new();
+        let mut host = Capture::default();
+        let mut src = String::new();
+        for (line, ok) in [
+            ("let n = 2 let f = fn(x) { x * n }", true),
+            ("n = n + 1 f(2)", true),
+            ("let g = f n = 10 g(1) 1 / 0", false),
+            ("match n { 3 => f(n), _ => 0 } g", false),
+            ("f(1) + n", true),
+        ] {
+            let program = format!("{src}{line}\n");
+            let result = session.run(&parse(&program).unwrap(), &mut host);
+            assert_eq!(result.is_ok(), ok, "{line}");
+            if ok {
+                src = program;
+            }
+        }
+        // The failed lines printed what they did before failing, but neither
+        // `g` nor the assignment to `n` survived them.
+        assert_eq!(host.values, vec![4, 2, 6, 5]);
+    }
+}
@@ -0,0 +1,43 @@
+use cb_parse::parse;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use std::hint::black_box;
+
+/// Programs both backends run, named for what they spend their time on.
+/// The JIT's times include compiling the program.
+const PROGRAMS: [(&str, &str); 3] = [
+    (
+        "arithmetic",
+        "let i = 0 let s = 0 while i < 100000 { s = s + i * 7 / 3 - i i = i + 1 } s",
+    ),
+    (
+        "nested_loops",
+        "let s = 0 'outer: for i in 0..300 { for j in 0..300 {\n\
+         if j > i { continue 'outer } if (i + j) / 2 * 2 == i + j { s = s + 1 } } } s",
+    ),
+    (
+        "strings",
+        "let s = \"\" for i in 0..2000 { s = s + to_string(i) } len(s)",
+    ),
+];
+
+fn backends(c: &mut Criterion) {
+    let mut group = c.benchmark_group("backends");
+    for (name, src) in PROGRAMS {
+        let ast = parse(src).unwrap();
+        let types = cb_typeck::check(&ast).unwrap();
+        assert_eq!(
+            cb_jit::run(&ast, &types).unwrap(),
+            cb_interp::run(&ast).unwrap()
+        );
+        group.bench_with_input(BenchmarkId::new("interp", name), &ast, |b, ast| {
+            b.iter(|| cb_interp::run(black_box(ast)).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("jit", name), &ast, |b, ast| {
+            b.iter(|| cb_jit::run(black_box(ast), &types).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, backends);
+criterion_main!(benches);
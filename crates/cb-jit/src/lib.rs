@@ -0,0 +1,883 @@
+#[cfg(test)]
+mod test;
+
+use cb_parse::{Ast, Atom, Expr, ExprId, Loop, LoopId, LoopKind, Op, SideTable, Stmt, Symbol};
+use cb_prelude::{Capture, Exit, Host, Intrinsic, SliceError};
+use cb_typeck::Type as CheckedType;
+use cranelift_codegen::entity::EntityRef;
+use cranelift_codegen::ir::{
+    condcodes::IntCC, types, AbiParam, Block, FuncRef, InstBuilder, Type, Value,
+};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, Linkage, Module};
+use std::fmt;
+
+type CResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+/// JIT compiles a program to native code, given the types that
+/// [`cb_typeck::check`] found for its expressions, runs it and returns the
+/// value of every top level expression in order, just like `cb_interp::run`.
+pub fn run(ast: &Ast, types: &SideTable<ExprId, CheckedType>) -> CResult<Vec<i32>> {
+    let mut host = Capture::default();
+    run_with(ast, types, &mut host)?;
+    Ok(host.values)
+}
+
+/// Like [`run`], with the program's input and output going to `host`, like
+/// `cb_interp::run_with`. Of the prelude, `print` and `println` of integers
+/// and string literals, `exit` and `assert` are supported, as are strings:
+/// the code holds handles to strings that the host keeps until the run ends.
+/// Variables, which hold numbers or such handles, are Cranelift variables,
+/// and loops are blocks that jump back to their start.
+pub fn run_with(
+    ast: &Ast,
+    types: &SideTable<ExprId, CheckedType>,
+    host: &mut dyn Host,
+) -> CResult<()> {
+    let mut flags = settings::builder();
+    flags.set("use_colocated_libcalls", "false")?;
+    flags.set("is_pic", "false")?;
+    let isa = cranelift_native::builder()?.finish(settings::Flags::new(flags))?;
+    let mut builder = JITBuilder::with_isa(isa, default_libcall_names());
+    builder.symbol("cb_print", cb_print as *const u8);
+    builder.symbol("cb_divide_by_zero", cb_divide_by_zero as *const u8);
+    builder.symbol("cb_write", cb_write as *const u8);
+    builder.symbol("cb_write_int", cb_write_int as *const u8);
+    builder.symbol("cb_exit", cb_exit as *const u8);
+    builder.symbol("cb_assert_failed", cb_assert_failed as *const u8);
+    builder.symbol("cb_string", cb_string as *const u8);
+    builder.symbol("cb_concat", cb_concat as *const u8);
+    builder.symbol("cb_equal", cb_equal as *const u8);
+    builder.symbol("cb_len", cb_len as *const u8);
+    builder.symbol("cb_slice", cb_slice as *const u8);
+    builder.symbol("cb_to_string", cb_to_string as *const u8);
+    builder.symbol("cb_parse_int", cb_parse_int as *const u8);
+    builder.symbol("cb_read_line", cb_read_line as *const u8);
+    builder.symbol("cb_write_string", cb_write_string as *const u8);
+    builder.symbol("cb_failed", cb_failed as *const u8);
+    let mut module = JITModule::new(builder);
+
+    let ptr = module.target_config().pointer_type();
+    let mut ctx = module.make_context();
+    ctx.func.signature.params.push(AbiParam::new(ptr));
+
+    let mut fn_ctx = FunctionBuilderContext::new();
+    let mut builder = FunctionBuilder::new(&mut ctx.func, &mut fn_ctx);
+    let entry = builder.create_block();
+    builder.append_block_params_for_function_params(entry);
+    builder.switch_to_block(entry);
+    builder.seal_block(entry);
+    // Every callback takes the state first.
+    let mut import = |name: &str, params: &[Type], returns: &[Type]| -> CResult<FuncRef> {
+        let mut sig = module.make_signature();
+        sig.params.push(AbiParam::new(ptr));
+        sig.params
+            .extend(params.iter().map(|&ty| AbiParam::new(ty)));
+        sig.returns
+            .extend(returns.iter().map(|&ty| AbiParam::new(ty)));
+        let func = module.declare_function(name, Linkage::Import, &sig)?;
+        Ok(module.declare_func_in_func(func, builder.func))
+    };
+    let (i32, handle) = (types::I32, types::I32);
+    let print = import("cb_print", &[i32], &[])?;
+    let trap = import("cb_divide_by_zero", &[], &[])?;
+    let write = import("cb_write", &[ptr, ptr], &[])?;
+    let write_int = import("cb_write_int", &[i32], &[])?;
+    let exit = import("cb_exit", &[i32], &[])?;
+    let assert_failed = import("cb_assert_failed", &[], &[])?;
+    let strings = Strings {
+        new: import("cb_string", &[ptr, ptr], &[handle])?,
+        concat: import("cb_concat", &[handle, handle], &[handle])?,
+        equal: import("cb_equal", &[handle, handle], &[i32])?,
+        len: import("cb_len", &[handle], &[i32])?,
+        slice: import("cb_slice", &[handle, i32, i32], &[handle])?,
+        to_string: import("cb_to_string", &[i32], &[handle])?,
+        parse_int: import("cb_parse_int", &[handle], &[i32])?,
+        read_line: import("cb_read_line", &[], &[handle])?,
+        write: import("cb_write_string", &[handle], &[])?,
+        failed: import("cb_failed", &[], &[i32])?,
+    };
+    let state = builder.block_params(entry)[0];
+    let mut codegen = CodeGen {
+        ast,
+        types,
+        builder,
+        state,
+        ptr,
+        print,
+        trap,
+        write,
+        write_int,
+        exit,
+        assert_failed,
+        strings,
+        vars: vec![],
+        var_count: 0,
+        loops: vec![],
+        texts: vec![],
+    };
+    for &stmt in ast.program() {
+        // A struct or enum declaration only names a type, so it generates no
+        // code.
+        if let Stmt::Expr(expr) = ast[stmt] {
+            codegen.statement(expr, Sink::Print)?;
+        }
+    }
+    codegen.builder.ins().return_(&[]);
+    codegen.builder.finalize();
+    // The code points into these, so they must outlive the run.
+    let texts = codegen.texts;
+
+    let main = module.declare_function("main", Linkage::Export, &ctx.func.signature)?;
+    module.define_function(main, &mut ctx)?;
+    module.clear_context(&mut ctx);
+    module.finalize_definitions()?;
+
+    let mut state = State {
+        host,
+        error: None,
+        exit: None,
+        strings: vec![],
+    };
+    let code = module.get_finalized_function(main);
+    // SAFETY: `main` was just compiled with the signature `fn(*mut State)`
+    // and only hands the pointer back to the callbacks below. The texts it
+    // points into are alive until after it returns.
+    unsafe {
+        let main = std::mem::transmute::<*const u8, extern "C" fn(*mut State)>(code);
+        main(&mut state);
+        module.free_memory();
+    }
+    drop(texts);
+    match (state.error, state.exit) {
+        (Some(e), _) => Err(Box::new(e)),
+        (None, Some(code)) => Err(Box::new(Exit(code))),
+        (None, None) => Ok(()),
+    }
+}
+
+#[derive(Debug)]
+enum JitError {
+    Unit,
+    /// A feature only the interpreter runs so far, named in the plural.
+    Unsupported(&'static str),
+    DivideByZero,
+    AssertionFailed,
+    ParseInt(String),
+    Slice(SliceError),
+}
+
+impl fmt::Display for JitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unit => write!(f, "statement without a value used as a value"),
+            Self::Unsupported(what) => write!(f, "{what} are only supported by the interpreter"),
+            Self::DivideByZero => write!(f, "attempt to divide by zero"),
+            Self::AssertionFailed => write!(f, "{}", cb_prelude::ASSERTION_FAILED),
+            Self::ParseInt(text) => write!(f, "cannot parse {text:?} as an integer"),
+            Self::Slice(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for JitError {}
+
+/// What the compiled code can observe from the host while it runs.
+struct State<'a> {
+    host: &'a mut dyn Host,
+    error: Option<JitError>,
+    exit: Option<i32>,
+    /// Every string the program made, which the code refers to by index.
+    /// They are all freed when the run ends.
+    strings: Vec<String>,
+}
+
+impl State<'_> {
+    fn string(&mut self, text: String) -> i32 {
+        self.strings.push(text);
+        self.strings.len() as i32 - 1
+    }
+}
+
+// Each callback's `state` is the `&mut State` that `run_with` passes to
+// `main`, and `run_with` does not touch the state again until `main`
+// returns, so for the length of a call the callback holds the only access.
+
+extern "C" fn cb_print(state: *mut State, value: i32) {
+    // SAFETY: `state` is valid and unaliased for this call, and only its
+    // `host` is borrowed, to pass it the value.
+    unsafe { (*state).host.value(value) }
+}
+
+extern "C" fn cb_write(state: *mut State, text: *const u8, len: usize) {
+    // SAFETY: `text` and `len` are the address and length of a `Box<str>` in
+    // `CodeGen::texts`, which `run_with` keeps until `main` has returned, so
+    // they make a live, valid UTF-8 slice. `state` is valid and unaliased,
+    // and only its `host` is borrowed.
+    unsafe {
+        let text = std::str::from_utf8_unchecked(std::slice::from_raw_parts(text, len));
+        (*state).host.write(text)
+    }
+}
+
+extern "C" fn cb_write_int(state: *mut State, value: i32) {
+    // SAFETY: `state` is valid and unaliased for this call; the text is made
+    // before `host` is borrowed to write it.
+    unsafe { (*state).host.write(&value.to_string()) }
+}
+
+extern "C" fn cb_exit(state: *mut State, code: i32) {
+    // SAFETY: `state` is valid and unaliased for this call, and `exit` is a
+    // plain value that nothing borrows.
+    unsafe { (*state).exit = Some(code) }
+}
+
+extern "C" fn cb_assert_failed(state: *mut State) {
+    // SAFETY: `state` is valid and unaliased for this call, and nothing
+    // borrows `error` while it is replaced.
+    unsafe { (*state).error = Some(JitError::AssertionFailed) }
+}
+
+extern "C" fn cb_divide_by_zero(state: *mut State) {
+    // SAFETY: as in `cb_assert_failed`, only `error` is written, and nothing
+    // borrows it.
+    unsafe { (*state).error = Some(JitError::DivideByZero) }
+}
+
+// The string callbacks below take handles that the state gave out. A handle
+// is only ever used to index `strings`, which checks it.
+
+extern "C" fn cb_string(state: *mut State, text: *const u8, len: usize) -> i32 {
+    // SAFETY: as in `cb_write`, `text` and `len` are those of a text that
+    // outlives `main`, and are only read here, to copy them into `strings`.
+    unsafe {
+        let text = std::str::from_utf8_unchecked(std::slice::from_raw_parts(text, len));
+        (*state).string(text.to_string())
+    }
+}
+
+extern "C" fn cb_concat(state: *mut State, lhs: i32, rhs: i32) -> i32 {
+    // SAFETY: `state` is valid and unaliased, so it may be borrowed mutably
+    // for the whole call. Both strings are copied out before the new one is
+    // pushed.
+    let state = unsafe { &mut *state };
+    let text = format!(
+        "{}{}",
+        state.strings[lhs as usize], state.strings[rhs as usize]
+    );
+    state.string(text)
+}
+
+extern "C" fn cb_equal(state: *mut State, lhs: i32, rhs: i32) -> i32 {
+    // SAFETY: `state` is valid, and this call only reads from it.
+    let state = unsafe { &*state };
+    (state.strings[lhs as usize] == state.strings[rhs as usize]) as i32
+}
+
+extern "C" fn cb_len(state: *mut State, text: i32) -> i32 {
+    // SAFETY: `state` is valid, and this only reads one of its strings.
+    unsafe { (&(*state).strings)[text as usize].len() as i32 }
+}
+
+extern "C" fn cb_slice(state: *mut State, text: i32, from: i32, to: i32) -> i32 {
+    // SAFETY: `state` is valid and unaliased for the whole call. The slice
+    // is copied before it is pushed, so no borrow of `strings` is alive
+    // when `strings` grows.
+    let state = unsafe { &mut *state };
+    match cb_prelude::slice(&state.strings[text as usize], from, to) {
+        Ok(slice) => {
+            let slice = slice.to_string();
+            state.string(slice)
+        }
+        Err(e) => {
+            state.error = Some(JitError::Slice(e));
+            0
+        }
+    }
+}
+
+extern "C" fn cb_to_string(state: *mut State, value: i32) -> i32 {
+    // SAFETY: `state` is valid and unaliased, and the only access is the
+    // push onto `strings`.
+    unsafe { (*state).string(value.to_string()) }
+}
+
+extern "C" fn cb_parse_int(state: *mut State, text: i32) -> i32 {
+    // SAFETY: `state` is valid and unaliased for the whole call. The text
+    // borrowed from `strings` is a different field from `error`, the only
+    // one written.
+    let state = unsafe { &mut *state };
+    let text = &state.strings[text as usize];
+    match text.parse() {
+        Ok(value) => value,
+        Err(_) => {
+            state.error = Some(JitError::ParseInt(text.clone()));
+            0
+        }
+    }
+}
+
+extern "C" fn cb_read_line(state: *mut State) -> i32 {
+    // SAFETY: `state` is valid and unaliased for the whole call; the borrow
+    // of `host` ends before the line is pushed onto `strings`.
+    let state = unsafe { &mut *state };
+    let line = state.host.read_line().unwrap_or_default();
+    state.string(line)
+}
+
+extern "C" fn cb_write_string(state: *mut State, text: i32) {
+    // SAFETY: `state` is valid and unaliased for the whole call; `host` is
+    // borrowed mutably and `strings` only read, and they are separate
+    // fields.
+    let state = unsafe { &mut *state };
+    state.host.write(&state.strings[text as usize])
+}
+
+extern "C" fn cb_failed(state: *mut State) -> i32 {
+    // SAFETY: `state` is valid, and this only reads `error`.
+    unsafe { (*state).error.is_some() as i32 }
+}
+
+/// The callbacks that work with strings.
+struct Strings {
+    new: FuncRef,
+    concat: FuncRef,
+    equal: FuncRef,
+    len: FuncRef,
+    slice: FuncRef,
+    to_string: FuncRef,
+    parse_int: FuncRef,
+    read_line: FuncRef,
+    write: FuncRef,
+    /// Whether the last of the others failed, which stops the program.
+    failed: FuncRef,
+}
+
+struct CodeGen<'a> {
+    ast: &'a Ast,
+    /// The type the checker gave each expression.
+    types: &'a SideTable<ExprId, CheckedType>,
+    builder: FunctionBuilder<'a>,
+    state: Value,
+    /// The type of pointers, and so of the lengths of strings.
+    ptr: Type,
+    print: FuncRef,
+    trap: FuncRef,
+    write: FuncRef,
+    write_int: FuncRef,
+    exit: FuncRef,
+    assert_failed: FuncRef,
+    strings: Strings,
+    /// Variables in scope, innermost last.
+    vars: Vec<Var>,
+    /// How many Cranelift variables have been declared, counting the hidden
+    /// ones of `for` loops.
+    var_count: usize,
+    /// The loops being compiled, innermost last.
+    loops: Vec<Frame>,
+    /// The text of string literals and of what is written as it is, which
+    /// the code points into. Each is boxed, so it stays put as more are
+    /// added.
+    texts: Vec<Box<str>>,
+}
+
+/// What the instructions for a statement do with the value they compute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sink {
+    /// It is printed, as the value of a top level expression is.
+    Print,
+    /// It is dropped, as the value of a statement in a block other than the
+    /// last is.
+    Drop,
+}
+
+struct Var {
+    name: Symbol,
+    var: Variable,
+}
+
+/// A loop, with the blocks that `continue` and `break` jump to.
+struct Frame {
+    label: Option<Symbol>,
+    next: Block,
+    exit: Block,
+}
+
+impl CodeGen<'_> {
+    fn statement(&mut self, expr: ExprId, sink: Sink) -> CResult<()> {
+        match self.ast[expr] {
+            Expr::If(c, b) => {
+                let then_block = self.builder.create_block();
+                let merge_block = self.builder.create_block();
+                let c = self.expression(c)?;
+                self.builder
+                    .ins()
+                    .brif(c, then_block, &[], merge_block, &[]);
+                self.builder.switch_to_block(then_block);
+                self.builder.seal_block(then_block);
+                self.statement(b, sink)?;
+                self.builder.ins().jump(merge_block, &[]);
+                self.builder.switch_to_block(merge_block);
+                self.builder.seal_block(merge_block);
+            }
+            Expr::IfElse(c, b1, b2) => {
+                let then_block = self.builder.create_block();
+                let else_block = self.builder.create_block();
+                let merge_block = self.builder.create_block();
+                let c = self.expression(c)?;
+                self.builder.ins().brif(c, then_block, &[], else_block, &[]);
+                self.builder.switch_to_block(then_block);
+                self.builder.seal_block(then_block);
+                self.statement(b1, sink)?;
+                self.builder.ins().jump(merge_block, &[]);
+                self.builder.switch_to_block(else_block);
+                self.builder.seal_block(else_block);
+                self.statement(b2, sink)?;
+                self.builder.ins().jump(merge_block, &[]);
+                self.builder.switch_to_block(merge_block);
+                self.builder.seal_block(merge_block);
+            }
+            Expr::Block(block) => {
+                let depth = self.vars.len();
+                let stmts = &self.ast[block];
+                for (i, &stmt) in stmts.iter().enumerate() {
+                    let sink = if i + 1 == stmts.len() {
+                        sink
+                    } else {
+                        Sink::Drop
+                    };
+                    self.statement(stmt, sink)?;
+                }
+                self.vars.truncate(depth);
+            }
+            Expr::Let(name, value) => {
+                let value = self.expression(value)?;
+                let var = self.declare(types::I32);
+                self.builder.def_var(var, value);
+                self.vars.push(Var { name, var });
+            }
+            Expr::Assign(target, value) => self.assign(target, value)?,
+            Expr::Loop(id) => self.run_loop(id)?,
+            Expr::Break(_, Some(_)) => return Err(Box::new(JitError::Unsupported("loop values"))),
+            Expr::Break(label, None) => {
+                let exit = self.target(label).exit;
+                self.jump(exit);
+            }
+            Expr::Continue(label) => {
+                let next = self.target(label).next;
+                self.jump(next);
+            }
+            Expr::Call(call) if self.is_unit(expr) => {
+                let call = &self.ast[call];
+                let Some(intrinsic) = self.intrinsic(call.callee) else {
+                    unreachable!("only calls to the prelude are unit");
+                };
+                self.intrinsic_call(intrinsic, &call.args)?;
+            }
+            _ if sink == Sink::Drop => {
+                self.expression(expr)?;
+            }
+            Expr::Atom(Atom::Str(text)) => self.write(&format!("{}\n", &self.ast[text])),
+            _ if self.types.get(expr) == Some(&CheckedType::String) => {
+                let text = self.expression(expr)?;
+                self.builder
+                    .ins()
+                    .call(self.strings.write, &[self.state, text]);
+                self.write("\n");
+            }
+            _ => {
+                let value = self.expression(expr)?;
+                self.builder.ins().call(self.print, &[self.state, value]);
+            }
+        }
+        Ok(())
+    }
+
+    fn declare(&mut self, ty: Type) -> Variable {
+        let var = Variable::new(self.var_count);
+        self.var_count += 1;
+        self.builder.declare_var(var, ty);
+        var
+    }
+
+    fn lookup(&self, name: Symbol) -> Option<&Var> {
+        self.vars.iter().rev().find(|var| var.name == name)
+    }
+
+    fn assign(&mut self, target: ExprId, value: ExprId) -> CResult<()> {
+        let name = match self.ast[target] {
+            Expr::Atom(Atom::Id(name)) => name,
+            Expr::Index(..) => return Err(Box::new(JitError::Unsupported("arrays"))),
+            Expr::Field(..) => return Err(Box::new(JitError::Unsupported("structs"))),
+            _ => unreachable!("the parser only allows places to be assigned to"),
+        };
+        let value = self.expression(value)?;
+        let Some(var) = self.lookup(name) else {
+            unreachable!("the type checker binds '{}'", &self.ast[name]);
+        };
+        let var = var.var;
+        self.builder.def_var(var, value);
+        Ok(())
+    }
+
+    /// Compiles a loop. The bounds of a `for` are kept in hidden variables,
+    /// and its variable is a copy of the counter so that assigning to it
+    /// only changes the current iteration, as in the interpreter.
+    fn run_loop(&mut self, id: LoopId) -> CResult<()> {
+        let Loop { label, kind, body } = self.ast[id];
+        let header = self.builder.create_block();
+        let body_block = self.builder.create_block();
+        let exit = self.builder.create_block();
+        let depth = self.vars.len();
+        let next = match kind {
+            LoopKind::Loop => {
+                self.builder.ins().jump(header, &[]);
+                self.builder.switch_to_block(header);
+                self.builder.ins().jump(body_block, &[]);
+                header
+            }
+            LoopKind::While(c) => {
+                self.builder.ins().jump(header, &[]);
+                self.builder.switch_to_block(header);
+                let c = self.expression(c)?;
+                self.builder.ins().brif(c, body_block, &[], exit, &[]);
+                header
+            }
+            LoopKind::For(name, from, to) => {
+                let from = self.expression(from)?;
+                let to = self.expression(to)?;
+                let (counter, end) = (self.declare(types::I32), self.declare(types::I32));
+                self.builder.def_var(counter, from);
+                self.builder.def_var(end, to);
+                self.builder.ins().jump(header, &[]);
+                self.builder.switch_to_block(header);
+                let (i, end) = (self.builder.use_var(counter), self.builder.use_var(end));
+                let more = self.builder.ins().icmp(IntCC::SignedLessThan, i, end);
+                self.builder.ins().brif(more, body_block, &[], exit, &[]);
+                // `continue` goes on with the next value of the counter.
+                let next = self.builder.create_block();
+                self.builder.switch_to_block(next);
+                let i_next = self.builder.use_var(counter);
+                let i_next = self.builder.ins().iadd_imm(i_next, 1);
+                self.builder.def_var(counter, i_next);
+                self.builder.ins().jump(header, &[]);
+                self.builder.switch_to_block(body_block);
+                let var = self.declare(types::I32);
+                self.builder.def_var(var, i);
+                self.vars.push(Var { name, var });
+                next
+            }
+        };
+        if next == header {
+            self.builder.switch_to_block(body_block);
+        }
+        self.builder.seal_block(body_block);
+        self.loops.push(Frame { label, next, exit });
+        let result = self.statement(body, Sink::Drop);
+        self.loops.pop();
+        result?;
+        self.vars.truncate(depth);
+        self.builder.ins().jump(next, &[]);
+        if next != header {
+            self.builder.seal_block(next);
+        }
+        self.builder.seal_block(header);
+        self.builder.switch_to_block(exit);
+        self.builder.seal_block(exit);
+        Ok(())
+    }
+
+    /// The innermost loop, or the one with `label`, which the parser made
+    /// sure exists.
+    fn target(&self, label: Option<Symbol>) -> &Frame {
+        let frame = match label {
+            Some(label) => self.loops.iter().rev().find(|l| l.label == Some(label)),
+            None => self.loops.last(),
+        };
+        frame.expect("the parser only allows jumps inside loops")
+    }
+
+    /// Jumps to `block`. Anything after the jump is never run.
+    fn jump(&mut self, block: Block) {
+        self.builder.ins().jump(block, &[]);
+        let after = self.builder.create_block();
+        self.builder.switch_to_block(after);
+        self.builder.seal_block(after);
+    }
+
+    /// Calls the host for `print`, `println`, `exit` or `assert`.
+    fn intrinsic_call(&mut self, intrinsic: Intrinsic, args: &[ExprId]) -> CResult<()> {
+        let &[arg] = args else {
+            return Err(Box::new(JitError::Unsupported("functions")));
+        };
+        match (intrinsic, self.ast[arg]) {
+            (Intrinsic::Print, Expr::Atom(Atom::Str(text))) => self.write(&self.ast[text]),
+            (Intrinsic::Println, Expr::Atom(Atom::Str(text))) => {
+                self.write(&format!("{}\n", &self.ast[text]))
+            }
+            (Intrinsic::Print | Intrinsic::Println, _) => {
+                let value = self.expression(arg)?;
+                let write = match self.types.get(arg) == Some(&CheckedType::String) {
+                    true => self.strings.write,
+                    false => self.write_int,
+                };
+                self.builder.ins().call(write, &[self.state, value]);
+                if intrinsic == Intrinsic::Println {
+                    self.write("\n");
+                }
+            }
+            (Intrinsic::Exit, _) => {
+                let code = self.expression(arg)?;
+                self.builder.ins().call(self.exit, &[self.state, code]);
+                self.builder.ins().return_(&[]);
+                // Anything after `exit` is never run.
+                let after = self.builder.create_block();
+                self.builder.switch_to_block(after);
+                self.builder.seal_block(after);
+            }
+            (Intrinsic::Assert, _) => {
+                let c = self.expression(arg)?;
+                let fail_block = self.builder.create_block();
+                let ok_block = self.builder.create_block();
+                self.builder.ins().brif(c, ok_block, &[], fail_block, &[]);
+                self.builder.switch_to_block(fail_block);
+                self.builder.seal_block(fail_block);
+                self.builder.ins().call(self.assert_failed, &[self.state]);
+                self.builder.ins().return_(&[]);
+                self.builder.switch_to_block(ok_block);
+                self.builder.seal_block(ok_block);
+            }
+            _ => unreachable!("'{intrinsic}' has a value"),
+        }
+        Ok(())
+    }
+
+    /// Writes `text`, a copy of which is kept until the run ends.
+    fn write(&mut self, text: &str) {
+        let (ptr, len) = self.text(text);
+        self.builder.ins().call(self.write, &[self.state, ptr, len]);
+    }
+
+    /// Keeps a copy of `text` for the run and returns its address and
+    /// length.
+    fn text(&mut self, text: &str) -> (Value, Value) {
+        let text = Box::<str>::from(text);
+        let ptr = self.builder.ins().iconst(self.ptr, text.as_ptr() as i64);
+        let len = self.builder.ins().iconst(self.ptr, text.len() as i64);
+        self.texts.push(text);
+        (ptr, len)
+    }
+
+    /// The callback `intrinsic` calls, with the value it returns, and
+    /// whether it can fail.
+    fn callback(&self, intrinsic: Intrinsic) -> (FuncRef, bool) {
+        match intrinsic {
+            Intrinsic::Len => (self.strings.len, false),
+            Intrinsic::ToString => (self.strings.to_string, false),
+            Intrinsic::ReadLine => (self.strings.read_line, false),
+            Intrinsic::ParseInt => (self.strings.parse_int, true),
+            Intrinsic::Slice => (self.strings.slice, true),
+            _ => unreachable!("'{intrinsic}' has no value"),
+        }
+    }
+
+    /// Calls a callback that returns a value.
+    fn call(&mut self, func: FuncRef, args: &[Value]) -> Value {
+        let args = [&[self.state], args].concat();
+        let call = self.builder.ins().call(func, &args);
+        self.builder.inst_results(call)[0]
+    }
+
+    /// Stops the program if the last callback failed.
+    fn check(&mut self) {
+        let failed = self.call(self.strings.failed, &[]);
+        let fail_block = self.builder.create_block();
+        let ok_block = self.builder.create_block();
+        self.builder
+            .ins()
+            .brif(failed, fail_block, &[], ok_block, &[]);
+        self.builder.switch_to_block(fail_block);
+        self.builder.seal_block(fail_block);
+        self.builder.ins().return_(&[]);
+        self.builder.switch_to_block(ok_block);
+        self.builder.seal_block(ok_block);
+    }
+
+    fn expression(&mut self, expr: ExprId) -> CResult<Value> {
+        let ast = self.ast;
+        let value = match ast[expr] {
+            Expr::Atom(Atom::Int(i)) => self.builder.ins().iconst(types::I32, i64::from(i)),
+            Expr::Atom(Atom::Id(id)) => match self.lookup(id) {
+                Some(var) => self.builder.use_var(var.var),
+                None if Intrinsic::lookup(&ast[id]).is_some() => {
+                    return Err(Box::new(JitError::Unsupported("functions")))
+                }
+                None => unreachable!("the type checker binds '{}'", &ast[id]),
+            },
+            Expr::Atom(Atom::Str(text)) => {
+                let (ptr, len) = self.text(&ast[text]);
+                self.call(self.strings.new, &[ptr, len])
+            }
+            Expr::Call(call) => {
+                let (callee, args) = (ast[call].callee, &ast[call].args);
+                let error = match (self.intrinsic(callee), ast[callee]) {
+                    (Some(Intrinsic::Len), _)
+                        if args.first().and_then(|&arg| self.types.get(arg))
+                            != Some(&CheckedType::String) =>
+                    {
+                        JitError::Unsupported("arrays")
+                    }
+                    (Some(i), _) if i.is_unit() => JitError::Unit,
+                    (Some(i), _) if args.len() != i.arity() => JitError::Unsupported("functions"),
+                    (Some(i), _) => {
+                        let args = args
+                            .iter()
+                            .map(|&arg| self.expression(arg))
+                            .collect::<CResult<Vec<_>>>()?;
+                        let (func, fallible) = self.callback(i);
+                        let value = self.call(func, &args);
+                        if fallible {
+                            self.check();
+                        }
+                        return Ok(value);
+                    }
+                    (None, _) => JitError::Unsupported("functions"),
+                };
+                return Err(Box::new(error));
+            }
+            Expr::Unary(Op::Minus, rhs) => {
+                let rhs = self.expression(rhs)?;
+                self.builder.ins().ineg(rhs)
+            }
+            Expr::Unary(op, _) => unreachable!("'{op}' is not a unary operator"),
+            Expr::Binary(op, lhs, rhs) => {
+                let strings = self.types.get(lhs) == Some(&CheckedType::String);
+                let lhs = self.expression(lhs)?;
+                let rhs = self.expression(rhs)?;
+                match (strings, op) {
+                    (false, _) => self.binary(op, lhs, rhs),
+                    (true, Op::Plus) => self.call(self.strings.concat, &[lhs, rhs]),
+                    (true, _) => {
+                        let equal = self.call(self.strings.equal, &[lhs, rhs]);
+                        match op {
+                            Op::Eq => equal,
+                            _ => self.builder.ins().bxor_imm(equal, 1),
+                        }
+                    }
+                }
+            }
+            Expr::IfElse(c, b1, b2) => {
+                let then_block = self.builder.create_block();
+                let else_block = self.builder.create_block();
+                let merge_block = self.builder.create_block();
+                self.builder.append_block_param(merge_block, types::I32);
+                let c = self.expression(c)?;
+                self.builder.ins().brif(c, then_block, &[], else_block, &[]);
+                self.builder.switch_to_block(then_block);
+                self.builder.seal_block(then_block);
+                let value = self.expression(b1)?;
+                self.builder.ins().jump(merge_block, &[value]);
+                self.builder.switch_to_block(else_block);
+                self.builder.seal_block(else_block);
+                let value = self.expression(b2)?;
+                self.builder.ins().jump(merge_block, &[value]);
+                self.builder.switch_to_block(merge_block);
+                self.builder.seal_block(merge_block);
+                self.builder.block_params(merge_block)[0]
+            }
+            Expr::If(..) => {
+                unreachable!("the type checker rejects an 'if' without 'else' as a value")
+            }
+            Expr::Lambda(_) => return Err(Box::new(JitError::Unsupported("functions"))),
+            Expr::Array(_) | Expr::Repeat(..) | Expr::Index(..) => {
+                return Err(Box::new(JitError::Unsupported("arrays")))
+            }
+            Expr::Block(_) => return Err(Box::new(JitError::Unsupported("block values"))),
+            Expr::Loop(_) => return Err(Box::new(JitError::Unsupported("loop values"))),
+            Expr::Assign(..) | Expr::Let(..) | Expr::Break(..) | Expr::Continue(_) => {
+                return Err(Box::new(JitError::Unit))
+            }
+            Expr::StructLit(_) | Expr::Field(..) => {
+                return Err(Box::new(JitError::Unsupported("structs")))
+            }
+            Expr::VariantLit(_) | Expr::Match(_) => {
+                return Err(Box::new(JitError::Unsupported("enums")))
+            }
+        };
+        Ok(value)
+    }
+
+    /// The function from the prelude that `callee` names, unless a variable
+    /// shadows it.
+    fn intrinsic(&self, callee: ExprId) -> Option<Intrinsic> {
+        match self.ast[callee] {
+            Expr::Atom(Atom::Id(id)) if self.lookup(id).is_none() => {
+                Intrinsic::lookup(&self.ast[id])
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether `expr` is a call that only has an effect, and so no value.
+    fn is_unit(&self, expr: ExprId) -> bool {
+        match self.ast[expr] {
+            Expr::Call(call) => self
+                .intrinsic(self.ast[call].callee)
+                .is_some_and(Intrinsic::is_unit),
+            _ => false,
+        }
+    }
+
+    fn binary(&mut self, op: Op, lhs: Value, rhs: Value) -> Value {
+        let ins = self.builder.ins();
+        match op {
+            Op::Plus => ins.iadd(lhs, rhs),
+            Op::Minus => ins.isub(lhs, rhs),
+            Op::Mult => ins.imul(lhs, rhs),
+            Op::Div => self.divide(lhs, rhs),
+            Op::Grt => {
+                let flag = ins.icmp(IntCC::SignedGreaterThan, lhs, rhs);
+                self.builder.ins().uextend(types::I32, flag)
+            }
+            Op::Les => {
+                let flag = ins.icmp(IntCC::SignedLessThan, lhs, rhs);
+                self.builder.ins().uextend(types::I32, flag)
+            }
+            Op::Eq => {
+                let flag = ins.icmp(IntCC::Equal, lhs, rhs);
+                self.builder.ins().uextend(types::I32, flag)
+            }
+            Op::Ne => {
+                let flag = ins.icmp(IntCC::NotEqual, lhs, rhs);
+                self.builder.ins().uextend(types::I32, flag)
+            }
+        }
+    }
+
+    /// `sdiv` traps on a zero divisor and on `i32::MIN / -1`, so report the
+    /// first to the host and wrap the second the way the interpreter does.
+    fn divide(&mut self, lhs: Value, rhs: Value) -> Value {
+        let zero_block = self.builder.create_block();
+        let div_block = self.builder.create_block();
+        self.builder
+            .ins()
+            .brif(rhs, div_block, &[], zero_block, &[]);
+
+        self.builder.switch_to_block(zero_block);
+        self.builder.seal_block(zero_block);
+        self.builder.ins().call(self.trap, &[self.state]);
+        self.builder.ins().return_(&[]);
+
+        self.builder.switch_to_block(div_block);
+        self.builder.seal_block(div_block);
+        let ins = self.builder.ins();
+        let is_neg_one = ins.icmp_imm(IntCC::Equal, rhs, -1);
+        let one = self.builder.ins().iconst(types::I32, 1);
+        let divisor = self.builder.ins().select(is_neg_one, one, rhs);
+        let quotient = self.builder.ins().sdiv(lhs, divisor);
+        let negated = self.builder.ins().ineg(lhs);
+        self.builder.ins().select(is_neg_one, negated, quotient)
+    }
+}
@@ -0,0 +1,147 @@
+use super::{run, run_with};
+use cb_parse::{parse, Ast, ExprId, SideTable};
+use cb_prelude::{Capture, Exit};
+use cb_typeck::Type;
+
+/// Parses and type checks a program, which [`run`] expects.
+fn check(src: &str) -> (Ast, SideTable<ExprId, Type>) {
+    let ast = parse(src).unwrap();
+    let types = cb_typeck::check(&ast).unwrap();
+    (ast, types)
+}
+
+macro_rules! setup_test {
+    ($name:ident, $input:expr $(,)?) => {
+        #[test]
+        fn $name() {
+            let (ast, types) = check($input);
+            let expected = cb_interp::run(&ast).unwrap();
+            assert_eq!(run(&ast, &types).unwrap(), expected);
+        }
+    };
+}
+
+setup_test!(jit_atom, "1");
+setup_test!(jit_unary, "-1 - -(2 * 3)");
+setup_test!(jit_precedence, "-7 / 2 1 + 2 * 3 (1 + 2) * 3 10 - 4 - 3");
+setup_test!(jit_wrapping_division, "-2147483648 / -1");
+setup_test!(jit_comparison, "1 > 2 1 < 2");
+setup_test!(jit_if, "if 1 > 3 { 1 } if 1 < 3 { 2 }");
+setup_test!(jit_if_else, "if 1 > 3 { 1 } else { 2 }");
+setup_test!(
+    jit_if_else_chain,
+    "if 3 < 1 { 1 } else if 1 > 2 { 2 } else if 2 > 1 { if 1 > 0 { 3 } } else { 4 }"
+);
+
+setup_test!(
+    jit_variables,
+    "let x = 2 let y = x * 3 x = x + y x let x = x - 1 x if x > 0 { let z = x z } else { x }"
+);
+setup_test!(
+    jit_while,
+    "let i = 0 let s = 0 while i < 5 { s = s + i i = i + 1 } s"
+);
+setup_test!(
+    jit_for,
+    "let s = 0 for i in 0..4 { i = i * 10 s = s + i } s\n\
+     'outer: for i in 1..5 { for j in 0..9 { if j > i { continue 'outer } if i == 4 { break 'outer } s = s + 1 } } s\n\
+     loop { s = s - 7 if s < 0 { break } } s for i in 3..1 { s = 0 } s"
+);
+
+#[test]
+fn jit_divide_by_zero() {
+    let (ast, types) = check("1 2 / (1 - 1) 3");
+    let err = run(&ast, &types).unwrap_err();
+    assert_eq!(err.to_string(), "attempt to divide by zero");
+}
+
+#[test]
+fn jit_unsupported() {
+    for (src, what) in [
+        ("fn(x) { x }(1)", "functions"),
+        ("let f = fn(x) { x } f(1)", "functions"),
+        ("let p = println 1", "functions"),
+        ("[1, 2][0]", "arrays"),
+        ("let a = [1] a[0]", "arrays"),
+        ("let a = [1] a[0] = 2", "arrays"),
+        ("struct P { x: i32 } P { x: 1 }.x", "structs"),
+        ("enum E { A } match E::A { E::A => 1 }", "enums"),
+        ("loop { break 1 }", "loop values"),
+        ("let x = loop { break 1 } x", "loop values"),
+        ("1 + if 1 { let y = 2 y } else { 3 }", "block values"),
+    ] {
+        let (ast, types) = check(src);
+        let err = run(&ast, &types).unwrap_err();
+        let message = format!("{what} are only supported by the interpreter");
+        assert_eq!(err.to_string(), message, "{src}");
+    }
+}
+
+/// Checks that the compiled program writes what the interpreter does and
+/// stops the same way.
+fn check_output(src: &str) {
+    let (ast, types) = check(src);
+    let (mut expected, mut found) = (Capture::default(), Capture::default());
+    let expected_result = cb_interp::run_with(&ast, &mut expected).map_err(|e| e.to_string());
+    let result = run_with(&ast, &types, &mut found).map_err(|e| e.to_string());
+    assert_eq!(result, expected_result);
+    assert_eq!(found.output, expected.output);
+}
+
+#[test]
+fn jit_prelude() {
+    check_output(
+        "print(1 + 2) println(-4) print(\"a\\t\\\"b\\\" \") println(\"é\") \"s\" 5\n\
+         if 1 > 2 { println(1) } else { print(\"\\0x\\n\") } assert(1)",
+    );
+    check_output("1 if 2 > 1 { exit(2) } 3");
+    let (ast, types) = check("exit(4) 1");
+    let exit = run(&ast, &types).unwrap_err();
+    assert_eq!(exit.downcast_ref::<Exit>(), Some(&Exit(4)));
+    let (ast, types) = check("1 assert(0 > 1) 2");
+    let err = run(&ast, &types).unwrap_err();
+    assert_eq!(err.to_string(), "assertion failed");
+}
+
+#[test]
+fn jit_string_variables() {
+    check_output(
+        "let s = \"a\" for i in 0..3 { s = s + to_string(i) } println(s) s let s = len(s) s",
+    );
+    check_output("let i = 0 while 1 { i = i + 1 if i > 2 { exit(i) } }");
+}
+
+setup_test!(
+    jit_equality,
+    "(1 == 1) (1 != 1) (\"ab\" == (\"a\" + \"b\")) (\"a\" != \"b\") len(\"hé\")"
+);
+
+#[test]
+fn jit_strings() {
+    check_output(
+        "println(\"n = \" + to_string(parse_int(\"-42\") * 2)) slice(\"héllo\", 1, 3)\n\
+         if len(\"abc\") > 2 { \"long\" } else { \"short\" } print(slice(\"ab\", 1, 2) + \"\\n\")",
+    );
+    let mut host = Capture::new("7\n");
+    let (ast, types) = check("println(to_string(parse_int(read_line()) + 1)) read_line()");
+    run_with(&ast, &types, &mut host).unwrap();
+    assert_eq!(host.output, "8\n\n");
+}
+
+#[test]
+fn jit_string_errors() {
+    let mut host = Capture::default();
+    let (ast, types) = check("println(1) slice(\"é\", 0, 1) println(2)");
+    let err = run_with(&ast, &types, &mut host).unwrap_err();
+    assert_eq!(err.to_string(), "byte index 1 is not a char boundary");
+    assert_eq!(host.output, "1\n");
+    let (ast, types) = check("parse_int(\"x\") 2");
+    let err = run(&ast, &types).unwrap_err();
+    assert_eq!(err.to_string(), "cannot parse \"x\" as an integer");
+    let (ast, types) = check("slice(\"abc\", 2, 1)");
+    let err = run(&ast, &types).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "slice out of bounds: the length is 3 but the range is 2..1"
+    );
+}
@@ -0,0 +1,51 @@
+use cb_lexer::{Scanner, TokenKind};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use std::hint::black_box;
+
+/// Builds a program of at least `size` bytes out of every kind of token,
+/// with comments that contain multi-byte characters.
+fn generate(size: usize) -> String {
+    let mut src = String::with_capacity(size + 128);
+    let mut i = 0u32;
+    while src.len() < size {
+        let n = i.wrapping_mul(2_654_435_761) % 1000;
+        src.push_str(&format!(
+            "// step {i}: λ → ∑\nif x{n} > {n} {{\n    (a_{i} + {n}) * -b / 7\n}} else {{\n    c <= 1_000\n}}\n"
+        ));
+        i += 1;
+    }
+    src
+}
+
+fn scanner(c: &mut Criterion) {
+    let src = generate(4 << 20);
+    let mut group = c.benchmark_group("scanner");
+    group.throughput(Throughput::Bytes(src.len() as u64));
+    group.bench_function("tokens", |b| {
+        b.iter(|| {
+            Scanner::new(black_box(&src))
+                .take_while(|t| !t.is_eof())
+                .count()
+        })
+    });
+    group.bench_function("tokens_with_trivia", |b| {
+        b.iter(|| {
+            Scanner::with_trivia(black_box(&src))
+                .take_while(|t| !t.is_eof())
+                .count()
+        })
+    });
+    group.bench_function("identifier_text", |b| {
+        b.iter(|| {
+            Scanner::new(black_box(&src))
+                .take_while(|t| !t.is_eof())
+                .filter(|t| t.kind == TokenKind::Id)
+                .map(|t| t.text(&src).len())
+                .sum::<usize>()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, scanner);
+criterion_main!(benches);
@@ -0,0 +1,46 @@
+use crate::Span;
+
+/// The value of a string literal, given its text including the quotes. On
+/// error, returns the part of the text at fault, relative to its start.
+pub fn unescape(text: &str) -> Result<String, (Span, &'static str)> {
+    let mut value = String::new();
+    let mut chars = text.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok(value),
+            '\\' => {
+                let escaped = match chars.next() {
+                    Some((_, 'n')) => '\n',
+                    Some((_, 't')) => '\t',
+                    Some((_, 'r')) => '\r',
+                    Some((_, '0')) => '\0',
+                    Some((_, '\\')) => '\\',
+                    Some((_, '"')) => '"',
+                    Some((j, c)) => return Err((i..j + c.len_utf8(), "unknown escape")),
+                    None => break,
+                };
+                value.push(escaped);
+            }
+            c => value.push(c),
+        }
+    }
+    Err((0..text.len(), "unterminated string"))
+}
+
+/// Writes `value` as a string literal that [`unescape`] turns back into it.
+pub fn escape(value: &str) -> String {
+    let mut text = String::from('"');
+    for c in value.chars() {
+        match c {
+            '\n' => text.push_str("\\n"),
+            '\t' => text.push_str("\\t"),
+            '\r' => text.push_str("\\r"),
+            '\0' => text.push_str("\\0"),
+            '\\' => text.push_str("\\\\"),
+            '"' => text.push_str("\\\""),
+            c => text.push(c),
+        }
+    }
+    text.push('"');
+    text
+}
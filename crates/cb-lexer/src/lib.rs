@@ -1,22 +1,14 @@
+mod escape;
 mod scanner;
+mod source_map;
+mod symbol;
 #[cfg(test)]
 mod test;
 mod token;
 
 pub type Span = std::ops::Range<usize>;
+pub use crate::escape::{escape, unescape};
 pub use crate::scanner::Scanner;
-pub use crate::token::Token;
-
-pub enum TokenDebug {
-    True,
-    False,
-}
-
-impl From<bool> for TokenDebug {
-    fn from(b: bool) -> Self {
-        if b {
-            return Self::True;
-        }
-        Self::False
-    }
-}
+pub use crate::source_map::{FileId, SourceFile, SourceMap};
+pub use crate::symbol::{Interner, Symbol};
+pub use crate::token::{Token, TokenKind};
@@ -1,161 +1,169 @@
-use super::{Span, Token, TokenDebug};
-use std::{iter::Peekable, str::Chars};
-type Stream<'a> = Peekable<Chars<'a>>;
+use super::{Token, TokenKind};
 
+/// Splits source text into tokens. The scanner only tracks byte offsets into
+/// the source, so tokens are spans and their text is borrowed from `src`.
 pub struct Scanner<'a> {
-    stream: Stream<'a>,
-    span: Span,
-    current: Option<char>,
-    previous: Option<char>,
-    token_debug: TokenDebug,
+    src: &'a str,
+    start: usize,
+    position: usize,
+    trivia: bool,
 }
 
 impl<'a> Scanner<'a> {
-    pub fn new(src: &'a str, token_debug: TokenDebug) -> Self {
+    pub fn new(src: &'a str) -> Self {
         Self {
-            stream: src.chars().peekable(),
-            span: 0..0,
-            current: None,
-            previous: None,
-            token_debug,
+            src,
+            start: 0,
+            position: 0,
+            trivia: false,
         }
     }
-    fn advance(&mut self) {
-        match self.current {
-            Some(c) => {
-                self.span.end += c.to_string().as_bytes().len();
-            }
-            _ => {
-                let Some(c) = self.previous else {
-                    self.span.end += 1;
-                    return;
-                };
-                self.span.end += c.to_string().as_bytes().len();
-            }
+
+    /// A scanner that also yields whitespace and comments, so that the
+    /// tokens cover every byte of `src`.
+    pub fn with_trivia(src: &'a str) -> Self {
+        Self {
+            trivia: true,
+            ..Self::new(src)
         }
     }
 
-    fn next_char(&mut self) -> Option<char> {
-        self.previous = self.current;
-        self.current = self.stream.next();
-        self.advance();
-        self.current
+    fn peek(&self) -> Option<char> {
+        match self.src.as_bytes().get(self.position) {
+            Some(&b) if b.is_ascii() => Some(b as char),
+            Some(_) => self.src[self.position..].chars().next(),
+            None => None,
+        }
     }
 
-    fn next_if(&mut self, func: impl FnOnce(&char) -> bool) -> Option<char> {
-        match (self.current, self.stream.next_if(func)) {
-            (Some(current), next @ Some(_)) => {
-                self.previous = Some(current);
-                self.current = next;
-                self.advance();
-            }
-            (_, next @ Some(_)) => self.current = next,
-            _ => return None,
-        }
-        self.current
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += c.len_utf8();
+        Some(c)
     }
 
-    fn matched(&mut self, check: char) -> bool {
-        match self.peek_char() {
-            Some(c) => c == &check,
-            _ => false,
+    fn eat(&mut self, expected: char) -> bool {
+        let matched = self.peek() == Some(expected);
+        if matched {
+            self.position += expected.len_utf8();
         }
+        matched
     }
 
-    fn peek_char(&mut self) -> Option<&char> {
-        self.stream.peek()
+    fn eat_while(&mut self, predicate: impl Fn(char) -> bool) {
+        while self.peek().is_some_and(&predicate) {
+            self.bump();
+        }
     }
 
-    fn reset_span(&mut self) {
-        self.span.start = self.span.end;
+    fn number(&mut self) -> TokenKind {
+        self.eat_while(|c| c.is_ascii_digit() || c == '_');
+        // A `..` after an integer is a range, as in `0..10`.
+        if self.peek() != Some('.') || self.src[self.position..].starts_with("..") {
+            return TokenKind::Int;
+        }
+        self.eat_while(|c| c.is_ascii_digit() || c == '_' || c == '.');
+        TokenKind::Float
     }
 
-    fn span(&mut self) -> Span {
-        let span = self.span.clone();
-        self.reset_span();
-        span
+    /// Scans to the closing quote, stepping over escapes. An unterminated
+    /// string runs to the end of the line and is reported by the parser.
+    fn string(&mut self) -> TokenKind {
+        while let Some(c) = self.peek() {
+            match c {
+                '"' => {
+                    self.bump();
+                    break;
+                }
+                '\\' => {
+                    self.bump();
+                    if self.peek().is_some_and(|c| c != '\n') {
+                        self.bump();
+                    }
+                }
+                '\n' => break,
+                _ => {
+                    self.bump();
+                }
+            }
+        }
+        TokenKind::String
     }
 
-    fn number(&mut self) -> (Token, Span) {
-        let mut number = self.current.unwrap().to_string();
-        while let Some(ch) = self.next_if(|c| c.is_ascii_digit() || c == &'_' || c == &'.') {
-            number.push(ch);
-        }
-        let span = self.span();
-        let token = if number.contains('.') {
-            Token::Float(number)
-        } else {
-            Token::Int(number)
-        };
-        (token, span)
+    fn id(&mut self) -> TokenKind {
+        self.eat_while(|c| c.is_ascii_alphanumeric() || c == '_');
+        TokenKind::lookup(&self.src[self.start..self.position]).unwrap_or(TokenKind::Id)
     }
 
-    fn id(&mut self) -> (Token, Span) {
-        let mut ident = self.current.unwrap().to_string();
-        while let Some(ch) = self.next_if(|c| c.is_ascii_alphanumeric() || c == &'_') {
-            ident.push(ch);
+    /// Picks `long` if the next character is `second`, otherwise `short`.
+    fn either(&mut self, second: char, long: TokenKind, short: TokenKind) -> TokenKind {
+        match self.eat(second) {
+            true => long,
+            false => short,
         }
-        let span = self.span();
-        let token = Token::lookup(&ident).map_or(Token::Id(ident), |i| i);
-        (token, span)
     }
 
-    fn op_token(&mut self, op: &str) -> (Token, Span) {
-        debug_assert!(!op.is_empty());
-        for _ in 0..op.chars().count().saturating_sub(1) {
-            self.next_char();
+    fn kind(&mut self, ch: char) -> TokenKind {
+        match ch {
+            num if num.is_ascii_digit() => self.number(),
+            ident if ident.is_ascii_alphabetic() || ident == '_' => self.id(),
+            '-' => self.either('>', TokenKind::Arrow, TokenKind::Minus),
+            '=' if self.eat('>') => TokenKind::FatArrow,
+            '=' => self.either('=', TokenKind::EqEq, TokenKind::Eq),
+            '>' => self.either('=', TokenKind::GreaterEq, TokenKind::Greater),
+            '<' => self.either('=', TokenKind::LessEq, TokenKind::Less),
+            '!' => self.either('=', TokenKind::BangEq, TokenKind::Bang),
+            '+' => TokenKind::Plus,
+            '*' => TokenKind::Star,
+            '/' if self.eat('/') => {
+                self.eat_while(|c| c != '\n');
+                TokenKind::Comment
+            }
+            '/' => TokenKind::Slash,
+            '.' if self.eat('.') => TokenKind::DotDot,
+            '.' => TokenKind::Dot,
+            '"' => self.string(),
+            '\'' if self.peek().is_some_and(|c| c.is_ascii_alphabetic()) => {
+                self.eat_while(|c| c.is_ascii_alphanumeric() || c == '_');
+                TokenKind::Label
+            }
+            ':' => self.either(':', TokenKind::ColonColon, TokenKind::Colon),
+            ';' => TokenKind::Semicolon,
+            ',' => TokenKind::Comma,
+            '(' => TokenKind::LParen,
+            ')' => TokenKind::RParen,
+            '[' => TokenKind::LBracket,
+            ']' => TokenKind::RBracket,
+            '{' => TokenKind::LBrace,
+            '}' => TokenKind::RBrace,
+            'λ' => TokenKind::Lambda,
+            ws if is_whitespace(ws) => {
+                self.eat_while(is_whitespace);
+                TokenKind::Whitespace
+            }
+            _ => TokenKind::Error,
         }
-        (Token::Op(op.into()), self.span())
     }
 }
 
 impl<'a> Iterator for Scanner<'a> {
-    type Item = (Token, Span);
+    type Item = Token;
     fn next(&mut self) -> Option<Self::Item> {
-        let Some(ch) = self.next_char() else {
-            let token = Some((Token::Eof, self.span()));
-            if let TokenDebug::True = self.token_debug {
-                let t = &token.clone().unwrap();
-                eprintln!("{t:?}");
-            }
-            return token;
-        };
-        let token = match ch {
-            num if num.is_ascii_digit() => Some(self.number()),
-            ident if ident.is_ascii_alphabetic() => Some(self.id()),
-            '-' if self.matched('>') => Some(self.op_token("->")),
-            '=' if self.matched('=') => Some(self.op_token("==")),
-            '>' if self.matched('=') => Some(self.op_token(">=")),
-            '<' if self.matched('=') => Some(self.op_token("<=")),
-            '!' if self.matched('=') => Some(self.op_token("!=")),
-            '!' => Some(self.op_token("!")),
-            '>' => Some(self.op_token(">")),
-            '<' => Some(self.op_token("<")),
-            '+' => Some(self.op_token("+")),
-            '-' => Some(self.op_token("-")),
-            '*' => Some(self.op_token("*")),
-            '/' => Some(self.op_token("/")),
-            '=' => Some(self.op_token("=")),
-            ':' => Some(self.op_token(":")),
-            ';' => Some(self.op_token(";")),
-            ',' => Some(self.op_token(",")),
-            '(' => Some(self.op_token("(")),
-            ')' => Some(self.op_token(")")),
-            '[' => Some(self.op_token("[")),
-            ']' => Some(self.op_token("]")),
-            '{' => Some(self.op_token("{")),
-            '}' => Some(self.op_token("}")),
-            'λ' => Some(self.op_token("λ")),
-            ' ' | '\n' => {
-                self.reset_span();
-                self.next()
+        loop {
+            self.start = self.position;
+            let Some(ch) = self.bump() else {
+                let end = self.src.len();
+                return Some(Token::new(TokenKind::Eof, end..end));
+            };
+            let kind = self.kind(ch);
+            if kind.is_trivia() && !self.trivia {
+                continue;
             }
-            _ => Some((Token::Error(ch.into()), self.span())),
-        };
-        if let TokenDebug::True = self.token_debug {
-            let t = &token.clone().unwrap_or((Token::Eof, self.span()));
-            eprintln!("{t:?}");
+            return Some(Token::new(kind, self.start..self.position));
         }
-        token
     }
 }
+
+fn is_whitespace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\r' | '\n')
+}
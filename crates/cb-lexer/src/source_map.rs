@@ -0,0 +1,65 @@
+use crate::Span;
+
+/// Identifies a file in a [`SourceMap`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FileId(u32);
+
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    /// The path the file was read from, as given.
+    pub name: String,
+    pub src: String,
+    /// Where the file starts among all the files in the map.
+    pub start: usize,
+}
+
+/// Every file of a program laid end to end, each one byte after the last,
+/// so that an offset names both a file and a place in it. Spans of a
+/// program built from several files are offsets into the map, which makes
+/// the file of any span recoverable from the span alone. The first file
+/// starts at zero, so a program of one file has the spans it always had.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, name: impl Into<String>, src: impl Into<String>) -> FileId {
+        let start = self.files.last().map_or(0, |f| f.start + f.src.len() + 1);
+        let id = FileId(u32::try_from(self.files.len()).expect("too many files"));
+        self.files.push(SourceFile {
+            name: name.into(),
+            src: src.into(),
+            start,
+        });
+        id
+    }
+
+    pub fn file(&self, id: FileId) -> &SourceFile {
+        &self.files[id.0 as usize]
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = (FileId, &SourceFile)> {
+        self.files
+            .iter()
+            .enumerate()
+            .map(|(i, f)| (FileId(i as u32), f))
+    }
+
+    /// The file that `offset` falls in, including the empty span at its end.
+    pub fn lookup(&self, offset: usize) -> FileId {
+        let index = self.files.partition_point(|f| f.start <= offset);
+        FileId(index.saturating_sub(1) as u32)
+    }
+
+    /// The file of `span` and where the span is within that file.
+    pub fn locate(&self, span: &Span) -> (FileId, Span) {
+        let id = self.lookup(span.start);
+        let start = self.file(id).start;
+        (id, span.start - start..span.end - start)
+    }
+}
@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+use std::ops::Index;
+use std::sync::Arc;
+
+/// An interned string. Symbols for equal strings are equal, so comparing
+/// and hashing them is an integer operation, and they are `Copy`. A symbol
+/// only stands for a string in the [`Interner`] that made it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+/// The strings that symbols stand for. Every tree owns the interner of its
+/// names, so they are freed along with it.
+#[derive(Debug, Clone, Default)]
+pub struct Interner {
+    symbols: HashMap<Arc<str>, Symbol>,
+    strings: Vec<Arc<str>>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn intern(&mut self, string: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(string) {
+            return symbol;
+        }
+        let symbol = Symbol(u32::try_from(self.strings.len()).expect("too many symbols"));
+        let string: Arc<str> = string.into();
+        self.strings.push(string.clone());
+        self.symbols.insert(string, symbol);
+        symbol
+    }
+
+    /// The symbol for `string`, if it has been interned.
+    pub fn get(&self, string: &str) -> Option<Symbol> {
+        self.symbols.get(string).copied()
+    }
+}
+
+impl Index<Symbol> for Interner {
+    type Output = str;
+    fn index(&self, symbol: Symbol) -> &str {
+        &self.strings[symbol.0 as usize]
+    }
+}
+
+#[cfg(feature = "serde")]
+thread_local! {
+    /// The interner that symbols are written from and read into during
+    /// [`Interner::serde`].
+    static SERDE: std::cell::RefCell<Option<Interner>> = const { std::cell::RefCell::new(None) };
+}
+
+#[cfg(feature = "serde")]
+impl Interner {
+    /// Runs `f`, which serializes or deserializes symbols of this interner,
+    /// and returns the interner, with the symbols read interned, along with
+    /// what `f` returned. Symbols are written as their text and interned
+    /// again when read, since the numbers differ from one tree to the next.
+    pub fn serde<R>(self, f: impl FnOnce() -> R) -> (Self, R) {
+        let outer = SERDE.replace(Some(self));
+        let result = f();
+        let names = SERDE.replace(outer).expect("the interner is still there");
+        (names, result)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Symbol {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SERDE.with_borrow(|names| match names {
+            Some(names) => serializer.serialize_str(&names[*self]),
+            None => Err(serde::ser::Error::custom(
+                "symbols can only be written by Interner::serde",
+            )),
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Symbol {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let string = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        SERDE.with_borrow_mut(|names| match names {
+            Some(names) => Ok(names.intern(&string)),
+            None => Err(serde::de::Error::custom(
+                "symbols can only be read by Interner::serde",
+            )),
+        })
+    }
+}
@@ -1,8 +1,8 @@
-use super::{Scanner, Token, TokenDebug};
-use Token::*;
+use super::{escape, unescape, Interner, Scanner, SourceMap, TokenKind};
+use TokenKind::*;
 
-fn get_next<'a>(scanner: &mut Scanner, src: &'a str) -> Option<(Token, &'a str)> {
-    scanner.next().map(|(t, s)| (t, &src[s]))
+fn get_next<'a>(scanner: &mut Scanner, src: &'a str) -> Option<(TokenKind, &'a str)> {
+    scanner.next().map(|t| (t.kind, t.text(src)))
 }
 
 macro_rules! setup_test {
@@ -10,17 +10,32 @@ macro_rules! setup_test {
         #[test]
         fn $name() {
             let src = $input;
-            let mut scanner = Scanner::new(src, TokenDebug::True);
+            let mut scanner = Scanner::new(src);
             $(
                 assert_eq!(
                     get_next(&mut scanner, src),
-                    Some(($token($output.into()), $output)), "{src}"
+                    Some(($token, $output)), "{src}"
                 );
             )*
         }
     }
 }
-setup_test!(emoji_hell, "λlambdaλ", (Op, "λ"), (Id, "lambda"), (Op, "λ"),);
+setup_test!(
+    emoji_hell,
+    "λlambdaλ",
+    (Lambda, "λ"),
+    (Id, "lambda"),
+    (Lambda, "λ"),
+);
+
+setup_test!(
+    scanner_comments,
+    "// leading\n1 / 2 // trailing\r\n\t// more\n3",
+    (Int, "1"),
+    (Slash, "/"),
+    (Int, "2"),
+    (Int, "3"),
+);
 
 setup_test!(
     scanner_main,
@@ -28,15 +43,15 @@ setup_test!(
     return 0;
 }
 "#,
-    (KeyWord, "fn"),
+    (Fn, "fn"),
     (Id, "main"),
-    (Op, "("),
-    (Op, ")"),
-    (Op, "{"),
-    (KeyWord, "return"),
+    (LParen, "("),
+    (RParen, ")"),
+    (LBrace, "{"),
+    (Return, "return"),
     (Int, "0"),
-    (Op, ";"),
-    (Op, "}"),
+    (Semicolon, ";"),
+    (RBrace, "}"),
 );
 
 setup_test!(
@@ -48,43 +63,248 @@ fn main() {
     let x = add(123, 321);
     return 0;
 }"#,
-    (KeyWord, "fn"),
+    (Fn, "fn"),
     (Id, "add"),
-    (Op, "("),
+    (LParen, "("),
     (Id, "x"),
-    (Op, ":"),
+    (Colon, ":"),
     (Id, "u64"),
-    (Op, ","),
+    (Comma, ","),
     (Id, "y"),
-    (Op, ":"),
+    (Colon, ":"),
     (Id, "u64"),
-    (Op, ")"),
-    (Op, "->"),
+    (RParen, ")"),
+    (Arrow, "->"),
     (Id, "u64"),
-    (Op, "{"),
-    (KeyWord, "return"),
+    (LBrace, "{"),
+    (Return, "return"),
     (Id, "x"),
-    (Op, "+"),
+    (Plus, "+"),
     (Id, "y"),
-    (Op, ";"),
-    (Op, "}"),
-    (KeyWord, "fn"),
+    (Semicolon, ";"),
+    (RBrace, "}"),
+    (Fn, "fn"),
     (Id, "main"),
-    (Op, "("),
-    (Op, ")"),
-    (Op, "{"),
-    (KeyWord, "let"),
+    (LParen, "("),
+    (RParen, ")"),
+    (LBrace, "{"),
+    (Let, "let"),
     (Id, "x"),
-    (Op, "="),
+    (Eq, "="),
     (Id, "add"),
-    (Op, "("),
+    (LParen, "("),
     (Int, "123"),
-    (Op, ","),
+    (Comma, ","),
     (Int, "321"),
-    (Op, ")"),
-    (Op, ";"),
-    (KeyWord, "return"),
+    (RParen, ")"),
+    (Semicolon, ";"),
+    (Return, "return"),
+    (Int, "0"),
+    (Semicolon, ";"),
+    (RBrace, "}"),
+);
+
+#[test]
+fn scanner_trivia() {
+    let src = "1 +\t2 // two\r\n\n λ";
+    let tokens: Vec<_> = Scanner::with_trivia(src)
+        .take_while(|t| !t.is_eof())
+        .map(|t| (t.kind, t.text(src)))
+        .collect();
+    assert_eq!(
+        tokens,
+        [
+            (Int, "1"),
+            (Whitespace, " "),
+            (Plus, "+"),
+            (Whitespace, "\t"),
+            (Int, "2"),
+            (Whitespace, " "),
+            (Comment, "// two\r"),
+            (Whitespace, "\n\n "),
+            (Lambda, "λ"),
+        ]
+    );
+}
+
+#[test]
+fn scanner_eof() {
+    let src = "1 é";
+    let mut scanner = Scanner::new(src);
+    assert_eq!(get_next(&mut scanner, src), Some((Int, "1")));
+    assert_eq!(get_next(&mut scanner, src), Some((Error, "é")));
+    assert_eq!(get_next(&mut scanner, src), Some((Eof, "")));
+    assert_eq!(get_next(&mut scanner, src), Some((Eof, "")));
+}
+
+setup_test!(
+    multi_byte,
+    "x🦀y 日本 λ->",
+    (Id, "x"),
+    (Error, "🦀"),
+    (Id, "y"),
+    (Error, "日"),
+    (Error, "本"),
+    (Lambda, "λ"),
+    (Arrow, "->"),
+    (Eof, ""),
+);
+
+#[test]
+fn symbols() {
+    let src = "abc xyz abc";
+    let mut names = Interner::new();
+    let symbols: Vec<_> = Scanner::new(src)
+        .take_while(|t| !t.is_eof())
+        .map(|t| t.symbol(src, &mut names))
+        .collect();
+    assert_eq!(symbols[0], symbols[2]);
+    assert_ne!(symbols[0], symbols[1]);
+    assert_eq!(&names[symbols[1]], "xyz");
+    assert_eq!(names.get("abc"), Some(symbols[0]));
+    assert_eq!(names.get("shared"), None);
+
+    // Each interner has symbols of its own, which go away with it.
+    let mut other = Interner::new();
+    assert_eq!(other.intern("xyz"), symbols[0]);
+    assert_eq!(&other[symbols[0]], "xyz");
+}
+
+setup_test!(
+    loops,
+    "'outer: for i in 0..n { while 1.5 { break 'outer } continue }",
+    (Label, "'outer"),
+    (Colon, ":"),
+    (For, "for"),
+    (Id, "i"),
+    (In, "in"),
     (Int, "0"),
-    (Op, ";"),
-    (Op, "}"),
+    (DotDot, ".."),
+    (Id, "n"),
+    (LBrace, "{"),
+    (While, "while"),
+    (Float, "1.5"),
+    (LBrace, "{"),
+    (Break, "break"),
+    (Label, "'outer"),
+    (RBrace, "}"),
+    (Continue, "continue"),
+    (RBrace, "}"),
+    (Eof, ""),
 );
+
+setup_test!(
+    structs,
+    "struct P { x: i32 } p.x = a[0].y . 1.5",
+    (Struct, "struct"),
+    (Id, "P"),
+    (LBrace, "{"),
+    (Id, "x"),
+    (Colon, ":"),
+    (Id, "i32"),
+    (RBrace, "}"),
+    (Id, "p"),
+    (Dot, "."),
+    (Id, "x"),
+    (Eq, "="),
+    (Id, "a"),
+    (LBracket, "["),
+    (Int, "0"),
+    (RBracket, "]"),
+    (Dot, "."),
+    (Id, "y"),
+    (Dot, "."),
+    (Float, "1.5"),
+    (Eof, ""),
+);
+
+setup_test!(
+    enums,
+    "enum E { A(i32) } match e { E::A(_x) if x => 1, _ => 2 }",
+    (Enum, "enum"),
+    (Id, "E"),
+    (LBrace, "{"),
+    (Id, "A"),
+    (LParen, "("),
+    (Id, "i32"),
+    (RParen, ")"),
+    (RBrace, "}"),
+    (Match, "match"),
+    (Id, "e"),
+    (LBrace, "{"),
+    (Id, "E"),
+    (ColonColon, "::"),
+    (Id, "A"),
+    (LParen, "("),
+    (Id, "_x"),
+    (RParen, ")"),
+    (If, "if"),
+    (Id, "x"),
+    (FatArrow, "=>"),
+    (Int, "1"),
+    (Comma, ","),
+    (Underscore, "_"),
+    (FatArrow, "=>"),
+    (Int, "2"),
+    (RBrace, "}"),
+    (Eof, ""),
+);
+
+setup_test!(
+    modules,
+    "pub mod a; use a::b;",
+    (Pub, "pub"),
+    (Mod, "mod"),
+    (Id, "a"),
+    (Semicolon, ";"),
+    (Use, "use"),
+    (Id, "a"),
+    (ColonColon, "::"),
+    (Id, "b"),
+    (Semicolon, ";"),
+    (Eof, ""),
+);
+
+setup_test!(
+    strings,
+    r#"print("a \"b\" // c") "λ\\" "open
+1"#,
+    (Id, "print"),
+    (LParen, "("),
+    (String, r#""a \"b\" // c""#),
+    (RParen, ")"),
+    (String, r#""λ\\""#),
+    (String, "\"open"),
+    (Int, "1"),
+    (Eof, ""),
+);
+
+#[test]
+fn string_values() {
+    assert_eq!(
+        unescape(r#""a\tb\n\"\\\0""#),
+        Ok("a\tb\n\"\\\0".to_string())
+    );
+    assert_eq!(unescape(r#""λ""#), Ok("λ".to_string()));
+    assert_eq!(unescape(r#""a\q""#), Err((2..4, "unknown escape")));
+    assert_eq!(unescape(r#""a\""#), Err((0..4, "unterminated string")));
+    assert_eq!(unescape(r#""a"#), Err((0..2, "unterminated string")));
+    for value in ["", "plain", "tab\tquote\"back\\slash\n", "λ\0"] {
+        assert_eq!(unescape(&escape(value)).as_deref(), Ok(value));
+    }
+}
+
+#[test]
+fn source_map() {
+    let mut map = SourceMap::new();
+    let main = map.add("main.cb", "mod a;\n1");
+    let a = map.add("a.cb", "2 + x");
+    assert_eq!(map.file(main).start, 0);
+    assert_eq!(map.file(a).start, 9);
+    assert_eq!(map.lookup(7), main);
+    assert_eq!(map.lookup(8), main);
+    assert_eq!(map.lookup(9), a);
+    assert_eq!(map.locate(&(13..14)), (a, 4..5));
+    assert_eq!(map.locate(&(7..8)), (main, 7..8));
+    assert_eq!(&map.file(a).src[map.locate(&(13..14)).1], "x");
+}
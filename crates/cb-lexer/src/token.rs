@@ -1,66 +1,264 @@
+use crate::{Interner, Span, Symbol};
 use std::fmt;
 
-macro_rules! is_token {
-    ($i:ident, $t:ident) => {
-        pub fn $i(&self) -> bool {
-            match self {
-                Self::$t(_) => true,
-                _ => false,
-            }
-        }
-    };
-}
-
-#[derive(Debug, Clone, PartialEq)]
-pub enum Token {
-    Id(String),
-    Int(String),
-    Float(String),
-    String(String),
-    Char(String),
-    Op(String),
-    KeyWord(String),
-    Error(String),
+/// What a token is, with one variant per keyword and operator. The text of
+/// identifiers and literals is recovered from the source with
+/// [`Token::text`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    Id,
+    Int,
+    Float,
+    String,
+    Char,
+    // Keywords
+    Fn,
+    True,
+    False,
+    Return,
+    Let,
+    And,
+    Or,
+    Not,
+    If,
+    Else,
+    While,
+    Loop,
+    For,
+    In,
+    Break,
+    Continue,
+    Struct,
+    Enum,
+    Match,
+    /// The `_` pattern, which matches anything.
+    Underscore,
+    Mod,
+    Use,
+    Pub,
+    /// A loop label such as `'outer`, including the quote.
+    Label,
+    // Operators
+    Arrow,
+    FatArrow,
+    ColonColon,
+    DotDot,
+    Dot,
+    EqEq,
+    GreaterEq,
+    LessEq,
+    BangEq,
+    Bang,
+    Greater,
+    Less,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Eq,
+    Colon,
+    Semicolon,
+    Comma,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    LBrace,
+    RBrace,
+    Lambda,
+    /// Spaces, tabs and line breaks, only produced by [`Scanner::with_trivia`].
+    ///
+    /// [`Scanner::with_trivia`]: crate::Scanner::with_trivia
+    Whitespace,
+    /// A `//` comment up to the end of the line, only produced by
+    /// [`Scanner::with_trivia`].
+    ///
+    /// [`Scanner::with_trivia`]: crate::Scanner::with_trivia
+    Comment,
+    /// A character that does not start any token.
+    Error,
     Eof,
 }
 
-impl fmt::Display for Token {
+impl fmt::Display for TokenKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::Id(i) => write!(f, "{}", i),
-            Self::Int(i) => write!(f, "{}", i),
-            Self::Float(i) => write!(f, "{}", i),
-            Self::String(s) => write!(f, "{}", s),
-            Self::Char(c) => write!(f, "{}", c),
-            Self::Op(o) => write!(f, "{}", o),
-            Self::KeyWord(kw) => write!(f, "{}", kw),
-            Self::Error(e) => write!(f, "unknown token: '{e}'"),
-            Self::Eof => write!(f, "EOF"),
+        match self.fixed_text() {
+            Some(text) => write!(f, "{text}"),
+            None => match self {
+                Self::Id => write!(f, "identifier"),
+                Self::Int => write!(f, "integer"),
+                Self::Float => write!(f, "float"),
+                Self::String => write!(f, "string"),
+                Self::Char => write!(f, "character"),
+                Self::Label => write!(f, "label"),
+                Self::Whitespace => write!(f, "whitespace"),
+                Self::Comment => write!(f, "comment"),
+                Self::Error => write!(f, "unknown token"),
+                _ => write!(f, "end of input"),
+            },
         }
     }
 }
 
-impl Token {
+impl TokenKind {
     pub fn lookup(name: &str) -> Option<Self> {
         match name {
-            "fn" | "true" | "false" | "return" | "let" | "and" | "or" | "not" | "if" | "else" => {
-                Some(Token::KeyWord(name.into()))
-            }
+            "fn" => Some(Self::Fn),
+            "true" => Some(Self::True),
+            "false" => Some(Self::False),
+            "return" => Some(Self::Return),
+            "let" => Some(Self::Let),
+            "and" => Some(Self::And),
+            "or" => Some(Self::Or),
+            "not" => Some(Self::Not),
+            "if" => Some(Self::If),
+            "else" => Some(Self::Else),
+            "while" => Some(Self::While),
+            "loop" => Some(Self::Loop),
+            "for" => Some(Self::For),
+            "in" => Some(Self::In),
+            "break" => Some(Self::Break),
+            "continue" => Some(Self::Continue),
+            "struct" => Some(Self::Struct),
+            "enum" => Some(Self::Enum),
+            "match" => Some(Self::Match),
+            "_" => Some(Self::Underscore),
+            "mod" => Some(Self::Mod),
+            "use" => Some(Self::Use),
+            "pub" => Some(Self::Pub),
             _ => None,
         }
     }
 
-    is_token!(is_id, Id);
-    is_token!(is_int, Int);
-    is_token!(is_float, Float);
-    is_token!(is_string, String);
-    is_token!(is_char, Char);
-    is_token!(is_op, Op);
-    is_token!(is_keyword, KeyWord);
+    /// The spelling of keywords and operators, which never varies.
+    pub fn fixed_text(self) -> Option<&'static str> {
+        let text = match self {
+            Self::Fn => "fn",
+            Self::True => "true",
+            Self::False => "false",
+            Self::Return => "return",
+            Self::Let => "let",
+            Self::And => "and",
+            Self::Or => "or",
+            Self::Not => "not",
+            Self::If => "if",
+            Self::Else => "else",
+            Self::While => "while",
+            Self::Loop => "loop",
+            Self::For => "for",
+            Self::In => "in",
+            Self::Break => "break",
+            Self::Continue => "continue",
+            Self::Struct => "struct",
+            Self::Enum => "enum",
+            Self::Match => "match",
+            Self::Underscore => "_",
+            Self::Mod => "mod",
+            Self::Use => "use",
+            Self::Pub => "pub",
+            Self::Arrow => "->",
+            Self::FatArrow => "=>",
+            Self::ColonColon => "::",
+            Self::DotDot => "..",
+            Self::Dot => ".",
+            Self::EqEq => "==",
+            Self::GreaterEq => ">=",
+            Self::LessEq => "<=",
+            Self::BangEq => "!=",
+            Self::Bang => "!",
+            Self::Greater => ">",
+            Self::Less => "<",
+            Self::Plus => "+",
+            Self::Minus => "-",
+            Self::Star => "*",
+            Self::Slash => "/",
+            Self::Eq => "=",
+            Self::Colon => ":",
+            Self::Semicolon => ";",
+            Self::Comma => ",",
+            Self::LParen => "(",
+            Self::RParen => ")",
+            Self::LBracket => "[",
+            Self::RBracket => "]",
+            Self::LBrace => "{",
+            Self::RBrace => "}",
+            Self::Lambda => "λ",
+            Self::Id
+            | Self::Int
+            | Self::Float
+            | Self::String
+            | Self::Char
+            | Self::Label
+            | Self::Whitespace
+            | Self::Comment
+            | Self::Error
+            | Self::Eof => return None,
+        };
+        Some(text)
+    }
+
+    pub fn is_keyword(self) -> bool {
+        matches!(
+            self,
+            Self::Fn
+                | Self::True
+                | Self::False
+                | Self::Return
+                | Self::Let
+                | Self::And
+                | Self::Or
+                | Self::Not
+                | Self::If
+                | Self::Else
+                | Self::While
+                | Self::Loop
+                | Self::For
+                | Self::In
+                | Self::Break
+                | Self::Continue
+                | Self::Struct
+                | Self::Enum
+                | Self::Match
+                | Self::Underscore
+                | Self::Mod
+                | Self::Use
+                | Self::Pub
+        )
+    }
+
+    pub fn is_op(self) -> bool {
+        self.fixed_text().is_some() && !self.is_keyword()
+    }
+
+    /// Whether the token carries no meaning for the parser.
+    pub fn is_trivia(self) -> bool {
+        matches!(self, Self::Whitespace | Self::Comment)
+    }
+}
+
+/// A token is its kind and where it is in the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+impl Token {
+    pub fn new(kind: TokenKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+
+    /// The token as written in `src`, the source it was scanned from.
+    pub fn text<'a>(&self, src: &'a str) -> &'a str {
+        &src[self.span.clone()]
+    }
+
+    /// The token's text, interned in `names`.
+    pub fn symbol(&self, src: &str, names: &mut Interner) -> Symbol {
+        names.intern(self.text(src))
+    }
+
     pub fn is_eof(&self) -> bool {
-        match self {
-            Self::Eof => true,
-            _ => false,
-        }
+        self.kind == TokenKind::Eof
     }
 }
@@ -0,0 +1,263 @@
+use cb_lexer::{Scanner, Span, Token, TokenKind};
+use cb_parse::syntax::{self, SyntaxKind, SyntaxNode};
+use cb_parse::{Ast, Atom, Expr, ExprId};
+use cb_typeck::{Binding, Checked};
+use lsp_types::{DocumentSymbol, Position, Range, SymbolKind};
+
+/// Everything the server knows about one version of a document.
+pub struct Analysis {
+    src: String,
+    pub tokens: Vec<Token>,
+    pub diagnostics: Vec<(Span, String)>,
+    /// The tree of the document, if it parses.
+    ast: Option<Ast>,
+    /// What the type checker learned about the tree, up to its first error.
+    checked: Checked,
+    /// The lossless tree, which exists even when the document has errors.
+    syntax: SyntaxNode,
+}
+
+impl Analysis {
+    pub fn new(src: &str) -> Self {
+        let tokens: Vec<_> = Scanner::new(src).take_while(|t| !t.is_eof()).collect();
+        let mut diagnostics = vec![];
+        let mut checked = Checked::default();
+        let parse = syntax::parse(src);
+        let ast = match parse.lower() {
+            Ok(ast) => {
+                checked = cb_typeck::analyze(&ast);
+                if let Some(e) = &checked.error {
+                    diagnostics.push((e.span.clone(), e.message.clone()));
+                }
+                Some(ast)
+            }
+            Err(e) => {
+                diagnostics.push((e.span, e.message));
+                None
+            }
+        };
+        Self {
+            src: src.to_string(),
+            tokens,
+            diagnostics,
+            ast,
+            checked,
+            syntax: parse.syntax(),
+        }
+    }
+
+    /// The token under `offset`, preferring the one that starts there.
+    pub fn token_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens
+            .iter()
+            .find(|t| t.span.start <= offset && offset < t.span.end)
+            .or_else(|| self.tokens.iter().find(|t| t.span.end == offset))
+    }
+
+    pub fn text(&self, token: &Token) -> &str {
+        token.text(&self.src)
+    }
+
+    /// The type of the innermost expression around a token, as the type
+    /// checker inferred it. The name a `let` binds has the type of its value.
+    pub fn type_of(&self, token: &Token) -> Option<String> {
+        let ast = self.ast.as_ref()?;
+        let expr = ast
+            .expr_ids()
+            .filter(|&expr| {
+                let span = ast.span(expr);
+                span.start <= token.span.start && token.span.end <= span.end
+            })
+            .min_by_key(|&expr| ast.span(expr).len())?;
+        let expr = match ast[expr] {
+            Expr::Let(_, value) if token.kind == TokenKind::Id => value,
+            _ => expr,
+        };
+        self.checked
+            .types
+            .get(expr)
+            .map(|ty| ty.display(ast).to_string())
+    }
+
+    /// The name that the identifier `token` refers to: the variable the type
+    /// checker bound it to, or else the struct or enum of that name.
+    pub fn definition(&self, token: &Token) -> Option<Span> {
+        if token.kind != TokenKind::Id {
+            return None;
+        }
+        if let Some(binding) = self.binding(token) {
+            return self.binder(binding, self.text(token));
+        }
+        self.type_name(self.text(token))
+    }
+
+    /// Every mention of the variable under `token`: the name its binder
+    /// introduces and each name the type checker bound to it. A struct or
+    /// enum is no variable, so its name is found wherever it is written.
+    pub fn references(&self, token: &Token) -> Vec<Span> {
+        if token.kind != TokenKind::Id {
+            return vec![];
+        }
+        let name = self.text(token);
+        let Some(ast) = &self.ast else {
+            return vec![];
+        };
+        let uses: Vec<_> = self
+            .names(ast)
+            .filter(|&expr| self.src.get(ast.span(expr)) == Some(name))
+            .filter_map(|expr| {
+                let binder = self.binder(*self.checked.bindings.get(expr)?, name)?;
+                Some((ast.span(expr), binder))
+            })
+            .collect();
+        let binder = uses
+            .iter()
+            .find(|(span, binder)| *span == token.span || *binder == token.span)
+            .map(|(_, binder)| binder.clone());
+        match binder {
+            Some(binder) => {
+                let uses = uses.into_iter().filter(|(_, b)| *b == binder);
+                std::iter::once(binder.clone())
+                    .chain(uses.map(|(span, _)| span))
+                    .collect()
+            }
+            None if self.type_name(name).is_some() => self
+                .tokens
+                .iter()
+                .filter(|t| t.kind == TokenKind::Id && self.text(t) == name)
+                .map(|t| t.span.clone())
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// The expressions that are a name.
+    fn names<'a>(&self, ast: &'a Ast) -> impl Iterator<Item = ExprId> + 'a {
+        ast.expr_ids()
+            .filter(|&expr| matches!(ast[expr], Expr::Atom(Atom::Id(_))))
+    }
+
+    /// What the type checker bound the name `token` to.
+    fn binding(&self, token: &Token) -> Option<Binding> {
+        let ast = self.ast.as_ref()?;
+        let expr = self.names(ast).find(|&expr| ast.span(expr) == token.span)?;
+        self.checked.bindings.get(expr).copied()
+    }
+
+    /// The span of the name `name` that `binding` introduces.
+    fn binder(&self, binding: Binding, name: &str) -> Option<Span> {
+        let span = binding.span(self.ast.as_ref()?);
+        // The binder's name is the first mention of it in its node.
+        self.tokens
+            .iter()
+            .filter(|t| span.start <= t.span.start && t.span.end <= span.end)
+            .find(|t| t.kind == TokenKind::Id && self.text(t) == name)
+            .map(|t| t.span.clone())
+    }
+
+    /// The name of the struct or enum called `name`.
+    fn type_name(&self, name: &str) -> Option<Span> {
+        self.syntax
+            .children()
+            .filter(|node| matches!(node.kind(), SyntaxKind::Struct | SyntaxKind::Enum))
+            .filter_map(|node| name_span(&node))
+            .find(|span| &self.src[span.clone()] == name)
+    }
+
+    /// The structs, enums and variables declared at the top level, with the
+    /// fields and variants of the types.
+    pub fn symbols(&self) -> Vec<DocumentSymbol> {
+        self.syntax
+            .children()
+            .filter_map(|node| {
+                let (kind, members, member_kind) = match node.kind() {
+                    SyntaxKind::Struct => {
+                        (SymbolKind::STRUCT, SyntaxKind::FieldDecl, SymbolKind::FIELD)
+                    }
+                    SyntaxKind::Enum => (
+                        SymbolKind::ENUM,
+                        SyntaxKind::VariantDecl,
+                        SymbolKind::ENUM_MEMBER,
+                    ),
+                    SyntaxKind::Let => {
+                        let lambda = node.children().any(|n| n.kind() == SyntaxKind::Lambda);
+                        let kind = match lambda {
+                            true => SymbolKind::FUNCTION,
+                            false => SymbolKind::VARIABLE,
+                        };
+                        (kind, SyntaxKind::Error, kind)
+                    }
+                    _ => return None,
+                };
+                let children = node
+                    .children()
+                    .filter(|n| n.kind() == members)
+                    .filter_map(|n| self.symbol(&n, member_kind, vec![]))
+                    .collect();
+                self.symbol(&node, kind, children)
+            })
+            .collect()
+    }
+
+    #[allow(deprecated)]
+    fn symbol(
+        &self,
+        node: &SyntaxNode,
+        kind: SymbolKind,
+        children: Vec<DocumentSymbol>,
+    ) -> Option<DocumentSymbol> {
+        let name = name_span(node)?;
+        let range = node.text_range();
+        Some(DocumentSymbol {
+            name: self.src[name.clone()].to_string(),
+            detail: None,
+            kind,
+            tags: None,
+            deprecated: None,
+            range: self.range(&(range.start().into()..range.end().into())),
+            selection_range: self.range(&name),
+            children: (!children.is_empty()).then_some(children),
+        })
+    }
+
+    pub fn position(&self, offset: usize) -> Position {
+        let before = &self.src[..offset];
+        let line = before.matches('\n').count();
+        let start = before.rfind('\n').map_or(0, |i| i + 1);
+        let character = self.src[start..offset].encode_utf16().count();
+        Position::new(line as u32, character as u32)
+    }
+
+    pub fn offset(&self, position: Position) -> usize {
+        let mut start = 0;
+        for _ in 0..position.line {
+            match self.src[start..].find('\n') {
+                Some(i) => start += i + 1,
+                None => return self.src.len(),
+            }
+        }
+        let mut units = 0;
+        for (i, c) in self.src[start..].char_indices() {
+            if units >= position.character as usize || c == '\n' {
+                return start + i;
+            }
+            units += c.len_utf16();
+        }
+        self.src.len()
+    }
+
+    pub fn range(&self, span: &Span) -> Range {
+        Range::new(self.position(span.start), self.position(span.end))
+    }
+}
+
+/// The span of the name a declaration node introduces, which is its first
+/// identifier.
+fn name_span(node: &SyntaxNode) -> Option<Span> {
+    let token = node
+        .children_with_tokens()
+        .filter_map(|e| e.into_token())
+        .find(|t| t.kind() == SyntaxKind::Id)?;
+    let range = token.text_range();
+    Some(range.start().into()..range.end().into())
+}
@@ -0,0 +1,282 @@
+mod analysis;
+#[cfg(test)]
+mod test;
+
+use crate::analysis::Analysis;
+use cb_lexer::TokenKind;
+use lsp_server::{Connection, Message, Notification, Request, RequestId, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _,
+    PublishDiagnostics,
+};
+use lsp_types::request::{
+    DocumentSymbolRequest, GotoDefinition, HoverRequest, References, Request as _,
+    SemanticTokensFullRequest,
+};
+use lsp_types::{
+    Diagnostic, DiagnosticSeverity, DocumentSymbolResponse, GotoDefinitionResponse, Hover,
+    HoverContents, HoverProviderCapability, Location, MarkupContent, MarkupKind, OneOf,
+    PublishDiagnosticsParams, SemanticToken, SemanticTokenType, SemanticTokens,
+    SemanticTokensFullOptions, SemanticTokensLegend, SemanticTokensOptions, SemanticTokensResult,
+    SemanticTokensServerCapabilities, ServerCapabilities, TextDocumentPositionParams,
+    TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+use serde_json::Value;
+use std::collections::HashMap;
+
+type CResult<T> = Result<T, Box<dyn std::error::Error + Sync + Send>>;
+
+const LEGEND: [SemanticTokenType; 4] = [
+    SemanticTokenType::KEYWORD,
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::NUMBER,
+    SemanticTokenType::OPERATOR,
+];
+
+/// Serves the language server protocol over stdin and stdout until the
+/// client asks it to exit.
+pub fn stdio() -> CResult<()> {
+    let (connection, io_threads) = Connection::stdio();
+    serve(&connection)?;
+    // The writer thread only finishes once every sender is gone.
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
+
+/// Runs the initialize handshake and then answers requests on `connection`
+/// until the client shuts the server down.
+pub fn serve(connection: &Connection) -> CResult<()> {
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        references_provider: Some(OneOf::Left(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        semantic_tokens_provider: Some(SemanticTokensServerCapabilities::SemanticTokensOptions(
+            SemanticTokensOptions {
+                legend: SemanticTokensLegend {
+                    token_types: LEGEND.to_vec(),
+                    token_modifiers: vec![],
+                },
+                full: Some(SemanticTokensFullOptions::Bool(true)),
+                ..Default::default()
+            },
+        )),
+        ..Default::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+
+    let mut server = Server::default();
+    for msg in &connection.receiver {
+        match msg {
+            Message::Request(req) => {
+                if connection.handle_shutdown(&req)? {
+                    return Ok(());
+                }
+                let response = server.request(req);
+                connection.sender.send(Message::Response(response))?;
+            }
+            Message::Notification(not) => {
+                if let Some(diagnostics) = server.notification(not)? {
+                    connection.sender.send(Message::Notification(diagnostics))?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct Server {
+    documents: HashMap<Url, Analysis>,
+}
+
+impl Server {
+    /// Keeps the open documents up to date and returns the diagnostics to
+    /// publish for the one that changed.
+    fn notification(&mut self, not: Notification) -> CResult<Option<Notification>> {
+        let (uri, src) = match not.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: lsp_types::DidOpenTextDocumentParams =
+                    serde_json::from_value(not.params)?;
+                (params.text_document.uri, params.text_document.text)
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: lsp_types::DidChangeTextDocumentParams =
+                    serde_json::from_value(not.params)?;
+                let Some(change) = params.content_changes.into_iter().last() else {
+                    return Ok(None);
+                };
+                (params.text_document.uri, change.text)
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: lsp_types::DidCloseTextDocumentParams =
+                    serde_json::from_value(not.params)?;
+                self.documents.remove(&params.text_document.uri);
+                return Ok(None);
+            }
+            _ => return Ok(None),
+        };
+        let analysis = Analysis::new(&src);
+        let diagnostics = analysis
+            .diagnostics
+            .iter()
+            .map(|(span, message)| Diagnostic {
+                range: analysis.range(span),
+                severity: Some(DiagnosticSeverity::ERROR),
+                source: Some("cbc".into()),
+                message: message.clone(),
+                ..Default::default()
+            })
+            .collect();
+        self.documents.insert(uri.clone(), analysis);
+        let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
+        Ok(Some(Notification::new(
+            PublishDiagnostics::METHOD.into(),
+            params,
+        )))
+    }
+
+    fn request(&self, req: Request) -> Response {
+        let id = req.id.clone();
+        let result = match req.method.as_str() {
+            HoverRequest::METHOD => self.hover(req),
+            GotoDefinition::METHOD => self.definition(req),
+            References::METHOD => self.references(req),
+            DocumentSymbolRequest::METHOD => self.document_symbols(req),
+            SemanticTokensFullRequest::METHOD => self.semantic_tokens(req),
+            _ => {
+                let message = format!("unsupported request '{}'", req.method);
+                let code = lsp_server::ErrorCode::MethodNotFound as i32;
+                return Response::new_err(id, code, message);
+            }
+        };
+        match result {
+            Ok(value) => Response::new_ok(id, value),
+            Err(e) => error(id, e),
+        }
+    }
+
+    fn lookup(&self, position: &TextDocumentPositionParams) -> CResult<(&Analysis, usize)> {
+        let uri = &position.text_document.uri;
+        let Some(analysis) = self.documents.get(uri) else {
+            return Err(format!("'{uri}' is not open").into());
+        };
+        Ok((analysis, analysis.offset(position.position)))
+    }
+
+    fn hover(&self, req: Request) -> CResult<Value> {
+        let (_, params) = req.extract::<lsp_types::HoverParams>(HoverRequest::METHOD)?;
+        let (analysis, offset) = self.lookup(&params.text_document_position_params)?;
+        let Some(token) = analysis.token_at(offset) else {
+            return Ok(Value::Null);
+        };
+        let Some(ty) = analysis.type_of(token) else {
+            return Ok(Value::Null);
+        };
+        let hover = Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: format!("```cflat\n{ty}\n```"),
+            }),
+            range: Some(analysis.range(&token.span)),
+        };
+        Ok(serde_json::to_value(hover)?)
+    }
+
+    fn definition(&self, req: Request) -> CResult<Value> {
+        let (_, params) = req.extract::<lsp_types::GotoDefinitionParams>(GotoDefinition::METHOD)?;
+        let position = &params.text_document_position_params;
+        let (analysis, offset) = self.lookup(position)?;
+        let Some(span) = analysis
+            .token_at(offset)
+            .and_then(|t| analysis.definition(t))
+        else {
+            return Ok(Value::Null);
+        };
+        let location = Location::new(position.text_document.uri.clone(), analysis.range(&span));
+        Ok(serde_json::to_value(GotoDefinitionResponse::Scalar(
+            location,
+        ))?)
+    }
+
+    fn references(&self, req: Request) -> CResult<Value> {
+        let (_, params) = req.extract::<lsp_types::ReferenceParams>(References::METHOD)?;
+        let (analysis, offset) = self.lookup(&params.text_document_position)?;
+        let Some(token) = analysis.token_at(offset) else {
+            return Ok(Value::Null);
+        };
+        let uri = &params.text_document_position.text_document.uri;
+        let locations: Vec<_> = analysis
+            .references(token)
+            .iter()
+            .map(|span| Location::new(uri.clone(), analysis.range(span)))
+            .collect();
+        Ok(serde_json::to_value(locations)?)
+    }
+
+    /// The top level declarations, with the fields and variants of structs
+    /// and enums nested inside them.
+    fn document_symbols(&self, req: Request) -> CResult<Value> {
+        let (_, params) =
+            req.extract::<lsp_types::DocumentSymbolParams>(DocumentSymbolRequest::METHOD)?;
+        let uri = &params.text_document.uri;
+        let Some(analysis) = self.documents.get(uri) else {
+            return Err(format!("'{uri}' is not open").into());
+        };
+        Ok(serde_json::to_value(DocumentSymbolResponse::Nested(
+            analysis.symbols(),
+        ))?)
+    }
+
+    fn semantic_tokens(&self, req: Request) -> CResult<Value> {
+        let (_, params) =
+            req.extract::<lsp_types::SemanticTokensParams>(SemanticTokensFullRequest::METHOD)?;
+        let uri = &params.text_document.uri;
+        let Some(analysis) = self.documents.get(uri) else {
+            return Err(format!("'{uri}' is not open").into());
+        };
+        let mut data = vec![];
+        let mut previous = lsp_types::Position::new(0, 0);
+        for token in &analysis.tokens {
+            let token_type = match token.kind {
+                TokenKind::Id => SemanticTokenType::VARIABLE,
+                TokenKind::Int | TokenKind::Float => SemanticTokenType::NUMBER,
+                kind if kind.is_keyword() => SemanticTokenType::KEYWORD,
+                kind if kind.is_op() => SemanticTokenType::OPERATOR,
+                _ => continue,
+            };
+            let range = analysis.range(&token.span);
+            // Tokens never span lines, so the length is measured on one line.
+            let length = range.end.character - range.start.character;
+            let delta_line = range.start.line - previous.line;
+            let delta_start = match delta_line {
+                0 => range.start.character - previous.character,
+                _ => range.start.character,
+            };
+            data.push(SemanticToken {
+                delta_line,
+                delta_start,
+                length,
+                token_type: LEGEND.iter().position(|t| *t == token_type).unwrap() as u32,
+                token_modifiers_bitset: 0,
+            });
+            previous = range.start;
+        }
+        let tokens = SemanticTokensResult::Tokens(SemanticTokens {
+            result_id: None,
+            data,
+        });
+        Ok(serde_json::to_value(tokens)?)
+    }
+}
+
+fn error(id: RequestId, e: Box<dyn std::error::Error + Sync + Send>) -> Response {
+    Response::new_err(
+        id,
+        lsp_server::ErrorCode::InvalidParams as i32,
+        e.to_string(),
+    )
+}
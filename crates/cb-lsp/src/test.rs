@@ -0,0 +1,283 @@
+use super::serve;
+use lsp_server::{Connection, Message, Notification, Request, RequestId};
+use serde_json::{json, Value};
+use std::thread::JoinHandle;
+
+const URI: &str = "file:///test.cb";
+
+/// A scripted client talking to a server running on another thread.
+struct Client {
+    connection: Connection,
+    server: Option<JoinHandle<()>>,
+    next_id: i32,
+}
+
+impl Client {
+    fn new() -> Self {
+        let (server, connection) = Connection::memory();
+        let server = std::thread::spawn(move || serve(&server).unwrap());
+        let mut client = Self {
+            connection,
+            server: Some(server),
+            next_id: 0,
+        };
+        client.request("initialize", json!({ "capabilities": {} }));
+        client.notify("initialized", json!({}));
+        client
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        let id = RequestId::from(self.next_id);
+        let request = Request::new(id.clone(), method.into(), params);
+        self.connection.sender.send(request.into()).unwrap();
+        match self.connection.receiver.recv().unwrap() {
+            Message::Response(response) if response.id == id => {
+                assert!(response.error.is_none(), "{:?}", response.error);
+                response.result.unwrap_or(Value::Null)
+            }
+            msg => panic!("expected a response to '{method}' but got {msg:?}"),
+        }
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        let notification = Notification::new(method.into(), params);
+        self.connection.sender.send(notification.into()).unwrap();
+    }
+
+    /// Opens (or replaces) the test document and returns its diagnostics.
+    fn open(&mut self, text: &str) -> Value {
+        self.notify(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": { "uri": URI, "languageId": "cflat", "version": 1, "text": text }
+            }),
+        );
+        self.diagnostics()
+    }
+
+    fn diagnostics(&mut self) -> Value {
+        match self.connection.receiver.recv().unwrap() {
+            Message::Notification(n) if n.method == "textDocument/publishDiagnostics" => {
+                n.params["diagnostics"].clone()
+            }
+            msg => panic!("expected diagnostics but got {msg:?}"),
+        }
+    }
+
+    fn at(&mut self, method: &str, line: u32, character: u32) -> Value {
+        self.request(
+            method,
+            json!({
+                "textDocument": { "uri": URI },
+                "position": { "line": line, "character": character },
+                "context": { "includeDeclaration": true }
+            }),
+        )
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.request("shutdown", Value::Null);
+        self.notify("exit", Value::Null);
+        if let Some(server) = self.server.take() {
+            server.join().unwrap();
+        }
+    }
+}
+
+fn range(start: (u32, u32), end: (u32, u32)) -> Value {
+    json!({
+        "start": { "line": start.0, "character": start.1 },
+        "end": { "line": end.0, "character": end.1 }
+    })
+}
+
+/// The range and message of each diagnostic.
+fn messages(diagnostics: Value) -> Vec<(Value, String)> {
+    diagnostics
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|d| {
+            (
+                d["range"].clone(),
+                d["message"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn lsp_publishes_diagnostics() {
+    let mut client = Client::new();
+    assert_eq!(client.open("1 + 2 * 3"), json!([]));
+    assert_eq!(client.open("let x = 1\nx"), json!([]));
+
+    assert_eq!(
+        messages(client.open("if 1 > 3 {\n    a + 1\n}\n1 + ;")),
+        vec![(range((3, 4), (3, 5)), "unexpected ';'".to_string())]
+    );
+    assert_eq!(
+        messages(client.open("if 1 > 3 {\n    a + 1\n}")),
+        vec![(range((1, 4), (1, 5)), "unbound identifier 'a'".to_string())]
+    );
+    assert_eq!(
+        messages(client.open("let s = \"a\"\ns - 1")),
+        vec![(
+            range((1, 0), (1, 1)),
+            "expected 'i32' but found 'String'".to_string()
+        )]
+    );
+
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": URI, "version": 2 },
+            "contentChanges": [{ "text": "1 + 1" }]
+        }),
+    );
+    assert_eq!(client.diagnostics(), json!([]));
+}
+
+#[test]
+fn lsp_hover_shows_types() {
+    let mut client = Client::new();
+    client.open("let s = \"a\"\ns + to_string(12)\nlet f = fn(x) { x * 2 }");
+    let hover = client.at("textDocument/hover", 1, 0);
+    assert_eq!(hover["contents"]["value"], "```cflat\nString\n```");
+    assert_eq!(hover["range"], range((1, 0), (1, 1)));
+    let hover = client.at("textDocument/hover", 1, 2);
+    assert_eq!(hover["contents"]["value"], "```cflat\nString\n```");
+    let hover = client.at("textDocument/hover", 1, 15);
+    assert_eq!(hover["contents"]["value"], "```cflat\ni32\n```");
+    let hover = client.at("textDocument/hover", 2, 4);
+    assert_eq!(hover["contents"]["value"], "```cflat\nfn(i32) -> i32\n```");
+    assert_eq!(client.at("textDocument/hover", 0, 0), Value::Null);
+}
+
+#[test]
+fn lsp_references() {
+    let mut client = Client::new();
+    client.open(
+        "struct P { x: i32 }\nlet x = 1\nlet y = x + 1\n\
+         let x = P { x: x }\nx.x + y",
+    );
+    let references = |client: &mut Client, line, character| -> Vec<_> {
+        let references = client.at("textDocument/references", line, character);
+        references
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|l| l["range"].clone())
+            .collect()
+    };
+    let shadowed = vec![
+        range((1, 4), (1, 5)),
+        range((2, 8), (2, 9)),
+        range((3, 15), (3, 16)),
+    ];
+    assert_eq!(references(&mut client, 1, 4), shadowed);
+    assert_eq!(references(&mut client, 3, 15), shadowed);
+    let shadowing = vec![range((3, 4), (3, 5)), range((4, 0), (4, 1))];
+    assert_eq!(references(&mut client, 4, 0), shadowing);
+    assert!(references(&mut client, 3, 12).is_empty());
+    assert!(references(&mut client, 4, 2).is_empty());
+    let struct_name = vec![range((0, 7), (0, 8)), range((3, 8), (3, 9))];
+    assert_eq!(references(&mut client, 3, 8), struct_name);
+
+    client.open("a + b\nif a > 1 { a }");
+    assert!(references(&mut client, 1, 3).is_empty());
+    assert_eq!(client.at("textDocument/definition", 1, 3), Value::Null);
+}
+
+#[test]
+fn lsp_definition() {
+    let mut client = Client::new();
+    client.open(
+        "struct P { x: i32 }\nlet x = 1\nlet f = fn(x) { x + 1 }\n\
+         for i in 0..x { f(i) }\nP { x: x }.x",
+    );
+    let mut definition = |line, character| {
+        let location = client.at("textDocument/definition", line, character);
+        location["range"].clone()
+    };
+    assert_eq!(definition(2, 16), range((2, 11), (2, 12)));
+    assert_eq!(definition(3, 12), range((1, 4), (1, 5)));
+    assert_eq!(definition(3, 16), range((2, 4), (2, 5)));
+    assert_eq!(definition(3, 18), range((3, 4), (3, 5)));
+    assert_eq!(definition(4, 0), range((0, 7), (0, 8)));
+    assert_eq!(definition(4, 7), range((1, 4), (1, 5)));
+    assert_eq!(definition(4, 11), Value::Null);
+}
+
+#[test]
+fn lsp_semantic_tokens() {
+    let mut client = Client::new();
+    client.open("if x > 10 {\n  x\n}");
+    let tokens = client.request(
+        "textDocument/semanticTokens/full",
+        json!({ "textDocument": { "uri": URI } }),
+    );
+    #[rustfmt::skip]
+    let expected = json!([
+        0, 0, 2, 0, 0, // if
+        0, 3, 1, 1, 0, // x
+        0, 2, 1, 3, 0, // >
+        0, 2, 2, 2, 0, // 10
+        0, 3, 1, 3, 0, // {
+        1, 2, 1, 1, 0, // x
+        1, 0, 1, 3, 0, // }
+    ]);
+    assert_eq!(tokens["data"], expected);
+
+    let symbols = client.request(
+        "textDocument/documentSymbol",
+        json!({ "textDocument": { "uri": URI } }),
+    );
+    assert_eq!(symbols, json!([]));
+
+    client.open("struct P { x: i32 }\nenum E { A, B(i32) }\nlet f = fn(x) { x }\nlet y = f(1)");
+    let symbols = client.request(
+        "textDocument/documentSymbol",
+        json!({ "textDocument": { "uri": URI } }),
+    );
+    let outline = |symbols: &Value| -> Vec<(String, u64, Value)> {
+        symbols
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| {
+                let name = s["name"].as_str().unwrap().to_string();
+                (
+                    name,
+                    s["kind"].as_u64().unwrap(),
+                    s["selectionRange"].clone(),
+                )
+            })
+            .collect()
+    };
+    assert_eq!(
+        outline(&symbols),
+        [
+            ("P".to_string(), 23, range((0, 7), (0, 8))),
+            ("E".to_string(), 10, range((1, 5), (1, 6))),
+            ("f".to_string(), 12, range((2, 4), (2, 5))),
+            ("y".to_string(), 13, range((3, 4), (3, 5))),
+        ]
+    );
+    assert_eq!(symbols[0]["range"], range((0, 0), (0, 19)));
+    assert_eq!(
+        outline(&symbols[0]["children"]),
+        [("x".to_string(), 8, range((0, 11), (0, 12)))]
+    );
+    assert_eq!(
+        outline(&symbols[1]["children"]),
+        [
+            ("A".to_string(), 22, range((1, 9), (1, 10))),
+            ("B".to_string(), 22, range((1, 12), (1, 13))),
+        ]
+    );
+    assert_eq!(symbols[2].get("children"), None);
+}
@@ -0,0 +1,231 @@
+//! Programs made of several files.
+//!
+//! `mod name;` declares a module whose statements are in `name.cb`, next to
+//! the root file or, for a module in `dir/parent.cb`, in `dir/parent/`.
+//! `use a::b::name;` brings the item `name` of module `a::b` into scope;
+//! paths start at the root file. An item is private to the module that
+//! declares it, and to that module's submodules, unless it is marked `pub`.
+//!
+//! Loading links every module into one [`Ast`] that the rest of the
+//! compiler checks and runs like a program of one file.
+
+mod link;
+#[cfg(test)]
+mod test;
+
+use cb_lexer::{FileId, SourceMap, Span};
+use cb_parse::{syntax, Ast, Interner, Stmt, Symbol};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// A linked program and the files it was loaded from. Spans in `ast` are
+/// offsets into `sources`.
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub ast: Ast,
+    pub sources: SourceMap,
+}
+
+impl Program {
+    /// An error at `span` of the program, reported in the file it is in.
+    pub fn error(&self, span: &Span, message: impl Into<String>) -> ModuleError {
+        ModuleError::at(&self.sources, span, message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleError {
+    /// The file the error is in.
+    pub file: String,
+    /// Where in the file, or `None` if the error is about the whole file.
+    pub span: Option<Span>,
+    pub message: String,
+}
+
+impl ModuleError {
+    /// An error at a span of a program loaded into `sources`.
+    pub fn at(sources: &SourceMap, span: &Span, message: impl Into<String>) -> Self {
+        let (file, span) = sources.locate(span);
+        Self {
+            file: sources.file(file).name.clone(),
+            span: Some(span),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ModuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.span {
+            Some(span) => write!(
+                f,
+                "{}: {}:{} {}",
+                self.file, span.start, span.end, self.message
+            ),
+            None => write!(f, "{}: {}", self.file, self.message),
+        }
+    }
+}
+
+impl std::error::Error for ModuleError {}
+
+/// A parsed file and the modules it declares.
+#[derive(Debug)]
+struct Module {
+    /// The names of the modules leading to this one from the root.
+    path: Vec<Symbol>,
+    file: FileId,
+    /// Whether modules outside the parent can use this one.
+    public: bool,
+    /// The root of the program or dependency that this module is part of,
+    /// where its paths start.
+    krate: usize,
+    ast: Ast,
+    /// The modules declared in this one, in order.
+    children: Vec<(Symbol, usize)>,
+}
+
+/// Loads the program whose root file is `filename`, with source `src`, and
+/// every module it declares, then links them.
+pub fn load(filename: &str, src: String) -> Result<Program, ModuleError> {
+    load_with(filename, src, &[])
+}
+
+/// Like [`load`], with the root files of other programs, by name, loaded as
+/// public modules of the root that come before the modules it declares.
+pub fn load_with(
+    filename: &str,
+    src: String,
+    dependencies: &[(String, PathBuf)],
+) -> Result<Program, ModuleError> {
+    let mut loader = Loader::default();
+    loader.load(
+        filename,
+        src,
+        &parent(Path::new(filename)),
+        vec![],
+        true,
+        None,
+    )?;
+    let mods = loader.modules[0].children.len();
+    for (name, path) in dependencies {
+        let symbol = loader.names.intern(name);
+        if loader.modules[0].children.iter().any(|&(n, _)| n == symbol) {
+            let message = format!("module '{name}' has the name of a dependency");
+            return Err(loader.error(0, message));
+        }
+        let filename = path.to_string_lossy();
+        let src = std::fs::read_to_string(path).map_err(|e| {
+            let message = format!("cannot read dependency '{name}' from '{filename}': {e}");
+            loader.error(0, message)
+        })?;
+        let dependency = loader.load(&filename, src, &parent(path), vec![symbol], true, None)?;
+        loader.modules[0].children.push((symbol, dependency));
+    }
+    loader.modules[0].children.rotate_left(mods);
+    let ast = link::link(&loader.modules, &loader.sources, loader.names)?;
+    Ok(Program {
+        ast,
+        sources: loader.sources,
+    })
+}
+
+#[derive(Default)]
+struct Loader {
+    sources: SourceMap,
+    modules: Vec<Module>,
+    /// The names of every module so far. Each file is lowered into them, so
+    /// that a name is the same symbol in all of them.
+    names: Interner,
+}
+
+impl Loader {
+    /// Parses a file, whose modules are in `dir`, and loads those modules
+    /// depth first. A file with no `krate` is the root of a program or of a
+    /// dependency. Returns the index of the file's module.
+    fn load(
+        &mut self,
+        filename: &str,
+        src: String,
+        dir: &Path,
+        path: Vec<Symbol>,
+        public: bool,
+        krate: Option<usize>,
+    ) -> Result<usize, ModuleError> {
+        let names = std::mem::take(&mut self.names);
+        let mut ast = syntax::parse(&src)
+            .lower_with(names)
+            .map_err(|e| ModuleError {
+                file: filename.to_string(),
+                span: Some(e.span),
+                message: e.message,
+            })?;
+        self.names = ast.names().clone();
+        let file = self.sources.add(filename, src);
+        ast.offset_spans(self.sources.file(file).start);
+        let mods = ast
+            .program()
+            .iter()
+            .filter_map(|&stmt| match ast[stmt] {
+                Stmt::Mod(name) => Some((name, ast.is_public(stmt))),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let index = self.modules.len();
+        let krate = krate.unwrap_or(index);
+        self.modules.push(Module {
+            path: path.clone(),
+            file,
+            public,
+            krate,
+            ast,
+            children: vec![],
+        });
+        for (name, public) in mods {
+            let child = dir.join(format!("{}.cb", &self.names[name]));
+            let child_name = child.to_string_lossy().into_owned();
+            let child_src = std::fs::read_to_string(&child).map_err(|e| {
+                let name = &self.names[name];
+                let message = format!("cannot read module '{name}' from '{child_name}': {e}");
+                self.error(index, message)
+            })?;
+            let mut child_path = path.clone();
+            child_path.push(name);
+            let child_dir = dir.join(&self.names[name]);
+            let child = self.load(
+                &child_name,
+                child_src,
+                &child_dir,
+                child_path,
+                public,
+                Some(krate),
+            )?;
+            self.modules[index].children.push((name, child));
+        }
+        Ok(index)
+    }
+}
+
+impl Loader {
+    /// An error about module `index` as a whole.
+    fn error(&self, index: usize, message: String) -> ModuleError {
+        ModuleError {
+            file: self.sources.file(self.modules[index].file).name.clone(),
+            span: None,
+            message,
+        }
+    }
+}
+
+/// The directory a file is in.
+fn parent(path: &Path) -> PathBuf {
+    path.parent().map_or_else(PathBuf::new, Path::to_path_buf)
+}
+
+/// Joins a path of `names` with `::`.
+fn join(names: &Interner, path: &[Symbol]) -> String {
+    path.iter()
+        .map(|&name| &names[name])
+        .collect::<Vec<_>>()
+        .join("::")
+}
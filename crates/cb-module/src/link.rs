@@ -0,0 +1,417 @@
+//! Links modules into one program. Each module's submodules come first, in
+//! the order they are declared, and then its own statements. Items of a
+//! module other than the root are renamed to their path, such as `a::b::x`,
+//! so that the same name in two files never refers to the same item.
+
+use crate::{join, Module, ModuleError};
+use cb_lexer::{Interner, SourceMap};
+use cb_parse::visit::Fold;
+use cb_parse::{
+    Arm, Ast, Atom, BlockId, Enum, Expr, ExprId, Field, Lambda, LambdaId, Loop, LoopId, LoopKind,
+    Match, MatchId, Param, Pat, PatId, Stmt, Struct, StructLit, StructLitId, Symbol, TypeExpr,
+    Variant, VariantLit, VariantLitId,
+};
+use std::collections::HashMap;
+
+/// What a name at the top level of a module stands for.
+#[derive(Debug, Clone)]
+enum Item {
+    /// A `let`, by its linked name.
+    Value(Symbol),
+    /// A struct or an enum, by its linked name.
+    Type(TypeExpr),
+}
+
+/// The items in scope at the top level of a module, by the names the module
+/// uses for them.
+#[derive(Default)]
+struct Scope {
+    values: HashMap<Symbol, Symbol>,
+    types: HashMap<Symbol, TypeExpr>,
+}
+
+impl Scope {
+    fn insert(&mut self, name: Symbol, item: Item) {
+        match item {
+            Item::Value(linked) => {
+                self.values.insert(name, linked);
+            }
+            Item::Type(ty) => {
+                self.types.insert(name, ty);
+            }
+        }
+    }
+
+    fn type_name(&self, name: Symbol) -> Symbol {
+        match self.types.get(&name) {
+            Some(TypeExpr::Struct(linked) | TypeExpr::Enum(linked)) => *linked,
+            _ => name,
+        }
+    }
+
+    /// Renames the structs and enums in a type. The parser takes imported
+    /// types to be structs, so this also corrects imported enums.
+    fn ty(&self, ty: &TypeExpr) -> TypeExpr {
+        match ty {
+            TypeExpr::Int => TypeExpr::Int,
+            TypeExpr::String => TypeExpr::String,
+            TypeExpr::Fn(params, ret) => TypeExpr::Fn(
+                params.iter().map(|param| self.ty(param)).collect(),
+                Box::new(self.ty(ret)),
+            ),
+            TypeExpr::Array(elem, len) => TypeExpr::Array(Box::new(self.ty(elem)), *len),
+            TypeExpr::Struct(name) | TypeExpr::Enum(name) => {
+                self.types.get(name).cloned().unwrap_or_else(|| ty.clone())
+            }
+        }
+    }
+}
+
+/// Links `modules`, whose names are all in `names`.
+pub(crate) fn link(
+    modules: &[Module],
+    sources: &SourceMap,
+    names: Interner,
+) -> Result<Ast, ModuleError> {
+    let mut linker = Linker {
+        modules,
+        sources,
+        linked: vec![false; modules.len()],
+    };
+    let mut out = Ast::with_names(names);
+    linker.module(0, &mut out)?;
+    Ok(out)
+}
+
+struct Linker<'a> {
+    modules: &'a [Module],
+    sources: &'a SourceMap,
+    /// Which modules are already in the program.
+    linked: Vec<bool>,
+}
+
+impl<'a> Linker<'a> {
+    fn module(&mut self, index: usize, out: &mut Ast) -> Result<(), ModuleError> {
+        let module: &'a Module = &self.modules[index];
+        for &(_, child) in &module.children {
+            self.module(child, out)?;
+        }
+        let ast = &module.ast;
+        let mut scope = Scope::default();
+        for &stmt in ast.program() {
+            let stmt = match ast[stmt] {
+                Stmt::Mod(_) => continue,
+                Stmt::Use(path) => {
+                    let path = &ast[path];
+                    let item = self
+                        .resolve(out, index, path)
+                        .map_err(|message| ModuleError {
+                            file: self.sources.file(module.file).name.clone(),
+                            span: None,
+                            message,
+                        })?;
+                    scope.insert(path[path.len() - 1], item);
+                    continue;
+                }
+                Stmt::Struct(decl) => {
+                    let Struct { name, fields } = &ast[decl];
+                    let fields = fields
+                        .iter()
+                        .map(|field| Field {
+                            name: field.name,
+                            ty: scope.ty(&field.ty),
+                        })
+                        .collect();
+                    let linked = self.qualify(out, index, *name);
+                    scope.insert(*name, Item::Type(TypeExpr::Struct(linked)));
+                    Stmt::Struct(out.alloc_struct(Struct {
+                        name: linked,
+                        fields,
+                    }))
+                }
+                Stmt::Enum(decl) => {
+                    let Enum { name, variants } = &ast[decl];
+                    let variants = variants
+                        .iter()
+                        .map(|variant| Variant {
+                            name: variant.name,
+                            fields: variant.fields.iter().map(|ty| scope.ty(ty)).collect(),
+                        })
+                        .collect();
+                    let linked = self.qualify(out, index, *name);
+                    scope.insert(*name, Item::Type(TypeExpr::Enum(linked)));
+                    Stmt::Enum(out.alloc_enum(Enum {
+                        name: linked,
+                        variants,
+                    }))
+                }
+                Stmt::Expr(expr) => {
+                    let mut names = Names {
+                        scope: &scope,
+                        locals: vec![],
+                    };
+                    let expr = match ast[expr] {
+                        Expr::Let(name, value) => {
+                            let value = names.fold_expr(ast, out, value);
+                            let linked = self.qualify(out, index, name);
+                            scope.insert(name, Item::Value(linked));
+                            out.alloc_expr(Expr::Let(linked, value), ast.span(expr))
+                        }
+                        _ => names.fold_expr(ast, out, expr),
+                    };
+                    Stmt::Expr(expr)
+                }
+            };
+            let stmt = out.alloc_stmt(stmt);
+            out.push(stmt);
+        }
+        self.linked[index] = true;
+        Ok(())
+    }
+
+    /// The name an item of a module has in the linked program `out`.
+    fn qualify(&self, out: &mut Ast, index: usize, name: Symbol) -> Symbol {
+        match &self.modules[index].path[..] {
+            [] => name,
+            path => {
+                let linked = format!("{}::{}", join(out.names(), path), &out[name]);
+                out.intern(&linked)
+            }
+        }
+    }
+
+    /// Whether module `index` is module `from` or one of its parents, whose
+    /// private items `from` can use.
+    fn within(&self, from: usize, index: usize) -> bool {
+        self.modules[from]
+            .path
+            .starts_with(&self.modules[index].path)
+    }
+
+    /// Finds the item that `path` names, as seen from module `from`. Only
+    /// modules linked already can be used, which also rules out imports
+    /// that refer to one another.
+    fn resolve(&self, out: &mut Ast, from: usize, path: &[Symbol]) -> Result<Item, String> {
+        let unresolved = |out: &Ast| format!("unresolved import '{}'", join(out.names(), path));
+        let Some((&name, parents)) = path.split_last().filter(|(_, p)| !p.is_empty()) else {
+            return Err(unresolved(out));
+        };
+        let child = |index: usize, segment| {
+            let children = &self.modules[index].children;
+            children
+                .iter()
+                .find(|&&(n, _)| n == segment)
+                .map(|&(_, c)| c)
+        };
+        // Paths start at the root of the module's own program or dependency,
+        // and can also name a dependency of the program being linked.
+        let mut index = self.modules[from].krate;
+        if child(index, parents[0]).is_none()
+            && child(0, parents[0]).is_some_and(|c| self.modules[c].krate == c)
+        {
+            index = 0;
+        }
+        for &segment in parents {
+            let Some(child) = child(index, segment) else {
+                return Err(unresolved(out));
+            };
+            if !self.modules[child].public && !self.within(from, index) {
+                let child = join(out.names(), &self.modules[child].path);
+                return Err(format!("module '{child}' is private"));
+            }
+            index = child;
+        }
+        if !self.linked[index] {
+            return Err(format!(
+                "cannot use '{}' before module '{}' is linked; only submodules and modules declared earlier can be used",
+                join(out.names(), path),
+                join(out.names(), &self.modules[index].path)
+            ));
+        }
+        let ast = &self.modules[index].ast;
+        for &stmt in ast.program().iter().rev() {
+            let item = match ast[stmt] {
+                Stmt::Expr(expr) => match ast[expr] {
+                    Expr::Let(n, _) if n == name => Item::Value(self.qualify(out, index, name)),
+                    _ => continue,
+                },
+                Stmt::Struct(decl) if ast[decl].name == name => {
+                    Item::Type(TypeExpr::Struct(self.qualify(out, index, name)))
+                }
+                Stmt::Enum(decl) if ast[decl].name == name => {
+                    Item::Type(TypeExpr::Enum(self.qualify(out, index, name)))
+                }
+                Stmt::Use(u) if ast[u].last() == Some(&name) => {
+                    self.resolve(out, index, &ast[u])?
+                }
+                _ => continue,
+            };
+            if !ast.is_public(stmt) && !self.within(from, index) {
+                return Err(format!("'{}' is private", join(out.names(), path)));
+            }
+            return Ok(item);
+        }
+        Err(unresolved(out))
+    }
+}
+
+/// Renames the items a statement uses to their linked names, leaving
+/// locals, which may shadow items, as they are.
+struct Names<'a> {
+    scope: &'a Scope,
+    locals: Vec<Symbol>,
+}
+
+impl Fold for Names<'_> {
+    fn fold_atom(&mut self, ast: &Ast, out: &mut Ast, expr: ExprId, atom: Atom) -> ExprId {
+        let atom = match atom {
+            Atom::Id(name) if !self.locals.contains(&name) => {
+                Atom::Id(self.scope.values.get(&name).copied().unwrap_or(name))
+            }
+            Atom::Str(value) => Atom::Str(out.alloc_str(&ast[value])),
+            atom => atom,
+        };
+        out.alloc_expr(Expr::Atom(atom), ast.span(expr))
+    }
+
+    fn fold_lambda(&mut self, ast: &Ast, out: &mut Ast, expr: ExprId, lambda: LambdaId) -> ExprId {
+        let Lambda { params, ret, body } = &ast[lambda];
+        let scope = self.locals.len();
+        let params = params
+            .iter()
+            .map(|param| Param {
+                name: param.name,
+                ty: param.ty.as_ref().map(|ty| self.scope.ty(ty)),
+            })
+            .collect::<Vec<_>>();
+        self.locals.extend(params.iter().map(|param| param.name));
+        let body = self.fold_expr(ast, out, *body);
+        self.locals.truncate(scope);
+        let lambda = out.alloc_lambda(Lambda {
+            params,
+            ret: ret.as_ref().map(|ty| self.scope.ty(ty)),
+            body,
+        });
+        out.alloc_expr(Expr::Lambda(lambda), ast.span(expr))
+    }
+
+    fn fold_block(&mut self, ast: &Ast, out: &mut Ast, expr: ExprId, block: BlockId) -> ExprId {
+        let scope = self.locals.len();
+        let stmts = ast[block]
+            .iter()
+            .map(|&stmt| self.fold_expr(ast, out, stmt))
+            .collect();
+        self.locals.truncate(scope);
+        let block = out.alloc_block(stmts);
+        out.alloc_expr(Expr::Block(block), ast.span(expr))
+    }
+
+    fn fold_let(
+        &mut self,
+        ast: &Ast,
+        out: &mut Ast,
+        expr: ExprId,
+        name: Symbol,
+        value: ExprId,
+    ) -> ExprId {
+        let value = self.fold_expr(ast, out, value);
+        self.locals.push(name);
+        out.alloc_expr(Expr::Let(name, value), ast.span(expr))
+    }
+
+    fn fold_loop(&mut self, ast: &Ast, out: &mut Ast, expr: ExprId, l: LoopId) -> ExprId {
+        let Loop { label, kind, body } = ast[l];
+        let scope = self.locals.len();
+        let kind = match kind {
+            LoopKind::Loop => LoopKind::Loop,
+            LoopKind::While(c) => LoopKind::While(self.fold_expr(ast, out, c)),
+            LoopKind::For(name, from, to) => {
+                let from = self.fold_expr(ast, out, from);
+                let to = self.fold_expr(ast, out, to);
+                self.locals.push(name);
+                LoopKind::For(name, from, to)
+            }
+        };
+        let body = self.fold_expr(ast, out, body);
+        self.locals.truncate(scope);
+        let l = out.alloc_loop(Loop { label, kind, body });
+        out.alloc_expr(Expr::Loop(l), ast.span(expr))
+    }
+
+    fn fold_struct_lit(
+        &mut self,
+        ast: &Ast,
+        out: &mut Ast,
+        expr: ExprId,
+        lit: StructLitId,
+    ) -> ExprId {
+        let fields = ast[lit]
+            .fields
+            .iter()
+            .map(|&(name, value)| (name, self.fold_expr(ast, out, value)))
+            .collect();
+        let lit = out.alloc_struct_lit(StructLit {
+            name: self.scope.type_name(ast[lit].name),
+            fields,
+        });
+        out.alloc_expr(Expr::StructLit(lit), ast.span(expr))
+    }
+
+    fn fold_variant_lit(
+        &mut self,
+        ast: &Ast,
+        out: &mut Ast,
+        expr: ExprId,
+        lit: VariantLitId,
+    ) -> ExprId {
+        let VariantLit { ty, variant, args } = &ast[lit];
+        let args = args
+            .iter()
+            .map(|&arg| self.fold_expr(ast, out, arg))
+            .collect();
+        let lit = out.alloc_variant_lit(VariantLit {
+            ty: self.scope.type_name(*ty),
+            variant: *variant,
+            args,
+        });
+        out.alloc_expr(Expr::VariantLit(lit), ast.span(expr))
+    }
+
+    fn fold_match(&mut self, ast: &Ast, out: &mut Ast, expr: ExprId, m: MatchId) -> ExprId {
+        let scrutinee = self.fold_expr(ast, out, ast[m].scrutinee);
+        let arms = ast[m]
+            .arms
+            .iter()
+            .map(|arm| {
+                let scope = self.locals.len();
+                let arm = Arm {
+                    pat: self.fold_pat(ast, out, arm.pat),
+                    guard: arm.guard.map(|guard| self.fold_expr(ast, out, guard)),
+                    body: self.fold_expr(ast, out, arm.body),
+                };
+                self.locals.truncate(scope);
+                arm
+            })
+            .collect();
+        let m = out.alloc_match(Match { scrutinee, arms });
+        out.alloc_expr(Expr::Match(m), ast.span(expr))
+    }
+
+    fn fold_pat(&mut self, ast: &Ast, out: &mut Ast, pat: PatId) -> PatId {
+        let copy = match &ast[pat] {
+            Pat::Variant(ty, variant, args) => {
+                let args = args
+                    .iter()
+                    .map(|&arg| self.fold_pat(ast, out, arg))
+                    .collect();
+                Pat::Variant(self.scope.type_name(*ty), *variant, args)
+            }
+            Pat::Bind(name) => {
+                self.locals.push(*name);
+                Pat::Bind(*name)
+            }
+            pat => pat.clone(),
+        };
+        out.alloc_pat(copy, ast.pat_span(pat))
+    }
+}
@@ -0,0 +1,212 @@
+use super::{load_with, Program};
+use std::fs;
+
+/// Files of a program, by path from the directory of `main.cb`.
+type Files<'a> = &'a [(&'a str, &'a str)];
+
+/// Writes `files` to a fresh directory and loads its `main.cb`. Errors are
+/// rendered with paths relative to that directory.
+fn load_files(name: &str, files: Files) -> Result<Program, String> {
+    load_files_with(name, files, &[])
+}
+
+/// Like [`load_files`], with dependencies given by name and root file.
+fn load_files_with(name: &str, files: Files, dependencies: Files) -> Result<Program, String> {
+    let dir = std::env::temp_dir().join(format!("cb-module-{}-{name}", std::process::id()));
+    for (path, src) in files {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, src).unwrap();
+    }
+    let main = dir.join("main.cb");
+    let src = fs::read_to_string(&main).unwrap();
+    let dependencies = dependencies
+        .iter()
+        .map(|(name, path)| (name.to_string(), dir.join(path)))
+        .collect::<Vec<_>>();
+    let result = load_with(&main.to_string_lossy(), src, &dependencies);
+    fs::remove_dir_all(&dir).unwrap();
+    result.map_err(|e| e.to_string().replace(&format!("{}/", dir.display()), ""))
+}
+
+/// Loads, checks and runs a program.
+fn run(name: &str, files: Files) -> Vec<i32> {
+    let program = load_files(name, files).unwrap();
+    cb_typeck::check(&program.ast).unwrap();
+    cb_interp::run(&program.ast).unwrap()
+}
+
+#[test]
+fn single_file() {
+    assert_eq!(run("single", &[("main.cb", "let x = 2 x * 3")]), [6]);
+}
+
+#[test]
+fn nested_modules() {
+    let files = [
+        ("main.cb", "mod a;\nuse a::b::double;\nuse a::y;\ndouble(y)"),
+        (
+            "a.cb",
+            "pub mod b;\nlet hidden = 10\npub let y = hidden + 1",
+        ),
+        ("a/b.cb", "pub let double = fn(n) { n * 2 }"),
+    ];
+    assert_eq!(run("nested", &files), [22]);
+}
+
+#[test]
+fn names_do_not_collide() {
+    let files = [
+        ("main.cb", "mod a;\nuse a::get;\nlet x = 1\nx + get()"),
+        ("a.cb", "let x = 40\npub let get = fn() { x }"),
+    ];
+    assert_eq!(run("collide", &files), [41]);
+}
+
+#[test]
+fn locals_shadow_items() {
+    let files = [
+        ("main.cb", "mod a;\nuse a::f;\nf(5)"),
+        (
+            "a.cb",
+            "let n = 100\nlet m = 1000\npub let f = fn(n) { let m = n + 1 match m { n => n + m } }",
+        ),
+    ];
+    assert_eq!(run("shadow", &files), [12]);
+}
+
+#[test]
+fn types_and_reexports() {
+    let files = [
+        (
+            "main.cb",
+            "mod shapes;\nuse shapes::Shape;\nuse shapes::area;\n\
+             let f = fn(s: Shape) { area(s) }\nf(Shape::Rect(2, 3))\n\
+             match Shape::Empty { Shape::Empty => 1, _ => 0 }",
+        ),
+        ("shapes.cb", "pub mod kinds;\npub use shapes::kinds::Shape;\npub use shapes::kinds::area;"),
+        (
+            "shapes/kinds.cb",
+            "pub enum Shape { Rect(i32, i32), Empty }\n\
+             pub let area = fn(s: Shape) { match s { Shape::Rect(w, h) => w * h, Shape::Empty => 0 } }",
+        ),
+    ];
+    assert_eq!(run("types", &files), [6, 1]);
+
+    let files = [
+        ("main.cb", "mod s;\nuse s::P;\nP { x: 1 }.x"),
+        ("s.cb", "pub struct P { x: i32 }"),
+    ];
+    assert_eq!(run("struct_lit", &files), [1]);
+}
+
+#[test]
+fn children_see_private_items_of_parents() {
+    let files = [
+        ("main.cb", "mod a;\nmod b;\nuse b::z;\nz"),
+        ("a.cb", "let secret = 7"),
+        ("b.cb", "pub let z = 3"),
+    ];
+    assert_eq!(run("private_ok", &files), [3]);
+    let files = [
+        ("main.cb", "mod a;\nmod b;\nuse b::z;\nz"),
+        ("a.cb", "mod c;\nlet secret = 7"),
+        ("a/c.cb", "let nothing = 0"),
+        ("b.cb", "pub let z = 3"),
+    ];
+    assert_eq!(run("private_child", &files), [3]);
+}
+
+#[test]
+fn dependencies() {
+    let files = [
+        ("main.cb", "mod a;\nuse util::twice;\nuse a::y;\ntwice(y)"),
+        ("a.cb", "use base::one;\npub let y = one + 1"),
+        (
+            "util/main.cb",
+            "mod inner;\nuse inner::f;\npub let twice = fn(n) { f(n) * 2 }",
+        ),
+        ("util/inner.cb", "pub let f = fn(n) { n }"),
+        ("base/main.cb", "pub let one = 1"),
+    ];
+    let dependencies = [("base", "base/main.cb"), ("util", "util/main.cb")];
+    let program = load_files_with("dependencies", &files[..], &dependencies).unwrap();
+    cb_typeck::check(&program.ast).unwrap();
+    assert_eq!(cb_interp::run(&program.ast).unwrap(), [4]);
+    let files = [
+        ("main.cb", "mod util;"),
+        ("util.cb", ""),
+        ("util/main.cb", ""),
+    ];
+    let e = load_files_with("clash", &files, &[("util", "util/main.cb")]).unwrap_err();
+    assert_eq!(e, "main.cb: module 'util' has the name of a dependency");
+}
+
+#[test]
+fn errors() {
+    let cases: &[(&str, Files, &str)] = &[
+        (
+            "missing",
+            &[("main.cb", "mod a;")],
+            "main.cb: cannot read module 'a' from 'a.cb'",
+        ),
+        (
+            "parse",
+            &[("main.cb", "mod a;"), ("a.cb", "let x = ")],
+            "a.cb: 8:8 expected an expression but found end of input",
+        ),
+        (
+            "unresolved",
+            &[("main.cb", "mod a;\nuse a::y;"), ("a.cb", "let x = 1")],
+            "main.cb: unresolved import 'a::y'",
+        ),
+        (
+            "private_item",
+            &[("main.cb", "mod a;\nuse a::x;"), ("a.cb", "let x = 1")],
+            "main.cb: 'a::x' is private",
+        ),
+        (
+            "private_module",
+            &[
+                ("main.cb", "mod a;\nuse a::b::x;"),
+                ("a.cb", "mod b;"),
+                ("a/b.cb", "pub let x = 1"),
+            ],
+            "main.cb: module 'a::b' is private",
+        ),
+        (
+            "order",
+            &[
+                ("main.cb", "mod a;\nmod b;"),
+                ("a.cb", "use b::x;"),
+                ("b.cb", "pub let x = 1"),
+            ],
+            "a.cb: cannot use 'b::x' before module 'b' is linked",
+        ),
+        (
+            "itself",
+            &[("main.cb", "mod a;\nuse a::x;"), ("a.cb", "pub use a::x;")],
+            "a.cb: cannot use 'a::x' before module 'a' is linked",
+        ),
+    ];
+    for (name, files, expected) in cases {
+        let e = load_files(name, files).unwrap_err();
+        assert!(e.starts_with(expected), "{name}: {e}");
+    }
+}
+
+#[test]
+fn spans_are_located_in_their_file() {
+    let files = [
+        ("main.cb", "mod a;\nuse a::x;\nx + 1"),
+        ("a.cb", "pub let x = fn() { 1 }"),
+    ];
+    let program = load_files("spans", &files).unwrap();
+    let e = cb_typeck::check(&program.ast).unwrap_err();
+    let e = e.downcast_ref::<cb_typeck::TypeError>().unwrap();
+    let e = program.error(&e.span, &e.message).to_string();
+    assert!(
+        e.ends_with("main.cb: 17:18 expected 'i32' but found 'fn() -> i32'"),
+        "{e}"
+    );
+}
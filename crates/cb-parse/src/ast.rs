@@ -0,0 +1,1571 @@
+//! The abstract syntax tree. Nodes live in the arenas of an [`Ast`] and
+//! refer to their children by id, so passes can attach information to nodes
+//! in a [`SideTable`] instead of changing or copying the tree.
+
+use cb_lexer::{Interner, Span, Symbol, TokenKind};
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Index, IndexMut};
+
+/// An index into one of the arenas of an [`Ast`].
+pub trait Id: Copy {
+    fn new(index: usize) -> Self;
+    fn index(self) -> usize;
+}
+
+macro_rules! id {
+    ($(#[$doc:meta])* $name:ident) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        #[cfg_attr(
+            feature = "serde",
+            derive(serde::Serialize, serde::Deserialize),
+            serde(transparent)
+        )]
+        pub struct $name(u32);
+
+        impl Id for $name {
+            fn new(index: usize) -> Self {
+                Self(u32::try_from(index).expect("too many nodes"))
+            }
+
+            fn index(self) -> usize {
+                self.0 as usize
+            }
+        }
+    };
+}
+
+id!(
+    /// Refers to an [`Expr`] in an [`Ast`].
+    ExprId
+);
+id!(
+    /// Refers to a [`Stmt`] in an [`Ast`].
+    StmtId
+);
+id!(
+    /// Refers to a [`Lambda`] in an [`Ast`].
+    LambdaId
+);
+id!(
+    /// Refers to a [`Call`] in an [`Ast`].
+    CallId
+);
+id!(
+    /// Refers to the elements of an array literal in an [`Ast`].
+    ArrayId
+);
+id!(
+    /// Refers to the statements of a block in an [`Ast`].
+    BlockId
+);
+id!(
+    /// Refers to a [`Loop`] in an [`Ast`].
+    LoopId
+);
+id!(
+    /// Refers to a [`Struct`] declaration in an [`Ast`].
+    StructId
+);
+id!(
+    /// Refers to a [`StructLit`] in an [`Ast`].
+    StructLitId
+);
+id!(
+    /// Refers to an [`Enum`] declaration in an [`Ast`].
+    EnumId
+);
+id!(
+    /// Refers to a [`VariantLit`] in an [`Ast`].
+    VariantLitId
+);
+id!(
+    /// Refers to a [`Match`] in an [`Ast`].
+    MatchId
+);
+id!(
+    /// Refers to a [`Pat`] in an [`Ast`].
+    PatId
+);
+id!(
+    /// Refers to the path of a `use` in an [`Ast`].
+    UseId
+);
+id!(
+    /// Refers to the value of a string literal in an [`Ast`].
+    StrId
+);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Op {
+    Minus,
+    Plus,
+    Mult,
+    Div,
+    Grt,
+    Les,
+    Eq,
+    Ne,
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Minus => write!(f, "-"),
+            Self::Plus => write!(f, "+"),
+            Self::Mult => write!(f, "*"),
+            Self::Div => write!(f, "/"),
+            Self::Grt => write!(f, ">"),
+            Self::Les => write!(f, "<"),
+            Self::Eq => write!(f, "=="),
+            Self::Ne => write!(f, "!="),
+        }
+    }
+}
+
+impl TryFrom<TokenKind> for Op {
+    type Error = &'static str;
+    fn try_from(value: TokenKind) -> Result<Self, Self::Error> {
+        match value {
+            TokenKind::Minus => Ok(Self::Minus),
+            TokenKind::Plus => Ok(Self::Plus),
+            TokenKind::Star => Ok(Self::Mult),
+            TokenKind::Slash => Ok(Self::Div),
+            TokenKind::Greater => Ok(Self::Grt),
+            TokenKind::Less => Ok(Self::Les),
+            TokenKind::EqEq => Ok(Self::Eq),
+            TokenKind::BangEq => Ok(Self::Ne),
+            _ => Err("not an operator"),
+        }
+    }
+}
+
+impl TryFrom<&str> for Op {
+    type Error = &'static str;
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "-" => Ok(Self::Minus),
+            "+" => Ok(Self::Plus),
+            "*" => Ok(Self::Mult),
+            "/" => Ok(Self::Div),
+            ">" => Ok(Self::Grt),
+            "<" => Ok(Self::Les),
+            "==" => Ok(Self::Eq),
+            "!=" => Ok(Self::Ne),
+            _ => Err("not an operator"),
+        }
+    }
+}
+
+impl TryFrom<&String> for Op {
+    type Error = &'static str;
+    fn try_from(value: &String) -> Result<Self, Self::Error> {
+        Self::try_from(value.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Atom {
+    Int(i32),
+    Id(Symbol),
+    /// A string literal, whose value is kept in the [`Ast`] rather than
+    /// interned, so that it is freed with the tree.
+    Str(StrId),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Expr {
+    Atom(Atom),
+    Unary(Op, ExprId),
+    Binary(Op, ExprId, ExprId),
+    If(ExprId, ExprId),
+    IfElse(ExprId, ExprId, ExprId),
+    Lambda(LambdaId),
+    Call(CallId),
+    /// An array literal listing its elements, `[1, 2, 3]`.
+    Array(ArrayId),
+    /// An array literal of one element repeated, `[0; 16]`.
+    Repeat(ExprId, u32),
+    /// `array[index]`.
+    Index(ExprId, ExprId),
+    /// `target = value`, where the target is a variable or an element of an
+    /// array in one. This is a statement and has no value.
+    Assign(ExprId, ExprId),
+    /// A block of several statements, or of one that declares a variable,
+    /// whose value is that of the last statement. Blocks of a single other
+    /// statement are just that statement.
+    Block(BlockId),
+    /// `let name = value`, which declares a variable for the rest of the
+    /// enclosing block. This is a statement and has no value.
+    Let(Symbol, ExprId),
+    Loop(LoopId),
+    /// `break 'label value`, leaving the innermost loop or the labelled one.
+    /// Only a `loop` can be left with a value.
+    Break(Option<Symbol>, Option<ExprId>),
+    /// `continue 'label`.
+    Continue(Option<Symbol>),
+    /// A struct literal, `Point { x: 1, y: 2 }`.
+    StructLit(StructLitId),
+    /// `value.field`.
+    Field(ExprId, Symbol),
+    /// A variant of an enum, `Shape::Rect(1, 2)` or `Shape::Empty`.
+    VariantLit(VariantLitId),
+    /// `match value { pattern => result, ... }`.
+    Match(MatchId),
+}
+
+/// A type written in the source, on a parameter or as a return type.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TypeExpr {
+    Int,
+    String,
+    Fn(Vec<TypeExpr>, Box<TypeExpr>),
+    Array(Box<TypeExpr>, u32),
+    /// A struct declared earlier in the program.
+    Struct(Symbol),
+    /// An enum declared earlier in the program.
+    Enum(Symbol),
+}
+
+impl fmt::Display for Named<'_, TypeExpr> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let named = |ty| Named {
+            names: self.names,
+            node: ty,
+        };
+        match self.node {
+            TypeExpr::Int => write!(f, "i32"),
+            TypeExpr::String => write!(f, "String"),
+            TypeExpr::Fn(params, ret) => {
+                write!(f, "fn(")?;
+                for (i, param) in params.iter().enumerate() {
+                    match i {
+                        0 => write!(f, "{}", named(param))?,
+                        _ => write!(f, ", {}", named(param))?,
+                    }
+                }
+                write!(f, ") -> {}", named(ret))
+            }
+            TypeExpr::Array(elem, len) => write!(f, "[{}; {len}]", named(elem)),
+            TypeExpr::Struct(name) | TypeExpr::Enum(name) => write!(f, "{}", &self.names[*name]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Param {
+    pub name: Symbol,
+    /// The declared type, or `None` for the type checker to infer.
+    pub ty: Option<TypeExpr>,
+}
+
+impl fmt::Display for Named<'_, Param> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = &self.names[self.node.name];
+        match &self.node.ty {
+            Some(node) => write!(
+                f,
+                "{name}: {}",
+                Named {
+                    names: self.names,
+                    node
+                }
+            ),
+            None => write!(f, "{name}"),
+        }
+    }
+}
+
+/// An anonymous function, `λ(x: i32) -> i32 { x + 1 }` or `fn(x) { x + 1 }`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Lambda {
+    pub params: Vec<Param>,
+    pub ret: Option<TypeExpr>,
+    pub body: ExprId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Call {
+    pub callee: ExprId,
+    pub args: Vec<ExprId>,
+}
+
+/// A loop, its label if it has one, and its body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Loop {
+    pub label: Option<Symbol>,
+    pub kind: LoopKind,
+    pub body: ExprId,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LoopKind {
+    /// `loop { }`, which only ends with a `break`.
+    Loop,
+    /// `while condition { }`.
+    While(ExprId),
+    /// `for name in start..end { }`, counting up from `start` to just before
+    /// `end`.
+    For(Symbol, ExprId, ExprId),
+}
+
+impl Loop {
+    /// The expressions of the loop other than its body, in source order.
+    pub fn header(&self) -> Vec<ExprId> {
+        match self.kind {
+            LoopKind::Loop => vec![],
+            LoopKind::While(c) => vec![c],
+            LoopKind::For(_, start, end) => vec![start, end],
+        }
+    }
+}
+
+/// `struct Point { x: i32, y: i32 }`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Struct {
+    pub name: Symbol,
+    pub fields: Vec<Field>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Field {
+    pub name: Symbol,
+    pub ty: TypeExpr,
+}
+
+impl fmt::Display for Named<'_, Struct> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(struct {}", &self.names[self.node.name])?;
+        for node in &self.node.fields {
+            write!(
+                f,
+                " ({})",
+                Named {
+                    names: self.names,
+                    node
+                }
+            )?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl fmt::Display for Named<'_, Field> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ty = Named {
+            names: self.names,
+            node: &self.node.ty,
+        };
+        write!(f, "{}: {ty}", &self.names[self.node.name])
+    }
+}
+
+/// The fields of a struct literal in the order they were written, which
+/// is the order they are evaluated in.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StructLit {
+    pub name: Symbol,
+    pub fields: Vec<(Symbol, ExprId)>,
+}
+
+/// `enum Shape { Circle(i32), Rect(i32, i32), Empty }`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Enum {
+    pub name: Symbol,
+    pub variants: Vec<Variant>,
+}
+
+/// A variant of an enum and the types of the values it carries.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Variant {
+    pub name: Symbol,
+    pub fields: Vec<TypeExpr>,
+}
+
+impl fmt::Display for Named<'_, Enum> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(enum {}", &self.names[self.node.name])?;
+        for variant in &self.node.variants {
+            write!(f, " ({}", &self.names[variant.name])?;
+            for node in &variant.fields {
+                write!(
+                    f,
+                    " {}",
+                    Named {
+                        names: self.names,
+                        node
+                    }
+                )?;
+            }
+            write!(f, ")")?;
+        }
+        write!(f, ")")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VariantLit {
+    /// The enum the variant belongs to.
+    pub ty: Symbol,
+    pub variant: Symbol,
+    pub args: Vec<ExprId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Match {
+    pub scrutinee: ExprId,
+    pub arms: Vec<Arm>,
+}
+
+/// `pattern if guard => body`. The first arm whose pattern matches and whose
+/// guard holds is the one that runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Arm {
+    pub pat: PatId,
+    pub guard: Option<ExprId>,
+    pub body: ExprId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Pat {
+    /// `_`, which matches anything.
+    Wild,
+    /// An integer, which matches only itself.
+    Int(i32),
+    /// A name, which matches anything and binds it.
+    Bind(Symbol),
+    /// `Shape::Rect(w, h)`, matching a variant and then its values.
+    Variant(Symbol, Symbol, Vec<PatId>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Stmt {
+    /// An expression whose value, if it has one, is printed.
+    Expr(ExprId),
+    /// A struct declaration, which names a type for the statements after it.
+    Struct(StructId),
+    /// An enum declaration, which names a type for the statements after it.
+    Enum(EnumId),
+    /// `mod name;`, a module whose statements are in a file of that name.
+    Mod(Symbol),
+    /// `use module::name;`, which names an item of another module here.
+    Use(UseId),
+}
+
+/// A whole program: the arenas every node lives in, the top level statements
+/// in order, the span of source each expression was parsed from, and the
+/// names its symbols stand for.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(remote = "Self"))]
+pub struct Ast {
+    exprs: Vec<Expr>,
+    stmts: Vec<Stmt>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
+    lambdas: Vec<Lambda>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
+    calls: Vec<Call>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
+    arrays: Vec<Vec<ExprId>>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
+    blocks: Vec<Vec<ExprId>>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
+    loops: Vec<Loop>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
+    structs: Vec<Struct>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
+    struct_lits: Vec<StructLit>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
+    enums: Vec<Enum>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
+    variant_lits: Vec<VariantLit>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
+    matches: Vec<Match>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
+    pats: Vec<Pat>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
+    uses: Vec<Vec<Symbol>>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
+    strings: Vec<Box<str>>,
+    program: Vec<StmtId>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "SideTable::is_empty"))]
+    public: SideTable<StmtId, bool>,
+    pub spans: SideTable<ExprId, Span>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "SideTable::is_empty"))]
+    pub pat_spans: SideTable<PatId, Span>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    names: Interner,
+}
+
+impl Ast {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An empty tree whose names start out as `names`, so that its symbols
+    /// are those of the tree the names came from.
+    pub fn with_names(names: Interner) -> Self {
+        Self {
+            names,
+            ..Self::default()
+        }
+    }
+
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        self.names.intern(name)
+    }
+
+    /// The names of the tree, which its symbols are indices into.
+    pub fn names(&self) -> &Interner {
+        &self.names
+    }
+
+    pub fn alloc_expr(&mut self, expr: Expr, span: Span) -> ExprId {
+        let id = ExprId::new(self.exprs.len());
+        self.exprs.push(expr);
+        self.spans.insert(id, span);
+        id
+    }
+
+    pub fn alloc_stmt(&mut self, stmt: Stmt) -> StmtId {
+        let id = StmtId::new(self.stmts.len());
+        self.stmts.push(stmt);
+        id
+    }
+
+    pub fn alloc_lambda(&mut self, lambda: Lambda) -> LambdaId {
+        let id = LambdaId::new(self.lambdas.len());
+        self.lambdas.push(lambda);
+        id
+    }
+
+    pub fn alloc_call(&mut self, call: Call) -> CallId {
+        let id = CallId::new(self.calls.len());
+        self.calls.push(call);
+        id
+    }
+
+    pub fn alloc_array(&mut self, elems: Vec<ExprId>) -> ArrayId {
+        let id = ArrayId::new(self.arrays.len());
+        self.arrays.push(elems);
+        id
+    }
+
+    pub fn alloc_block(&mut self, stmts: Vec<ExprId>) -> BlockId {
+        let id = BlockId::new(self.blocks.len());
+        self.blocks.push(stmts);
+        id
+    }
+
+    pub fn alloc_loop(&mut self, l: Loop) -> LoopId {
+        let id = LoopId::new(self.loops.len());
+        self.loops.push(l);
+        id
+    }
+
+    pub fn alloc_struct(&mut self, decl: Struct) -> StructId {
+        let id = StructId::new(self.structs.len());
+        self.structs.push(decl);
+        id
+    }
+
+    pub fn alloc_struct_lit(&mut self, lit: StructLit) -> StructLitId {
+        let id = StructLitId::new(self.struct_lits.len());
+        self.struct_lits.push(lit);
+        id
+    }
+
+    pub fn alloc_enum(&mut self, decl: Enum) -> EnumId {
+        let id = EnumId::new(self.enums.len());
+        self.enums.push(decl);
+        id
+    }
+
+    pub fn alloc_variant_lit(&mut self, lit: VariantLit) -> VariantLitId {
+        let id = VariantLitId::new(self.variant_lits.len());
+        self.variant_lits.push(lit);
+        id
+    }
+
+    pub fn alloc_match(&mut self, m: Match) -> MatchId {
+        let id = MatchId::new(self.matches.len());
+        self.matches.push(m);
+        id
+    }
+
+    pub fn alloc_pat(&mut self, pat: Pat, span: Span) -> PatId {
+        let id = PatId::new(self.pats.len());
+        self.pats.push(pat);
+        self.pat_spans.insert(id, span);
+        id
+    }
+
+    pub fn alloc_use(&mut self, path: Vec<Symbol>) -> UseId {
+        let id = UseId::new(self.uses.len());
+        self.uses.push(path);
+        id
+    }
+
+    pub fn alloc_str(&mut self, value: &str) -> StrId {
+        let id = StrId::new(self.strings.len());
+        self.strings.push(value.into());
+        id
+    }
+
+    /// Formats an atom the way it is written, with string literals quoted
+    /// and escaped.
+    pub fn display_atom(&self, atom: Atom) -> String {
+        match atom {
+            Atom::Int(i) => i.to_string(),
+            Atom::Id(i) => self[i].to_string(),
+            Atom::Str(s) => cb_lexer::escape(&self[s]),
+        }
+    }
+
+    /// Marks a top level statement as `pub`, so other modules can use what
+    /// it declares.
+    pub fn set_public(&mut self, stmt: StmtId) {
+        self.public.insert(stmt, true);
+    }
+
+    pub fn is_public(&self, stmt: StmtId) -> bool {
+        self.public.get(stmt).is_some_and(|&public| public)
+    }
+
+    /// Appends a statement to the top level of the program.
+    pub fn push(&mut self, stmt: StmtId) {
+        self.program.push(stmt);
+    }
+
+    /// The top level statements in the order they run.
+    pub fn program(&self) -> &[StmtId] {
+        &self.program
+    }
+
+    /// Every expression id, children before their parents.
+    pub fn expr_ids(&self) -> impl Iterator<Item = ExprId> {
+        (0..self.exprs.len()).map(ExprId::new)
+    }
+
+    /// The expressions directly inside `id`, in source order.
+    pub fn children(&self, id: ExprId) -> Vec<ExprId> {
+        match self[id] {
+            Expr::Atom(_) | Expr::Break(_, None) | Expr::Continue(_) => vec![],
+            Expr::Unary(_, e)
+            | Expr::Repeat(e, _)
+            | Expr::Let(_, e)
+            | Expr::Break(_, Some(e))
+            | Expr::Field(e, _) => vec![e],
+            Expr::Binary(_, l, r) | Expr::If(l, r) | Expr::Index(l, r) | Expr::Assign(l, r) => {
+                vec![l, r]
+            }
+            Expr::IfElse(c, b1, b2) => vec![c, b1, b2],
+            Expr::Lambda(l) => vec![self[l].body],
+            Expr::Call(c) => {
+                let call = &self[c];
+                std::iter::once(call.callee)
+                    .chain(call.args.iter().copied())
+                    .collect()
+            }
+            Expr::Array(a) => self[a].to_vec(),
+            Expr::Block(b) => self[b].to_vec(),
+            Expr::Loop(l) => {
+                let mut children = self[l].header();
+                children.push(self[l].body);
+                children
+            }
+            Expr::StructLit(s) => self[s].fields.iter().map(|&(_, e)| e).collect(),
+            Expr::VariantLit(v) => self[v].args.clone(),
+            Expr::Match(m) => {
+                let m = &self[m];
+                let mut children = vec![m.scrutinee];
+                for arm in &m.arms {
+                    children.extend(arm.guard);
+                    children.push(arm.body);
+                }
+                children
+            }
+        }
+    }
+
+    pub fn span(&self, id: ExprId) -> Span {
+        self.spans.get(id).cloned().unwrap_or(0..0)
+    }
+
+    pub fn pat_span(&self, id: PatId) -> Span {
+        self.pat_spans.get(id).cloned().unwrap_or(0..0)
+    }
+
+    /// Moves every span `offset` bytes later, for a tree parsed from a file
+    /// that starts there in a [`SourceMap`](cb_lexer::SourceMap).
+    pub fn offset_spans(&mut self, offset: usize) {
+        let spans = self.spans.values_mut().chain(self.pat_spans.values_mut());
+        for span in spans {
+            *span = span.start + offset..span.end + offset;
+        }
+    }
+
+    /// Formats a pattern the way it is written, such as `Shape::Rect(w, _)`.
+    pub fn display_pat(&self, id: PatId) -> impl fmt::Display + '_ {
+        DisplayPat { ast: self, id }
+    }
+
+    /// Formats an expression as an s-expression such as `(+ 1 (* 2 3))`.
+    pub fn display(&self, id: ExprId) -> impl fmt::Display + '_ {
+        Display { ast: self, id }
+    }
+
+    /// Formats a type, a parameter or a declaration with the names of the
+    /// tree, such as `fn(i32) -> Point`.
+    pub fn named<'a, T>(&'a self, node: &'a T) -> Named<'a, T> {
+        Named {
+            names: &self.names,
+            node,
+        }
+    }
+
+    /// Whether two expressions, possibly from different trees, have the
+    /// same shape.
+    fn same_expr(&self, a: ExprId, other: &Self, b: ExprId) -> bool {
+        match (self[a], other[b]) {
+            (Expr::Atom(Atom::Str(x)), Expr::Atom(Atom::Str(y))) => self[x] == other[y],
+            (Expr::Atom(Atom::Id(x)), Expr::Atom(Atom::Id(y))) => self.same_name(x, other, y),
+            (Expr::Atom(x), Expr::Atom(y)) => x == y,
+            (Expr::Unary(o1, x), Expr::Unary(o2, y)) => o1 == o2 && self.same_expr(x, other, y),
+            (Expr::Binary(o1, x1, x2), Expr::Binary(o2, y1, y2)) => {
+                o1 == o2 && self.same_expr(x1, other, y1) && self.same_expr(x2, other, y2)
+            }
+            (Expr::If(x1, x2), Expr::If(y1, y2)) => {
+                self.same_expr(x1, other, y1) && self.same_expr(x2, other, y2)
+            }
+            (Expr::IfElse(x1, x2, x3), Expr::IfElse(y1, y2, y3)) => {
+                self.same_expr(x1, other, y1)
+                    && self.same_expr(x2, other, y2)
+                    && self.same_expr(x3, other, y3)
+            }
+            (Expr::Lambda(x), Expr::Lambda(y)) => {
+                let (x, y) = (&self[x], &other[y]);
+                x.params.len() == y.params.len()
+                    && x.params.iter().zip(&y.params).all(|(p, q)| {
+                        self.same_name(p.name, other, q.name)
+                            && self.same_type_opt(&p.ty, other, &q.ty)
+                    })
+                    && self.same_type_opt(&x.ret, other, &y.ret)
+                    && self.same_expr(x.body, other, y.body)
+            }
+            (Expr::Call(x), Expr::Call(y)) => {
+                let (x, y) = (&self[x], &other[y]);
+                x.args.len() == y.args.len()
+                    && self.same_expr(x.callee, other, y.callee)
+                    && x.args
+                        .iter()
+                        .zip(&y.args)
+                        .all(|(&a, &b)| self.same_expr(a, other, b))
+            }
+            (Expr::Array(x), Expr::Array(y)) => {
+                let (x, y) = (&self[x], &other[y]);
+                x.len() == y.len() && x.iter().zip(y).all(|(&a, &b)| self.same_expr(a, other, b))
+            }
+            (Expr::Repeat(x, n), Expr::Repeat(y, m)) => n == m && self.same_expr(x, other, y),
+            (Expr::Block(x), Expr::Block(y)) => {
+                let (x, y) = (&self[x], &other[y]);
+                x.len() == y.len() && x.iter().zip(y).all(|(&a, &b)| self.same_expr(a, other, b))
+            }
+            (Expr::Let(n, x), Expr::Let(m, y)) => {
+                self.same_name(n, other, m) && self.same_expr(x, other, y)
+            }
+            (Expr::Loop(x), Expr::Loop(y)) => {
+                let (x, y) = (&self[x], &other[y]);
+                let kinds = match (x.kind, y.kind) {
+                    (LoopKind::Loop, LoopKind::Loop) | (LoopKind::While(_), LoopKind::While(_)) => {
+                        true
+                    }
+                    (LoopKind::For(n, ..), LoopKind::For(m, ..)) => self.same_name(n, other, m),
+                    _ => false,
+                };
+                let (hx, hy) = (x.header(), y.header());
+                self.same_label(x.label, other, y.label)
+                    && kinds
+                    && hx
+                        .iter()
+                        .zip(&hy)
+                        .all(|(&a, &b)| self.same_expr(a, other, b))
+                    && self.same_expr(x.body, other, y.body)
+            }
+            (Expr::Break(l1, None), Expr::Break(l2, None)) => self.same_label(l1, other, l2),
+            (Expr::Break(l1, Some(x)), Expr::Break(l2, Some(y))) => {
+                self.same_label(l1, other, l2) && self.same_expr(x, other, y)
+            }
+            (Expr::Continue(l1), Expr::Continue(l2)) => self.same_label(l1, other, l2),
+            (Expr::StructLit(x), Expr::StructLit(y)) => {
+                let (x, y) = (&self[x], &other[y]);
+                self.same_name(x.name, other, y.name)
+                    && x.fields.len() == y.fields.len()
+                    && x.fields.iter().zip(&y.fields).all(|(&(n, a), &(m, b))| {
+                        self.same_name(n, other, m) && self.same_expr(a, other, b)
+                    })
+            }
+            (Expr::Field(x, n), Expr::Field(y, m)) => {
+                self.same_name(n, other, m) && self.same_expr(x, other, y)
+            }
+            (Expr::VariantLit(x), Expr::VariantLit(y)) => {
+                let (x, y) = (&self[x], &other[y]);
+                self.same_name(x.ty, other, y.ty)
+                    && self.same_name(x.variant, other, y.variant)
+                    && x.args.len() == y.args.len()
+                    && x.args
+                        .iter()
+                        .zip(&y.args)
+                        .all(|(&a, &b)| self.same_expr(a, other, b))
+            }
+            (Expr::Match(x), Expr::Match(y)) => {
+                let (x, y) = (&self[x], &other[y]);
+                x.arms.len() == y.arms.len()
+                    && self.same_expr(x.scrutinee, other, y.scrutinee)
+                    && x.arms.iter().zip(&y.arms).all(|(a, b)| {
+                        let guards = match (a.guard, b.guard) {
+                            (None, None) => true,
+                            (Some(g), Some(h)) => self.same_expr(g, other, h),
+                            _ => false,
+                        };
+                        guards
+                            && self.same_pat(a.pat, other, b.pat)
+                            && self.same_expr(a.body, other, b.body)
+                    })
+            }
+            (Expr::Index(x1, x2), Expr::Index(y1, y2))
+            | (Expr::Assign(x1, x2), Expr::Assign(y1, y2)) => {
+                self.same_expr(x1, other, y1) && self.same_expr(x2, other, y2)
+            }
+            _ => false,
+        }
+    }
+
+    fn same_stmt(&self, a: StmtId, other: &Self, b: StmtId) -> bool {
+        if self.is_public(a) != other.is_public(b) {
+            return false;
+        }
+        match (self[a], other[b]) {
+            (Stmt::Expr(x), Stmt::Expr(y)) => self.same_expr(x, other, y),
+            (Stmt::Struct(x), Stmt::Struct(y)) => {
+                let (x, y) = (&self[x], &other[y]);
+                self.same_name(x.name, other, y.name)
+                    && x.fields.len() == y.fields.len()
+                    && x.fields.iter().zip(&y.fields).all(|(f, g)| {
+                        self.same_name(f.name, other, g.name) && self.same_type(&f.ty, other, &g.ty)
+                    })
+            }
+            (Stmt::Enum(x), Stmt::Enum(y)) => {
+                let (x, y) = (&self[x], &other[y]);
+                self.same_name(x.name, other, y.name)
+                    && x.variants.len() == y.variants.len()
+                    && x.variants.iter().zip(&y.variants).all(|(v, w)| {
+                        self.same_name(v.name, other, w.name)
+                            && v.fields.len() == w.fields.len()
+                            && v.fields
+                                .iter()
+                                .zip(&w.fields)
+                                .all(|(a, b)| self.same_type(a, other, b))
+                    })
+            }
+            (Stmt::Mod(x), Stmt::Mod(y)) => self.same_name(x, other, y),
+            (Stmt::Use(x), Stmt::Use(y)) => {
+                let (x, y) = (&self[x], &other[y]);
+                x.len() == y.len() && x.iter().zip(y).all(|(&a, &b)| self.same_name(a, other, b))
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether two names, possibly from different trees, are the same.
+    fn same_name(&self, a: Symbol, other: &Self, b: Symbol) -> bool {
+        self[a] == other[b]
+    }
+
+    fn same_label(&self, a: Option<Symbol>, other: &Self, b: Option<Symbol>) -> bool {
+        match (a, b) {
+            (Some(a), Some(b)) => self.same_name(a, other, b),
+            (a, b) => a.is_none() && b.is_none(),
+        }
+    }
+
+    fn same_type(&self, a: &TypeExpr, other: &Self, b: &TypeExpr) -> bool {
+        match (a, b) {
+            (TypeExpr::Fn(p1, r1), TypeExpr::Fn(p2, r2)) => {
+                p1.len() == p2.len()
+                    && p1.iter().zip(p2).all(|(x, y)| self.same_type(x, other, y))
+                    && self.same_type(r1, other, r2)
+            }
+            (TypeExpr::Array(x, n), TypeExpr::Array(y, m)) => n == m && self.same_type(x, other, y),
+            (TypeExpr::Struct(x), TypeExpr::Struct(y)) | (TypeExpr::Enum(x), TypeExpr::Enum(y)) => {
+                self.same_name(*x, other, *y)
+            }
+            (a, b) => a == b,
+        }
+    }
+
+    fn same_type_opt(&self, a: &Option<TypeExpr>, other: &Self, b: &Option<TypeExpr>) -> bool {
+        match (a, b) {
+            (Some(a), Some(b)) => self.same_type(a, other, b),
+            (a, b) => a.is_none() && b.is_none(),
+        }
+    }
+
+    fn same_pat(&self, a: PatId, other: &Self, b: PatId) -> bool {
+        match (&self[a], &other[b]) {
+            (Pat::Bind(x), Pat::Bind(y)) => self.same_name(*x, other, *y),
+            (Pat::Variant(t1, v1, xs), Pat::Variant(t2, v2, ys)) => {
+                self.same_name(*t1, other, *t2)
+                    && self.same_name(*v1, other, *v2)
+                    && xs.len() == ys.len()
+                    && xs.iter().zip(ys).all(|(&x, &y)| self.same_pat(x, other, y))
+            }
+            (x, y) => x == y,
+        }
+    }
+}
+
+/// Symbols are written as the names they stand for, which only the tree
+/// knows.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Ast {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let names = self.names.clone();
+        names.serde(|| Ast::serialize(self, serializer)).1
+    }
+}
+
+/// Names are interned in the tree as they are read.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Ast {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (names, arenas) = Interner::new().serde(|| Arenas::deserialize(deserializer));
+        let mut ast = Self::try_from(arenas?).map_err(serde::de::Error::custom)?;
+        ast.names = names;
+        Ok(ast)
+    }
+}
+
+/// An [`Ast`] as read from outside, before its ids have been checked.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct Arenas {
+    exprs: Vec<Expr>,
+    stmts: Vec<Stmt>,
+    #[serde(default)]
+    lambdas: Vec<Lambda>,
+    #[serde(default)]
+    calls: Vec<Call>,
+    #[serde(default)]
+    arrays: Vec<Vec<ExprId>>,
+    #[serde(default)]
+    blocks: Vec<Vec<ExprId>>,
+    #[serde(default)]
+    loops: Vec<Loop>,
+    #[serde(default)]
+    structs: Vec<Struct>,
+    #[serde(default)]
+    struct_lits: Vec<StructLit>,
+    #[serde(default)]
+    enums: Vec<Enum>,
+    #[serde(default)]
+    variant_lits: Vec<VariantLit>,
+    #[serde(default)]
+    matches: Vec<Match>,
+    #[serde(default)]
+    pats: Vec<Pat>,
+    #[serde(default)]
+    uses: Vec<Vec<Symbol>>,
+    #[serde(default)]
+    strings: Vec<Box<str>>,
+    program: Vec<StmtId>,
+    #[serde(default)]
+    public: SideTable<StmtId, bool>,
+    #[serde(default)]
+    spans: SideTable<ExprId, Span>,
+    #[serde(default)]
+    pat_spans: SideTable<PatId, Span>,
+}
+
+/// Every id must refer to a node that exists, and children must come before
+/// their parents, so that a tree built by another tool can have no cycles.
+/// Only `-` can be a unary operator; the names of binary operators are
+/// checked when they are read.
+#[cfg(feature = "serde")]
+impl TryFrom<Arenas> for Ast {
+    type Error = String;
+    fn try_from(arenas: Arenas) -> Result<Self, String> {
+        let ast = Self {
+            exprs: arenas.exprs,
+            stmts: arenas.stmts,
+            lambdas: arenas.lambdas,
+            calls: arenas.calls,
+            arrays: arenas.arrays,
+            blocks: arenas.blocks,
+            loops: arenas.loops,
+            structs: arenas.structs,
+            struct_lits: arenas.struct_lits,
+            enums: arenas.enums,
+            variant_lits: arenas.variant_lits,
+            matches: arenas.matches,
+            pats: arenas.pats,
+            uses: arenas.uses,
+            strings: arenas.strings,
+            program: arenas.program,
+            public: arenas.public,
+            spans: arenas.spans,
+            pat_spans: arenas.pat_spans,
+            names: Interner::new(),
+        };
+        for (index, pat) in ast.pats.iter().enumerate() {
+            if let Pat::Variant(_, _, args) = pat {
+                if let Some(arg) = args.iter().find(|a| a.index() >= index) {
+                    let arg = arg.index();
+                    return Err(format!(
+                        "pattern {index} refers to pattern {arg}, which does not come before it"
+                    ));
+                }
+            }
+        }
+        for (index, expr) in ast.exprs.iter().enumerate() {
+            match *expr {
+                Expr::Lambda(l) if l.index() >= ast.lambdas.len() => {
+                    let l = l.index();
+                    return Err(format!("expression {index} refers to missing lambda {l}"));
+                }
+                Expr::Call(c) if c.index() >= ast.calls.len() => {
+                    let c = c.index();
+                    return Err(format!("expression {index} refers to missing call {c}"));
+                }
+                Expr::Array(a) if a.index() >= ast.arrays.len() => {
+                    let a = a.index();
+                    return Err(format!("expression {index} refers to missing array {a}"));
+                }
+                Expr::Block(b) if b.index() >= ast.blocks.len() => {
+                    let b = b.index();
+                    return Err(format!("expression {index} refers to missing block {b}"));
+                }
+                Expr::Loop(l) if l.index() >= ast.loops.len() => {
+                    let l = l.index();
+                    return Err(format!("expression {index} refers to missing loop {l}"));
+                }
+                Expr::StructLit(s) if s.index() >= ast.struct_lits.len() => {
+                    let s = s.index();
+                    return Err(format!(
+                        "expression {index} refers to missing struct literal {s}"
+                    ));
+                }
+                Expr::VariantLit(v) if v.index() >= ast.variant_lits.len() => {
+                    let v = v.index();
+                    return Err(format!(
+                        "expression {index} refers to missing variant literal {v}"
+                    ));
+                }
+                Expr::Atom(Atom::Str(s)) if s.index() >= ast.strings.len() => {
+                    let s = s.index();
+                    return Err(format!("expression {index} refers to missing string {s}"));
+                }
+                Expr::Unary(op, _) if op != Op::Minus => {
+                    return Err(format!(
+                        "expression {index} applies '{op}', which is not a unary operator"
+                    ));
+                }
+                Expr::Match(m) if m.index() >= ast.matches.len() => {
+                    let m = m.index();
+                    return Err(format!("expression {index} refers to missing match {m}"));
+                }
+                Expr::Match(m) => {
+                    if let Some(arm) = ast[m].arms.iter().find(|a| a.pat.index() >= ast.pats.len())
+                    {
+                        let p = arm.pat.index();
+                        return Err(format!("expression {index} refers to missing pattern {p}"));
+                    }
+                }
+                _ => {}
+            }
+            let children = ast.children(ExprId::new(index));
+            if let Some(child) = children.iter().find(|c| c.index() >= index) {
+                let child = child.index();
+                return Err(format!(
+                    "expression {index} refers to expression {child}, which does not come before it"
+                ));
+            }
+        }
+        for (index, stmt) in ast.stmts.iter().enumerate() {
+            match *stmt {
+                Stmt::Expr(expr) if expr.index() >= ast.exprs.len() => {
+                    let expr = expr.index();
+                    return Err(format!(
+                        "statement {index} refers to missing expression {expr}"
+                    ));
+                }
+                Stmt::Struct(s) if s.index() >= ast.structs.len() => {
+                    let s = s.index();
+                    return Err(format!("statement {index} refers to missing struct {s}"));
+                }
+                Stmt::Enum(e) if e.index() >= ast.enums.len() => {
+                    let e = e.index();
+                    return Err(format!("statement {index} refers to missing enum {e}"));
+                }
+                Stmt::Use(u) if u.index() >= ast.uses.len() => {
+                    let u = u.index();
+                    return Err(format!("statement {index} refers to missing use {u}"));
+                }
+                _ => {}
+            }
+        }
+        if let Some(stmt) = ast.program.iter().find(|s| s.index() >= ast.stmts.len()) {
+            return Err(format!(
+                "program refers to missing statement {}",
+                stmt.index()
+            ));
+        }
+        Ok(ast)
+    }
+}
+
+/// Trees are equal when their programs have the same shape, no matter where
+/// in the arenas the nodes are or which spans they were parsed from.
+impl PartialEq for Ast {
+    fn eq(&self, other: &Self) -> bool {
+        self.program.len() == other.program.len()
+            && self
+                .program
+                .iter()
+                .zip(&other.program)
+                .all(|(&a, &b)| self.same_stmt(a, other, b))
+    }
+}
+
+impl Eq for Ast {}
+
+impl Index<ExprId> for Ast {
+    type Output = Expr;
+    fn index(&self, id: ExprId) -> &Expr {
+        &self.exprs[id.index()]
+    }
+}
+
+impl IndexMut<ExprId> for Ast {
+    fn index_mut(&mut self, id: ExprId) -> &mut Expr {
+        &mut self.exprs[id.index()]
+    }
+}
+
+impl Index<StmtId> for Ast {
+    type Output = Stmt;
+    fn index(&self, id: StmtId) -> &Stmt {
+        &self.stmts[id.index()]
+    }
+}
+
+impl Index<LambdaId> for Ast {
+    type Output = Lambda;
+    fn index(&self, id: LambdaId) -> &Lambda {
+        &self.lambdas[id.index()]
+    }
+}
+
+impl IndexMut<LambdaId> for Ast {
+    fn index_mut(&mut self, id: LambdaId) -> &mut Lambda {
+        &mut self.lambdas[id.index()]
+    }
+}
+
+impl Index<CallId> for Ast {
+    type Output = Call;
+    fn index(&self, id: CallId) -> &Call {
+        &self.calls[id.index()]
+    }
+}
+
+impl IndexMut<CallId> for Ast {
+    fn index_mut(&mut self, id: CallId) -> &mut Call {
+        &mut self.calls[id.index()]
+    }
+}
+
+impl Index<ArrayId> for Ast {
+    type Output = Vec<ExprId>;
+    fn index(&self, id: ArrayId) -> &Vec<ExprId> {
+        &self.arrays[id.index()]
+    }
+}
+
+impl IndexMut<ArrayId> for Ast {
+    fn index_mut(&mut self, id: ArrayId) -> &mut Vec<ExprId> {
+        &mut self.arrays[id.index()]
+    }
+}
+
+impl Index<BlockId> for Ast {
+    type Output = Vec<ExprId>;
+    fn index(&self, id: BlockId) -> &Vec<ExprId> {
+        &self.blocks[id.index()]
+    }
+}
+
+impl IndexMut<BlockId> for Ast {
+    fn index_mut(&mut self, id: BlockId) -> &mut Vec<ExprId> {
+        &mut self.blocks[id.index()]
+    }
+}
+
+impl Index<LoopId> for Ast {
+    type Output = Loop;
+    fn index(&self, id: LoopId) -> &Loop {
+        &self.loops[id.index()]
+    }
+}
+
+impl IndexMut<LoopId> for Ast {
+    fn index_mut(&mut self, id: LoopId) -> &mut Loop {
+        &mut self.loops[id.index()]
+    }
+}
+
+impl Index<StructId> for Ast {
+    type Output = Struct;
+    fn index(&self, id: StructId) -> &Struct {
+        &self.structs[id.index()]
+    }
+}
+
+impl IndexMut<StructId> for Ast {
+    fn index_mut(&mut self, id: StructId) -> &mut Struct {
+        &mut self.structs[id.index()]
+    }
+}
+
+impl Index<StructLitId> for Ast {
+    type Output = StructLit;
+    fn index(&self, id: StructLitId) -> &StructLit {
+        &self.struct_lits[id.index()]
+    }
+}
+
+impl IndexMut<StructLitId> for Ast {
+    fn index_mut(&mut self, id: StructLitId) -> &mut StructLit {
+        &mut self.struct_lits[id.index()]
+    }
+}
+
+impl Index<EnumId> for Ast {
+    type Output = Enum;
+    fn index(&self, id: EnumId) -> &Enum {
+        &self.enums[id.index()]
+    }
+}
+
+impl IndexMut<EnumId> for Ast {
+    fn index_mut(&mut self, id: EnumId) -> &mut Enum {
+        &mut self.enums[id.index()]
+    }
+}
+
+impl Index<VariantLitId> for Ast {
+    type Output = VariantLit;
+    fn index(&self, id: VariantLitId) -> &VariantLit {
+        &self.variant_lits[id.index()]
+    }
+}
+
+impl IndexMut<VariantLitId> for Ast {
+    fn index_mut(&mut self, id: VariantLitId) -> &mut VariantLit {
+        &mut self.variant_lits[id.index()]
+    }
+}
+
+impl Index<MatchId> for Ast {
+    type Output = Match;
+    fn index(&self, id: MatchId) -> &Match {
+        &self.matches[id.index()]
+    }
+}
+
+impl IndexMut<MatchId> for Ast {
+    fn index_mut(&mut self, id: MatchId) -> &mut Match {
+        &mut self.matches[id.index()]
+    }
+}
+
+impl Index<PatId> for Ast {
+    type Output = Pat;
+    fn index(&self, id: PatId) -> &Pat {
+        &self.pats[id.index()]
+    }
+}
+
+impl IndexMut<PatId> for Ast {
+    fn index_mut(&mut self, id: PatId) -> &mut Pat {
+        &mut self.pats[id.index()]
+    }
+}
+
+impl Index<UseId> for Ast {
+    type Output = Vec<Symbol>;
+    fn index(&self, id: UseId) -> &Vec<Symbol> {
+        &self.uses[id.index()]
+    }
+}
+
+impl Index<StrId> for Ast {
+    type Output = str;
+    fn index(&self, id: StrId) -> &str {
+        &self.strings[id.index()]
+    }
+}
+
+impl Index<Symbol> for Ast {
+    type Output = str;
+    fn index(&self, symbol: Symbol) -> &str {
+        &self.names[symbol]
+    }
+}
+
+impl IndexMut<StmtId> for Ast {
+    fn index_mut(&mut self, id: StmtId) -> &mut Stmt {
+        &mut self.stmts[id.index()]
+    }
+}
+
+/// A node together with the names of its tree, which it is formatted with.
+pub struct Named<'a, T> {
+    names: &'a Interner,
+    node: &'a T,
+}
+
+struct Display<'a> {
+    ast: &'a Ast,
+    id: ExprId,
+}
+
+impl fmt::Display for Display<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let d = |id| self.ast.display(id);
+        let ast = self.ast;
+        match self.ast[self.id] {
+            Expr::Atom(atom) => write!(f, "{}", self.ast.display_atom(atom)),
+            Expr::Unary(op, expr) => write!(f, "({op} {})", d(expr)),
+            Expr::Binary(op, lhs, rhs) => write!(f, "({op} {} {})", d(lhs), d(rhs)),
+            Expr::If(c, b) => write!(f, "(if ({}) ({}))", d(c), d(b)),
+            Expr::IfElse(c, b1, b2) => {
+                write!(f, "(if ({}) then ({}) else ({}))", d(c), d(b1), d(b2))
+            }
+            Expr::Lambda(l) => {
+                let lambda = &self.ast[l];
+                write!(f, "(λ (")?;
+                for (i, param) in lambda.params.iter().enumerate() {
+                    match i {
+                        0 => write!(f, "{}", ast.named(param))?,
+                        _ => write!(f, " {}", ast.named(param))?,
+                    }
+                }
+                match &lambda.ret {
+                    Some(ret) => write!(f, ") -> {} {})", ast.named(ret), d(lambda.body)),
+                    None => write!(f, ") {})", d(lambda.body)),
+                }
+            }
+            Expr::Call(c) => {
+                let call = &self.ast[c];
+                write!(f, "(call {}", d(call.callee))?;
+                for &arg in &call.args {
+                    write!(f, " {}", d(arg))?;
+                }
+                write!(f, ")")
+            }
+            Expr::Array(a) => {
+                write!(f, "(array")?;
+                for &elem in &self.ast[a] {
+                    write!(f, " {}", d(elem))?;
+                }
+                write!(f, ")")
+            }
+            Expr::Repeat(elem, len) => write!(f, "(array {}; {len})", d(elem)),
+            Expr::Index(array, index) => write!(f, "(index {} {})", d(array), d(index)),
+            Expr::Assign(target, value) => write!(f, "(= {} {})", d(target), d(value)),
+            Expr::Block(b) => {
+                write!(f, "(block")?;
+                for &stmt in &self.ast[b] {
+                    write!(f, " {}", d(stmt))?;
+                }
+                write!(f, ")")
+            }
+            Expr::Let(name, value) => write!(f, "(let {} {})", &ast[name], d(value)),
+            Expr::Loop(l) => {
+                let l = &self.ast[l];
+                match l.kind {
+                    LoopKind::Loop => write!(f, "(loop")?,
+                    LoopKind::While(c) => write!(f, "(while {}", d(c))?,
+                    LoopKind::For(name, start, end) => {
+                        write!(f, "(for {} {} {}", &ast[name], d(start), d(end))?
+                    }
+                }
+                if let Some(label) = l.label {
+                    write!(f, " {}", &ast[label])?;
+                }
+                write!(f, " {})", d(l.body))
+            }
+            Expr::Break(label, value) => {
+                write!(f, "(break")?;
+                if let Some(label) = label {
+                    write!(f, " {}", &ast[label])?;
+                }
+                if let Some(value) = value {
+                    write!(f, " {}", d(value))?;
+                }
+                write!(f, ")")
+            }
+            Expr::Continue(Some(label)) => write!(f, "(continue {})", &ast[label]),
+            Expr::Continue(None) => write!(f, "(continue)"),
+            Expr::StructLit(s) => {
+                let lit = &self.ast[s];
+                write!(f, "({}", &ast[lit.name])?;
+                for &(name, value) in &lit.fields {
+                    write!(f, " ({} {})", &ast[name], d(value))?;
+                }
+                write!(f, ")")
+            }
+            Expr::Field(value, name) => write!(f, "(. {} {})", d(value), &ast[name]),
+            Expr::VariantLit(v) => {
+                let lit = &self.ast[v];
+                write!(f, "({}::{}", &ast[lit.ty], &ast[lit.variant])?;
+                for &arg in &lit.args {
+                    write!(f, " {}", d(arg))?;
+                }
+                write!(f, ")")
+            }
+            Expr::Match(m) => {
+                let m = &self.ast[m];
+                write!(f, "(match {}", d(m.scrutinee))?;
+                for arm in &m.arms {
+                    write!(f, " ({}", self.ast.display_pat(arm.pat))?;
+                    if let Some(guard) = arm.guard {
+                        write!(f, " if {}", d(guard))?;
+                    }
+                    write!(f, " {})", d(arm.body))?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+struct DisplayPat<'a> {
+    ast: &'a Ast,
+    id: PatId,
+}
+
+impl fmt::Display for DisplayPat<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.ast[self.id] {
+            Pat::Wild => write!(f, "_"),
+            Pat::Int(i) => write!(f, "{i}"),
+            Pat::Bind(name) => write!(f, "{}", &self.ast[*name]),
+            Pat::Variant(ty, variant, args) => {
+                write!(f, "{}::{}", &self.ast[*ty], &self.ast[*variant])?;
+                if !args.is_empty() {
+                    write!(f, "(")?;
+                    for (i, &arg) in args.iter().enumerate() {
+                        match i {
+                            0 => write!(f, "{}", self.ast.display_pat(arg))?,
+                            _ => write!(f, ", {}", self.ast.display_pat(arg))?,
+                        }
+                    }
+                    write!(f, ")")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Information about nodes, filled in by a pass and keyed by their ids.
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct SideTable<K, V> {
+    values: Vec<Option<V>>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    key: PhantomData<K>,
+}
+
+impl<K, V> Default for SideTable<K, V> {
+    fn default() -> Self {
+        Self {
+            values: vec![],
+            key: PhantomData,
+        }
+    }
+}
+
+impl<K: Id, V> SideTable<K, V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the value for `key`, returning the one it replaces.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let index = key.index();
+        if index >= self.values.len() {
+            self.values.resize_with(index + 1, || None);
+        }
+        self.values[index].replace(value)
+    }
+
+    pub fn get(&self, key: K) -> Option<&V> {
+        self.values.get(key.index())?.as_ref()
+    }
+
+    pub fn get_mut(&mut self, key: K) -> Option<&mut V> {
+        self.values.get_mut(key.index())?.as_mut()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.iter().all(Option::is_none)
+    }
+
+    pub fn contains(&self, key: K) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (K, &V)> {
+        self.values
+            .iter()
+            .enumerate()
+            .filter_map(|(i, v)| Some((K::new(i), v.as_ref()?)))
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut V> {
+        self.values.iter_mut().flatten()
+    }
+}
+
+impl<K: Id, V> Index<K> for SideTable<K, V> {
+    type Output = V;
+    fn index(&self, key: K) -> &V {
+        self.get(key).expect("no entry for node")
+    }
+}
@@ -0,0 +1,290 @@
+//! Decision trees, which `match` and `if`/`else` chains both lower to.
+//!
+//! A `match` is compiled column by column: the first pattern that tests
+//! something splits the arms by what they expect there, so each part of the
+//! scrutinee is tested at most once on the way to an arm. An `if` chain is a
+//! `match` on nothing whose arms are all guards.
+
+use crate::{Ast, Expr, ExprId, MatchId, Pat, PatId, Symbol};
+
+/// Where a value sits inside the scrutinee: the positions of the variant
+/// values to go through, outermost first.
+pub type Path = Vec<usize>;
+
+/// What a [`Decision::Switch`] compares a value with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Case {
+    Int(i32),
+    /// The enum and the variant.
+    Variant(Symbol, Symbol),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decision {
+    /// Nothing matched, which the type checker rules out for a `match`.
+    Fail,
+    /// Binds each name to the value at its path, then runs `body` if there
+    /// is no guard or it holds, and goes on with `otherwise` if not. An `if`
+    /// without an `else` ends in a leaf without a body.
+    Leaf {
+        bindings: Vec<(Symbol, Path)>,
+        guard: Option<ExprId>,
+        body: Option<ExprId>,
+        otherwise: Box<Decision>,
+    },
+    /// Goes to the decision of the first case equal to the value at `path`,
+    /// or to `default` if there is none.
+    Switch {
+        path: Path,
+        cases: Vec<(Case, Decision)>,
+        default: Box<Decision>,
+    },
+}
+
+/// Lowers a `match`. Arms are tried in order, as the tree is built from
+/// them in order.
+pub fn lower_match(ast: &Ast, id: MatchId) -> Decision {
+    let rows = ast[id]
+        .arms
+        .iter()
+        .map(|arm| {
+            Row {
+                columns: vec![],
+                bindings: vec![],
+                guard: arm.guard,
+                body: arm.body,
+            }
+            .with(ast, vec![], arm.pat)
+        })
+        .collect();
+    compile(ast, rows)
+}
+
+/// Lowers `if` and `if`/`else` chains, each condition becoming the guard of
+/// an arm that matches anything. Any other expression is a leaf that runs it.
+pub fn lower_if(ast: &Ast, expr: ExprId) -> Decision {
+    let (condition, then, otherwise) = match ast[expr] {
+        Expr::If(c, b) => (c, b, leaf(None)),
+        Expr::IfElse(c, b1, b2) => (c, b1, lower_if(ast, b2)),
+        _ => return leaf(Some(expr)),
+    };
+    Decision::Leaf {
+        bindings: vec![],
+        guard: Some(condition),
+        body: Some(then),
+        otherwise: Box::new(otherwise),
+    }
+}
+
+fn leaf(body: Option<ExprId>) -> Decision {
+    Decision::Leaf {
+        bindings: vec![],
+        guard: None,
+        body,
+        otherwise: Box::new(Decision::Fail),
+    }
+}
+
+/// An arm with the patterns it still has to test. Names and wildcards test
+/// nothing, so they never stay in `columns`.
+#[derive(Clone)]
+struct Row {
+    columns: Vec<(Path, PatId)>,
+    bindings: Vec<(Symbol, Path)>,
+    guard: Option<ExprId>,
+    body: ExprId,
+}
+
+impl Row {
+    /// Adds `pat` as a column for the value at `path`.
+    fn with(mut self, ast: &Ast, path: Path, pat: PatId) -> Self {
+        match ast[pat] {
+            Pat::Wild => {}
+            Pat::Bind(name) => self.bindings.push((name, path)),
+            Pat::Int(_) | Pat::Variant(..) => self.columns.push((path, pat)),
+        }
+        self
+    }
+
+    /// The pattern this row has for the value at `path`, if it tests it.
+    fn column(&self, path: &Path) -> Option<PatId> {
+        self.columns
+            .iter()
+            .find(|(p, _)| p == path)
+            .map(|&(_, pat)| pat)
+    }
+}
+
+fn case(ast: &Ast, pat: PatId) -> Case {
+    match ast[pat] {
+        Pat::Int(i) => Case::Int(i),
+        Pat::Variant(ty, variant, _) => Case::Variant(ty, variant),
+        Pat::Wild | Pat::Bind(_) => unreachable!("only tests are kept as columns"),
+    }
+}
+
+fn compile(ast: &Ast, rows: Vec<Row>) -> Decision {
+    let Some(first) = rows.first() else {
+        return Decision::Fail;
+    };
+    let Some((path, _)) = first.columns.first() else {
+        let otherwise = match first.guard {
+            Some(_) => compile(ast, rows[1..].to_vec()),
+            None => Decision::Fail,
+        };
+        return Decision::Leaf {
+            bindings: first.bindings.clone(),
+            guard: first.guard,
+            body: Some(first.body),
+            otherwise: Box::new(otherwise),
+        };
+    };
+    let path = path.clone();
+    let mut cases = vec![];
+    for row in &rows {
+        if let Some(pat) = row.column(&path) {
+            let case = case(ast, pat);
+            if !cases.contains(&case) {
+                cases.push(case);
+            }
+        }
+    }
+    let cases = cases
+        .into_iter()
+        .map(|case| (case, compile(ast, specialize(ast, &rows, &path, case))))
+        .collect();
+    let default = rows
+        .iter()
+        .filter(|row| row.column(&path).is_none())
+        .cloned()
+        .collect();
+    Decision::Switch {
+        path,
+        cases,
+        default: Box::new(compile(ast, default)),
+    }
+}
+
+/// The rows that can match when the value at `path` is `case`, with the
+/// patterns for the variant's values in place of the one that tested it.
+fn specialize(ast: &Ast, rows: &[Row], path: &Path, case: Case) -> Vec<Row> {
+    rows.iter()
+        .filter_map(|row| {
+            let Some(index) = row.columns.iter().position(|(p, _)| p == path) else {
+                return Some(row.clone());
+            };
+            let pat = row.columns[index].1;
+            if self::case(ast, pat) != case {
+                return None;
+            }
+            let mut row = row.clone();
+            row.columns.remove(index);
+            if let Pat::Variant(_, _, args) = &ast[pat] {
+                for (i, &arg) in args.iter().enumerate() {
+                    let path = [&path[..], &[i]].concat();
+                    row = row.with(ast, path, arg);
+                }
+            }
+            Some(row)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, Stmt};
+
+    /// The tree as an s-expression, with bodies and guards as their source.
+    fn show(src: &str, ast: &Ast, decision: &Decision) -> String {
+        match decision {
+            Decision::Fail => "fail".into(),
+            Decision::Leaf {
+                bindings,
+                guard,
+                body,
+                otherwise,
+            } => {
+                let mut out = "(leaf".to_string();
+                for (name, path) in bindings {
+                    out += &format!(" {}={path:?}", &ast[*name]);
+                }
+                if let Some(guard) = guard {
+                    out += &format!(" if {}", &src[ast.span(*guard)]);
+                }
+                match body {
+                    Some(body) => out += &format!(" => {}", &src[ast.span(*body)]),
+                    None => out += " => ()",
+                }
+                if guard.is_some() {
+                    out += &format!(" else {}", show(src, ast, otherwise));
+                }
+                out + ")"
+            }
+            Decision::Switch {
+                path,
+                cases,
+                default,
+            } => {
+                let mut out = format!("(switch {path:?}");
+                for (case, decision) in cases {
+                    let case = match case {
+                        Case::Int(i) => i.to_string(),
+                        Case::Variant(ty, variant) => format!("{}::{}", &ast[*ty], &ast[*variant]),
+                    };
+                    out += &format!(" ({case} {})", show(src, ast, decision));
+                }
+                out + &format!(" (_ {}))", show(src, ast, default))
+            }
+        }
+    }
+
+    fn lower(src: &str) -> String {
+        let ast = parse(src).unwrap();
+        let expr = ast
+            .program()
+            .iter()
+            .rev()
+            .find_map(|&stmt| match ast[stmt] {
+                Stmt::Expr(expr) => Some(expr),
+                _ => None,
+            })
+            .unwrap();
+        let decision = match ast[expr] {
+            Expr::Match(m) => lower_match(&ast, m),
+            _ => lower_if(&ast, expr),
+        };
+        show(src, &ast, &decision)
+    }
+
+    #[test]
+    fn matches() {
+        assert_eq!(
+            lower("match x { 1 => a, n if n > 9 => b, _ => c }"),
+            "(switch [] (1 (leaf => a)) (_ (leaf n=[] if n > 9 => b else (leaf => c))))"
+        );
+        assert_eq!(
+            lower(
+                "enum S { C(i32), R(i32, i32), E }\n\
+                 match s { S::R(w, 0) => w, S::C(r) => r, S::R(_, h) => h, _ => 0 }"
+            ),
+            "(switch [] (S::R (switch [1] (0 (leaf w=[0] => w)) (_ (leaf h=[1] => h)))) \
+             (S::C (leaf r=[0] => r)) (_ (leaf => 0)))"
+        );
+        assert_eq!(lower("match x { }"), "fail");
+    }
+
+    #[test]
+    fn if_chains() {
+        assert_eq!(lower("if x { 1 }"), "(leaf if x => 1 else (leaf => ()))");
+        assert_eq!(
+            lower("if x { 1 } else if y { 2 } else { 3 }"),
+            "(leaf if x => 1 else (leaf if y => 2 else (leaf => 3)))"
+        );
+        // An `if` lowers to the `match` whose arms are guards.
+        assert_eq!(
+            lower("if x { 1 } else { 3 }"),
+            lower("match 0 { _ if x => { 1 }, _ => { 3 } }")
+        );
+    }
+}
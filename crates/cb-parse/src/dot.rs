@@ -0,0 +1,215 @@
+use super::visit::{walk_program, Visitor};
+use super::{
+    ArrayId, Ast, Atom, BlockId, CallId, ExprId, LambdaId, LoopId, LoopKind, MatchId, Op,
+    StructLitId, Symbol, VariantLitId,
+};
+use std::fmt::{Display, Write};
+
+/// Renders a program as a Graphviz `digraph` with one node per expression.
+pub fn dot(ast: &Ast) -> String {
+    let mut graph = Graph::default();
+    graph.out.push_str("digraph ast {\n");
+    walk_program(&mut graph, ast);
+    graph.out.push_str("}\n");
+    graph.out
+}
+
+#[derive(Default)]
+struct Graph {
+    out: String,
+    count: usize,
+}
+
+impl Graph {
+    /// Writes a node and returns its number.
+    fn node(&mut self, label: impl Display) -> usize {
+        let id = self.count;
+        self.count += 1;
+        let label = label.to_string().replace('\\', "\\\\").replace('"', "\\\"");
+        let _ = writeln!(self.out, "    node{id} [label=\"{label}\"];");
+        id
+    }
+
+    /// Writes an edge to the node `child` is about to become, then `child`.
+    fn edge(&mut self, ast: &Ast, id: usize, label: &str, child: ExprId) {
+        let child_id = self.count;
+        match label {
+            "" => {
+                let _ = writeln!(self.out, "    node{id} -> node{child_id};");
+            }
+            _ => {
+                let _ = writeln!(
+                    self.out,
+                    "    node{id} -> node{child_id} [label=\"{label}\"];"
+                );
+            }
+        }
+        self.visit_expr(ast, child);
+    }
+}
+
+impl Visitor for Graph {
+    fn visit_atom(&mut self, ast: &Ast, _expr: ExprId, atom: Atom) {
+        self.node(ast.display_atom(atom));
+    }
+
+    fn visit_unary(&mut self, ast: &Ast, _expr: ExprId, op: Op, rhs: ExprId) {
+        let id = self.node(op);
+        self.edge(ast, id, "", rhs);
+    }
+
+    fn visit_binary(&mut self, ast: &Ast, _expr: ExprId, op: Op, lhs: ExprId, rhs: ExprId) {
+        let id = self.node(op);
+        self.edge(ast, id, "", lhs);
+        self.edge(ast, id, "", rhs);
+    }
+
+    fn visit_if(&mut self, ast: &Ast, _expr: ExprId, cond: ExprId, then: ExprId) {
+        let id = self.node("if");
+        self.edge(ast, id, "cond", cond);
+        self.edge(ast, id, "then", then);
+    }
+
+    fn visit_if_else(
+        &mut self,
+        ast: &Ast,
+        _expr: ExprId,
+        cond: ExprId,
+        then: ExprId,
+        otherwise: ExprId,
+    ) {
+        let id = self.node("if");
+        self.edge(ast, id, "cond", cond);
+        self.edge(ast, id, "then", then);
+        self.edge(ast, id, "else", otherwise);
+    }
+
+    fn visit_lambda(&mut self, ast: &Ast, _expr: ExprId, lambda: LambdaId) {
+        let names: Vec<_> = ast[lambda].params.iter().map(|p| &ast[p.name]).collect();
+        let id = self.node(format!("λ({})", names.join(", ")));
+        self.edge(ast, id, "body", ast[lambda].body);
+    }
+
+    fn visit_call(&mut self, ast: &Ast, _expr: ExprId, call: CallId) {
+        let id = self.node("call");
+        self.edge(ast, id, "callee", ast[call].callee);
+        for &arg in &ast[call].args {
+            self.edge(ast, id, "", arg);
+        }
+    }
+
+    fn visit_array(&mut self, ast: &Ast, _expr: ExprId, array: ArrayId) {
+        let id = self.node("[]");
+        for &elem in &ast[array] {
+            self.edge(ast, id, "", elem);
+        }
+    }
+
+    fn visit_repeat(&mut self, ast: &Ast, _expr: ExprId, elem: ExprId, len: u32) {
+        let id = self.node(format!("[; {len}]"));
+        self.edge(ast, id, "", elem);
+    }
+
+    fn visit_index(&mut self, ast: &Ast, _expr: ExprId, array: ExprId, index: ExprId) {
+        let id = self.node("index");
+        self.edge(ast, id, "array", array);
+        self.edge(ast, id, "index", index);
+    }
+
+    fn visit_assign(&mut self, ast: &Ast, _expr: ExprId, target: ExprId, value: ExprId) {
+        let id = self.node("=");
+        self.edge(ast, id, "", target);
+        self.edge(ast, id, "", value);
+    }
+
+    fn visit_block(&mut self, ast: &Ast, _expr: ExprId, block: BlockId) {
+        let id = self.node("{}");
+        for &stmt in &ast[block] {
+            self.edge(ast, id, "", stmt);
+        }
+    }
+
+    fn visit_let(&mut self, ast: &Ast, _expr: ExprId, name: Symbol, value: ExprId) {
+        let id = self.node(format!("let {}", &ast[name]));
+        self.edge(ast, id, "", value);
+    }
+
+    fn visit_loop(&mut self, ast: &Ast, _expr: ExprId, l: LoopId) {
+        let l = ast[l];
+        let label = match l.label {
+            Some(label) => format!("{}: ", &ast[label]),
+            None => String::new(),
+        };
+        let id = match l.kind {
+            LoopKind::Loop => self.node(format!("{label}loop")),
+            LoopKind::While(c) => {
+                let id = self.node(format!("{label}while"));
+                self.edge(ast, id, "cond", c);
+                id
+            }
+            LoopKind::For(name, from, to) => {
+                let id = self.node(format!("{label}for {}", &ast[name]));
+                self.edge(ast, id, "from", from);
+                self.edge(ast, id, "to", to);
+                id
+            }
+        };
+        self.edge(ast, id, "body", l.body);
+    }
+
+    fn visit_break(
+        &mut self,
+        ast: &Ast,
+        _expr: ExprId,
+        label: Option<Symbol>,
+        value: Option<ExprId>,
+    ) {
+        let id = match label {
+            Some(label) => self.node(format!("break {}", &ast[label])),
+            None => self.node("break"),
+        };
+        if let Some(value) = value {
+            self.edge(ast, id, "", value);
+        }
+    }
+
+    fn visit_continue(&mut self, ast: &Ast, _expr: ExprId, label: Option<Symbol>) {
+        match label {
+            Some(label) => self.node(format!("continue {}", &ast[label])),
+            None => self.node("continue"),
+        };
+    }
+
+    fn visit_struct_lit(&mut self, ast: &Ast, _expr: ExprId, lit: StructLitId) {
+        let id = self.node(&ast[ast[lit].name]);
+        for &(name, value) in &ast[lit].fields {
+            self.edge(ast, id, &ast[name], value);
+        }
+    }
+
+    fn visit_field(&mut self, ast: &Ast, _expr: ExprId, value: ExprId, name: Symbol) {
+        let id = self.node(format!(".{}", &ast[name]));
+        self.edge(ast, id, "", value);
+    }
+
+    fn visit_variant_lit(&mut self, ast: &Ast, _expr: ExprId, lit: VariantLitId) {
+        let id = self.node(format!("{}::{}", &ast[ast[lit].ty], &ast[ast[lit].variant]));
+        for &arg in &ast[lit].args {
+            self.edge(ast, id, "", arg);
+        }
+    }
+
+    /// Each arm is an edge to its body labelled with the pattern, after an
+    /// edge to its guard if it has one.
+    fn visit_match(&mut self, ast: &Ast, _expr: ExprId, m: MatchId) {
+        let id = self.node("match");
+        self.edge(ast, id, "", ast[m].scrutinee);
+        for arm in &ast[m].arms {
+            let pat = ast.display_pat(arm.pat).to_string();
+            if let Some(guard) = arm.guard {
+                self.edge(ast, id, &format!("{pat} if"), guard);
+            }
+            self.edge(ast, id, &pat, arm.body);
+        }
+    }
+}
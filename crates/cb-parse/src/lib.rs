@@ -1,306 +1,99 @@
-use cb_lexer::{Scanner, Span, Token, TokenDebug};
-use std::fmt;
-use std::iter::Peekable;
+mod ast;
+pub mod decision;
+mod dot;
+mod print;
+pub mod syntax;
+pub mod visit;
+
+use cb_lexer::TokenKind;
+
+pub use crate::ast::{
+    Arm, ArrayId, Ast, Atom, BlockId, Call, CallId, Enum, EnumId, Expr, ExprId, Field, Id, Lambda,
+    LambdaId, Loop, LoopId, LoopKind, Match, MatchId, Named, Op, Param, Pat, PatId, SideTable,
+    Stmt, StmtId, StrId, Struct, StructId, StructLit, StructLitId, TypeExpr, UseId, Variant,
+    VariantLit, VariantLitId,
+};
+pub use crate::dot::dot;
+pub use crate::print::print;
+pub use crate::syntax::SyntaxError;
+pub use cb_lexer::{Interner, Symbol};
 
 type CResult<T> = Result<T, Box<dyn std::error::Error>>;
 
-pub enum ParseDebug {
-    True,
-    False,
+/// Parses `src` into its [`syntax`] tree and lowers that to an [`Ast`],
+/// failing with the first [`SyntaxError`] in the tree.
+pub fn parse(src: &str) -> CResult<Ast> {
+    Ok(syntax::parse(src).lower()?)
 }
 
-impl From<bool> for ParseDebug {
-    fn from(b: bool) -> Self {
-        if b {
-            return Self::True;
-        }
-        Self::False
-    }
-}
-
-pub fn parse(src: &str, scan_debug: TokenDebug, parse_debug: ParseDebug) -> CResult<Vec<Expr>> {
-    let lexer = Scanner::new(src, scan_debug);
-    let mut parser = Parser::new(lexer.peekable());
-    let ast = parser.parse();
-    if let ParseDebug::True = parse_debug {
-        dbg!(&ast);
-    }
-    ast
-}
-
-#[derive(Debug)]
-enum ParserError {
-    BadToken(Option<(Token, Span)>),
-    Expected(Token, (Token, Span)),
-}
-impl fmt::Display for ParserError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::BadToken(Some((t, s))) => write!(f, "{}:{} {t:?}", s.start, s.end),
-            Self::BadToken(None) => write!(f, "BadToken::None"),
-            Self::Expected(expected, (found, s)) => write!(
-                f,
-                "{}:{} expected '{expected:?}' but found '{found:?}",
-                s.start, s.end
-            ),
-        }
-    }
-}
-
-impl std::error::Error for ParserError {}
-
+/// How tightly an operator binds, which the parser and the printer share.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 enum Precedence {
     None,
     Primary,
-    Term,   // + -
-    Factor, // * /
-    // Or,         // or
-    // And,        // and
-    // Equality,   // == !=
-    Comparison, // < > <= >=
-    Assignment, // =
-    // Call,       // . ()
-    // Func,
-    Unary, // ! -
-}
-
-impl From<Token> for Precedence {
-    fn from(token: Token) -> Self {
-        match token {
-            Token::Id(_) => Self::None,
-            Token::Int(_) => Self::None,
-            Token::Float(_) => Self::None,
-            Token::String(_) => Self::None,
-            Token::Char(_) => Self::None,
-            Token::Op(ref op) => match op.as_str() {
-                "+" | "-" => Self::Term,
-                "*" | "/" => Self::Factor,
-                ">" | "<" | ">=" | "<=" | "==" | "!=" => Self::Comparison,
-                "=" => Self::Assignment,
-                _ => Self::None,
-            },
-            Token::KeyWord(ref b) if b == "true" || b == "false" => Self::Primary,
-            _ => Self::None,
-        }
-    }
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum Op {
-    Minus,
-    Plus,
-    Mult,
-    Div,
-    Grt,
-    Les,
-}
-
-impl fmt::Display for Op {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::Minus => write!(f, "-"),
-            Self::Plus => write!(f, "+"),
-            Self::Mult => write!(f, "*"),
-            Self::Div => write!(f, "/"),
-            Self::Grt => write!(f, ">"),
-            Self::Les => write!(f, "<"),
-        }
-    }
-}
-
-impl TryFrom<Token> for Op {
-    type Error = &'static str;
-    fn try_from(value: Token) -> Result<Self, Self::Error> {
-        match value {
-            Token::Op(ref op) => Self::try_from(op),
-            _ => Err("not an operator"),
-        }
-    }
-}
-
-impl TryFrom<&str> for Op {
-    type Error = &'static str;
-    fn try_from(value: &str) -> Result<Self, Self::Error> {
-        match value {
-            "-" => Ok(Self::Minus),
-            "+" => Ok(Self::Plus),
-            "*" => Ok(Self::Mult),
-            "/" => Ok(Self::Div),
-            ">" => Ok(Self::Grt),
-            "<" => Ok(Self::Les),
-            _ => Err("not an operator"),
-        }
-    }
-}
-
-impl TryFrom<&String> for Op {
-    type Error = &'static str;
-    fn try_from(value: &String) -> Result<Self, Self::Error> {
-        Self::try_from(value.as_str())
-    }
-}
-
-#[derive(Debug, Clone, PartialEq)]
-pub enum Atom {
-    Int(i32),
-    Id(String),
+    Term,       // + -
+    Factor,     // * /
+    Comparison, // < > == !=
+    Unary,      // -
 }
 
-impl fmt::Display for Atom {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::Int(i) => write!(f, "{i}"),
-            Self::Id(i) => write!(f, "{i}"),
+impl From<Op> for Precedence {
+    fn from(op: Op) -> Self {
+        match op {
+            Op::Plus | Op::Minus => Self::Term,
+            Op::Mult | Op::Div => Self::Factor,
+            Op::Grt | Op::Les | Op::Eq | Op::Ne => Self::Comparison,
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum Expr {
-    Atom(Atom),
-    Unary(Op, Box<Self>),
-    Binary(Op, Box<Self>, Box<Self>),
-    If(Box<Self>, Box<Self>),
-    IfElse(Box<Self>, Box<Self>, Box<Self>),
+/// The value of an integer literal, which may separate digits with `_`, or
+/// `None` if it does not fit in an `i32`.
+fn int_value(text: &str) -> Option<i32> {
+    text.replace('_', "").parse().ok()
 }
 
-impl fmt::Display for Expr {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::Atom(i) => write!(f, "{i}"),
-            Self::Unary(op, expr) => write!(f, "({op} {expr})"),
-            Self::Binary(op, lhs, rhs) => write!(f, "({op} {lhs} {rhs})"),
-            Self::If(c, b) => write!(f, "(if ({c}) ({b}))"),
-            Self::IfElse(c, b1, b2) => write!(f, "(if ({c}) then ({b1}) else ({b2}))"),
-        }
-    }
-}
-
-struct Parser<'a> {
-    lexer: Peekable<Scanner<'a>>,
-}
-
-impl<'a> Parser<'a> {
-    fn new(lexer: Peekable<Scanner<'a>>) -> Self {
-        Self { lexer }
-    }
-
-    fn is_end(&mut self) -> bool {
-        matches!(self.lexer.peek().unwrap(), (Token::Eof, _))
-    }
-
-    fn check(&mut self, expected: Token) -> bool {
-        self.peek() == expected
-    }
-
-    fn consume(&mut self, expected: Token) -> CResult<Span> {
-        if self.peek() == expected {
-            return Ok(self.lexer.next().map(|(_, s)| s).unwrap());
-        }
-        let found = self.lexer.peek().unwrap().clone();
-        Err(Box::new(ParserError::Expected(expected, found)))
-    }
-
-    fn peek(&mut self) -> Token {
-        self.lexer
-            .peek()
-            .map(|(t, _)| t.clone())
-            .unwrap_or(Token::Eof)
-    }
-
-    fn program(&mut self) -> CResult<Expr> {
-        self.if_statement()
-    }
-
-    fn if_statement(&mut self) -> CResult<Expr> {
-        if self.check(Token::KeyWord("if".into())) {
-            let span = self.consume(Token::KeyWord("if".into()))?;
-            let condition = self.expression(Precedence::None)?;
-            self.consume(Token::Op("{".into()))?;
-            let branch = self.if_statement()?;
-            self.consume(Token::Op("}".into()))?;
-            if self.check(Token::KeyWord("else".into())) {
-                return self.if_else_statement(span, condition, branch);
-            }
-            let expr = Expr::If(Box::new(condition), Box::new(branch));
-            return Ok(expr);
-        }
-        self.expression(Precedence::None)
-    }
-
-    fn if_else_statement(&mut self, _span: Span, condition: Expr, branch1: Expr) -> CResult<Expr> {
-        self.consume(Token::KeyWord("else".into()))?;
-        let branch2 = if self.check(Token::KeyWord("if".into())) {
-            self.if_statement()?
-        } else {
-            self.consume(Token::Op("{".into()))?;
-            let branch2 = self.if_statement()?;
-            self.consume(Token::Op("}".into()))?;
-            branch2
-        };
-        Ok(Expr::IfElse(
-            Box::new(condition),
-            Box::new(branch1),
-            Box::new(branch2),
-        ))
-    }
-
-    fn expression(&mut self, min_bp: Precedence) -> CResult<Expr> {
-        let mut lhs = match self.lexer.next() {
-            Some((Token::Int(a), _)) => Expr::Atom(Atom::Int(a.parse().unwrap())),
-            Some((Token::Id(id), _)) => Expr::Atom(Atom::Id(id.into())),
-            Some((Token::Op(ref op), _)) if op == "(" => {
-                let lhs = self.expression(Precedence::None)?;
-                self.lexer.next();
-                lhs
-            }
-            Some((Token::Op(ref op), _)) if op == "-" => {
-                let op = Op::try_from(op.as_str())?;
-                let rhs = self.expression(Precedence::Unary)?;
-                Expr::Unary(op, Box::new(rhs))
-            }
-            t => return Err(Box::new(ParserError::BadToken(t))),
-        };
-        loop {
-            let Some((token, _)) = self.lexer.peek() else {
-                    break;
-                };
-            let bp = Precedence::from(token.clone());
-            let op = match Op::try_from(token.clone()) {
-                Ok(o) => o,
-                Err(_) => break,
-            };
-            if bp <= min_bp {
-                break;
-            }
-            self.lexer.next();
-            match bp {
-                Precedence::Term | Precedence::Factor | Precedence::Comparison => {
-                    let rhs = self.expression(bp)?;
-                    lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
-                }
-                _ => {}
-            }
-        }
-        Ok(lhs)
-    }
-
-    fn parse(&mut self) -> CResult<Vec<Expr>> {
-        let mut result = vec![];
-        while !self.is_end() {
-            let e = self.program()?;
-            result.push(e);
-        }
-        Ok(result)
-    }
+/// Whether a token of this kind can start an expression, which decides
+/// whether a `break` is followed by a value.
+fn starts_expression(kind: TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::Int
+            | TokenKind::String
+            | TokenKind::Id
+            | TokenKind::LParen
+            | TokenKind::Minus
+            | TokenKind::LBracket
+            | TokenKind::Lambda
+            | TokenKind::Fn
+    )
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    fn tparse(src: &str) -> Vec<Expr> {
-        parse(src, TokenDebug::True, ParseDebug::True).unwrap_or(vec![])
+    use std::fmt;
+    fn tparse(src: &str) -> Vec<String> {
+        let ast = parse(src).unwrap_or_default();
+        let expr = |id: StmtId| match ast[id] {
+            Stmt::Expr(expr) => ast.display(expr).to_string(),
+            Stmt::Struct(decl) => ast.named(&ast[decl]).to_string(),
+            Stmt::Enum(decl) => ast.named(&ast[decl]).to_string(),
+            Stmt::Mod(name) => format!("(mod {})", &ast[name]),
+            Stmt::Use(path) => format!(
+                "(use {})",
+                ast[path]
+                    .iter()
+                    .map(|&s| &ast[s])
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+        };
+        let stmt = |&id: &StmtId| match ast.is_public(id) {
+            true => format!("(pub {})", expr(id)),
+            false => expr(id),
+        };
+        ast.program().iter().map(stmt).collect()
     }
 
     fn into_string<E: fmt::Display>(i: &mut impl Iterator<Item = E>) -> String {
@@ -326,6 +119,10 @@ mod tests {
         let exprs = tparse("-1");
         let mut exprs = exprs.iter();
         assert_eq!(into_string(&mut exprs), "(- 1)");
+        assert_eq!(
+            tparse("-2147483648 1 - -2147483648 * 2 (-(-2147483648))"),
+            ["-2147483648", "(- 1 (* -2147483648 2))", "(- -2147483648)"]
+        );
     }
 
     #[test]
@@ -350,6 +147,47 @@ mod tests {
         assert_eq!(into_string(&mut exprs), "(if ((> 1 3)) ((+ a b)))");
     }
 
+    #[test]
+    fn strings() {
+        assert_eq!(
+            tparse(r#"print("a\tb") let f = fn(s: String) -> String { s }"#),
+            [
+                r#"(call print "a\tb")"#,
+                "(let f (λ (s: String) -> String s))"
+            ]
+        );
+        let ast = parse(r#""\"q\"""#).unwrap();
+        let Stmt::Expr(expr) = ast[ast.program()[0]] else {
+            panic!("expected an expression");
+        };
+        let Expr::Atom(Atom::Str(value)) = ast[expr] else {
+            panic!("expected a string");
+        };
+        assert_eq!(&ast[value], "\"q\"");
+    }
+
+    #[test]
+    fn dot_graph() {
+        let ast = parse("if 1 > 3 { -a }").unwrap();
+        assert_eq!(
+            dot(&ast),
+            r#"digraph ast {
+    node0 [label="if"];
+    node0 -> node1 [label="cond"];
+    node1 [label=">"];
+    node1 -> node2;
+    node2 [label="1"];
+    node1 -> node3;
+    node3 [label="3"];
+    node0 -> node4 [label="then"];
+    node4 [label="-"];
+    node4 -> node5;
+    node5 [label="a"];
+}
+"#
+        );
+    }
+
     #[test]
     fn if_else_statement() {
         let exprs = tparse("if x > y { x } else { y }");
@@ -363,4 +201,379 @@ mod tests {
             "(if ((> x y)) then (x) else ((if ((< x y)) then ((+ y y)) else (y))))"
         );
     }
+
+    #[test]
+    fn lambdas() {
+        assert_eq!(
+            tparse("λ(x: i32) -> i32 { x + 1 }"),
+            ["(λ (x: i32) -> i32 (+ x 1))"]
+        );
+        assert_eq!(
+            tparse("fn(f: fn(i32) -> i32, y) { f(y) }"),
+            ["(λ (f: fn(i32) -> i32 y) (call f y))"]
+        );
+        assert_eq!(
+            tparse("fn() { if x { 1 } else { 2 } }(  )"),
+            ["(call (λ () (if (x) then (1) else (2))))"]
+        );
+    }
+
+    #[test]
+    fn calls() {
+        assert_eq!(
+            tparse("f(1, 2 + 3)(4) * 2"),
+            ["(* (call (call f 1 (+ 2 3)) 4) 2)"]
+        );
+        assert_eq!(tparse("-f(x)"), ["(- (call f x))"]);
+        assert_eq!(tparse("(f)(x) f (x)"), ["(call f x)", "f", "x"]);
+    }
+
+    #[test]
+    fn arrays() {
+        assert_eq!(
+            tparse("[1, 2][0] [x; 3] []"),
+            ["(index (array 1 2) 0)", "(array x; 3)", "(array)"]
+        );
+        assert_eq!(tparse("a [0]"), ["a", "(array 0)"]);
+        assert_eq!(tparse("-a[i][j] * 2"), ["(* (- (index (index a i) j)) 2)"]);
+        assert_eq!(
+            tparse("if x { m[0][1] = 2 + 3 }"),
+            ["(if (x) ((= (index (index m 0) 1) (+ 2 3))))"]
+        );
+        assert_eq!(
+            tparse("fn(a: [[i32; 2]; 3]) { a }"),
+            ["(λ (a: [[i32; 2]; 3]) a)"]
+        );
+    }
+
+    #[test]
+    fn loops() {
+        assert_eq!(
+            tparse("let x = 0 while x < 3 { x = x + 1 } x"),
+            ["(let x 0)", "(while (< x 3) (= x (+ x 1)))", "x"]
+        );
+        assert_eq!(
+            tparse("'outer: for i in 0..n { loop { break 'outer } continue }"),
+            ["(for i 0 n 'outer (block (loop (break 'outer)) (continue)))"]
+        );
+        assert_eq!(
+            tparse("loop { let y = f(1) break y + 1 }"),
+            ["(loop (block (let y (call f 1)) (break (+ y 1))))"]
+        );
+        assert_eq!(tparse("while 1 { break }"), ["(while 1 (break))"]);
+        assert_eq!(tparse("for i in 0..3 {}"), ["(for i 0 3 (block))"]);
+        assert_eq!(tparse(r#"loop { break "a" }"#), [r#"(loop (break "a"))"#]);
+    }
+
+    #[test]
+    fn block_like_expressions() {
+        assert_eq!(
+            tparse("let x = loop { break 5 } (loop { break 1 }) + 1"),
+            ["(let x (loop (break 5)))", "(+ (loop (break 1)) 1)"]
+        );
+        assert_eq!(
+            tparse("let y = if x { 1 } else { 2 } * 3 f(match x { _ => 0 })"),
+            [
+                "(let y (* (if (x) then (1) else (2)) 3))",
+                "(call f (match x (_ 0)))"
+            ]
+        );
+        // At the start of a statement they end it, as they always have.
+        assert_eq!(
+            tparse("if x { 1 } -1 loop { break } [1]"),
+            ["(if (x) (1))", "(- 1)", "(loop (break))", "(array 1)"]
+        );
+        assert_eq!(
+            tparse("let z = 'a: while 1 { break 'a }.x"),
+            ["(let z (. (while 1 'a (break 'a)) x))"]
+        );
+    }
+
+    #[test]
+    fn structs() {
+        assert_eq!(
+            tparse("struct P { x: i32, y: [i32; 2] } let p = P { x: 1, y: [2, 3] } p.y[0] = p.x"),
+            [
+                "(struct P (x: i32) (y: [i32; 2]))",
+                "(let p (P (x 1) (y (array 2 3))))",
+                "(= (index (. p y) 0) (. p x))"
+            ]
+        );
+        assert_eq!(
+            tparse("struct E {} struct L { e: E } L { e: E {} } . e"),
+            ["(struct E)", "(struct L (e: E))", "(. (L (e (E))) e)"]
+        );
+        assert_eq!(
+            tparse("struct P { x: i32 } fn(p: P) -> P { p }"),
+            ["(struct P (x: i32))", "(λ (p: P) -> P p)"]
+        );
+    }
+
+    #[test]
+    fn enums() {
+        assert_eq!(
+            tparse("enum S { C(i32), R(i32, i32), E } let s = S::R(1, 2) S::E S::C (1)"),
+            [
+                "(enum S (C i32) (R i32 i32) (E))",
+                "(let s (S::R 1 2))",
+                "(S::E)",
+                "(S::C)",
+                "1"
+            ]
+        );
+        assert_eq!(
+            tparse("match s { S::R(w, _) if w > 1 => w, S::C(-1) => { 0 }, x => { let y = x y } }"),
+            ["(match s (S::R(w, _) if (> w 1) w) (S::C(-1) 0) (x (block (let y x) y)))"]
+        );
+        assert_eq!(
+            tparse("enum O { N } fn(o: O) -> O { match o { _ => O::N } }"),
+            ["(enum O (N))", "(λ (o: O) -> O (match o (_ (O::N))))"]
+        );
+        assert_eq!(
+            tparse("loop { match 1 { 0 => break, _ => continue } }"),
+            ["(loop (match 1 (0 (break)) (_ (continue))))"]
+        );
+    }
+
+    #[test]
+    fn modules() {
+        assert_eq!(
+            tparse("mod a; pub mod b; use a::f; pub use b::c::P; pub let x = fn(p: P) { f(p) }"),
+            [
+                "(mod a)",
+                "(pub (mod b))",
+                "(use a f)",
+                "(pub (use b c P))",
+                "(pub (let x (λ (p: P) (call f p))))"
+            ]
+        );
+        assert_eq!(
+            tparse("pub struct P {} pub enum E { A }"),
+            ["(pub (struct P))", "(pub (enum E (A)))"]
+        );
+        assert_ne!(parse("pub let x = 1").unwrap(), parse("let x = 1").unwrap());
+    }
+
+    #[test]
+    fn spans() {
+        let src = "if x > y { -(x) } 1 + 2";
+        let ast = parse(src).unwrap();
+        let spans: Vec<_> = ast.expr_ids().map(|id| &src[ast.span(id)]).collect();
+        assert_eq!(
+            spans,
+            [
+                "x",
+                "y",
+                "x > y",
+                "x",
+                "-(x)",
+                "if x > y { -(x) }",
+                "1",
+                "2",
+                "1 + 2"
+            ]
+        );
+    }
+
+    #[test]
+    fn side_table() {
+        let ast = parse("1 + 2 * 3").unwrap();
+        let mut values = SideTable::new();
+        for id in ast.expr_ids() {
+            let value = match ast[id] {
+                Expr::Atom(Atom::Int(i)) => i,
+                Expr::Binary(Op::Plus, l, r) => values[l] + values[r],
+                Expr::Binary(Op::Mult, l, r) => values[l] * values[r],
+                _ => unreachable!(),
+            };
+            values.insert(id, value);
+        }
+        let Stmt::Expr(root) = ast[ast.program()[0]] else {
+            unreachable!()
+        };
+        assert_eq!(values.get(root), Some(&7));
+        assert_eq!(values.iter().count(), 5);
+        assert!(!SideTable::<ExprId, i32>::new().contains(root));
+    }
+
+    #[test]
+    fn equality_ignores_layout() {
+        assert_eq!(parse("(1 + 2) * x").unwrap(), parse("(1+2)*x").unwrap());
+        assert_ne!(parse("1 + 2 * x").unwrap(), parse("(1 + 2) * x").unwrap());
+        assert_ne!(parse("1 2").unwrap(), parse("1").unwrap());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json() {
+        let ast = parse("1 + -x").unwrap();
+        let json = serde_json::to_string(&ast).unwrap();
+        assert_eq!(
+            json,
+            r#"{"exprs":[{"Atom":{"Int":1}},{"Atom":{"Id":"x"}},{"Unary":["Minus",1]},{"Binary":["Plus",0,2]}],"stmts":[{"Expr":3}],"program":[0],"spans":[{"start":0,"end":1},{"start":5,"end":6},{"start":4,"end":6},{"start":0,"end":6}]}"#
+        );
+        let back: Ast = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, ast);
+        assert_eq!(back.span(ExprId::new(2)), 4..6);
+
+        let src = "if x > 1 { 2 } else if y { (3) } else { -4 / z } λ(x: i32) { f(x, 1) } a[0] = [1; 2][b] 'l: for i in 0..3 { let j = i loop { break j } continue 'l } struct P { x: i32 } P { x: 1 }.x enum E { A(P), B } match E::B { E::A(p) if p.x => 1, _ => 2 }";
+        let ast = parse(src).unwrap();
+        let back: Ast = serde_json::from_str(&serde_json::to_string(&ast).unwrap()).unwrap();
+        assert_eq!(back, ast);
+        assert_eq!(back.pat_span(PatId::new(1)), ast.pat_span(PatId::new(1)));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_from_tools() {
+        let read = |json| serde_json::from_str::<Ast>(json).map_err(|e| e.to_string());
+        let ast = read(
+            r#"{"exprs":[{"Atom":{"Int":2}},{"Binary":["Mult",0,0]}],"stmts":[{"Expr":1}],"program":[0,0]}"#,
+        );
+        assert_eq!(ast.unwrap(), parse("2 * 2 2 * 2").unwrap());
+        let error = read(r#"{"exprs":[{"Unary":["Minus",0]}],"stmts":[],"program":[]}"#);
+        assert_eq!(
+            error.unwrap_err(),
+            "expression 0 refers to expression 0, which does not come before it"
+        );
+        let error =
+            read(r#"{"exprs":[{"Atom":{"Int":1}},{"Unary":["Plus",0]}],"stmts":[],"program":[]}"#);
+        assert_eq!(
+            error.unwrap_err(),
+            "expression 1 applies '+', which is not a unary operator"
+        );
+        let error = read(
+            r#"{"exprs":[{"Atom":{"Int":1}},{"Binary":["Mod",0,0]}],"stmts":[],"program":[]}"#,
+        );
+        assert_eq!(
+            error.unwrap_err(),
+            "unknown variant `Mod`, expected one of `Minus`, `Plus`, `Mult`, `Div`, `Grt`, `Les`, `Eq`, `Ne` at line 1 column 45"
+        );
+        let error = read(r#"{"exprs":[],"stmts":[{"Expr":0}],"program":[]}"#);
+        assert_eq!(
+            error.unwrap_err(),
+            "statement 0 refers to missing expression 0"
+        );
+        let error = read(r#"{"exprs":[],"stmts":[{"Struct":0}],"program":[]}"#);
+        assert_eq!(error.unwrap_err(), "statement 0 refers to missing struct 0");
+        let error = read(
+            r#"{"exprs":[{"Atom":{"Int":1}},{"Match":0}],"stmts":[],"matches":[{"scrutinee":0,"arms":[{"pat":0,"guard":null,"body":0}]}],"program":[]}"#,
+        );
+        assert_eq!(
+            error.unwrap_err(),
+            "expression 1 refers to missing pattern 0"
+        );
+        let error =
+            read(r#"{"exprs":[],"stmts":[],"pats":[{"Variant":["E","A",[0]]}],"program":[]}"#);
+        assert_eq!(
+            error.unwrap_err(),
+            "pattern 0 refers to pattern 0, which does not come before it"
+        );
+        let ast = read(
+            r#"{"exprs":[{"Atom":{"Str":0}}],"stmts":[{"Expr":0}],"strings":["a\n"],"program":[0]}"#,
+        );
+        assert_eq!(ast.unwrap(), parse(r#""a\n""#).unwrap());
+        let error = read(r#"{"exprs":[{"Atom":{"Str":0}}],"stmts":[],"program":[]}"#);
+        assert_eq!(
+            error.unwrap_err(),
+            "expression 0 refers to missing string 0"
+        );
+        let error = read(r#"{"exprs":[],"stmts":[],"program":[3]}"#);
+        assert_eq!(error.unwrap_err(), "program refers to missing statement 3");
+    }
+
+    #[test]
+    fn errors() {
+        let error = |src| parse(src).unwrap_err().to_string();
+        assert_eq!(
+            error("1 +"),
+            "3:3 expected an expression but found end of input"
+        );
+        assert_eq!(error("(1 2)"), "3:4 expected ')' but found '2'");
+        assert_eq!(
+            error("if 1 { 2 "),
+            "9:9 expected '}' but found end of input"
+        );
+        assert_eq!(error("1 + @"), "4:5 unknown token '@'");
+        assert_eq!(
+            error("1 + 2147483648"),
+            "4:14 integer literal '2147483648' is too large"
+        );
+        assert_eq!(
+            error("-2147483649"),
+            "1:11 integer literal '2147483649' is too large"
+        );
+        assert_eq!(error("fn(x: bool) { x }"), "6:10 unknown type 'bool'");
+        assert_eq!(error("f(1,)"), "4:5 expected an expression but found ')'");
+        assert_eq!(
+            error("λ(1) { 1 }"),
+            "3:4 expected 'identifier' but found '1'"
+        );
+        assert_eq!(error("break"), "0:5 'break' outside of a loop");
+        assert_eq!(
+            error("loop { fn() { continue } }"),
+            "14:22 'continue' outside of a loop"
+        );
+        assert_eq!(
+            error("'a: loop { break 'b }"),
+            "17:19 no loop is labelled 'b"
+        );
+        assert_eq!(
+            error("while 1 { break 2 }"),
+            "10:15 'break' with a value in a 'while' loop"
+        );
+        assert_eq!(
+            error("f(x) = 1"),
+            "5:6 only variables, array elements and fields can be assigned to"
+        );
+        assert_eq!(error("P { x: 1 }"), "2:3 unexpected '{'");
+        assert_eq!(
+            error("struct P { x: i32, x: i32 }"),
+            "19:20 'x' is defined twice"
+        );
+        assert_eq!(
+            error("struct P { x: i32 } P { x: 1, x: 2 }"),
+            "30:31 'x' is defined twice"
+        );
+        assert_eq!(
+            error("struct P {} struct P {}"),
+            "19:20 'P' is defined twice"
+        );
+        assert_eq!(error("struct P { x: Q }"), "14:15 unknown type 'Q'");
+        assert_eq!(
+            error("struct P {} enum P { A }"),
+            "17:18 'P' is defined twice"
+        );
+        assert_eq!(error("enum E { A, A }"), "12:13 'A' is defined twice");
+        assert_eq!(
+            error("match e { E::A(x, x) => x }"),
+            "18:19 'x' is defined twice"
+        );
+        assert_eq!(
+            error("match e { 1 => 2, }"),
+            "18:19 expected a pattern but found '}'"
+        );
+        assert_eq!(
+            error("match e { -2147483649 => 2 }"),
+            "10:21 integer literal '-2147483649' is too large"
+        );
+        assert_eq!(
+            error("fn() { match 1 { _ => break } }"),
+            "22:27 'break' outside of a loop"
+        );
+        assert_eq!(
+            error("match e { 1 2 }"),
+            "12:13 expected '=>' but found '2'"
+        );
+        assert_eq!(error("mod a; mod a;"), "11:12 'a' is defined twice");
+        assert_eq!(error("use a;"), "5:6 expected '::' but found ';'");
+        assert_eq!(error("pub 1"), "0:3 unexpected 'pub'");
+        assert_eq!(error(r#"1 "ab\c""#), "5:7 unknown escape");
+        assert_eq!(error("\"ab\n1"), "0:3 unterminated string");
+        assert_eq!(error("if x { mod a; }"), "7:10 unexpected 'mod'");
+        assert_eq!(
+            parse("2_147_483_647").unwrap(),
+            parse("2147483647").unwrap()
+        );
+    }
 }
@@ -0,0 +1,580 @@
+use super::{Ast, Expr, ExprId, LoopKind, Precedence, Stmt};
+
+const INDENT: &str = "    ";
+
+/// Prints a program back as C Flat source, laid out the way `cbc fmt` would,
+/// with only the parentheses needed for it to parse back into the same tree.
+///
+/// Source cannot spell a negative literal, a statement such as a `let` or an
+/// assignment used as an operand, or a statement after a `break` that could
+/// be its value, so trees containing those print as the closest source,
+/// which parses back to a different tree.
+pub fn print(ast: &Ast) -> String {
+    let mut printer = Printer {
+        ast,
+        out: String::new(),
+        depth: 0,
+    };
+    let mut previous = None;
+    for &stmt in ast.program() {
+        if ast.is_public(stmt) {
+            printer.out.push_str("pub ");
+        }
+        match ast[stmt] {
+            Stmt::Expr(expr) => {
+                printer.statement(expr, previous);
+                previous = Some(expr);
+            }
+            Stmt::Struct(decl) => {
+                let decl = &ast[decl];
+                let fields: Vec<_> = decl
+                    .fields
+                    .iter()
+                    .map(|f| ast.named(f).to_string())
+                    .collect();
+                printer.line(&format!("struct {} {}", &ast[decl.name], braces(&fields)));
+                previous = None;
+            }
+            Stmt::Enum(decl) => {
+                let decl = &ast[decl];
+                let variants: Vec<_> = decl
+                    .variants
+                    .iter()
+                    .map(|v| {
+                        let fields: Vec<_> =
+                            v.fields.iter().map(|f| ast.named(f).to_string()).collect();
+                        match fields.is_empty() {
+                            true => ast[v.name].to_string(),
+                            false => format!("{}({})", &ast[v.name], fields.join(", ")),
+                        }
+                    })
+                    .collect();
+                printer.line(&format!("enum {} {}", &ast[decl.name], braces(&variants)));
+                previous = None;
+            }
+            Stmt::Mod(name) => {
+                printer.line(&format!("mod {};", &ast[name]));
+                previous = None;
+            }
+            Stmt::Use(path) => {
+                let path: Vec<_> = ast[path].iter().map(|&s| &ast[s]).collect();
+                printer.line(&format!("use {};", path.join("::")));
+                previous = None;
+            }
+        }
+    }
+    printer.out
+}
+
+struct Printer<'a> {
+    ast: &'a Ast,
+    out: String,
+    depth: usize,
+}
+
+impl Printer<'_> {
+    fn line(&mut self, line: &str) {
+        self.out.push_str(&INDENT.repeat(self.depth));
+        self.out.push_str(line);
+        self.out.push('\n');
+    }
+
+    /// Prints one statement on its own lines. A statement that starts with
+    /// `-` right after an expression would continue it as a subtraction, so
+    /// it is wrapped in parentheses.
+    fn statement(&mut self, expr: ExprId, previous: Option<ExprId>) {
+        match self.ast[expr] {
+            Expr::If(c, b) => {
+                let c = self.expression(c, Precedence::None);
+                self.line(&format!("if {c} {{"));
+                self.block(b);
+                self.line("}");
+            }
+            Expr::IfElse(c, b1, b2) => {
+                let c = self.expression(c, Precedence::None);
+                self.line(&format!("if {c} {{"));
+                self.block(b1);
+                self.else_chain(b2);
+            }
+            Expr::Assign(target, value) => {
+                let target = self.expression(target, Precedence::None);
+                let value = self.expression(value, Precedence::None);
+                self.line(&format!("{target} = {value}"));
+            }
+            Expr::Block(b) => {
+                let mut previous = previous;
+                for &stmt in &self.ast[b] {
+                    self.statement(stmt, previous);
+                    previous = Some(stmt);
+                }
+            }
+            Expr::Let(name, value) => {
+                // Only a `let` can hold an `if`, `match` or loop as its
+                // whole value without the parentheses of an operand.
+                let value = match self.ast[value] {
+                    Expr::If(..) | Expr::IfElse(..) | Expr::Match(_) | Expr::Loop(_) => {
+                        self.inline(value)
+                    }
+                    _ => self.expression(value, Precedence::None),
+                };
+                self.line(&format!("let {} = {value}", &self.ast[name]));
+            }
+            Expr::Loop(l) => {
+                let l = self.ast[l];
+                let label = match l.label {
+                    Some(label) => format!("{}: ", &self.ast[label]),
+                    None => String::new(),
+                };
+                let header = match l.kind {
+                    LoopKind::Loop => "loop".to_string(),
+                    LoopKind::While(c) => format!("while {}", self.expression(c, Precedence::None)),
+                    LoopKind::For(name, from, to) => {
+                        let from = self.expression(from, Precedence::None);
+                        let to = self.expression(to, Precedence::None);
+                        format!("for {} in {from}..{to}", &self.ast[name])
+                    }
+                };
+                self.line(&format!("{label}{header} {{"));
+                self.block(l.body);
+                self.line("}");
+            }
+            Expr::Break(label, value) => {
+                let mut code = "break".to_string();
+                if let Some(label) = label {
+                    code = format!("{code} {}", &self.ast[label]);
+                }
+                if let Some(value) = value {
+                    code = format!("{code} {}", self.expression(value, Precedence::None));
+                }
+                self.line(&code);
+            }
+            Expr::Continue(Some(label)) => {
+                self.line(&format!("continue {}", &self.ast[label]));
+            }
+            Expr::Continue(None) => self.line("continue"),
+            Expr::Match(m) => {
+                let m = &self.ast[m];
+                let scrutinee = self.expression(m.scrutinee, Precedence::None);
+                self.line(&format!("match {scrutinee} {{"));
+                self.depth += 1;
+                for (i, arm) in m.arms.iter().enumerate() {
+                    let mut head = self.ast.display_pat(arm.pat).to_string();
+                    if let Some(guard) = arm.guard {
+                        head = format!("{head} if {}", self.expression(guard, Precedence::None));
+                    }
+                    let comma = if i + 1 < m.arms.len() { "," } else { "" };
+                    // A body that needs braces of its own goes on its own
+                    // lines, while a `let` has to stay bare or it would be
+                    // scoped to a block.
+                    match self.ast[arm.body] {
+                        Expr::If(..)
+                        | Expr::IfElse(..)
+                        | Expr::Loop(_)
+                        | Expr::Block(_)
+                        | Expr::Match(_) => {
+                            self.line(&format!("{head} => {{"));
+                            self.block(arm.body);
+                            self.line(&format!("}}{comma}"));
+                        }
+                        _ => {
+                            let body = self.inline(arm.body);
+                            self.line(&format!("{head} => {body}{comma}"));
+                        }
+                    }
+                }
+                self.depth -= 1;
+                self.line("}");
+            }
+            _ => {
+                let code = self.expression(expr, Precedence::None);
+                let continues = previous.is_some_and(|p| !ends_with_block(self.ast, p));
+                match continues && code.starts_with('-') {
+                    true => self.line(&format!("({code})")),
+                    false => self.line(&code),
+                }
+            }
+        }
+    }
+
+    fn else_chain(&mut self, expr: ExprId) {
+        match self.ast[expr] {
+            Expr::If(c, b) => {
+                let c = self.expression(c, Precedence::None);
+                self.line(&format!("}} else if {c} {{"));
+                self.block(b);
+                self.line("}");
+            }
+            Expr::IfElse(c, b1, b2) => {
+                let c = self.expression(c, Precedence::None);
+                self.line(&format!("}} else if {c} {{"));
+                self.block(b1);
+                self.else_chain(b2);
+            }
+            _ => {
+                self.line("} else {");
+                self.block(expr);
+                self.line("}");
+            }
+        }
+    }
+
+    fn block(&mut self, expr: ExprId) {
+        self.depth += 1;
+        self.statement(expr, None);
+        self.depth -= 1;
+    }
+
+    /// Prints `expr` where the parser would stop at operators that bind no
+    /// tighter than `min_bp`, adding parentheses if `expr` is one of them.
+    fn expression(&self, expr: ExprId, min_bp: Precedence) -> String {
+        match self.ast[expr] {
+            Expr::Atom(atom) => self.ast.display_atom(atom),
+            Expr::Unary(op, rhs) => format!("{op}{}", self.expression(rhs, Precedence::Unary)),
+            Expr::Binary(op, lhs, rhs) => {
+                let bp = Precedence::from(op);
+                // Operators of equal precedence associate to the left, so
+                // only the right operand needs parentheses for them.
+                let lhs = self.expression(lhs, lower(bp));
+                let rhs = self.expression(rhs, bp);
+                parenthesize(format!("{lhs} {op} {rhs}"), bp <= min_bp)
+            }
+            Expr::If(..) | Expr::IfElse(..) | Expr::Match(_) | Expr::Loop(_) => {
+                format!("({})", self.inline(expr))
+            }
+            Expr::Lambda(lambda) => {
+                let lambda = &self.ast[lambda];
+                let params: Vec<_> = lambda
+                    .params
+                    .iter()
+                    .map(|p| self.ast.named(p).to_string())
+                    .collect();
+                let ret = match &lambda.ret {
+                    Some(ret) => format!(" -> {}", self.ast.named(ret)),
+                    None => String::new(),
+                };
+                let body = match self.inline(lambda.body) {
+                    body if body.is_empty() => "{}".to_string(),
+                    body => format!("{{ {body} }}"),
+                };
+                format!("λ({}){ret} {body}", params.join(", "))
+            }
+            Expr::Call(call) => {
+                let call = &self.ast[call];
+                let args: Vec<_> = call
+                    .args
+                    .iter()
+                    .map(|&arg| self.expression(arg, Precedence::None))
+                    .collect();
+                format!("{}({})", self.operand(call.callee), args.join(", "))
+            }
+            Expr::Array(array) => {
+                let elems: Vec<_> = self.ast[array]
+                    .iter()
+                    .map(|&elem| self.expression(elem, Precedence::None))
+                    .collect();
+                format!("[{}]", elems.join(", "))
+            }
+            Expr::Repeat(elem, len) => {
+                format!("[{}; {len}]", self.expression(elem, Precedence::None))
+            }
+            Expr::Index(array, index) => {
+                let index = self.expression(index, Precedence::None);
+                format!("{}[{index}]", self.operand(array))
+            }
+            Expr::StructLit(lit) => {
+                let lit = &self.ast[lit];
+                let fields: Vec<_> = lit
+                    .fields
+                    .iter()
+                    .map(|&(name, value)| {
+                        let value = self.expression(value, Precedence::None);
+                        format!("{}: {value}", &self.ast[name])
+                    })
+                    .collect();
+                format!("{} {}", &self.ast[lit.name], braces(&fields))
+            }
+            Expr::Field(value, name) => format!("{}.{}", self.operand(value), &self.ast[name]),
+            Expr::VariantLit(lit) => {
+                let lit = &self.ast[lit];
+                let args: Vec<_> = lit
+                    .args
+                    .iter()
+                    .map(|&arg| self.expression(arg, Precedence::None))
+                    .collect();
+                let (ty, variant) = (&self.ast[lit.ty], &self.ast[lit.variant]);
+                match args.is_empty() {
+                    true => format!("{ty}::{variant}"),
+                    false => format!("{ty}::{variant}({})", args.join(", ")),
+                }
+            }
+            Expr::Assign(..)
+            | Expr::Block(_)
+            | Expr::Let(..)
+            | Expr::Break(..)
+            | Expr::Continue(_) => format!("({})", self.inline(expr)),
+        }
+    }
+
+    /// Prints the operand of a call, an index or a field access, which binds
+    /// tighter than any operator.
+    fn operand(&self, expr: ExprId) -> String {
+        let code = self.expression(expr, Precedence::None);
+        match self.ast[expr] {
+            Expr::Atom(_)
+            | Expr::Lambda(_)
+            | Expr::Call(_)
+            | Expr::Array(_)
+            | Expr::Repeat(..)
+            | Expr::Index(..)
+            | Expr::StructLit(_)
+            | Expr::Field(..) => code,
+            // Without values, a `(` after the variant would be taken as them.
+            Expr::VariantLit(lit) if !self.ast[lit].args.is_empty() => code,
+            _ => format!("({code})"),
+        }
+    }
+
+    /// Prints `expr` as a statement on a single line.
+    fn inline(&self, expr: ExprId) -> String {
+        let mut printer = Printer {
+            ast: self.ast,
+            out: String::new(),
+            depth: 0,
+        };
+        printer.statement(expr, None);
+        let lines: Vec<_> = printer.out.lines().map(str::trim).collect();
+        lines.join(" ")
+    }
+}
+
+/// `{ a, b }`, or `{}` when there is nothing inside.
+fn braces(items: &[String]) -> String {
+    match items.is_empty() {
+        true => "{}".to_string(),
+        false => format!("{{ {} }}", items.join(", ")),
+    }
+}
+
+fn parenthesize(code: String, needed: bool) -> String {
+    match needed {
+        true => format!("({code})"),
+        false => code,
+    }
+}
+
+/// The precedence just below `bp`, so that an operand printed with it keeps
+/// operators of precedence `bp` unparenthesized.
+fn lower(bp: Precedence) -> Precedence {
+    match bp {
+        Precedence::Term => Precedence::Primary,
+        Precedence::Factor => Precedence::Term,
+        Precedence::Comparison => Precedence::Factor,
+        _ => Precedence::None,
+    }
+}
+
+/// Whether a statement ends with a `}`, after which a `-` cannot continue it.
+fn ends_with_block(ast: &Ast, expr: ExprId) -> bool {
+    matches!(
+        ast[expr],
+        Expr::If(..) | Expr::IfElse(..) | Expr::Loop(_) | Expr::Match(_)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, Atom, Op};
+    use proptest::prelude::*;
+
+    fn roundtrip(src: &str) -> String {
+        let ast = parse(src).unwrap();
+        let printed = print(&ast);
+        assert_eq!(parse(&printed).unwrap(), ast, "{printed}");
+        printed
+    }
+
+    #[test]
+    fn minimal_parens() {
+        assert_eq!(roundtrip("((1 + 2)) + 3"), "1 + 2 + 3\n");
+        assert_eq!(roundtrip("1 + (2 + 3)"), "1 + (2 + 3)\n");
+        assert_eq!(
+            roundtrip("(1 + 2) * 3 - 4 / (5 * 6)"),
+            "(1 + 2) * 3 - 4 / (5 * 6)\n"
+        );
+        assert_eq!(roundtrip("(1 * 2) > 3 + (4 < 5)"), "(1 * 2) > 3 + 4 < 5\n");
+        assert_eq!(roundtrip("-(-(x)) - -(1 + y)"), "--x - -(1 + y)\n");
+        assert_eq!(roundtrip("(-f)(x) * (g(1))((2))"), "(-f)(x) * g(1)(2)\n");
+    }
+
+    #[test]
+    fn arrays() {
+        assert_eq!(
+            roundtrip("[1,2+3][0]  [[0;4]; 2][(1)][x]  (-a)[1]"),
+            "[1, 2 + 3][0]\n[[0; 4]; 2][1][x]\n(-a)[1]\n"
+        );
+        assert_eq!(
+            roundtrip("a[0][1]=a[1][0]-1 []"),
+            "a[0][1] = a[1][0] - 1\n[]\n"
+        );
+    }
+
+    #[test]
+    fn loops() {
+        assert_eq!(
+            roundtrip("let i=0 'outer:while i<10{i=i+1 for j in 0..i{if j>3{continue 'outer}}}"),
+            "let i = 0\n'outer: while i < 10 {\n    i = i + 1\n    for j in 0..i {\n        if j > 3 {\n            continue 'outer\n        }\n    }\n}\n"
+        );
+        assert_eq!(
+            roundtrip("fn(){loop{break 1}}() if 1 {let x = 2} loop {} fn() {}"),
+            "λ() { loop { break 1 } }()\nif 1 {\n    let x = 2\n}\nloop {\n}\nλ() {}\n"
+        );
+        assert_eq!(roundtrip("let x = 1 (-x)"), "let x = 1\n(-x)\n");
+        assert_eq!(
+            roundtrip("let x = loop{break 5} (loop{break 1})+1 let y = if x{1}else{2}*3"),
+            "let x = loop { break 5 }\n(loop { break 1 }) + 1\nlet y = (if x { 1 } else { 2 }) * 3\n"
+        );
+    }
+
+    #[test]
+    fn structs() {
+        assert_eq!(
+            roundtrip("struct P{x:i32,y:[i32;2]} struct E{} let p=P{x:1,y:[2,3]} p.y[0]=p . x E{}"),
+            "struct P { x: i32, y: [i32; 2] }\nstruct E {}\nlet p = P { x: 1, y: [2, 3] }\np.y[0] = p.x\nE {}\n"
+        );
+        assert_eq!(
+            roundtrip("struct P{x:i32} (-P{x:1}.x).x P{x:2}.x"),
+            "struct P { x: i32 }\n(-P { x: 1 }.x).x\nP { x: 2 }.x\n"
+        );
+    }
+
+    #[test]
+    fn enums() {
+        assert_eq!(
+            roundtrip("enum S{C(i32),R(i32,i32),E} let s=S::R(1,2) (S::E)(1) S::C(-1)"),
+            "enum S { C(i32), R(i32, i32), E }\nlet s = S::R(1, 2)\n(S::E)(1)\nS::C(-1)\n"
+        );
+        assert_eq!(
+            roundtrip("match s{S::R(w,_) if w>1=>w,S::C(-1)=>{let y=1 y},x=>let y=x,_=>{if 1{2}}}"),
+            "match s {\n    S::R(w, _) if w > 1 => w,\n    S::C(-1) => {\n        let y = 1\n        y\n    },\n    x => let y = x,\n    _ => {\n        if 1 {\n            2\n        }\n    }\n}\n"
+        );
+        assert_eq!(
+            roundtrip("fn(s){match s{0=>1,_=>2}}"),
+            "λ(s) { match s { 0 => 1, _ => 2 } }\n"
+        );
+    }
+
+    #[test]
+    fn modules() {
+        assert_eq!(
+            roundtrip("pub mod a;mod b;use a::c::f;pub use b::P;pub let x=fn(p:P){f(p)}pub struct Q{}"),
+            "pub mod a;\nmod b;\nuse a::c::f;\npub use b::P;\npub let x = λ(p: P) { f(p) }\npub struct Q {}\n"
+        );
+    }
+
+    #[test]
+    fn lambdas() {
+        assert_eq!(
+            roundtrip("fn(x:i32,f:fn(i32)->i32)->i32{f(x)}"),
+            "λ(x: i32, f: fn(i32) -> i32) -> i32 { f(x) }\n"
+        );
+        assert_eq!(
+            roundtrip("λ(x) { if x { 1 } else { -2 } }(3)"),
+            "λ(x) { if x { 1 } else { -2 } }(3)\n"
+        );
+    }
+
+    #[test]
+    fn statements() {
+        assert_eq!(roundtrip("1 (-2) (-3 * 4)"), "1\n(-2)\n(-3 * 4)\n");
+        assert_eq!(
+            roundtrip("1 - 2 if x { -1 } -2"),
+            "1 - 2\nif x {\n    -1\n}\n-2\n"
+        );
+        assert_eq!(
+            roundtrip("if x>y{if y{1}}else if x<y{y+y}else{(y)}"),
+            "if x > y {\n    if y {\n        1\n    }\n} else if x < y {\n    y + y\n} else {\n    y\n}\n"
+        );
+    }
+
+    /// An expression tree in the shapes source can spell, to be allocated
+    /// into an [`Ast`].
+    #[derive(Debug, Clone)]
+    enum Tree {
+        Atom(Atom),
+        Id(&'static str),
+        Str(String),
+        Unary(Box<Tree>),
+        Binary(Op, Box<Tree>, Box<Tree>),
+        If(Box<Tree>, Box<Tree>),
+        IfElse(Box<Tree>, Box<Tree>, Box<Tree>),
+    }
+
+    impl Tree {
+        fn alloc(&self, ast: &mut Ast) -> ExprId {
+            let expr = match self {
+                Self::Atom(atom) => Expr::Atom(*atom),
+                Self::Id(name) => Expr::Atom(Atom::Id(ast.intern(name))),
+                Self::Str(value) => Expr::Atom(Atom::Str(ast.alloc_str(value))),
+                Self::Unary(rhs) => Expr::Unary(Op::Minus, rhs.alloc(ast)),
+                Self::Binary(op, lhs, rhs) => Expr::Binary(*op, lhs.alloc(ast), rhs.alloc(ast)),
+                Self::If(c, b) => Expr::If(c.alloc(ast), b.alloc(ast)),
+                Self::IfElse(c, b1, b2) => Expr::IfElse(c.alloc(ast), b1.alloc(ast), b2.alloc(ast)),
+            };
+            ast.alloc_expr(expr, 0..0)
+        }
+    }
+
+    fn operand() -> impl Strategy<Value = Tree> {
+        let atom = prop_oneof![
+            (0..=i32::MAX).prop_map(|i| Tree::Atom(Atom::Int(i))),
+            prop::sample::select(vec!["a", "b", "x", "foo"]).prop_map(Tree::Id),
+            "[a λ\"\\\n\t]{0,4}".prop_map(Tree::Str),
+        ];
+        let op = prop::sample::select(vec![
+            Op::Plus,
+            Op::Minus,
+            Op::Mult,
+            Op::Div,
+            Op::Grt,
+            Op::Les,
+            Op::Eq,
+            Op::Ne,
+        ]);
+        atom.prop_recursive(6, 48, 2, move |inner| {
+            prop_oneof![
+                inner.clone().prop_map(|e| Tree::Unary(Box::new(e))),
+                (op.clone(), inner.clone(), inner).prop_map(|(op, l, r)| Tree::Binary(
+                    op,
+                    Box::new(l),
+                    Box::new(r)
+                )),
+            ]
+        })
+    }
+
+    fn statement() -> impl Strategy<Value = Tree> {
+        operand().prop_recursive(3, 16, 3, |inner| {
+            prop_oneof![
+                (operand(), inner.clone()).prop_map(|(c, b)| Tree::If(Box::new(c), Box::new(b))),
+                (operand(), inner.clone(), inner).prop_map(|(c, b1, b2)| {
+                    Tree::IfElse(Box::new(c), Box::new(b1), Box::new(b2))
+                }),
+            ]
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn parse_inverts_print(program in prop::collection::vec(statement(), 0..6)) {
+            let mut ast = Ast::new();
+            for tree in &program {
+                let expr = tree.alloc(&mut ast);
+                let stmt = ast.alloc_stmt(Stmt::Expr(expr));
+                ast.push(stmt);
+            }
+            let printed = print(&ast);
+            prop_assert_eq!(parse(&printed).unwrap(), ast, "{}", printed);
+        }
+    }
+}
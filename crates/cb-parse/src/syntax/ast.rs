@@ -0,0 +1,996 @@
+//! Typed views over the syntax tree. Each wrapper holds a [`SyntaxNode`] of
+//! one kind and reads its parts out of the tree on demand, so a missing part
+//! in broken source is simply `None`.
+
+use crate::syntax::{SyntaxKind, SyntaxNode, SyntaxToken};
+use crate::{
+    Ast, Atom, EnumId, ExprId, Interner, LoopKind, Op, PatId, Stmt, StructId, Symbol, TypeExpr,
+};
+
+pub trait AstNode: Sized {
+    fn cast(node: SyntaxNode) -> Option<Self>;
+    fn syntax(&self) -> &SyntaxNode;
+}
+
+macro_rules! ast_node {
+    ($name:ident) => {
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        pub struct $name(SyntaxNode);
+
+        impl AstNode for $name {
+            fn cast(node: SyntaxNode) -> Option<Self> {
+                match node.kind() {
+                    SyntaxKind::$name => Some(Self(node)),
+                    _ => None,
+                }
+            }
+
+            fn syntax(&self) -> &SyntaxNode {
+                &self.0
+            }
+        }
+    };
+}
+
+ast_node!(Root);
+ast_node!(Literal);
+ast_node!(Name);
+ast_node!(Paren);
+ast_node!(Prefix);
+ast_node!(Binary);
+ast_node!(If);
+ast_node!(Block);
+ast_node!(Lambda);
+ast_node!(ParamList);
+ast_node!(Param);
+ast_node!(Type);
+ast_node!(Call);
+ast_node!(ArgList);
+ast_node!(Array);
+ast_node!(Index);
+ast_node!(Assign);
+ast_node!(Let);
+ast_node!(Loop);
+ast_node!(Break);
+ast_node!(Continue);
+ast_node!(Struct);
+ast_node!(FieldDecl);
+ast_node!(StructLit);
+ast_node!(FieldInit);
+ast_node!(Field);
+ast_node!(Enum);
+ast_node!(VariantDecl);
+ast_node!(VariantLit);
+ast_node!(Match);
+ast_node!(MatchArm);
+ast_node!(WildPat);
+ast_node!(LitPat);
+ast_node!(BindPat);
+ast_node!(VariantPat);
+ast_node!(Mod);
+ast_node!(Use);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Expr {
+    Literal(Literal),
+    Name(Name),
+    Paren(Paren),
+    Prefix(Prefix),
+    Binary(Binary),
+    If(If),
+    Lambda(Lambda),
+    Call(Call),
+    Array(Array),
+    Index(Index),
+    StructLit(StructLit),
+    Field(Field),
+    VariantLit(VariantLit),
+    Loop(Loop),
+    Match(Match),
+    /// The variants from here on are only found in statement position.
+    Assign(Assign),
+    Let(Let),
+    Break(Break),
+    Continue(Continue),
+}
+
+impl AstNode for Expr {
+    fn cast(node: SyntaxNode) -> Option<Self> {
+        let expr = match node.kind() {
+            SyntaxKind::Literal => Self::Literal(Literal(node)),
+            SyntaxKind::Name => Self::Name(Name(node)),
+            SyntaxKind::Paren => Self::Paren(Paren(node)),
+            SyntaxKind::Prefix => Self::Prefix(Prefix(node)),
+            SyntaxKind::Binary => Self::Binary(Binary(node)),
+            SyntaxKind::If => Self::If(If(node)),
+            SyntaxKind::Lambda => Self::Lambda(Lambda(node)),
+            SyntaxKind::Call => Self::Call(Call(node)),
+            SyntaxKind::Array => Self::Array(Array(node)),
+            SyntaxKind::Index => Self::Index(Index(node)),
+            SyntaxKind::StructLit => Self::StructLit(StructLit(node)),
+            SyntaxKind::Field => Self::Field(Field(node)),
+            SyntaxKind::VariantLit => Self::VariantLit(VariantLit(node)),
+            SyntaxKind::Assign => Self::Assign(Assign(node)),
+            SyntaxKind::Let => Self::Let(Let(node)),
+            SyntaxKind::Loop => Self::Loop(Loop(node)),
+            SyntaxKind::Break => Self::Break(Break(node)),
+            SyntaxKind::Continue => Self::Continue(Continue(node)),
+            SyntaxKind::Match => Self::Match(Match(node)),
+            _ => return None,
+        };
+        Some(expr)
+    }
+
+    fn syntax(&self) -> &SyntaxNode {
+        match self {
+            Self::Literal(e) => e.syntax(),
+            Self::Name(e) => e.syntax(),
+            Self::Paren(e) => e.syntax(),
+            Self::Prefix(e) => e.syntax(),
+            Self::Binary(e) => e.syntax(),
+            Self::If(e) => e.syntax(),
+            Self::Lambda(e) => e.syntax(),
+            Self::Call(e) => e.syntax(),
+            Self::Array(e) => e.syntax(),
+            Self::Index(e) => e.syntax(),
+            Self::StructLit(e) => e.syntax(),
+            Self::Field(e) => e.syntax(),
+            Self::VariantLit(e) => e.syntax(),
+            Self::Assign(e) => e.syntax(),
+            Self::Let(e) => e.syntax(),
+            Self::Loop(e) => e.syntax(),
+            Self::Break(e) => e.syntax(),
+            Self::Continue(e) => e.syntax(),
+            Self::Match(e) => e.syntax(),
+        }
+    }
+}
+
+impl Expr {
+    /// Allocates the expression in the tree the backends work on, or returns
+    /// `None` if any part of it is missing or malformed.
+    pub fn lower(&self, ast: &mut Ast) -> Option<ExprId> {
+        let expr = match self {
+            Self::Literal(e) => crate::Expr::Atom(e.atom(ast)?),
+            Self::Name(e) => crate::Expr::Atom(Atom::Id(e.symbol(ast)?)),
+            Self::Paren(e) => return e.expr()?.lower(ast),
+            Self::Prefix(e) => match e.negative_literal() {
+                Some(value) => crate::Expr::Atom(Atom::Int(value)),
+                None => crate::Expr::Unary(e.op()?, e.expr()?.lower(ast)?),
+            },
+            Self::Binary(e) => {
+                let lhs = e.lhs()?.lower(ast)?;
+                crate::Expr::Binary(e.op()?, lhs, e.rhs()?.lower(ast)?)
+            }
+            Self::If(e) => return e.lower(ast),
+            Self::Lambda(e) => {
+                let params = e
+                    .params()
+                    .map(|p| {
+                        let ty = match p.ty() {
+                            Some(ty) => Some(ty.lower(ast)?),
+                            None => None,
+                        };
+                        Some(crate::Param {
+                            name: p.symbol(ast)?,
+                            ty,
+                        })
+                    })
+                    .collect::<Option<_>>()?;
+                let ret = match e.ret() {
+                    Some(ret) => Some(ret.lower(ast)?),
+                    None => None,
+                };
+                let body = e.body()?.lower(ast)?;
+                let lambda = ast.alloc_lambda(crate::Lambda { params, ret, body });
+                crate::Expr::Lambda(lambda)
+            }
+            Self::Call(e) => {
+                let callee = e.callee()?.lower(ast)?;
+                let args = e.args().map(|arg| arg.lower(ast)).collect::<Option<_>>()?;
+                crate::Expr::Call(ast.alloc_call(crate::Call { callee, args }))
+            }
+            Self::Array(e) => {
+                let first = e.elems().next();
+                match e.length() {
+                    Some(len) => crate::Expr::Repeat(first?.lower(ast)?, len?),
+                    None => {
+                        let elems = e.elems().map(|e| e.lower(ast)).collect::<Option<_>>()?;
+                        crate::Expr::Array(ast.alloc_array(elems))
+                    }
+                }
+            }
+            Self::Index(e) => {
+                let base = e.base()?.lower(ast)?;
+                crate::Expr::Index(base, e.index()?.lower(ast)?)
+            }
+            Self::StructLit(e) => {
+                let fields = e
+                    .fields()
+                    .map(|f| Some((f.symbol(ast)?, f.value()?.lower(ast)?)))
+                    .collect::<Option<_>>()?;
+                let lit = crate::StructLit {
+                    name: e.symbol(ast)?,
+                    fields,
+                };
+                crate::Expr::StructLit(ast.alloc_struct_lit(lit))
+            }
+            Self::Field(e) => crate::Expr::Field(e.base()?.lower(ast)?, e.symbol(ast)?),
+            Self::VariantLit(e) => {
+                let args = e.args().map(|arg| arg.lower(ast)).collect::<Option<_>>()?;
+                let lit = crate::VariantLit {
+                    ty: e.ty(ast)?,
+                    variant: e.variant(ast)?,
+                    args,
+                };
+                crate::Expr::VariantLit(ast.alloc_variant_lit(lit))
+            }
+            Self::Assign(e) => {
+                let target = e.target()?;
+                if !target.is_place() {
+                    return None;
+                }
+                let target = target.lower(ast)?;
+                crate::Expr::Assign(target, e.value()?.lower(ast)?)
+            }
+            Self::Let(e) => crate::Expr::Let(e.symbol(ast)?, e.value()?.lower(ast)?),
+            Self::Loop(e) => {
+                let kind = match e.keyword()?.text() {
+                    "loop" => LoopKind::Loop,
+                    "while" => LoopKind::While(e.condition()?.lower(ast)?),
+                    "for" => {
+                        let from = e.from()?.lower(ast)?;
+                        LoopKind::For(e.symbol(ast)?, from, e.to()?.lower(ast)?)
+                    }
+                    _ => return None,
+                };
+                let body = e.body()?.lower(ast)?;
+                let l = crate::Loop {
+                    label: e.label(ast),
+                    kind,
+                    body,
+                };
+                crate::Expr::Loop(ast.alloc_loop(l))
+            }
+            Self::Break(e) => {
+                let value = match e.value() {
+                    Some(value) => Some(value.lower(ast)?),
+                    None => None,
+                };
+                crate::Expr::Break(e.label(ast), value)
+            }
+            Self::Continue(e) => crate::Expr::Continue(e.label(ast)),
+            Self::Match(e) => {
+                let scrutinee = e.scrutinee()?.lower(ast)?;
+                let arms = e.arms().map(|arm| arm.lower(ast)).collect::<Option<_>>()?;
+                crate::Expr::Match(ast.alloc_match(crate::Match { scrutinee, arms }))
+            }
+        };
+        Some(alloc(ast, expr, self.syntax()))
+    }
+}
+
+impl Expr {
+    /// Whether this is a name, possibly indexed or accessed one or more
+    /// times, the only kind of expression that can be assigned to.
+    pub fn is_place(&self) -> bool {
+        match self {
+            Self::Name(_) => true,
+            Self::Index(index) => index.base().is_some_and(|base| base.is_place()),
+            Self::Field(field) => field.base().is_some_and(|base| base.is_place()),
+            _ => false,
+        }
+    }
+}
+
+fn alloc(ast: &mut Ast, expr: crate::Expr, node: &SyntaxNode) -> ExprId {
+    let range = node.text_range();
+    ast.alloc_expr(expr, range.start().into()..range.end().into())
+}
+
+/// The first non-trivia token of `node`.
+fn first_token(node: &SyntaxNode) -> Option<SyntaxToken> {
+    node.children_with_tokens()
+        .filter_map(|e| e.into_token())
+        .find(|t| !t.kind().is_trivia())
+}
+
+fn children<N: AstNode>(node: &SyntaxNode) -> impl Iterator<Item = N> {
+    node.children().filter_map(N::cast)
+}
+
+impl Root {
+    pub fn exprs(&self) -> impl Iterator<Item = Expr> {
+        children(&self.0)
+    }
+
+    pub fn structs(&self) -> impl Iterator<Item = Struct> {
+        children(&self.0)
+    }
+
+    pub fn enums(&self) -> impl Iterator<Item = Enum> {
+        children(&self.0)
+    }
+
+    /// Lowers every top-level statement, or returns `None` if the tree
+    /// contains any error.
+    pub fn lower(&self) -> Option<Ast> {
+        self.lower_with(Interner::new())
+    }
+
+    /// Like [`Root::lower`], interning names into `names`, so that the
+    /// tree's symbols agree with those of other trees lowered into it.
+    pub fn lower_with(&self, names: Interner) -> Option<Ast> {
+        if self.0.descendants().any(|n| n.kind() == SyntaxKind::Error) {
+            return None;
+        }
+        let mut ast = Ast::with_names(names);
+        for node in self.0.children() {
+            let public = is_public(&node);
+            let stmt = match node.kind() {
+                SyntaxKind::Struct => Stmt::Struct(Struct(node).lower(&mut ast)?),
+                SyntaxKind::Enum => Stmt::Enum(Enum(node).lower(&mut ast)?),
+                SyntaxKind::Mod => Stmt::Mod(Mod(node).symbol(&mut ast)?),
+                SyntaxKind::Use => {
+                    let path = Use(node).path(&mut ast);
+                    Stmt::Use(ast.alloc_use(path))
+                }
+                _ => Stmt::Expr(Expr::cast(node)?.lower(&mut ast)?),
+            };
+            let stmt = ast.alloc_stmt(stmt);
+            if public {
+                ast.set_public(stmt);
+            }
+            ast.push(stmt);
+        }
+        Some(ast)
+    }
+}
+
+/// Whether a top level declaration follows a `pub`.
+fn is_public(node: &SyntaxNode) -> bool {
+    std::iter::successors(node.prev_sibling_or_token(), |e| e.prev_sibling_or_token())
+        .find(|e| !e.kind().is_trivia())
+        .is_some_and(|e| e.kind() == SyntaxKind::KeyWord && e.to_string() == "pub")
+}
+
+impl Literal {
+    pub fn token(&self) -> Option<SyntaxToken> {
+        first_token(&self.0)
+    }
+
+    pub fn value(&self) -> Option<i32> {
+        crate::int_value(self.token()?.text())
+    }
+
+    /// The integer or string the literal stands for, with a string's value
+    /// allocated in `ast`.
+    pub fn atom(&self, ast: &mut Ast) -> Option<Atom> {
+        let token = self.token()?;
+        match token.kind() {
+            SyntaxKind::String => {
+                let value = cb_lexer::unescape(token.text()).ok()?;
+                Some(Atom::Str(ast.alloc_str(&value)))
+            }
+            _ => Some(Atom::Int(crate::int_value(token.text())?)),
+        }
+    }
+}
+
+impl Name {
+    pub fn symbol(&self, ast: &mut Ast) -> Option<Symbol> {
+        Some(ast.intern(first_token(&self.0)?.text()))
+    }
+}
+
+impl Paren {
+    pub fn expr(&self) -> Option<Expr> {
+        children(&self.0).next()
+    }
+}
+
+impl Prefix {
+    pub fn op(&self) -> Option<Op> {
+        Op::try_from(first_token(&self.0)?.text()).ok()
+    }
+
+    pub fn expr(&self) -> Option<Expr> {
+        children(&self.0).next()
+    }
+
+    /// The value of a `-` applied to an integer literal that only fits in an
+    /// `i32` once negated, which is `2147483648` for `i32::MIN`.
+    pub fn negative_literal(&self) -> Option<i32> {
+        let Some(Expr::Literal(literal)) = self.expr() else {
+            return None;
+        };
+        let token = literal.token().filter(|t| t.kind() == SyntaxKind::Int)?;
+        match crate::int_value(token.text()) {
+            Some(_) => None,
+            None if self.op()? == Op::Minus => crate::int_value(&format!("-{}", token.text())),
+            None => None,
+        }
+    }
+}
+
+impl Binary {
+    pub fn op(&self) -> Option<Op> {
+        Op::try_from(first_token(&self.0)?.text()).ok()
+    }
+
+    pub fn lhs(&self) -> Option<Expr> {
+        children(&self.0).next()
+    }
+
+    pub fn rhs(&self) -> Option<Expr> {
+        children(&self.0).nth(1)
+    }
+}
+
+/// What follows `else`: another `if` or a block.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Else {
+    If(If),
+    Block(Block),
+}
+
+impl If {
+    pub fn condition(&self) -> Option<Expr> {
+        children(&self.0).next()
+    }
+
+    pub fn then_branch(&self) -> Option<Block> {
+        children(&self.0).next()
+    }
+
+    pub fn else_branch(&self) -> Option<Else> {
+        let mut nodes = self.0.children();
+        nodes.find(|n| n.kind() == SyntaxKind::Block)?;
+        nodes.find_map(|n| match n.kind() {
+            SyntaxKind::If => Some(Else::If(If(n))),
+            SyntaxKind::Block => Some(Else::Block(Block(n))),
+            _ => None,
+        })
+    }
+
+    fn lower(&self, ast: &mut Ast) -> Option<ExprId> {
+        let condition = self.condition()?.lower(ast)?;
+        let then = self.then_branch()?.lower(ast)?;
+        let expr = match self.else_branch() {
+            None => crate::Expr::If(condition, then),
+            Some(Else::If(e)) => crate::Expr::IfElse(condition, then, e.lower(ast)?),
+            Some(Else::Block(e)) => crate::Expr::IfElse(condition, then, e.lower(ast)?),
+        };
+        Some(alloc(ast, expr, &self.0))
+    }
+}
+
+impl Block {
+    pub fn stmts(&self) -> impl Iterator<Item = Expr> {
+        children(&self.0)
+    }
+
+    /// Lowers the statements: a single statement stands for the block,
+    /// unless it declares a variable.
+    pub fn lower(&self, ast: &mut Ast) -> Option<ExprId> {
+        let stmts: Vec<_> = self.stmts().collect();
+        if let [stmt] = &stmts[..] {
+            if !matches!(stmt, Expr::Let(_)) {
+                return stmt.lower(ast);
+            }
+        }
+        let stmts = stmts.iter().map(|s| s.lower(ast)).collect::<Option<_>>()?;
+        let block = ast.alloc_block(stmts);
+        Some(alloc(ast, crate::Expr::Block(block), &self.0))
+    }
+}
+
+impl Lambda {
+    pub fn params(&self) -> impl Iterator<Item = Param> {
+        children::<ParamList>(&self.0).flat_map(|list| children(&list.0).collect::<Vec<_>>())
+    }
+
+    /// The declared return type, which is the only type directly inside.
+    pub fn ret(&self) -> Option<Type> {
+        children(&self.0).next()
+    }
+
+    pub fn body(&self) -> Option<Block> {
+        children(&self.0).next()
+    }
+}
+
+impl Param {
+    pub fn symbol(&self, ast: &mut Ast) -> Option<Symbol> {
+        let token = first_token(&self.0).filter(|t| t.kind() == SyntaxKind::Id)?;
+        Some(ast.intern(token.text()))
+    }
+
+    pub fn ty(&self) -> Option<Type> {
+        children(&self.0).next()
+    }
+}
+
+impl Type {
+    /// The parameter types of a function type, which come before its `->`.
+    pub fn params(&self) -> impl Iterator<Item = Type> {
+        let arrow = self.arrow();
+        children::<Type>(&self.0)
+            .filter(move |t| arrow.is_some_and(|a| t.0.text_range().end() <= a))
+    }
+
+    /// The return type of a function type, after its `->`.
+    pub fn ret(&self) -> Option<Type> {
+        let arrow = self.arrow()?;
+        children::<Type>(&self.0).find(|t| t.0.text_range().start() >= arrow)
+    }
+
+    /// Whether `name` is declared as an enum before this type, which the
+    /// parser only lets it name if it is declared as a struct or an enum.
+    fn names_enum(&self, name: &str) -> bool {
+        let start = self.0.text_range().start();
+        let Some(root) = self.0.ancestors().last().and_then(Root::cast) else {
+            return false;
+        };
+        root.enums()
+            .any(|e| e.0.text_range().end() <= start && id(&e.0).is_some_and(|t| t.text() == name))
+    }
+
+    fn arrow(&self) -> Option<rowan::TextSize> {
+        self.0
+            .children_with_tokens()
+            .filter_map(|e| e.into_token())
+            .find(|t| t.text() == "->")
+            .map(|t| t.text_range().start())
+    }
+
+    /// The type, with the names of structs and enums interned in `ast`.
+    pub fn lower(&self, ast: &mut Ast) -> Option<TypeExpr> {
+        let token = first_token(&self.0)?;
+        match token.text() {
+            "i32" => Some(TypeExpr::Int),
+            "String" => Some(TypeExpr::String),
+            name if token.kind() == SyntaxKind::Id => match self.names_enum(name) {
+                true => Some(TypeExpr::Enum(ast.intern(name))),
+                false => Some(TypeExpr::Struct(ast.intern(name))),
+            },
+            "fn" => {
+                let params = self.params().map(|t| t.lower(ast)).collect::<Option<_>>()?;
+                Some(TypeExpr::Fn(params, Box::new(self.ret()?.lower(ast)?)))
+            }
+            "[" => {
+                let elem = children::<Type>(&self.0).next()?.lower(ast)?;
+                let len = int_token(&self.0)?;
+                Some(TypeExpr::Array(Box::new(elem), len))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The value of the integer token directly inside `node`, used as a length.
+fn int_token(node: &SyntaxNode) -> Option<u32> {
+    let token = node
+        .children_with_tokens()
+        .filter_map(|e| e.into_token())
+        .find(|t| t.kind() == SyntaxKind::Int)?;
+    crate::int_value(token.text()).map(|len| len as u32)
+}
+
+impl Call {
+    pub fn callee(&self) -> Option<Expr> {
+        children(&self.0).next()
+    }
+
+    pub fn args(&self) -> impl Iterator<Item = Expr> {
+        children::<ArgList>(&self.0).flat_map(|list| children(&list.0).collect::<Vec<_>>())
+    }
+}
+
+impl Array {
+    pub fn elems(&self) -> impl Iterator<Item = Expr> {
+        children(&self.0)
+    }
+
+    /// For `[elem; len]`, the length, which is `Some(None)` if it is missing
+    /// or too large. List literals have no length of their own.
+    pub fn length(&self) -> Option<Option<u32>> {
+        let repeat = self
+            .0
+            .children_with_tokens()
+            .filter_map(|e| e.into_token())
+            .any(|t| t.text() == ";");
+        repeat.then(|| int_token(&self.0))
+    }
+}
+
+impl Index {
+    pub fn base(&self) -> Option<Expr> {
+        children(&self.0).next()
+    }
+
+    pub fn index(&self) -> Option<Expr> {
+        children(&self.0).nth(1)
+    }
+}
+
+/// The text of the label token directly inside `node`, quote included.
+fn label(node: &SyntaxNode, ast: &mut Ast) -> Option<Symbol> {
+    let token = node
+        .children_with_tokens()
+        .filter_map(|e| e.into_token())
+        .find(|t| t.kind() == SyntaxKind::Label)?;
+    Some(ast.intern(token.text()))
+}
+
+/// The first name token directly inside `node`.
+fn id(node: &SyntaxNode) -> Option<SyntaxToken> {
+    node.children_with_tokens()
+        .filter_map(|e| e.into_token())
+        .find(|t| t.kind() == SyntaxKind::Id)
+}
+
+/// The first name directly inside `node`, interned in `ast`.
+fn id_token(node: &SyntaxNode, ast: &mut Ast) -> Option<Symbol> {
+    Some(ast.intern(id(node)?.text()))
+}
+
+impl Assign {
+    pub fn target(&self) -> Option<Expr> {
+        children(&self.0).next()
+    }
+
+    pub fn value(&self) -> Option<Expr> {
+        children(&self.0).nth(1)
+    }
+}
+
+impl Let {
+    pub fn symbol(&self, ast: &mut Ast) -> Option<Symbol> {
+        id_token(&self.0, ast)
+    }
+
+    pub fn value(&self) -> Option<Expr> {
+        children(&self.0).next()
+    }
+}
+
+impl Loop {
+    pub fn label(&self, ast: &mut Ast) -> Option<Symbol> {
+        label(&self.0, ast)
+    }
+
+    /// The `loop`, `while` or `for` keyword.
+    pub fn keyword(&self) -> Option<SyntaxToken> {
+        self.0
+            .children_with_tokens()
+            .filter_map(|e| e.into_token())
+            .find(|t| t.kind() == SyntaxKind::KeyWord)
+    }
+
+    /// The condition of a `while` loop.
+    pub fn condition(&self) -> Option<Expr> {
+        children(&self.0).next()
+    }
+
+    /// The variable of a `for` loop.
+    pub fn symbol(&self, ast: &mut Ast) -> Option<Symbol> {
+        id_token(&self.0, ast)
+    }
+
+    /// The start of a `for` loop's range.
+    pub fn from(&self) -> Option<Expr> {
+        children(&self.0).next()
+    }
+
+    /// The end of a `for` loop's range, which it stops before.
+    pub fn to(&self) -> Option<Expr> {
+        children(&self.0).nth(1)
+    }
+
+    pub fn body(&self) -> Option<Block> {
+        children(&self.0).next()
+    }
+}
+
+impl Break {
+    pub fn label(&self, ast: &mut Ast) -> Option<Symbol> {
+        label(&self.0, ast)
+    }
+
+    pub fn value(&self) -> Option<Expr> {
+        children(&self.0).next()
+    }
+}
+
+impl Continue {
+    pub fn label(&self, ast: &mut Ast) -> Option<Symbol> {
+        label(&self.0, ast)
+    }
+}
+
+impl Struct {
+    pub fn symbol(&self, ast: &mut Ast) -> Option<Symbol> {
+        id_token(&self.0, ast)
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = FieldDecl> {
+        children(&self.0)
+    }
+
+    pub fn lower(&self, ast: &mut Ast) -> Option<StructId> {
+        let fields = self
+            .fields()
+            .map(|f| {
+                Some(crate::Field {
+                    name: f.symbol(ast)?,
+                    ty: f.ty()?.lower(ast)?,
+                })
+            })
+            .collect::<Option<_>>()?;
+        let decl = crate::Struct {
+            name: self.symbol(ast)?,
+            fields,
+        };
+        Some(ast.alloc_struct(decl))
+    }
+}
+
+impl FieldDecl {
+    pub fn symbol(&self, ast: &mut Ast) -> Option<Symbol> {
+        id_token(&self.0, ast)
+    }
+
+    pub fn ty(&self) -> Option<Type> {
+        children(&self.0).next()
+    }
+}
+
+impl StructLit {
+    /// The name of the struct, which comes first.
+    pub fn symbol(&self, ast: &mut Ast) -> Option<Symbol> {
+        id_token(&self.0, ast)
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = FieldInit> {
+        children(&self.0)
+    }
+}
+
+impl FieldInit {
+    pub fn symbol(&self, ast: &mut Ast) -> Option<Symbol> {
+        id_token(&self.0, ast)
+    }
+
+    pub fn value(&self) -> Option<Expr> {
+        children(&self.0).next()
+    }
+}
+
+impl Field {
+    pub fn base(&self) -> Option<Expr> {
+        children(&self.0).next()
+    }
+
+    /// The name of the field, after the `.`.
+    pub fn symbol(&self, ast: &mut Ast) -> Option<Symbol> {
+        id_token(&self.0, ast)
+    }
+}
+
+impl Enum {
+    pub fn symbol(&self, ast: &mut Ast) -> Option<Symbol> {
+        id_token(&self.0, ast)
+    }
+
+    pub fn variants(&self) -> impl Iterator<Item = VariantDecl> {
+        children(&self.0)
+    }
+
+    pub fn lower(&self, ast: &mut Ast) -> Option<EnumId> {
+        let variants = self
+            .variants()
+            .map(|v| {
+                let fields = v.fields().map(|t| t.lower(ast)).collect::<Option<_>>()?;
+                Some(crate::Variant {
+                    name: v.symbol(ast)?,
+                    fields,
+                })
+            })
+            .collect::<Option<_>>()?;
+        let decl = crate::Enum {
+            name: self.symbol(ast)?,
+            variants,
+        };
+        Some(ast.alloc_enum(decl))
+    }
+}
+
+impl VariantDecl {
+    pub fn symbol(&self, ast: &mut Ast) -> Option<Symbol> {
+        id_token(&self.0, ast)
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = Type> {
+        children(&self.0)
+    }
+}
+
+/// The name tokens directly inside `node`.
+fn id_tokens(node: &SyntaxNode, ast: &mut Ast) -> Vec<Symbol> {
+    node.children_with_tokens()
+        .filter_map(|e| e.into_token())
+        .filter(|t| t.kind() == SyntaxKind::Id)
+        .map(|t| ast.intern(t.text()))
+        .collect()
+}
+
+impl VariantLit {
+    /// The name of the enum, before the `::`.
+    pub fn ty(&self, ast: &mut Ast) -> Option<Symbol> {
+        id_tokens(&self.0, ast).first().copied()
+    }
+
+    pub fn variant(&self, ast: &mut Ast) -> Option<Symbol> {
+        id_tokens(&self.0, ast).get(1).copied()
+    }
+
+    pub fn args(&self) -> impl Iterator<Item = Expr> {
+        children::<ArgList>(&self.0).flat_map(|list| children(&list.0).collect::<Vec<_>>())
+    }
+}
+
+impl Match {
+    pub fn scrutinee(&self) -> Option<Expr> {
+        children(&self.0).next()
+    }
+
+    pub fn arms(&self) -> impl Iterator<Item = MatchArm> {
+        children(&self.0)
+    }
+}
+
+/// The body of an arm: a block, or a statement on its own.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ArmBody {
+    Block(Block),
+    Expr(Expr),
+}
+
+impl MatchArm {
+    pub fn pat(&self) -> Option<Pat> {
+        children(&self.0).next()
+    }
+
+    /// The condition after `if`, which comes before the `=>`.
+    pub fn guard(&self) -> Option<Expr> {
+        let arrow = self.arrow()?;
+        children::<Expr>(&self.0).find(|e| e.syntax().text_range().end() <= arrow)
+    }
+
+    pub fn body(&self) -> Option<ArmBody> {
+        let arrow = self.arrow()?;
+        self.0
+            .children()
+            .filter(|n| n.text_range().start() >= arrow)
+            .find_map(|n| match n.kind() {
+                SyntaxKind::Block => Some(ArmBody::Block(Block(n))),
+                _ => Expr::cast(n).map(ArmBody::Expr),
+            })
+    }
+
+    fn arrow(&self) -> Option<rowan::TextSize> {
+        self.0
+            .children_with_tokens()
+            .filter_map(|e| e.into_token())
+            .find(|t| t.text() == "=>")
+            .map(|t| t.text_range().start())
+    }
+
+    fn lower(&self, ast: &mut Ast) -> Option<crate::Arm> {
+        let pat = self.pat()?.lower(ast)?;
+        let guard = match self.guard() {
+            Some(guard) => Some(guard.lower(ast)?),
+            None => None,
+        };
+        let body = match self.body()? {
+            ArmBody::Block(b) => b.lower(ast)?,
+            ArmBody::Expr(e) => e.lower(ast)?,
+        };
+        Some(crate::Arm { pat, guard, body })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Pat {
+    Wild(WildPat),
+    Lit(LitPat),
+    Bind(BindPat),
+    Variant(VariantPat),
+}
+
+impl AstNode for Pat {
+    fn cast(node: SyntaxNode) -> Option<Self> {
+        let pat = match node.kind() {
+            SyntaxKind::WildPat => Self::Wild(WildPat(node)),
+            SyntaxKind::LitPat => Self::Lit(LitPat(node)),
+            SyntaxKind::BindPat => Self::Bind(BindPat(node)),
+            SyntaxKind::VariantPat => Self::Variant(VariantPat(node)),
+            _ => return None,
+        };
+        Some(pat)
+    }
+
+    fn syntax(&self) -> &SyntaxNode {
+        match self {
+            Self::Wild(p) => p.syntax(),
+            Self::Lit(p) => p.syntax(),
+            Self::Bind(p) => p.syntax(),
+            Self::Variant(p) => p.syntax(),
+        }
+    }
+}
+
+impl Pat {
+    /// Allocates the pattern after the patterns inside it.
+    pub fn lower(&self, ast: &mut Ast) -> Option<PatId> {
+        let pat = match self {
+            Self::Wild(_) => crate::Pat::Wild,
+            Self::Lit(p) => crate::Pat::Int(p.value()?),
+            Self::Bind(p) => crate::Pat::Bind(id_token(&p.0, ast)?),
+            Self::Variant(p) => {
+                let args = p.args().map(|arg| arg.lower(ast)).collect::<Option<_>>()?;
+                crate::Pat::Variant(p.ty(ast)?, p.variant(ast)?, args)
+            }
+        };
+        let range = self.syntax().text_range();
+        Some(ast.alloc_pat(pat, range.start().into()..range.end().into()))
+    }
+}
+
+impl LitPat {
+    /// The value, negated if the pattern starts with `-`.
+    pub fn value(&self) -> Option<i32> {
+        let digits = int_token_text(&self.0)?;
+        match first_token(&self.0)?.text() {
+            "-" => crate::int_value(&format!("-{digits}")),
+            _ => crate::int_value(&digits),
+        }
+    }
+}
+
+fn int_token_text(node: &SyntaxNode) -> Option<String> {
+    let token = node
+        .children_with_tokens()
+        .filter_map(|e| e.into_token())
+        .find(|t| t.kind() == SyntaxKind::Int)?;
+    Some(token.text().to_string())
+}
+
+impl VariantPat {
+    /// The name of the enum, before the `::`.
+    pub fn ty(&self, ast: &mut Ast) -> Option<Symbol> {
+        id_tokens(&self.0, ast).first().copied()
+    }
+
+    pub fn variant(&self, ast: &mut Ast) -> Option<Symbol> {
+        id_tokens(&self.0, ast).get(1).copied()
+    }
+
+    pub fn args(&self) -> impl Iterator<Item = Pat> {
+        children(&self.0)
+    }
+}
+
+impl Mod {
+    pub fn symbol(&self, ast: &mut Ast) -> Option<Symbol> {
+        id_token(&self.0, ast)
+    }
+}
+
+impl Use {
+    /// The modules and then the name of the item.
+    pub fn path(&self, ast: &mut Ast) -> Vec<Symbol> {
+        id_tokens(&self.0, ast)
+    }
+}
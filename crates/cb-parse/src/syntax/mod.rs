@@ -0,0 +1,228 @@
+//! A lossless concrete syntax tree for C Flat.
+//!
+//! Every byte of the source, including whitespace, comments and anything
+//! that fails to parse, ends up in the tree, so `parse(src).syntax()` always
+//! prints back as `src`. The typed nodes in [`ast`] are views over the tree
+//! and can be lowered to the [`Ast`] the backends consume, which is how
+//! [`crate::parse`] builds one.
+
+pub mod ast;
+mod parser;
+#[cfg(test)]
+mod test;
+
+use crate::syntax::ast::AstNode;
+use crate::{Ast, Interner};
+use cb_lexer::Span;
+use rowan::GreenNode;
+use std::fmt;
+
+pub use crate::syntax::parser::parse;
+
+/// The kinds of tokens and nodes in the tree. Tokens mirror
+/// [`cb_lexer::TokenKind`] with keywords and operators grouped, nodes the
+/// expressions of the grammar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u16)]
+pub enum SyntaxKind {
+    Whitespace,
+    Comment,
+    Int,
+    Float,
+    String,
+    Char,
+    Id,
+    /// A loop label such as `'outer`.
+    Label,
+    KeyWord,
+    Op,
+    /// An unknown token, or a node wrapping tokens the parser had to skip.
+    Error,
+    Root,
+    Literal,
+    Name,
+    Paren,
+    Prefix,
+    Binary,
+    If,
+    Block,
+    Lambda,
+    ParamList,
+    Param,
+    Type,
+    Call,
+    ArgList,
+    /// An array literal, either a list of elements or `[elem; len]`.
+    Array,
+    Index,
+    Assign,
+    Let,
+    /// A `loop`, `while` or `for` loop, along with its label.
+    Loop,
+    Break,
+    Continue,
+    /// A struct declaration, `struct Name { field: T, ... }`.
+    Struct,
+    FieldDecl,
+    /// A struct literal, `Name { field: value, ... }`.
+    StructLit,
+    FieldInit,
+    /// A field access, `value.field`.
+    Field,
+    /// An enum declaration, `enum Name { Variant(T, ...), ... }`.
+    Enum,
+    VariantDecl,
+    /// A variant of an enum, `Name::Variant(value, ...)`.
+    VariantLit,
+    Match,
+    /// `pattern if guard => body` in a `match`.
+    MatchArm,
+    /// The `_` pattern.
+    WildPat,
+    /// An integer pattern, possibly negative.
+    LitPat,
+    /// A name bound by a pattern.
+    BindPat,
+    /// `Name::Variant(pattern, ...)` in a pattern.
+    VariantPat,
+    /// `mod name;`. Like every top level declaration, it may follow a
+    /// `pub` token.
+    Mod,
+    /// `use module::name;`.
+    Use,
+}
+
+impl SyntaxKind {
+    const ALL: [Self; 48] = [
+        Self::Whitespace,
+        Self::Comment,
+        Self::Int,
+        Self::Float,
+        Self::String,
+        Self::Char,
+        Self::Id,
+        Self::Label,
+        Self::KeyWord,
+        Self::Op,
+        Self::Error,
+        Self::Root,
+        Self::Literal,
+        Self::Name,
+        Self::Paren,
+        Self::Prefix,
+        Self::Binary,
+        Self::If,
+        Self::Block,
+        Self::Lambda,
+        Self::ParamList,
+        Self::Param,
+        Self::Type,
+        Self::Call,
+        Self::ArgList,
+        Self::Array,
+        Self::Index,
+        Self::Assign,
+        Self::Let,
+        Self::Loop,
+        Self::Break,
+        Self::Continue,
+        Self::Struct,
+        Self::FieldDecl,
+        Self::StructLit,
+        Self::FieldInit,
+        Self::Field,
+        Self::Enum,
+        Self::VariantDecl,
+        Self::VariantLit,
+        Self::Match,
+        Self::MatchArm,
+        Self::WildPat,
+        Self::LitPat,
+        Self::BindPat,
+        Self::VariantPat,
+        Self::Mod,
+        Self::Use,
+    ];
+
+    pub fn is_trivia(self) -> bool {
+        matches!(self, Self::Whitespace | Self::Comment)
+    }
+}
+
+impl From<SyntaxKind> for rowan::SyntaxKind {
+    fn from(kind: SyntaxKind) -> Self {
+        Self(kind as u16)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CFlat {}
+
+impl rowan::Language for CFlat {
+    type Kind = SyntaxKind;
+
+    fn kind_from_raw(raw: rowan::SyntaxKind) -> SyntaxKind {
+        SyntaxKind::ALL[raw.0 as usize]
+    }
+
+    fn kind_to_raw(kind: SyntaxKind) -> rowan::SyntaxKind {
+        kind.into()
+    }
+}
+
+pub type SyntaxNode = rowan::SyntaxNode<CFlat>;
+pub type SyntaxToken = rowan::SyntaxToken<CFlat>;
+pub type SyntaxElement = rowan::SyntaxElement<CFlat>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+    pub span: Span,
+    pub message: String,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{} {}", self.span.start, self.span.end, self.message)
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+/// The result of parsing: a tree that always exists, plus whatever went
+/// wrong while building it.
+#[derive(Debug, Clone)]
+pub struct Parse {
+    green: GreenNode,
+    errors: Vec<SyntaxError>,
+}
+
+impl Parse {
+    pub fn syntax(&self) -> SyntaxNode {
+        SyntaxNode::new_root(self.green.clone())
+    }
+
+    pub fn root(&self) -> ast::Root {
+        ast::Root::cast(self.syntax()).expect("the parser always builds a root")
+    }
+
+    pub fn errors(&self) -> &[SyntaxError] {
+        &self.errors
+    }
+
+    /// Lowers the tree to an [`Ast`], which needs a tree without errors, or
+    /// returns the first error.
+    pub fn lower(&self) -> Result<Ast, SyntaxError> {
+        self.lower_with(Interner::new())
+    }
+
+    /// Like [`Parse::lower`], interning names into `names`.
+    pub fn lower_with(&self, names: Interner) -> Result<Ast, SyntaxError> {
+        match self.errors.first() {
+            Some(e) => Err(e.clone()),
+            None => Ok(self
+                .root()
+                .lower_with(names)
+                .expect("a tree without errors lowers")),
+        }
+    }
+}
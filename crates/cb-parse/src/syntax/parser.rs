@@ -0,0 +1,819 @@
+use crate::syntax::{Parse, SyntaxError, SyntaxKind};
+use crate::{int_value, starts_expression, Op, Precedence};
+use cb_lexer::{Scanner, Span, Token, TokenKind};
+use rowan::{Checkpoint, GreenNodeBuilder};
+
+/// Parses `src` into a concrete syntax tree. This never fails: tokens that
+/// do not fit the grammar are wrapped in [`SyntaxKind::Error`] nodes and
+/// reported in [`Parse::errors`].
+pub fn parse(src: &str) -> Parse {
+    let tokens = Scanner::with_trivia(src)
+        .take_while(|t| !t.is_eof())
+        .collect();
+    let mut parser = Parser {
+        src,
+        tokens,
+        position: 0,
+        builder: GreenNodeBuilder::new(),
+        errors: vec![],
+        loops: vec![],
+        structs: vec![],
+        enums: vec![],
+        imports: vec![],
+        modules: vec![],
+    };
+    parser.root();
+    Parse {
+        green: parser.builder.finish(),
+        errors: parser.errors,
+    }
+}
+
+fn syntax_kind(kind: TokenKind) -> SyntaxKind {
+    match kind {
+        TokenKind::Id => SyntaxKind::Id,
+        TokenKind::Label => SyntaxKind::Label,
+        TokenKind::Int => SyntaxKind::Int,
+        TokenKind::Float => SyntaxKind::Float,
+        TokenKind::String => SyntaxKind::String,
+        TokenKind::Char => SyntaxKind::Char,
+        TokenKind::Whitespace => SyntaxKind::Whitespace,
+        TokenKind::Comment => SyntaxKind::Comment,
+        TokenKind::Error | TokenKind::Eof => SyntaxKind::Error,
+        k if k.is_keyword() => SyntaxKind::KeyWord,
+        _ => SyntaxKind::Op,
+    }
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    tokens: Vec<Token>,
+    position: usize,
+    builder: GreenNodeBuilder<'static>,
+    errors: Vec<SyntaxError>,
+    /// The label and keyword of each loop around the next token, innermost
+    /// last, to check `break` and `continue`.
+    loops: Vec<(Option<String>, TokenKind)>,
+    /// The names of the structs declared so far, which are the names that
+    /// can start a struct literal or be used as a type.
+    structs: Vec<String>,
+    /// The names of the enums declared so far, which can be used as types.
+    enums: Vec<String>,
+    /// The names brought in by `use`, which can also be used as types and
+    /// start struct literals.
+    imports: Vec<String>,
+    /// The modules declared so far.
+    modules: Vec<String>,
+}
+
+impl Parser<'_> {
+    /// The index of the next meaningful token.
+    fn lookahead(&self) -> Option<usize> {
+        (self.position..self.tokens.len()).find(|&i| !self.tokens[i].kind.is_trivia())
+    }
+
+    fn peek(&self) -> Option<TokenKind> {
+        self.lookahead().map(|i| self.tokens[i].kind)
+    }
+
+    /// The kind of the meaningful token after the next one.
+    fn peek_second(&self) -> Option<TokenKind> {
+        let next = self.lookahead()?;
+        let second = (next + 1..self.tokens.len()).find(|&i| !self.tokens[i].kind.is_trivia());
+        second.map(|i| self.tokens[i].kind)
+    }
+
+    fn peek_text(&self) -> &str {
+        self.lookahead()
+            .map_or("", |i| self.tokens[i].text(self.src))
+    }
+
+    /// Adds the trivia in front of the next token to the current node. This
+    /// only happens right before a token or node is added, so trailing trivia
+    /// ends up in the parent of the node it follows.
+    fn trivia(&mut self) {
+        while self
+            .tokens
+            .get(self.position)
+            .is_some_and(|t| t.kind.is_trivia())
+        {
+            self.push_token();
+        }
+    }
+
+    fn start_node(&mut self, kind: SyntaxKind) {
+        self.trivia();
+        self.builder.start_node(kind.into());
+    }
+
+    fn checkpoint(&mut self) -> Checkpoint {
+        self.trivia();
+        self.builder.checkpoint()
+    }
+
+    fn at(&self, kind: TokenKind) -> bool {
+        self.peek() == Some(kind)
+    }
+
+    /// Braces and closing brackets are left for the enclosing block or
+    /// bracket to recover at, rather than skipped as part of an operand.
+    fn at_delimiter(&self) -> bool {
+        matches!(
+            self.peek(),
+            Some(TokenKind::LBrace | TokenKind::RBrace | TokenKind::RParen | TokenKind::RBracket)
+        )
+    }
+
+    /// Whether the next token follows the previous one with nothing between.
+    fn at_adjacent(&self, kind: TokenKind) -> bool {
+        self.tokens
+            .get(self.position)
+            .is_some_and(|t| t.kind == kind)
+    }
+
+    fn bump(&mut self) {
+        self.trivia();
+        self.push_token();
+    }
+
+    fn push_token(&mut self) {
+        let token = &self.tokens[self.position];
+        let kind = syntax_kind(token.kind).into();
+        self.builder.token(kind, token.text(self.src));
+        self.position += 1;
+    }
+
+    fn error(&mut self, message: String) {
+        let span = self.next_span();
+        self.errors.push(SyntaxError { span, message });
+    }
+
+    fn next_span(&self) -> Span {
+        match self.lookahead() {
+            Some(i) => self.tokens[i].span.clone(),
+            None => self.src.len()..self.src.len(),
+        }
+    }
+
+    /// Reports the next token as unexpected and skips over it.
+    fn skip(&mut self) {
+        let message = match self.peek() {
+            Some(TokenKind::Error) => format!("unknown token '{}'", self.peek_text()),
+            Some(_) => format!("unexpected '{}'", self.peek_text()),
+            None => return,
+        };
+        self.error(message);
+        self.start_node(SyntaxKind::Error);
+        self.bump();
+        self.builder.finish_node();
+    }
+
+    /// Expects an integer literal that fits in an `i32`, negated if its `-`
+    /// starts at `minus`.
+    fn int(&mut self, minus: Option<usize>) {
+        if let Some(i) = self.lookahead().filter(|_| self.at(TokenKind::Int)) {
+            let span = self.tokens[i].span.clone();
+            let digits = &self.src[span.clone()];
+            let value = match minus {
+                Some(_) => int_value(&format!("-{digits}")),
+                None => int_value(digits),
+            };
+            if value.is_none() {
+                let span = minus.unwrap_or(span.start)..span.end;
+                let message = format!("integer literal '{}' is too large", &self.src[span.clone()]);
+                self.errors.push(SyntaxError { span, message });
+            }
+        }
+        self.expect(TokenKind::Int);
+    }
+
+    /// Whether the next token is an integer literal that only fits in an
+    /// `i32` negated, after a `-`.
+    fn at_negated_literal(&self) -> bool {
+        let digits = self.peek_text();
+        self.at(TokenKind::Int)
+            && int_value(digits).is_none()
+            && int_value(&format!("-{digits}")).is_some()
+    }
+
+    fn expect(&mut self, kind: TokenKind) {
+        if self.at(kind) {
+            return self.bump();
+        }
+        let message = match self.peek() {
+            Some(_) => format!("expected '{kind}' but found '{}'", self.peek_text()),
+            None => format!("expected '{kind}' but found end of input"),
+        };
+        self.error(message);
+    }
+
+    fn root(&mut self) {
+        self.builder.start_node(SyntaxKind::Root.into());
+        while self.peek().is_some() {
+            match self.peek() {
+                _ if self.at_delimiter() => self.skip(),
+                // `pub` stays outside the declaration after it, so that the
+                // span of a public `let` starts at the `let`.
+                Some(TokenKind::Pub) => match self.peek_second() {
+                    Some(
+                        TokenKind::Struct
+                        | TokenKind::Enum
+                        | TokenKind::Mod
+                        | TokenKind::Use
+                        | TokenKind::Let,
+                    ) => self.bump(),
+                    _ => self.skip(),
+                },
+                Some(TokenKind::Struct) => self.struct_decl(),
+                Some(TokenKind::Enum) => self.enum_decl(),
+                Some(TokenKind::Mod) => self.mod_decl(),
+                Some(TokenKind::Use) => self.use_decl(),
+                _ => self.statement(),
+            }
+        }
+        self.trivia();
+        self.builder.finish_node();
+    }
+
+    fn struct_decl(&mut self) {
+        self.start_node(SyntaxKind::Struct);
+        self.bump();
+        let name = self.peek_text().to_string();
+        self.unique_name(&self.types());
+        let mut names = vec![];
+        self.list(TokenKind::LBrace, TokenKind::RBrace, |p| {
+            p.start_node(SyntaxKind::FieldDecl);
+            names.push(p.peek_text().to_string());
+            p.unique_name(&names[..names.len() - 1]);
+            p.expect(TokenKind::Colon);
+            p.type_expr();
+            p.builder.finish_node();
+        });
+        self.structs.push(name);
+        self.builder.finish_node();
+    }
+
+    fn enum_decl(&mut self) {
+        self.start_node(SyntaxKind::Enum);
+        self.bump();
+        let name = self.peek_text().to_string();
+        self.unique_name(&self.types());
+        let mut names = vec![];
+        self.list(TokenKind::LBrace, TokenKind::RBrace, |p| {
+            p.start_node(SyntaxKind::VariantDecl);
+            names.push(p.peek_text().to_string());
+            p.unique_name(&names[..names.len() - 1]);
+            if p.at(TokenKind::LParen) {
+                p.list(TokenKind::LParen, TokenKind::RParen, Self::type_expr);
+            }
+            p.builder.finish_node();
+        });
+        self.enums.push(name);
+        self.builder.finish_node();
+    }
+
+    fn mod_decl(&mut self) {
+        self.start_node(SyntaxKind::Mod);
+        self.bump();
+        let name = self.peek_text().to_string();
+        self.unique_name(&self.modules.clone());
+        self.expect(TokenKind::Semicolon);
+        self.modules.push(name);
+        self.builder.finish_node();
+    }
+
+    fn use_decl(&mut self) {
+        self.start_node(SyntaxKind::Use);
+        self.bump();
+        self.expect(TokenKind::Id);
+        self.expect(TokenKind::ColonColon);
+        let mut name = self.peek_text().to_string();
+        self.expect(TokenKind::Id);
+        while self.at(TokenKind::ColonColon) {
+            self.bump();
+            name = self.peek_text().to_string();
+            self.expect(TokenKind::Id);
+        }
+        self.expect(TokenKind::Semicolon);
+        self.imports.push(name);
+        self.builder.finish_node();
+    }
+
+    /// The names of the structs and enums declared so far.
+    fn types(&self) -> Vec<String> {
+        [&self.structs[..], &self.enums[..]].concat()
+    }
+
+    /// Expects a name that is not one of `taken`, such as a struct or a
+    /// field declared before.
+    fn unique_name(&mut self, taken: &[String]) {
+        if self.at(TokenKind::Id) && taken.iter().any(|t| t == self.peek_text()) {
+            self.error(format!("'{}' is defined twice", self.peek_text()));
+        }
+        self.expect(TokenKind::Id);
+    }
+
+    fn statement(&mut self) {
+        match self.peek() {
+            Some(TokenKind::If) => return self.if_statement(),
+            Some(TokenKind::Match) => return self.match_statement(),
+            Some(TokenKind::Let) => return self.let_statement(),
+            Some(TokenKind::Label | TokenKind::Loop | TokenKind::While | TokenKind::For) => {
+                return self.loop_statement();
+            }
+            Some(TokenKind::Break | TokenKind::Continue) => return self.jump(),
+            _ => {}
+        }
+        let checkpoint = self.checkpoint();
+        let place = self.expression(Precedence::None);
+        if self.at(TokenKind::Eq) {
+            if !place {
+                let message = "only variables, array elements and fields can be assigned to";
+                self.error(message.into());
+            }
+            self.builder
+                .start_node_at(checkpoint, SyntaxKind::Assign.into());
+            self.bump();
+            self.expression(Precedence::None);
+            self.builder.finish_node();
+        }
+    }
+
+    fn if_statement(&mut self) {
+        self.start_node(SyntaxKind::If);
+        self.bump();
+        self.expression(Precedence::None);
+        self.block();
+        if self.at(TokenKind::Else) {
+            self.bump();
+            match self.at(TokenKind::If) {
+                true => self.if_statement(),
+                false => self.block(),
+            }
+        }
+        self.builder.finish_node();
+    }
+
+    fn match_statement(&mut self) {
+        self.start_node(SyntaxKind::Match);
+        self.bump();
+        self.expression(Precedence::None);
+        self.list(TokenKind::LBrace, TokenKind::RBrace, |p| {
+            p.start_node(SyntaxKind::MatchArm);
+            p.pattern(&mut vec![]);
+            if p.at(TokenKind::If) {
+                p.bump();
+                p.expression(Precedence::None);
+            }
+            p.expect(TokenKind::FatArrow);
+            match p.at(TokenKind::LBrace) {
+                true => p.block(),
+                false => p.statement(),
+            }
+            p.builder.finish_node();
+        });
+        self.builder.finish_node();
+    }
+
+    /// Parses a pattern, reporting names already in `bound`.
+    fn pattern(&mut self, bound: &mut Vec<String>) {
+        match self.peek() {
+            Some(TokenKind::Underscore) => {
+                self.start_node(SyntaxKind::WildPat);
+                self.bump();
+            }
+            Some(TokenKind::Int) => {
+                self.start_node(SyntaxKind::LitPat);
+                self.int(None);
+            }
+            Some(TokenKind::Minus) => {
+                self.start_node(SyntaxKind::LitPat);
+                let minus = self.next_span().start;
+                self.bump();
+                self.int(Some(minus));
+            }
+            Some(TokenKind::Id) if self.peek_second() == Some(TokenKind::ColonColon) => {
+                self.start_node(SyntaxKind::VariantPat);
+                self.bump();
+                self.bump();
+                self.expect(TokenKind::Id);
+                if self.at(TokenKind::LParen) {
+                    self.list(TokenKind::LParen, TokenKind::RParen, |p| p.pattern(bound));
+                }
+            }
+            Some(TokenKind::Id) => {
+                let name = self.peek_text().to_string();
+                if bound.contains(&name) {
+                    self.error(format!("'{name}' is defined twice"));
+                }
+                bound.push(name);
+                self.start_node(SyntaxKind::BindPat);
+                self.bump();
+            }
+            Some(kind) if self.at_delimiter() => {
+                return self.error(format!("expected a pattern but found '{kind}'"));
+            }
+            Some(_) => return self.skip(),
+            None => return self.error("expected a pattern but found end of input".into()),
+        }
+        self.builder.finish_node();
+    }
+
+    fn block(&mut self) {
+        self.start_node(SyntaxKind::Block);
+        self.expect(TokenKind::LBrace);
+        while self.peek().is_some() && !self.at(TokenKind::RBrace) {
+            match self.at_delimiter() {
+                true => self.skip(),
+                false => self.statement(),
+            }
+        }
+        self.expect(TokenKind::RBrace);
+        self.builder.finish_node();
+    }
+
+    fn let_statement(&mut self) {
+        self.start_node(SyntaxKind::Let);
+        self.bump();
+        self.expect(TokenKind::Id);
+        self.expect(TokenKind::Eq);
+        self.expression(Precedence::None);
+        self.builder.finish_node();
+    }
+
+    fn loop_statement(&mut self) {
+        self.start_node(SyntaxKind::Loop);
+        let mut label = None;
+        if self.at(TokenKind::Label) {
+            label = Some(self.peek_text().to_string());
+            self.bump();
+            self.expect(TokenKind::Colon);
+        }
+        let kind = self.peek();
+        match kind {
+            Some(TokenKind::Loop) => self.bump(),
+            Some(TokenKind::While) => {
+                self.bump();
+                self.expression(Precedence::None);
+            }
+            Some(TokenKind::For) => {
+                self.bump();
+                self.expect(TokenKind::Id);
+                self.expect(TokenKind::In);
+                self.expression(Precedence::None);
+                self.expect(TokenKind::DotDot);
+                self.expression(Precedence::None);
+            }
+            Some(_) => return self.skip_node(),
+            None => {
+                self.error("unexpected end of input".into());
+                return self.builder.finish_node();
+            }
+        }
+        self.loops.push((label, kind.expect("matched above")));
+        self.block();
+        self.loops.pop();
+        self.builder.finish_node();
+    }
+
+    /// Reports the next token and finishes the current node after it.
+    fn skip_node(&mut self) {
+        self.skip();
+        self.builder.finish_node();
+    }
+
+    /// Parses a `break` or `continue`, checking that it has a loop to leave.
+    fn jump(&mut self) {
+        let kind = if self.at(TokenKind::Break) {
+            SyntaxKind::Break
+        } else {
+            SyntaxKind::Continue
+        };
+        let span = self.next_span();
+        let keyword = self.peek_text().to_string();
+        self.start_node(kind);
+        self.bump();
+        let target = if self.at(TokenKind::Label) {
+            let label = self.peek_text().to_string();
+            let target = self
+                .loops
+                .iter()
+                .rev()
+                .find(|l| l.0.as_deref() == Some(label.as_str()));
+            let target = target.map(|l| l.1);
+            if target.is_none() {
+                self.error(format!("no loop is labelled {label}"));
+            }
+            self.bump();
+            target
+        } else {
+            let target = self.loops.last().map(|l| l.1);
+            if target.is_none() {
+                let message = format!("'{keyword}' outside of a loop");
+                let span = span.clone();
+                self.errors.push(SyntaxError { span, message });
+            }
+            target
+        };
+        if kind == SyntaxKind::Break && self.peek().is_some_and(starts_expression) {
+            if let Some(target) = target.filter(|&t| t != TokenKind::Loop) {
+                let message = format!("'break' with a value in a '{target}' loop");
+                self.errors.push(SyntaxError { span, message });
+            }
+            self.expression(Precedence::None);
+        }
+        self.builder.finish_node();
+    }
+
+    /// Parses an expression, returning whether it can be assigned to: a
+    /// name, possibly indexed or accessed one or more times.
+    fn expression(&mut self, min_bp: Precedence) -> bool {
+        let checkpoint = self.checkpoint();
+        let Some(node) = self.primary() else {
+            return false;
+        };
+        let mut place = node == SyntaxKind::Name;
+        // Only a `(` or `[` with nothing before it is a call or an index,
+        // while a `.` may be spaced.
+        loop {
+            if self.at_adjacent(TokenKind::LParen) {
+                self.builder
+                    .start_node_at(checkpoint, SyntaxKind::Call.into());
+                self.start_node(SyntaxKind::ArgList);
+                self.list(TokenKind::LParen, TokenKind::RParen, |p| {
+                    p.expression(Precedence::None);
+                });
+                self.builder.finish_node();
+                place = false;
+            } else if self.at_adjacent(TokenKind::LBracket) {
+                self.builder
+                    .start_node_at(checkpoint, SyntaxKind::Index.into());
+                self.bump();
+                self.expression(Precedence::None);
+                self.expect(TokenKind::RBracket);
+            } else if self.at(TokenKind::Dot) {
+                self.builder
+                    .start_node_at(checkpoint, SyntaxKind::Field.into());
+                self.bump();
+                self.expect(TokenKind::Id);
+            } else {
+                break;
+            }
+            self.builder.finish_node();
+        }
+        while let Some(kind) = self.peek() {
+            let Ok(op) = Op::try_from(kind) else {
+                break;
+            };
+            let bp = Precedence::from(op);
+            if bp <= min_bp {
+                break;
+            }
+            self.builder
+                .start_node_at(checkpoint, SyntaxKind::Binary.into());
+            self.bump();
+            self.expression(bp);
+            self.builder.finish_node();
+            place = false;
+        }
+        place
+    }
+
+    /// Parses an operand, returning the kind of node it was if there was one.
+    fn primary(&mut self) -> Option<SyntaxKind> {
+        let node = match self.peek() {
+            Some(TokenKind::Int) => {
+                self.start_node(SyntaxKind::Literal);
+                self.int(None);
+                self.builder.finish_node();
+                return Some(SyntaxKind::Literal);
+            }
+            Some(TokenKind::String) => {
+                if let Err((span, message)) = cb_lexer::unescape(self.peek_text()) {
+                    let at = self.next_span().start;
+                    let span = at + span.start..at + span.end;
+                    self.errors.push(SyntaxError {
+                        span,
+                        message: message.into(),
+                    });
+                }
+                SyntaxKind::Literal
+            }
+            Some(TokenKind::Id) if self.peek_second() == Some(TokenKind::ColonColon) => {
+                self.variant_lit();
+                return Some(SyntaxKind::VariantLit);
+            }
+            Some(TokenKind::Id) if self.at_struct_lit() => {
+                self.struct_lit();
+                return Some(SyntaxKind::StructLit);
+            }
+            Some(TokenKind::Id) => SyntaxKind::Name,
+            Some(TokenKind::LParen) => SyntaxKind::Paren,
+            Some(TokenKind::Minus) => SyntaxKind::Prefix,
+            Some(TokenKind::Lambda | TokenKind::Fn) => {
+                self.lambda();
+                return Some(SyntaxKind::Lambda);
+            }
+            Some(TokenKind::LBracket) => {
+                self.array();
+                return Some(SyntaxKind::Array);
+            }
+            // These are operands inside an expression but end the statement
+            // they start.
+            Some(TokenKind::If) => {
+                self.if_statement();
+                return Some(SyntaxKind::If);
+            }
+            Some(TokenKind::Match) => {
+                self.match_statement();
+                return Some(SyntaxKind::Match);
+            }
+            Some(TokenKind::Label | TokenKind::Loop | TokenKind::While | TokenKind::For) => {
+                self.loop_statement();
+                return Some(SyntaxKind::Loop);
+            }
+            Some(kind) if self.at_delimiter() => {
+                self.error(format!("expected an expression but found '{kind}'"));
+                return None;
+            }
+            Some(_) => {
+                self.skip();
+                return None;
+            }
+            None => {
+                self.error("expected an expression but found end of input".into());
+                return None;
+            }
+        };
+        self.start_node(node);
+        self.bump();
+        match node {
+            SyntaxKind::Paren => {
+                self.expression(Precedence::None);
+                self.expect(TokenKind::RParen);
+            }
+            // A literal that only fits negated is the operand of the `-` on
+            // its own, before any operator after it.
+            SyntaxKind::Prefix if self.at_negated_literal() => {
+                self.start_node(SyntaxKind::Literal);
+                self.bump();
+                self.builder.finish_node();
+            }
+            SyntaxKind::Prefix => {
+                self.expression(Precedence::Unary);
+            }
+            _ => {}
+        }
+        self.builder.finish_node();
+        Some(node)
+    }
+
+    /// Whether the next tokens are a declared struct's or an import's name
+    /// and a `{`.
+    fn at_struct_lit(&self) -> bool {
+        self.peek_second() == Some(TokenKind::LBrace)
+            && self
+                .structs
+                .iter()
+                .chain(&self.imports)
+                .any(|s| s == self.peek_text())
+    }
+
+    fn struct_lit(&mut self) {
+        self.start_node(SyntaxKind::StructLit);
+        self.bump();
+        let mut names = vec![];
+        self.list(TokenKind::LBrace, TokenKind::RBrace, |p| {
+            p.start_node(SyntaxKind::FieldInit);
+            names.push(p.peek_text().to_string());
+            p.unique_name(&names[..names.len() - 1]);
+            p.expect(TokenKind::Colon);
+            p.expression(Precedence::None);
+            p.builder.finish_node();
+        });
+        self.builder.finish_node();
+    }
+
+    /// Parses `Name::Variant(value, ...)`, whose `(` must follow the variant
+    /// directly like a call's.
+    fn variant_lit(&mut self) {
+        self.start_node(SyntaxKind::VariantLit);
+        self.bump();
+        self.bump();
+        self.expect(TokenKind::Id);
+        if self.at_adjacent(TokenKind::LParen) {
+            self.start_node(SyntaxKind::ArgList);
+            self.list(TokenKind::LParen, TokenKind::RParen, |p| {
+                p.expression(Precedence::None);
+            });
+            self.builder.finish_node();
+        }
+        self.builder.finish_node();
+    }
+
+    /// Parses `item`s separated by commas between `open` and `close`.
+    fn list(&mut self, open: TokenKind, close: TokenKind, mut item: impl FnMut(&mut Self)) {
+        self.expect(open);
+        if self.peek().is_some() && !self.at(close) {
+            item(self);
+            while self.at(TokenKind::Comma) {
+                self.bump();
+                item(self);
+            }
+        }
+        self.recover();
+        self.expect(close);
+    }
+
+    /// Skips tokens up to the next delimiter.
+    fn recover(&mut self) {
+        while self.peek().is_some() && !self.at_delimiter() {
+            self.skip();
+        }
+    }
+
+    /// Parses `[a, b]` or `[a; n]`, which share a node and differ by the `;`.
+    fn array(&mut self) {
+        self.start_node(SyntaxKind::Array);
+        self.bump();
+        if self.peek().is_some() && !self.at(TokenKind::RBracket) {
+            self.expression(Precedence::None);
+            if self.at(TokenKind::Semicolon) {
+                self.bump();
+                self.int(None);
+            } else {
+                while self.at(TokenKind::Comma) {
+                    self.bump();
+                    self.expression(Precedence::None);
+                }
+            }
+        }
+        self.recover();
+        self.expect(TokenKind::RBracket);
+        self.builder.finish_node();
+    }
+
+    fn lambda(&mut self) {
+        let loops = std::mem::take(&mut self.loops);
+        self.start_node(SyntaxKind::Lambda);
+        self.bump();
+        self.start_node(SyntaxKind::ParamList);
+        self.list(TokenKind::LParen, TokenKind::RParen, |p| {
+            p.start_node(SyntaxKind::Param);
+            p.expect(TokenKind::Id);
+            if p.at(TokenKind::Colon) {
+                p.bump();
+                p.type_expr();
+            }
+            p.builder.finish_node();
+        });
+        self.builder.finish_node();
+        if self.at(TokenKind::Arrow) {
+            self.bump();
+            self.type_expr();
+        }
+        self.block();
+        self.builder.finish_node();
+        self.loops = loops;
+    }
+
+    fn type_expr(&mut self) {
+        self.start_node(SyntaxKind::Type);
+        match self.peek() {
+            Some(TokenKind::Id) if matches!(self.peek_text(), "i32" | "String") => self.bump(),
+            Some(TokenKind::Id)
+                if self
+                    .types()
+                    .iter()
+                    .chain(&self.imports)
+                    .any(|s| s == self.peek_text()) =>
+            {
+                self.bump()
+            }
+            // The name is kept out of the type so that it does not lower to
+            // a struct or enum that was never declared.
+            Some(TokenKind::Id) => {
+                self.error(format!("unknown type '{}'", self.peek_text()));
+                self.start_node(SyntaxKind::Error);
+                self.bump();
+                self.builder.finish_node();
+            }
+            Some(TokenKind::Fn) => {
+                self.bump();
+                self.list(TokenKind::LParen, TokenKind::RParen, Self::type_expr);
+                self.expect(TokenKind::Arrow);
+                self.type_expr();
+            }
+            Some(TokenKind::LBracket) => {
+                self.bump();
+                self.type_expr();
+                self.expect(TokenKind::Semicolon);
+                self.int(None);
+                self.expect(TokenKind::RBracket);
+            }
+            Some(_) => self.error(format!("expected a type but found '{}'", self.peek_text())),
+            None => self.error("expected a type but found end of input".into()),
+        }
+        self.builder.finish_node();
+    }
+}
@@ -0,0 +1,281 @@
+use super::ast::{AstNode, Else, Expr};
+use super::parse;
+use proptest::prelude::*;
+
+macro_rules! setup_test {
+    ($name:ident, $input:expr $(,)?) => {
+        #[test]
+        fn $name() {
+            let src = $input;
+            let parse = parse(src);
+            assert_eq!(parse.syntax().to_string(), src);
+            assert!(parse.errors().is_empty(), "{:?}", parse.errors());
+            let lowered = parse.lower().unwrap();
+            assert_eq!(crate::parse(&crate::print(&lowered)).unwrap(), lowered);
+        }
+    };
+}
+
+setup_test!(literal, "1");
+setup_test!(unary, " -1 ");
+setup_test!(binary, "1 + 2 * 3 > 4 - 5 / 6");
+setup_test!(parens, "(1 + 2) * 3");
+setup_test!(if_expr, "if 1 > 3 { a + b }");
+setup_test!(
+    if_else_chain,
+    "if x > y { x } // bigger\nelse if x < y {\n    y + y\n} else { y }",
+);
+setup_test!(lambda, "λ(x: i32, f: fn(i32) -> i32) -> i32 { f(x) }");
+setup_test!(ascii_lambda, "fn(x) { if x { 1 } else { 2 } }(3)");
+setup_test!(calls, "f(1, -2)(g) * (h)(4) f (5)");
+setup_test!(arrays, "[1, 2][0] + len([x; 3]) [] [[1]][0][0]");
+setup_test!(array_type, "fn(a: [[i32; 2]; 3]) { a[1] }");
+setup_test!(assign, "if x { m[i][j] = [0; 4] } a [0]");
+setup_test!(
+    loops,
+    "let s = 0 'rows: for i in 0..n { while s > i { s = s - 1 continue 'rows } }\nloop { break s }",
+);
+setup_test!(blocks, "fn() { let x = 1 x } fn() {} if x { let y = 2 }");
+setup_test!(
+    block_like_expressions,
+    "let x = loop { break 5 } (loop { break 1 }) + 1\nlet y = if x { 1 } else { 2 } * 3 f(match x { _ => 0 }) if x { 1 } -1",
+);
+setup_test!(
+    structs,
+    "struct P { x: i32, y: [i32; 2] } struct L { p: P }\nlet l = L { p: P { x: 1, y: [2, 3] } } l.p.y[0] = l . p.x fn(p: P) -> P { p }",
+);
+setup_test!(
+    enums,
+    "enum S { C(i32), E } enum T { A(S, fn(S) -> i32) }\nmatch T::A(S::C(-1), f) {\n    T::A(S::C(-2), _) => 1,\n    T::A(S::C(n), g) if n > 0 => { g(S::E) },\n    _ => match x { y => y }\n}",
+);
+setup_test!(
+    modules,
+    "pub mod a; mod b;\nuse a::c::f; pub use b::P;\npub let x = fn(p: P) { f(p) } pub struct Q {} pub enum E { A } P { x: 1 }.x",
+);
+setup_test!(
+    strings,
+    "println(\"a\\tb \\\"q\\\"\") fn(s: String) -> String { s } \"\"",
+);
+setup_test!(comments, "// leading\n1\t// one\r\n\n2 // trailing");
+
+#[test]
+fn samples() {
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../../samples");
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|e| e != "cb") {
+            continue;
+        }
+        let src = std::fs::read_to_string(&path).unwrap();
+        let parse = parse(&src);
+        assert_eq!(parse.syntax().to_string(), src, "{}", path.display());
+        assert!(parse.lower().is_ok(), "{}", path.display());
+    }
+}
+
+#[test]
+fn tree_shape() {
+    let parse = parse("if a {\n  -1 } // done\n");
+    assert_eq!(
+        format!("{:#?}", parse.syntax()),
+        r#"Root@0..22
+  If@0..13
+    KeyWord@0..2 "if"
+    Whitespace@2..3 " "
+    Name@3..4
+      Id@3..4 "a"
+    Whitespace@4..5 " "
+    Block@5..13
+      Op@5..6 "{"
+      Whitespace@6..9 "\n  "
+      Prefix@9..11
+        Op@9..10 "-"
+        Literal@10..11
+          Int@10..11 "1"
+      Whitespace@11..12 " "
+      Op@12..13 "}"
+  Whitespace@13..14 " "
+  Comment@14..21 "// done"
+  Whitespace@21..22 "\n"
+"#
+    );
+}
+
+#[test]
+fn typed_view() {
+    let parse = parse("if x { 1 } else if y { 2 } else { (3) }");
+    let Some(Expr::If(e)) = parse.root().exprs().next() else {
+        panic!("expected an if");
+    };
+    assert_eq!(e.condition().unwrap().syntax().to_string(), "x");
+    assert_eq!(e.then_branch().unwrap().syntax().to_string(), "{ 1 }");
+    let Some(Else::If(e)) = e.else_branch() else {
+        panic!("expected an else if");
+    };
+    let Some(Else::Block(block)) = e.else_branch() else {
+        panic!("expected an else block");
+    };
+    assert!(matches!(block.stmts().next(), Some(Expr::Paren(_))));
+}
+
+#[test]
+fn recovers_from_errors() {
+    let src = "1 + } if { 2 ) 3 else @ (4";
+    let parse = parse(src);
+    assert_eq!(parse.syntax().to_string(), src);
+    let errors: Vec<_> = parse.errors().iter().map(|e| e.to_string()).collect();
+    assert_eq!(
+        errors,
+        [
+            "4:5 expected an expression but found '}'",
+            "4:5 unexpected '}'",
+            "9:10 expected an expression but found '{'",
+            "13:14 unexpected ')'",
+            "17:21 unexpected 'else'",
+            "22:23 unknown token '@'",
+            "26:26 expected ')' but found end of input",
+            "26:26 expected '}' but found end of input",
+        ]
+    );
+    assert_eq!(parse.root().lower(), None);
+}
+
+#[test]
+fn function_errors() {
+    let src = "fn(x: bool, 1) -> { x }(2,)";
+    let parse = parse(src);
+    assert_eq!(parse.syntax().to_string(), src);
+    let errors: Vec<_> = parse.errors().iter().map(|e| e.to_string()).collect();
+    assert_eq!(
+        errors,
+        [
+            "6:10 unknown type 'bool'",
+            "12:13 expected 'identifier' but found '1'",
+            "12:13 unexpected '1'",
+            "18:19 expected a type but found '{'",
+            "26:27 expected an expression but found ')'",
+        ]
+    );
+}
+
+#[test]
+fn array_errors() {
+    let src = "[1; n] [1, 2; 3] a = 1 f(x)[0] = 2 [3";
+    let parse = parse(src);
+    assert_eq!(parse.syntax().to_string(), src);
+    let errors: Vec<_> = parse.errors().iter().map(|e| e.to_string()).collect();
+    assert_eq!(
+        errors,
+        [
+            "4:5 expected 'integer' but found 'n'",
+            "4:5 unexpected 'n'",
+            "12:13 unexpected ';'",
+            "14:15 unexpected '3'",
+            "31:32 only variables, array elements and fields can be assigned to",
+            "37:37 expected ']' but found end of input",
+        ]
+    );
+    assert_eq!(parse.root().lower(), None);
+}
+
+#[test]
+fn loop_errors() {
+    let src =
+        "break 1 'a: loop { fn() { continue } } while 1 { break 'b 2 } for i in 0..n { break 1 }";
+    let parse = parse(src);
+    assert_eq!(parse.syntax().to_string(), src);
+    let errors: Vec<_> = parse.errors().iter().map(|e| e.to_string()).collect();
+    assert_eq!(
+        errors,
+        [
+            "0:5 'break' outside of a loop",
+            "26:34 'continue' outside of a loop",
+            "55:57 no loop is labelled 'b",
+            "78:83 'break' with a value in a 'for' loop",
+        ]
+    );
+}
+
+#[test]
+fn struct_errors() {
+    let src = "struct P { x: Q, x: i32 } struct P {} P { x: 1, x: 2 }.y. 1";
+    let parse = parse(src);
+    assert_eq!(parse.syntax().to_string(), src);
+    let errors: Vec<_> = parse.errors().iter().map(|e| e.to_string()).collect();
+    assert_eq!(
+        errors,
+        [
+            "14:15 unknown type 'Q'",
+            "17:18 'x' is defined twice",
+            "33:34 'P' is defined twice",
+            "48:49 'x' is defined twice",
+            "58:59 expected 'identifier' but found '1'",
+        ]
+    );
+    assert_eq!(parse.root().lower(), None);
+}
+
+#[test]
+fn enum_errors() {
+    let src =
+        "enum E { A(i32), A } struct E {} match e { E::A(x, x) => 1, -9999999999 => 2, + => 3 }";
+    let parse = parse(src);
+    assert_eq!(parse.syntax().to_string(), src);
+    let errors: Vec<_> = parse.errors().iter().map(|e| e.to_string()).collect();
+    assert_eq!(
+        errors,
+        [
+            "17:18 'A' is defined twice",
+            "28:29 'E' is defined twice",
+            "51:52 'x' is defined twice",
+            "60:71 integer literal '-9999999999' is too large",
+            "78:79 unexpected '+'",
+        ]
+    );
+    assert_eq!(parse.root().lower(), None);
+}
+
+#[test]
+fn module_errors() {
+    let src = "mod a; mod a; use a; pub 1 use a::b::;";
+    let parse = parse(src);
+    assert_eq!(parse.syntax().to_string(), src);
+    let errors: Vec<_> = parse.errors().iter().map(|e| e.to_string()).collect();
+    assert_eq!(
+        errors,
+        [
+            "11:12 'a' is defined twice",
+            "19:20 expected '::' but found ';'",
+            "19:20 expected 'identifier' but found ';'",
+            "21:24 unexpected 'pub'",
+            "37:38 expected 'identifier' but found ';'",
+        ]
+    );
+    assert_eq!(parse.root().lower(), None);
+}
+
+#[test]
+fn string_errors() {
+    let src = "\"a\\qb\" \"open\n1";
+    let parse = parse(src);
+    assert_eq!(parse.syntax().to_string(), src);
+    let errors: Vec<_> = parse.errors().iter().map(|e| e.to_string()).collect();
+    assert_eq!(errors, ["2:4 unknown escape", "7:12 unterminated string"]);
+    assert_eq!(parse.root().lower(), None);
+}
+
+proptest! {
+    #[test]
+    fn lossless(src in any::<String>()) {
+        prop_assert_eq!(parse(&src).syntax().to_string(), src);
+    }
+
+    #[test]
+    fn lossless_near_valid(src in r#"(if|else|fn|λ|i32|->|let|loop|while|for|in|break|continue|'[a-z]|\.\.|struct|enum|match|mod|use|pub|=>|::|_|\.|[-+*/<>(){}\[\],;=: \n]|[0-9]{1,3} |[a-z]{1,3}|// ?[a-z]*\n|"[a-z\\]*"?)*"#) {
+        let parse = parse(&src);
+        prop_assert_eq!(parse.syntax().to_string(), src.clone());
+        if parse.errors().is_empty() {
+            prop_assert!(parse.root().lower().is_some(), "{}", src);
+        }
+    }
+}
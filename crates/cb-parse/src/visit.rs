@@ -0,0 +1,847 @@
+//! Traversals over an [`Ast`]. Each trait has one method per kind of node
+//! whose default walks into the node's children, so an implementation only
+//! overrides the nodes it cares about. The `walk_*` functions match every
+//! variant without a wildcard, so adding one to [`Expr`] or [`Stmt`] fails to
+//! compile here instead of being skipped by every pass.
+
+use crate::{
+    Arm, ArrayId, Ast, Atom, BlockId, Call, CallId, EnumId, Expr, ExprId, Lambda, LambdaId, Loop,
+    LoopId, LoopKind, Match, MatchId, Op, Pat, PatId, Stmt, StmtId, StructId, StructLit,
+    StructLitId, Symbol, UseId, VariantLit, VariantLitId,
+};
+
+/// Looks at a tree without changing it.
+pub trait Visitor {
+    fn visit_program(&mut self, ast: &Ast) {
+        walk_program(self, ast);
+    }
+
+    fn visit_stmt(&mut self, ast: &Ast, stmt: StmtId) {
+        walk_stmt(self, ast, stmt);
+    }
+
+    fn visit_expr(&mut self, ast: &Ast, expr: ExprId) {
+        walk_expr(self, ast, expr);
+    }
+
+    fn visit_atom(&mut self, _ast: &Ast, _expr: ExprId, _atom: Atom) {}
+
+    fn visit_unary(&mut self, ast: &Ast, _expr: ExprId, _op: Op, rhs: ExprId) {
+        self.visit_expr(ast, rhs);
+    }
+
+    fn visit_binary(&mut self, ast: &Ast, _expr: ExprId, _op: Op, lhs: ExprId, rhs: ExprId) {
+        self.visit_expr(ast, lhs);
+        self.visit_expr(ast, rhs);
+    }
+
+    fn visit_if(&mut self, ast: &Ast, _expr: ExprId, cond: ExprId, then: ExprId) {
+        self.visit_expr(ast, cond);
+        self.visit_expr(ast, then);
+    }
+
+    fn visit_if_else(
+        &mut self,
+        ast: &Ast,
+        _expr: ExprId,
+        cond: ExprId,
+        then: ExprId,
+        otherwise: ExprId,
+    ) {
+        self.visit_expr(ast, cond);
+        self.visit_expr(ast, then);
+        self.visit_expr(ast, otherwise);
+    }
+
+    fn visit_lambda(&mut self, ast: &Ast, _expr: ExprId, lambda: LambdaId) {
+        self.visit_expr(ast, ast[lambda].body);
+    }
+
+    fn visit_call(&mut self, ast: &Ast, _expr: ExprId, call: CallId) {
+        self.visit_expr(ast, ast[call].callee);
+        for &arg in &ast[call].args {
+            self.visit_expr(ast, arg);
+        }
+    }
+
+    fn visit_array(&mut self, ast: &Ast, _expr: ExprId, array: ArrayId) {
+        for &elem in &ast[array] {
+            self.visit_expr(ast, elem);
+        }
+    }
+
+    fn visit_repeat(&mut self, ast: &Ast, _expr: ExprId, elem: ExprId, _len: u32) {
+        self.visit_expr(ast, elem);
+    }
+
+    fn visit_index(&mut self, ast: &Ast, _expr: ExprId, array: ExprId, index: ExprId) {
+        self.visit_expr(ast, array);
+        self.visit_expr(ast, index);
+    }
+
+    fn visit_assign(&mut self, ast: &Ast, _expr: ExprId, target: ExprId, value: ExprId) {
+        self.visit_expr(ast, target);
+        self.visit_expr(ast, value);
+    }
+
+    fn visit_block(&mut self, ast: &Ast, _expr: ExprId, block: BlockId) {
+        for &stmt in &ast[block] {
+            self.visit_expr(ast, stmt);
+        }
+    }
+
+    fn visit_let(&mut self, ast: &Ast, _expr: ExprId, _name: Symbol, value: ExprId) {
+        self.visit_expr(ast, value);
+    }
+
+    fn visit_loop(&mut self, ast: &Ast, _expr: ExprId, l: LoopId) {
+        for header in ast[l].header() {
+            self.visit_expr(ast, header);
+        }
+        self.visit_expr(ast, ast[l].body);
+    }
+
+    fn visit_break(
+        &mut self,
+        ast: &Ast,
+        _expr: ExprId,
+        _label: Option<Symbol>,
+        value: Option<ExprId>,
+    ) {
+        if let Some(value) = value {
+            self.visit_expr(ast, value);
+        }
+    }
+
+    fn visit_continue(&mut self, _ast: &Ast, _expr: ExprId, _label: Option<Symbol>) {}
+
+    fn visit_struct(&mut self, _ast: &Ast, _decl: StructId) {}
+
+    fn visit_struct_lit(&mut self, ast: &Ast, _expr: ExprId, lit: StructLitId) {
+        for &(_, value) in &ast[lit].fields {
+            self.visit_expr(ast, value);
+        }
+    }
+
+    fn visit_field(&mut self, ast: &Ast, _expr: ExprId, value: ExprId, _name: Symbol) {
+        self.visit_expr(ast, value);
+    }
+
+    fn visit_enum(&mut self, _ast: &Ast, _decl: EnumId) {}
+
+    fn visit_mod(&mut self, _ast: &Ast, _name: Symbol) {}
+
+    fn visit_use(&mut self, _ast: &Ast, _path: UseId) {}
+
+    fn visit_variant_lit(&mut self, ast: &Ast, _expr: ExprId, lit: VariantLitId) {
+        for &arg in &ast[lit].args {
+            self.visit_expr(ast, arg);
+        }
+    }
+
+    fn visit_match(&mut self, ast: &Ast, _expr: ExprId, m: MatchId) {
+        self.visit_expr(ast, ast[m].scrutinee);
+        for arm in &ast[m].arms {
+            if let Some(guard) = arm.guard {
+                self.visit_expr(ast, guard);
+            }
+            self.visit_expr(ast, arm.body);
+        }
+    }
+}
+
+pub fn walk_program<V: Visitor + ?Sized>(v: &mut V, ast: &Ast) {
+    for &stmt in ast.program() {
+        v.visit_stmt(ast, stmt);
+    }
+}
+
+pub fn walk_stmt<V: Visitor + ?Sized>(v: &mut V, ast: &Ast, stmt: StmtId) {
+    match ast[stmt] {
+        Stmt::Expr(expr) => v.visit_expr(ast, expr),
+        Stmt::Struct(decl) => v.visit_struct(ast, decl),
+        Stmt::Enum(decl) => v.visit_enum(ast, decl),
+        Stmt::Mod(name) => v.visit_mod(ast, name),
+        Stmt::Use(path) => v.visit_use(ast, path),
+    }
+}
+
+pub fn walk_expr<V: Visitor + ?Sized>(v: &mut V, ast: &Ast, expr: ExprId) {
+    match ast[expr] {
+        Expr::Atom(atom) => v.visit_atom(ast, expr, atom),
+        Expr::Unary(op, rhs) => v.visit_unary(ast, expr, op, rhs),
+        Expr::Binary(op, lhs, rhs) => v.visit_binary(ast, expr, op, lhs, rhs),
+        Expr::If(c, b) => v.visit_if(ast, expr, c, b),
+        Expr::IfElse(c, b1, b2) => v.visit_if_else(ast, expr, c, b1, b2),
+        Expr::Lambda(lambda) => v.visit_lambda(ast, expr, lambda),
+        Expr::Call(call) => v.visit_call(ast, expr, call),
+        Expr::Array(array) => v.visit_array(ast, expr, array),
+        Expr::Repeat(elem, len) => v.visit_repeat(ast, expr, elem, len),
+        Expr::Index(array, index) => v.visit_index(ast, expr, array, index),
+        Expr::Assign(target, value) => v.visit_assign(ast, expr, target, value),
+        Expr::Block(block) => v.visit_block(ast, expr, block),
+        Expr::Let(name, value) => v.visit_let(ast, expr, name, value),
+        Expr::Loop(l) => v.visit_loop(ast, expr, l),
+        Expr::Break(label, value) => v.visit_break(ast, expr, label, value),
+        Expr::Continue(label) => v.visit_continue(ast, expr, label),
+        Expr::StructLit(lit) => v.visit_struct_lit(ast, expr, lit),
+        Expr::Field(value, name) => v.visit_field(ast, expr, value, name),
+        Expr::VariantLit(lit) => v.visit_variant_lit(ast, expr, lit),
+        Expr::Match(m) => v.visit_match(ast, expr, m),
+    }
+}
+
+/// Rewrites a tree in place. Nodes keep their ids, so side tables built
+/// before the pass still line up with the tree after it.
+pub trait VisitorMut {
+    fn visit_program_mut(&mut self, ast: &mut Ast) {
+        walk_program_mut(self, ast);
+    }
+
+    fn visit_stmt_mut(&mut self, ast: &mut Ast, stmt: StmtId) {
+        walk_stmt_mut(self, ast, stmt);
+    }
+
+    /// Visits the children of `expr` before the node itself, so a rewrite
+    /// sees children that have already been rewritten.
+    fn visit_expr_mut(&mut self, ast: &mut Ast, expr: ExprId) {
+        walk_expr_mut(self, ast, expr);
+    }
+
+    fn visit_atom_mut(&mut self, _ast: &mut Ast, _expr: ExprId) {}
+
+    fn visit_unary_mut(&mut self, _ast: &mut Ast, _expr: ExprId) {}
+
+    fn visit_binary_mut(&mut self, _ast: &mut Ast, _expr: ExprId) {}
+
+    fn visit_if_mut(&mut self, _ast: &mut Ast, _expr: ExprId) {}
+
+    fn visit_if_else_mut(&mut self, _ast: &mut Ast, _expr: ExprId) {}
+
+    fn visit_lambda_mut(&mut self, _ast: &mut Ast, _expr: ExprId) {}
+
+    fn visit_call_mut(&mut self, _ast: &mut Ast, _expr: ExprId) {}
+
+    fn visit_array_mut(&mut self, _ast: &mut Ast, _expr: ExprId) {}
+
+    fn visit_repeat_mut(&mut self, _ast: &mut Ast, _expr: ExprId) {}
+
+    fn visit_index_mut(&mut self, _ast: &mut Ast, _expr: ExprId) {}
+
+    fn visit_assign_mut(&mut self, _ast: &mut Ast, _expr: ExprId) {}
+
+    fn visit_block_mut(&mut self, _ast: &mut Ast, _expr: ExprId) {}
+
+    fn visit_let_mut(&mut self, _ast: &mut Ast, _expr: ExprId) {}
+
+    fn visit_loop_mut(&mut self, _ast: &mut Ast, _expr: ExprId) {}
+
+    fn visit_break_mut(&mut self, _ast: &mut Ast, _expr: ExprId) {}
+
+    fn visit_continue_mut(&mut self, _ast: &mut Ast, _expr: ExprId) {}
+
+    fn visit_struct_lit_mut(&mut self, _ast: &mut Ast, _expr: ExprId) {}
+
+    fn visit_field_mut(&mut self, _ast: &mut Ast, _expr: ExprId) {}
+
+    fn visit_variant_lit_mut(&mut self, _ast: &mut Ast, _expr: ExprId) {}
+
+    fn visit_match_mut(&mut self, _ast: &mut Ast, _expr: ExprId) {}
+}
+
+pub fn walk_program_mut<V: VisitorMut + ?Sized>(v: &mut V, ast: &mut Ast) {
+    for stmt in ast.program().to_vec() {
+        v.visit_stmt_mut(ast, stmt);
+    }
+}
+
+pub fn walk_stmt_mut<V: VisitorMut + ?Sized>(v: &mut V, ast: &mut Ast, stmt: StmtId) {
+    match ast[stmt] {
+        Stmt::Expr(expr) => v.visit_expr_mut(ast, expr),
+        Stmt::Struct(_) | Stmt::Enum(_) | Stmt::Mod(_) | Stmt::Use(_) => {}
+    }
+}
+
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(v: &mut V, ast: &mut Ast, expr: ExprId) {
+    match ast[expr] {
+        Expr::Atom(_) => v.visit_atom_mut(ast, expr),
+        Expr::Unary(_, rhs) => {
+            v.visit_expr_mut(ast, rhs);
+            v.visit_unary_mut(ast, expr);
+        }
+        Expr::Binary(_, lhs, rhs) => {
+            v.visit_expr_mut(ast, lhs);
+            v.visit_expr_mut(ast, rhs);
+            v.visit_binary_mut(ast, expr);
+        }
+        Expr::If(c, b) => {
+            v.visit_expr_mut(ast, c);
+            v.visit_expr_mut(ast, b);
+            v.visit_if_mut(ast, expr);
+        }
+        Expr::IfElse(c, b1, b2) => {
+            v.visit_expr_mut(ast, c);
+            v.visit_expr_mut(ast, b1);
+            v.visit_expr_mut(ast, b2);
+            v.visit_if_else_mut(ast, expr);
+        }
+        Expr::Lambda(lambda) => {
+            v.visit_expr_mut(ast, ast[lambda].body);
+            v.visit_lambda_mut(ast, expr);
+        }
+        Expr::Call(call) => {
+            v.visit_expr_mut(ast, ast[call].callee);
+            for arg in ast[call].args.clone() {
+                v.visit_expr_mut(ast, arg);
+            }
+            v.visit_call_mut(ast, expr);
+        }
+        Expr::Array(array) => {
+            for elem in ast[array].clone() {
+                v.visit_expr_mut(ast, elem);
+            }
+            v.visit_array_mut(ast, expr);
+        }
+        Expr::Repeat(elem, _) => {
+            v.visit_expr_mut(ast, elem);
+            v.visit_repeat_mut(ast, expr);
+        }
+        Expr::Index(array, index) => {
+            v.visit_expr_mut(ast, array);
+            v.visit_expr_mut(ast, index);
+            v.visit_index_mut(ast, expr);
+        }
+        Expr::Assign(target, value) => {
+            v.visit_expr_mut(ast, target);
+            v.visit_expr_mut(ast, value);
+            v.visit_assign_mut(ast, expr);
+        }
+        Expr::Block(block) => {
+            for stmt in ast[block].clone() {
+                v.visit_expr_mut(ast, stmt);
+            }
+            v.visit_block_mut(ast, expr);
+        }
+        Expr::Let(_, value) => {
+            v.visit_expr_mut(ast, value);
+            v.visit_let_mut(ast, expr);
+        }
+        Expr::Loop(l) => {
+            for header in ast[l].header() {
+                v.visit_expr_mut(ast, header);
+            }
+            v.visit_expr_mut(ast, ast[l].body);
+            v.visit_loop_mut(ast, expr);
+        }
+        Expr::Break(_, value) => {
+            if let Some(value) = value {
+                v.visit_expr_mut(ast, value);
+            }
+            v.visit_break_mut(ast, expr);
+        }
+        Expr::Continue(_) => v.visit_continue_mut(ast, expr),
+        Expr::StructLit(lit) => {
+            for (_, value) in ast[lit].fields.clone() {
+                v.visit_expr_mut(ast, value);
+            }
+            v.visit_struct_lit_mut(ast, expr);
+        }
+        Expr::Field(value, _) => {
+            v.visit_expr_mut(ast, value);
+            v.visit_field_mut(ast, expr);
+        }
+        Expr::VariantLit(lit) => {
+            for arg in ast[lit].args.clone() {
+                v.visit_expr_mut(ast, arg);
+            }
+            v.visit_variant_lit_mut(ast, expr);
+        }
+        Expr::Match(m) => {
+            v.visit_expr_mut(ast, ast[m].scrutinee);
+            for arm in ast[m].arms.clone() {
+                if let Some(guard) = arm.guard {
+                    v.visit_expr_mut(ast, guard);
+                }
+                v.visit_expr_mut(ast, arm.body);
+            }
+            v.visit_match_mut(ast, expr);
+        }
+    }
+}
+
+/// Builds a new tree from an old one. By default every node is copied along
+/// with its span; a fold overrides the nodes it wants to replace and can
+/// allocate anything it likes into `out` in their place. Symbols are copied
+/// as they are, so `out` needs the names of the old tree.
+pub trait Fold {
+    fn fold_program(&mut self, ast: &Ast) -> Ast {
+        fold_program(self, ast)
+    }
+
+    fn fold_stmt(&mut self, ast: &Ast, out: &mut Ast, stmt: StmtId) -> StmtId {
+        fold_stmt(self, ast, out, stmt)
+    }
+
+    fn fold_expr(&mut self, ast: &Ast, out: &mut Ast, expr: ExprId) -> ExprId {
+        fold_expr(self, ast, out, expr)
+    }
+
+    fn fold_atom(&mut self, ast: &Ast, out: &mut Ast, expr: ExprId, atom: Atom) -> ExprId {
+        let atom = match atom {
+            Atom::Str(value) => Atom::Str(out.alloc_str(&ast[value])),
+            atom => atom,
+        };
+        out.alloc_expr(Expr::Atom(atom), ast.span(expr))
+    }
+
+    fn fold_unary(
+        &mut self,
+        ast: &Ast,
+        out: &mut Ast,
+        expr: ExprId,
+        op: Op,
+        rhs: ExprId,
+    ) -> ExprId {
+        let rhs = self.fold_expr(ast, out, rhs);
+        out.alloc_expr(Expr::Unary(op, rhs), ast.span(expr))
+    }
+
+    fn fold_binary(
+        &mut self,
+        ast: &Ast,
+        out: &mut Ast,
+        expr: ExprId,
+        op: Op,
+        lhs: ExprId,
+        rhs: ExprId,
+    ) -> ExprId {
+        let lhs = self.fold_expr(ast, out, lhs);
+        let rhs = self.fold_expr(ast, out, rhs);
+        out.alloc_expr(Expr::Binary(op, lhs, rhs), ast.span(expr))
+    }
+
+    fn fold_if(
+        &mut self,
+        ast: &Ast,
+        out: &mut Ast,
+        expr: ExprId,
+        cond: ExprId,
+        then: ExprId,
+    ) -> ExprId {
+        let cond = self.fold_expr(ast, out, cond);
+        let then = self.fold_expr(ast, out, then);
+        out.alloc_expr(Expr::If(cond, then), ast.span(expr))
+    }
+
+    fn fold_if_else(
+        &mut self,
+        ast: &Ast,
+        out: &mut Ast,
+        expr: ExprId,
+        cond: ExprId,
+        then: ExprId,
+        otherwise: ExprId,
+    ) -> ExprId {
+        let cond = self.fold_expr(ast, out, cond);
+        let then = self.fold_expr(ast, out, then);
+        let otherwise = self.fold_expr(ast, out, otherwise);
+        out.alloc_expr(Expr::IfElse(cond, then, otherwise), ast.span(expr))
+    }
+
+    fn fold_lambda(&mut self, ast: &Ast, out: &mut Ast, expr: ExprId, lambda: LambdaId) -> ExprId {
+        let Lambda { params, ret, body } = &ast[lambda];
+        let body = self.fold_expr(ast, out, *body);
+        let lambda = out.alloc_lambda(Lambda {
+            params: params.clone(),
+            ret: ret.clone(),
+            body,
+        });
+        out.alloc_expr(Expr::Lambda(lambda), ast.span(expr))
+    }
+
+    fn fold_call(&mut self, ast: &Ast, out: &mut Ast, expr: ExprId, call: CallId) -> ExprId {
+        let callee = self.fold_expr(ast, out, ast[call].callee);
+        let args = ast[call]
+            .args
+            .iter()
+            .map(|&arg| self.fold_expr(ast, out, arg))
+            .collect();
+        let call = out.alloc_call(Call { callee, args });
+        out.alloc_expr(Expr::Call(call), ast.span(expr))
+    }
+
+    fn fold_array(&mut self, ast: &Ast, out: &mut Ast, expr: ExprId, array: ArrayId) -> ExprId {
+        let elems = ast[array]
+            .iter()
+            .map(|&elem| self.fold_expr(ast, out, elem))
+            .collect();
+        let array = out.alloc_array(elems);
+        out.alloc_expr(Expr::Array(array), ast.span(expr))
+    }
+
+    fn fold_repeat(
+        &mut self,
+        ast: &Ast,
+        out: &mut Ast,
+        expr: ExprId,
+        elem: ExprId,
+        len: u32,
+    ) -> ExprId {
+        let elem = self.fold_expr(ast, out, elem);
+        out.alloc_expr(Expr::Repeat(elem, len), ast.span(expr))
+    }
+
+    fn fold_index(
+        &mut self,
+        ast: &Ast,
+        out: &mut Ast,
+        expr: ExprId,
+        array: ExprId,
+        index: ExprId,
+    ) -> ExprId {
+        let array = self.fold_expr(ast, out, array);
+        let index = self.fold_expr(ast, out, index);
+        out.alloc_expr(Expr::Index(array, index), ast.span(expr))
+    }
+
+    fn fold_assign(
+        &mut self,
+        ast: &Ast,
+        out: &mut Ast,
+        expr: ExprId,
+        target: ExprId,
+        value: ExprId,
+    ) -> ExprId {
+        let target = self.fold_expr(ast, out, target);
+        let value = self.fold_expr(ast, out, value);
+        out.alloc_expr(Expr::Assign(target, value), ast.span(expr))
+    }
+
+    fn fold_block(&mut self, ast: &Ast, out: &mut Ast, expr: ExprId, block: BlockId) -> ExprId {
+        let stmts = ast[block]
+            .iter()
+            .map(|&stmt| self.fold_expr(ast, out, stmt))
+            .collect();
+        let block = out.alloc_block(stmts);
+        out.alloc_expr(Expr::Block(block), ast.span(expr))
+    }
+
+    fn fold_let(
+        &mut self,
+        ast: &Ast,
+        out: &mut Ast,
+        expr: ExprId,
+        name: Symbol,
+        value: ExprId,
+    ) -> ExprId {
+        let value = self.fold_expr(ast, out, value);
+        out.alloc_expr(Expr::Let(name, value), ast.span(expr))
+    }
+
+    fn fold_loop(&mut self, ast: &Ast, out: &mut Ast, expr: ExprId, l: LoopId) -> ExprId {
+        let Loop { label, kind, body } = ast[l];
+        let kind = match kind {
+            LoopKind::Loop => LoopKind::Loop,
+            LoopKind::While(c) => LoopKind::While(self.fold_expr(ast, out, c)),
+            LoopKind::For(name, from, to) => {
+                let from = self.fold_expr(ast, out, from);
+                LoopKind::For(name, from, self.fold_expr(ast, out, to))
+            }
+        };
+        let body = self.fold_expr(ast, out, body);
+        let l = out.alloc_loop(Loop { label, kind, body });
+        out.alloc_expr(Expr::Loop(l), ast.span(expr))
+    }
+
+    fn fold_break(
+        &mut self,
+        ast: &Ast,
+        out: &mut Ast,
+        expr: ExprId,
+        label: Option<Symbol>,
+        value: Option<ExprId>,
+    ) -> ExprId {
+        let value = value.map(|value| self.fold_expr(ast, out, value));
+        out.alloc_expr(Expr::Break(label, value), ast.span(expr))
+    }
+
+    fn fold_continue(
+        &mut self,
+        ast: &Ast,
+        out: &mut Ast,
+        expr: ExprId,
+        label: Option<Symbol>,
+    ) -> ExprId {
+        out.alloc_expr(Expr::Continue(label), ast.span(expr))
+    }
+
+    fn fold_struct_lit(
+        &mut self,
+        ast: &Ast,
+        out: &mut Ast,
+        expr: ExprId,
+        lit: StructLitId,
+    ) -> ExprId {
+        let fields = ast[lit]
+            .fields
+            .iter()
+            .map(|&(name, value)| (name, self.fold_expr(ast, out, value)))
+            .collect();
+        let lit = out.alloc_struct_lit(StructLit {
+            name: ast[lit].name,
+            fields,
+        });
+        out.alloc_expr(Expr::StructLit(lit), ast.span(expr))
+    }
+
+    fn fold_field(
+        &mut self,
+        ast: &Ast,
+        out: &mut Ast,
+        expr: ExprId,
+        value: ExprId,
+        name: Symbol,
+    ) -> ExprId {
+        let value = self.fold_expr(ast, out, value);
+        out.alloc_expr(Expr::Field(value, name), ast.span(expr))
+    }
+
+    fn fold_variant_lit(
+        &mut self,
+        ast: &Ast,
+        out: &mut Ast,
+        expr: ExprId,
+        lit: VariantLitId,
+    ) -> ExprId {
+        let VariantLit { ty, variant, args } = &ast[lit];
+        let args = args
+            .iter()
+            .map(|&arg| self.fold_expr(ast, out, arg))
+            .collect();
+        let lit = out.alloc_variant_lit(VariantLit {
+            ty: *ty,
+            variant: *variant,
+            args,
+        });
+        out.alloc_expr(Expr::VariantLit(lit), ast.span(expr))
+    }
+
+    fn fold_match(&mut self, ast: &Ast, out: &mut Ast, expr: ExprId, m: MatchId) -> ExprId {
+        let scrutinee = self.fold_expr(ast, out, ast[m].scrutinee);
+        let arms = ast[m]
+            .arms
+            .iter()
+            .map(|arm| Arm {
+                pat: self.fold_pat(ast, out, arm.pat),
+                guard: arm.guard.map(|guard| self.fold_expr(ast, out, guard)),
+                body: self.fold_expr(ast, out, arm.body),
+            })
+            .collect();
+        let m = out.alloc_match(Match { scrutinee, arms });
+        out.alloc_expr(Expr::Match(m), ast.span(expr))
+    }
+
+    /// Copies a pattern and the patterns inside it.
+    fn fold_pat(&mut self, ast: &Ast, out: &mut Ast, pat: PatId) -> PatId {
+        let copy = match &ast[pat] {
+            Pat::Variant(ty, variant, args) => {
+                let args = args
+                    .iter()
+                    .map(|&arg| self.fold_pat(ast, out, arg))
+                    .collect();
+                Pat::Variant(*ty, *variant, args)
+            }
+            pat => pat.clone(),
+        };
+        out.alloc_pat(copy, ast.pat_span(pat))
+    }
+}
+
+pub fn fold_program<F: Fold + ?Sized>(f: &mut F, ast: &Ast) -> Ast {
+    let mut out = Ast::with_names(ast.names().clone());
+    for &stmt in ast.program() {
+        let stmt = f.fold_stmt(ast, &mut out, stmt);
+        out.push(stmt);
+    }
+    out
+}
+
+pub fn fold_stmt<F: Fold + ?Sized>(f: &mut F, ast: &Ast, out: &mut Ast, id: StmtId) -> StmtId {
+    let stmt = match ast[id] {
+        Stmt::Expr(expr) => Stmt::Expr(f.fold_expr(ast, out, expr)),
+        Stmt::Struct(decl) => Stmt::Struct(out.alloc_struct(ast[decl].clone())),
+        Stmt::Enum(decl) => Stmt::Enum(out.alloc_enum(ast[decl].clone())),
+        Stmt::Mod(name) => Stmt::Mod(name),
+        Stmt::Use(path) => Stmt::Use(out.alloc_use(ast[path].clone())),
+    };
+    let copy = out.alloc_stmt(stmt);
+    if ast.is_public(id) {
+        out.set_public(copy);
+    }
+    copy
+}
+
+pub fn fold_expr<F: Fold + ?Sized>(f: &mut F, ast: &Ast, out: &mut Ast, expr: ExprId) -> ExprId {
+    match ast[expr] {
+        Expr::Atom(atom) => f.fold_atom(ast, out, expr, atom),
+        Expr::Unary(op, rhs) => f.fold_unary(ast, out, expr, op, rhs),
+        Expr::Binary(op, lhs, rhs) => f.fold_binary(ast, out, expr, op, lhs, rhs),
+        Expr::If(c, b) => f.fold_if(ast, out, expr, c, b),
+        Expr::IfElse(c, b1, b2) => f.fold_if_else(ast, out, expr, c, b1, b2),
+        Expr::Lambda(lambda) => f.fold_lambda(ast, out, expr, lambda),
+        Expr::Call(call) => f.fold_call(ast, out, expr, call),
+        Expr::Array(array) => f.fold_array(ast, out, expr, array),
+        Expr::Repeat(elem, len) => f.fold_repeat(ast, out, expr, elem, len),
+        Expr::Index(array, index) => f.fold_index(ast, out, expr, array, index),
+        Expr::Assign(target, value) => f.fold_assign(ast, out, expr, target, value),
+        Expr::Block(block) => f.fold_block(ast, out, expr, block),
+        Expr::Let(name, value) => f.fold_let(ast, out, expr, name, value),
+        Expr::Loop(l) => f.fold_loop(ast, out, expr, l),
+        Expr::Break(label, value) => f.fold_break(ast, out, expr, label, value),
+        Expr::Continue(label) => f.fold_continue(ast, out, expr, label),
+        Expr::StructLit(lit) => f.fold_struct_lit(ast, out, expr, lit),
+        Expr::Field(value, name) => f.fold_field(ast, out, expr, value, name),
+        Expr::VariantLit(lit) => f.fold_variant_lit(ast, out, expr, lit),
+        Expr::Match(m) => f.fold_match(ast, out, expr, m),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse, Id, SideTable};
+
+    /// Collects identifiers in source order, overriding nothing but atoms.
+    #[derive(Default)]
+    struct Names(Vec<String>);
+
+    impl Visitor for Names {
+        fn visit_atom(&mut self, ast: &Ast, _expr: ExprId, atom: Atom) {
+            if let Atom::Id(name) = atom {
+                self.0.push(ast[name].to_string());
+            }
+        }
+    }
+
+    #[test]
+    fn visitor_reaches_every_node() {
+        let src = "if a > b { -c } else if d { e * (f + g) } else { h } 1 + i(j, fn(k) { l })";
+        let loops = "'x: loop { let z = r while s { for y in p..q { break 'x t } continue } }";
+        let matches = "enum E { A(i32) } match u { E::A(x) if v => E::A(w), _ => x }";
+        let ast = parse(&format!("{src} m[n] = [o; 2] {loops} {matches}")).unwrap();
+        let mut names = Names::default();
+        names.visit_program(&ast);
+        assert_eq!(
+            names.0,
+            [
+                "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "l", "m", "n", "o", "r", "s",
+                "p", "q", "t", "u", "v", "w", "x"
+            ]
+        );
+    }
+
+    /// Records the depth of every expression in a side table.
+    struct Depths {
+        depth: usize,
+        table: SideTable<ExprId, usize>,
+    }
+
+    impl Visitor for Depths {
+        fn visit_expr(&mut self, ast: &Ast, expr: ExprId) {
+            self.table.insert(expr, self.depth);
+            self.depth += 1;
+            walk_expr(self, ast, expr);
+            self.depth -= 1;
+        }
+    }
+
+    #[test]
+    fn visitor_can_wrap_the_walk() {
+        let ast = parse("1 + 2 * 3").unwrap();
+        let mut depths = Depths {
+            depth: 0,
+            table: SideTable::new(),
+        };
+        depths.visit_program(&ast);
+        let depths: Vec<_> = ast.expr_ids().map(|id| depths.table[id]).collect();
+        assert_eq!(depths, [1, 2, 2, 1, 0]);
+    }
+
+    /// Rewrites `-x` into `0 - x` without moving any node.
+    struct Desugar;
+
+    impl VisitorMut for Desugar {
+        fn visit_unary_mut(&mut self, ast: &mut Ast, expr: ExprId) {
+            let Expr::Unary(Op::Minus, rhs) = ast[expr] else {
+                return;
+            };
+            let span = ast.span(expr);
+            let zero = ast.alloc_expr(Expr::Atom(Atom::Int(0)), span.start..span.start + 1);
+            ast[expr] = Expr::Binary(Op::Minus, zero, rhs);
+        }
+    }
+
+    #[test]
+    fn visitor_mut_rewrites_in_place() {
+        let mut ast = parse("-(1 + -2) * 3").unwrap();
+        let spans: Vec<_> = ast.expr_ids().map(|id| ast.span(id)).collect();
+        Desugar.visit_program_mut(&mut ast);
+        assert_eq!(ast, parse("(0 - (1 + (0 - 2))) * 3").unwrap());
+        let after: Vec<_> = ast.expr_ids().map(|id| ast.span(id)).collect();
+        assert_eq!(after[..spans.len()], spans);
+    }
+
+    /// Replaces arithmetic on constants with its result.
+    struct ConstFold;
+
+    impl Fold for ConstFold {
+        fn fold_binary(
+            &mut self,
+            ast: &Ast,
+            out: &mut Ast,
+            expr: ExprId,
+            op: Op,
+            lhs: ExprId,
+            rhs: ExprId,
+        ) -> ExprId {
+            let lhs = self.fold_expr(ast, out, lhs);
+            let rhs = self.fold_expr(ast, out, rhs);
+            let value = match (op, out[lhs], out[rhs]) {
+                (Op::Plus, Expr::Atom(Atom::Int(l)), Expr::Atom(Atom::Int(r))) => {
+                    Expr::Atom(Atom::Int(l.wrapping_add(r)))
+                }
+                (Op::Mult, Expr::Atom(Atom::Int(l)), Expr::Atom(Atom::Int(r))) => {
+                    Expr::Atom(Atom::Int(l.wrapping_mul(r)))
+                }
+                _ => Expr::Binary(op, lhs, rhs),
+            };
+            out.alloc_expr(value, ast.span(expr))
+        }
+    }
+
+    #[test]
+    fn fold_builds_a_new_tree() {
+        let ast = parse("if x { 1 + 2 * 3 } else { x + 2 * 2 }").unwrap();
+        let folded = ConstFold.fold_program(&ast);
+        assert_eq!(folded, parse("if x { 7 } else { x + 4 }").unwrap());
+        assert_eq!(ast, parse("if x { 1 + 2 * 3 } else { x + 2 * 2 }").unwrap());
+    }
+
+    struct Identity;
+
+    impl Fold for Identity {}
+
+    #[test]
+    fn default_fold_copies_spans() {
+        let src = "if a { -(1 + 2) } else { b / f(λ(x: [i32; 2]) { x[0] }, [3, 4]) } \
+            'l: while a { let b = 1 for i in 0..b { break 'l } loop { break 2 } continue } \
+            enum E { A(i32), B } match E::A(1) { E::A(-2) => 0, _ if a => 1, E::B => { 2 } }";
+        let ast = parse(src).unwrap();
+        let copy = Identity.fold_program(&ast);
+        assert_eq!(copy, ast);
+        let spans = |ast: &Ast| ast.expr_ids().map(|id| ast.span(id)).collect::<Vec<_>>();
+        assert_eq!(spans(&copy), spans(&ast));
+        assert_eq!(copy.pat_spans.iter().count(), 4);
+        assert_eq!(copy.pat_span(PatId::new(0)), ast.pat_span(PatId::new(0)));
+    }
+}
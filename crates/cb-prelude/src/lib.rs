@@ -0,0 +1,270 @@
+//! The prelude: functions every program can call without defining them.
+//!
+//! Each one is an [`Intrinsic`] that a backend lowers itself, calling out to
+//! a [`Host`] for anything that touches the outside world. A name from the
+//! prelude can be shadowed like any other.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{BufRead, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Intrinsic {
+    /// `len(a: [T; N]) -> i32`, or the length of a `String` in bytes.
+    Len,
+    /// `print(x)` writes an `i32` or a `String`.
+    Print,
+    /// `println(x)` writes an `i32` or a `String` and a newline.
+    Println,
+    /// `read_line() -> String`, without the newline, or empty at the end of
+    /// the input.
+    ReadLine,
+    /// `exit(code: i32)` stops the program.
+    Exit,
+    /// `assert(c: i32)` stops the program with an error if `c` is zero.
+    Assert,
+    /// `to_string(i: i32) -> String`
+    ToString,
+    /// `parse_int(s: String) -> i32`, which fails at run time if `s` is not
+    /// a decimal `i32`.
+    ParseInt,
+    /// `slice(s: String, from: i32, to: i32) -> String`, the bytes of `s`
+    /// from `from` up to `to`, which fails at run time unless both are in
+    /// bounds and on character boundaries.
+    Slice,
+}
+
+impl Intrinsic {
+    pub const ALL: [Self; 9] = [
+        Self::Len,
+        Self::Print,
+        Self::Println,
+        Self::ReadLine,
+        Self::Exit,
+        Self::Assert,
+        Self::ToString,
+        Self::ParseInt,
+        Self::Slice,
+    ];
+
+    pub fn lookup(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|i| i.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Len => "len",
+            Self::Print => "print",
+            Self::Println => "println",
+            Self::ReadLine => "read_line",
+            Self::Exit => "exit",
+            Self::Assert => "assert",
+            Self::ToString => "to_string",
+            Self::ParseInt => "parse_int",
+            Self::Slice => "slice",
+        }
+    }
+
+    /// How many arguments it takes.
+    pub fn arity(self) -> usize {
+        match self {
+            Self::ReadLine => 0,
+            Self::Slice => 3,
+            _ => 1,
+        }
+    }
+
+    /// Whether a call is only run for its effect, so it has no value.
+    pub fn is_unit(self) -> bool {
+        matches!(
+            self,
+            Self::Print | Self::Println | Self::Exit | Self::Assert
+        )
+    }
+}
+
+impl fmt::Display for Intrinsic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Where a running program's input and output go.
+pub trait Host {
+    /// The value of a top level expression, which is printed on its own line.
+    fn value(&mut self, value: i32) {
+        self.write(&format!("{value}\n"));
+    }
+
+    fn write(&mut self, text: &str);
+
+    /// The next line of input without its newline, or `None` at the end.
+    fn read_line(&mut self) -> Option<String>;
+}
+
+/// The process's standard input and output.
+#[derive(Debug, Default)]
+pub struct Stdio;
+
+impl Host for Stdio {
+    fn write(&mut self, text: &str) {
+        let mut stdout = std::io::stdout().lock();
+        let _ = stdout.write_all(text.as_bytes());
+        let _ = stdout.flush();
+    }
+
+    fn read_line(&mut self) -> Option<String> {
+        let mut line = String::new();
+        match std::io::stdin().lock().read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(trim_newline(line)),
+        }
+    }
+}
+
+/// Records output in memory and reads input from a string, for tests.
+#[derive(Debug, Default)]
+pub struct Capture {
+    /// The value of every top level expression in order.
+    pub values: Vec<i32>,
+    /// Everything written, values included, as it would have been printed.
+    pub output: String,
+    input: VecDeque<String>,
+}
+
+impl Capture {
+    pub fn new(input: &str) -> Self {
+        Self {
+            input: input.lines().map(String::from).collect(),
+            ..Self::default()
+        }
+    }
+}
+
+impl Host for Capture {
+    fn value(&mut self, value: i32) {
+        self.values.push(value);
+        self.output.push_str(&format!("{value}\n"));
+    }
+
+    fn write(&mut self, text: &str) {
+        self.output.push_str(text);
+    }
+
+    fn read_line(&mut self) -> Option<String> {
+        self.input.pop_front()
+    }
+}
+
+fn trim_newline(mut line: String) -> String {
+    if line.ends_with('\n') {
+        line.pop();
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    }
+    line
+}
+
+/// A program called `exit`, which travels as an error so that every backend
+/// stops where it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Exit(pub i32);
+
+impl fmt::Display for Exit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "exited with code {}", self.0)
+    }
+}
+
+impl std::error::Error for Exit {}
+
+/// The message of a failed `assert`.
+pub const ASSERTION_FAILED: &str = "assertion failed";
+
+/// Why `slice` failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SliceError {
+    OutOfBounds {
+        len: usize,
+        from: i32,
+        to: i32,
+    },
+    /// The index is inside the encoding of a character.
+    NotCharBoundary(i32),
+}
+
+impl fmt::Display for SliceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OutOfBounds { len, from, to } => write!(
+                f,
+                "slice out of bounds: the length is {len} but the range is {from}..{to}"
+            ),
+            Self::NotCharBoundary(index) => {
+                write!(f, "byte index {index} is not a char boundary")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SliceError {}
+
+/// The bytes of `text` from `from` up to `to`, as `slice` returns them.
+pub fn slice(text: &str, from: i32, to: i32) -> Result<&str, SliceError> {
+    let bounds = || SliceError::OutOfBounds {
+        len: text.len(),
+        from,
+        to,
+    };
+    let start = usize::try_from(from).map_err(|_| bounds())?;
+    let end = usize::try_from(to).map_err(|_| bounds())?;
+    if start > end || end > text.len() {
+        return Err(bounds());
+    }
+    for (index, i) in [(start, from), (end, to)] {
+        if !text.is_char_boundary(index) {
+            return Err(SliceError::NotCharBoundary(i));
+        }
+    }
+    Ok(&text[start..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        for intrinsic in Intrinsic::ALL {
+            assert_eq!(Intrinsic::lookup(intrinsic.name()), Some(intrinsic));
+        }
+        assert_eq!(Intrinsic::lookup("printf"), None);
+    }
+
+    #[test]
+    fn slices() {
+        assert_eq!(slice("héllo", 0, 1), Ok("h"));
+        assert_eq!(slice("héllo", 1, 3), Ok("é"));
+        assert_eq!(slice("héllo", 6, 6), Ok(""));
+        assert_eq!(slice("héllo", 2, 3), Err(SliceError::NotCharBoundary(2)));
+        assert_eq!(
+            slice("héllo", 3, 7).unwrap_err().to_string(),
+            "slice out of bounds: the length is 6 but the range is 3..7"
+        );
+        assert!(slice("abc", 2, 1).is_err());
+        assert!(slice("abc", -1, 1).is_err());
+    }
+
+    #[test]
+    fn capture() {
+        let mut host = Capture::new("a\r\nb\n");
+        host.write("x");
+        host.value(1);
+        assert_eq!(host.read_line().as_deref(), Some("a"));
+        assert_eq!(host.read_line().as_deref(), Some("b"));
+        assert_eq!(host.read_line(), None);
+        assert_eq!(host.values, [1]);
+        assert_eq!(host.output, "x1\n");
+    }
+}
@@ -0,0 +1,322 @@
+//! Projects described by a `cflat.toml` manifest.
+//!
+//! ```toml
+//! [package]
+//! name = "hello"
+//! version = "0.1.0"
+//! entry = "src/main.cb"
+//! target = "c"
+//! opt-level = 0
+//!
+//! [dependencies]
+//! util = { path = "../util" }
+//! ```
+//!
+//! Only `name` and `version` are required. A dependency is another project,
+//! whose entry file is loaded as a module named after the dependency.
+
+use cb_lexer::TokenKind;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+type CResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+/// The file a project is described by, at the root of the project.
+pub const MANIFEST: &str = "cflat.toml";
+
+/// What code is generated for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum Target {
+    #[default]
+    #[serde(rename = "c")]
+    C,
+    #[serde(rename = "wasm32")]
+    Wasm32,
+}
+
+impl Target {
+    pub fn lookup(name: &str) -> Option<Self> {
+        match name {
+            "c" => Some(Self::C),
+            "wasm32" => Some(Self::Wasm32),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub package: Package,
+    /// Other projects by the name they are used under.
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, Dependency>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Package {
+    pub name: String,
+    pub version: String,
+    /// The root file of the program, relative to the manifest.
+    #[serde(default = "default_entry")]
+    pub entry: PathBuf,
+    #[serde(default)]
+    pub target: Target,
+    /// From 0 to 3, passed on to the C compiler.
+    #[serde(default, rename = "opt-level")]
+    pub opt_level: u8,
+}
+
+fn default_entry() -> PathBuf {
+    PathBuf::from("src/main.cb")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Dependency {
+    /// The directory of the dependency's manifest, relative to this one.
+    pub path: PathBuf,
+}
+
+impl Manifest {
+    pub fn parse(src: &str) -> CResult<Self> {
+        let manifest: Self = toml::from_str(src)?;
+        check_name(&manifest.package.name)?;
+        for name in manifest.dependencies.keys() {
+            check_name(name)?;
+        }
+        if manifest.package.opt_level > 3 {
+            let level = manifest.package.opt_level;
+            return Err(format!("opt-level must be from 0 to 3, found {level}").into());
+        }
+        Ok(manifest)
+    }
+}
+
+/// Projects and dependencies are used as modules, so their names must be
+/// identifiers.
+fn check_name(name: &str) -> CResult<()> {
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && TokenKind::lookup(name).is_none();
+    match valid {
+        true => Ok(()),
+        false => Err(format!("'{name}' is not a valid name, expected an identifier").into()),
+    }
+}
+
+/// A manifest and the directory it is in.
+#[derive(Debug, Clone)]
+pub struct Project {
+    pub root: PathBuf,
+    pub manifest: Manifest,
+}
+
+impl Project {
+    /// Reads the manifest in `root`.
+    pub fn open(root: &Path) -> CResult<Self> {
+        let path = root.join(MANIFEST);
+        let src = std::fs::read_to_string(&path)
+            .map_err(|e| format!("failed to open '{}': {e}", path.display()))?;
+        let manifest = Manifest::parse(&src).map_err(|e| format!("{}: {e}", path.display()))?;
+        Ok(Self {
+            root: root.to_path_buf(),
+            manifest,
+        })
+    }
+
+    /// Finds the project that `dir` is in by looking for a manifest in it
+    /// and then in each of its parents.
+    pub fn find(dir: &Path) -> CResult<Option<Self>> {
+        match dir.ancestors().find(|dir| dir.join(MANIFEST).is_file()) {
+            Some(root) => Ok(Some(Self::open(root)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.manifest.package.name
+    }
+
+    pub fn entry(&self) -> PathBuf {
+        self.root.join(&self.manifest.package.entry)
+    }
+
+    /// Where artifacts are written.
+    pub fn target_dir(&self) -> PathBuf {
+        self.root.join("target")
+    }
+
+    /// The entry file of every dependency, direct or not, by name. A
+    /// dependency comes after its own dependencies.
+    pub fn dependencies(&self) -> CResult<Vec<(String, PathBuf)>> {
+        let mut found = vec![];
+        self.collect(&mut found, &mut vec![self.root.canonicalize()?])?;
+        Ok(found)
+    }
+
+    fn collect(&self, found: &mut Vec<(String, PathBuf)>, stack: &mut Vec<PathBuf>) -> CResult<()> {
+        for (name, dependency) in &self.manifest.dependencies {
+            let root = self.root.join(&dependency.path);
+            let root = root
+                .canonicalize()
+                .map_err(|e| format!("failed to open '{}': {e}", root.display()))?;
+            let project = Self::open(&root)?;
+            let entry = project.entry();
+            if let Some((_, other)) = found.iter().find(|(n, _)| n == name) {
+                if *other != entry {
+                    return Err(format!("two dependencies are named '{name}'").into());
+                }
+                continue;
+            }
+            if stack.contains(&project.root) {
+                return Err(format!("dependency '{name}' is part of a cycle").into());
+            }
+            stack.push(project.root.clone());
+            project.collect(found, stack)?;
+            stack.pop();
+            found.push((name.clone(), entry));
+        }
+        Ok(())
+    }
+}
+
+/// Creates a project called `name` in a new directory `dir`, with a
+/// manifest and a `src/main.cb` to start from.
+pub fn scaffold(dir: &Path, name: &str) -> CResult<()> {
+    check_name(name)?;
+    if dir.exists() {
+        return Err(format!("destination '{}' already exists", dir.display()).into());
+    }
+    let manifest = format!(
+        "[package]\nname = \"{name}\"\nversion = \"0.1.0\"\nentry = \"src/main.cb\"\n\n[dependencies]\n"
+    );
+    std::fs::create_dir_all(dir.join("src"))?;
+    std::fs::write(dir.join(MANIFEST), manifest)?;
+    std::fs::write(dir.join(".gitignore"), "/target\n")?;
+    std::fs::write(dir.join("src/main.cb"), "40 + 2\n")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("cb-project-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn defaults() {
+        let manifest = Manifest::parse("[package]\nname = \"a\"\nversion = \"1.0.0\"").unwrap();
+        assert_eq!(
+            manifest.package,
+            Package {
+                name: "a".into(),
+                version: "1.0.0".into(),
+                entry: "src/main.cb".into(),
+                target: Target::C,
+                opt_level: 0,
+            }
+        );
+        assert!(manifest.dependencies.is_empty());
+    }
+
+    #[test]
+    fn every_field() {
+        let src = "[package]\nname = \"app\"\nversion = \"0.2.0\"\nentry = \"app.cb\"\n\
+                   target = \"wasm32\"\nopt-level = 2\n\n[dependencies]\nutil = { path = \"../util\" }";
+        let manifest = Manifest::parse(src).unwrap();
+        assert_eq!(manifest.package.entry, PathBuf::from("app.cb"));
+        assert_eq!(manifest.package.target, Target::Wasm32);
+        assert_eq!(manifest.package.opt_level, 2);
+        assert_eq!(manifest.dependencies["util"].path, PathBuf::from("../util"));
+    }
+
+    #[test]
+    fn errors() {
+        let package = "[package]\nversion = \"1.0.0\"\n";
+        let cases = [
+            (
+                format!("{package}name = \"my-app\""),
+                "'my-app' is not a valid name",
+            ),
+            (
+                format!("{package}name = \"let\""),
+                "'let' is not a valid name",
+            ),
+            (
+                format!("{package}name = \"a\"\nopt-level = 4"),
+                "opt-level must be from 0 to 3",
+            ),
+            (
+                format!("{package}name = \"a\"\ntarget = \"x86\""),
+                "unknown variant `x86`",
+            ),
+            (
+                format!("{package}name = \"a\"\nauthor = \"b\""),
+                "unknown field `author`",
+            ),
+            (
+                "[package]\nname = \"a\"".to_string(),
+                "missing field `version`",
+            ),
+            (
+                format!("{package}name = \"a\"\n[dependencies]\n2d = {{ path = \"x\" }}"),
+                "'2d' is not a valid name",
+            ),
+        ];
+        for (src, expected) in cases {
+            let e = Manifest::parse(&src).unwrap_err().to_string();
+            assert!(e.contains(expected), "{src}: {e}");
+        }
+    }
+
+    #[test]
+    fn scaffold_and_find() {
+        let dir = temp_dir("scaffold");
+        scaffold(&dir, "hello").unwrap();
+        let project = Project::find(&dir.join("src")).unwrap().unwrap();
+        assert_eq!(project.root, dir);
+        assert_eq!(project.name(), "hello");
+        assert_eq!(project.entry(), dir.join("src/main.cb"));
+        assert!(project.entry().is_file());
+        let e = scaffold(&dir, "hello").unwrap_err().to_string();
+        assert!(e.starts_with("destination"), "{e}");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn dependencies() {
+        let dir = temp_dir("dependencies");
+        for name in ["app", "util", "base"] {
+            scaffold(&dir.join(name), name).unwrap();
+        }
+        let add = |project: &str, deps: &str| {
+            let path = dir.join(project).join(MANIFEST);
+            let manifest = std::fs::read_to_string(&path).unwrap();
+            std::fs::write(path, manifest + deps).unwrap();
+        };
+        add(
+            "app",
+            "util = { path = \"../util\" }\nbase = { path = \"../base\" }\n",
+        );
+        add("util", "base = { path = \"../base\" }\n");
+        let app = Project::open(&dir.join("app")).unwrap();
+        let names = app.dependencies().unwrap();
+        let names: Vec<_> = names.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["base", "util"]);
+
+        add("base", "app = { path = \"../app\" }\n");
+        let e = app.dependencies().unwrap_err().to_string();
+        assert_eq!(e, "dependency 'app' is part of a cycle");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
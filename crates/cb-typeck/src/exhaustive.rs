@@ -0,0 +1,195 @@
+//! Reachability and coverage of `match` arms.
+//!
+//! A row of patterns is useful against the rows before it when some value
+//! matches it and none of them. An arm is unreachable when its pattern is
+//! not useful, and a `match` covers every value when a row of wildcards is
+//! not. Only enums have a finite set of constructors, so a column of
+//! integers is covered only by a wildcard or a name.
+
+use crate::{Type, Variants};
+use cb_parse::{Ast, Pat, PatId, Symbol};
+
+/// A pattern reduced to what decides whether it matches, or a value that
+/// would be matched, for reporting one that no arm covers.
+#[derive(Debug, Clone)]
+pub(crate) enum Pattern {
+    Wild,
+    Int(i32),
+    /// The enum, the variant and the patterns for its values.
+    Variant(Symbol, Symbol, Vec<Pattern>),
+}
+
+impl Pattern {
+    pub(crate) fn new(ast: &Ast, pat: PatId) -> Self {
+        match &ast[pat] {
+            Pat::Wild | Pat::Bind(_) => Self::Wild,
+            Pat::Int(i) => Self::Int(*i),
+            Pat::Variant(name, variant, args) => Self::Variant(
+                *name,
+                *variant,
+                args.iter().map(|&arg| Self::new(ast, arg)).collect(),
+            ),
+        }
+    }
+}
+
+impl Pattern {
+    /// Formats the pattern the way it is written, with the names in `ast`.
+    pub(crate) fn display(&self, ast: &Ast) -> String {
+        match self {
+            Self::Wild => "_".to_string(),
+            Self::Int(i) => i.to_string(),
+            Self::Variant(name, variant, args) => {
+                let path = format!("{}::{}", &ast[*name], &ast[*variant]);
+                match args.is_empty() {
+                    true => path,
+                    false => {
+                        let args: Vec<_> = args.iter().map(|arg| arg.display(ast)).collect();
+                        format!("{path}({})", args.join(", "))
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// What the first column of a row can be narrowed to.
+#[derive(Clone, Copy)]
+enum Ctor {
+    Int(i32),
+    Variant(Symbol),
+}
+
+/// Whether some value matches `row` but none of `rows`. Every row has one
+/// pattern for each of `tys`.
+pub(crate) fn useful(
+    enums: &[(Symbol, Variants)],
+    rows: &[Vec<Pattern>],
+    row: &[Pattern],
+    tys: &[Type],
+) -> bool {
+    let (Some((head, rest)), Some((ty, tys))) = (row.split_first(), tys.split_first()) else {
+        return rows.is_empty();
+    };
+    match head {
+        Pattern::Int(i) => useful(enums, &specialize(rows, Ctor::Int(*i), 0), rest, tys),
+        Pattern::Variant(_, variant, args) => {
+            let fields = fields(enums, ty, *variant);
+            let rows = specialize(rows, Ctor::Variant(*variant), args.len());
+            useful(
+                enums,
+                &rows,
+                &[&args[..], rest].concat(),
+                &[fields, tys].concat(),
+            )
+        }
+        Pattern::Wild => match complete(enums, rows, ty) {
+            Some(variants) => variants.iter().any(|(variant, fields)| {
+                let rows = specialize(rows, Ctor::Variant(*variant), fields.len());
+                let row = [vec![Pattern::Wild; fields.len()], rest.to_vec()].concat();
+                useful(enums, &rows, &row, &[&fields[..], tys].concat())
+            }),
+            None => useful(enums, &default(rows), rest, tys),
+        },
+    }
+}
+
+/// Values, one for each of `tys`, that none of `rows` match, if there are
+/// any.
+pub(crate) fn missing(
+    enums: &[(Symbol, Variants)],
+    rows: &[Vec<Pattern>],
+    tys: &[Type],
+) -> Option<Vec<Pattern>> {
+    let Some((ty, tys)) = tys.split_first() else {
+        return rows.is_empty().then(Vec::new);
+    };
+    if let Some(variants) = complete(enums, rows, ty) {
+        let Type::Enum(name) = ty else {
+            unreachable!("only enums have every constructor listed");
+        };
+        return variants.iter().find_map(|(variant, fields)| {
+            let rows = specialize(rows, Ctor::Variant(*variant), fields.len());
+            let mut values = missing(enums, &rows, &[&fields[..], tys].concat())?;
+            let rest = values.split_off(fields.len());
+            let value = Pattern::Variant(*name, *variant, values);
+            Some([vec![value], rest].concat())
+        });
+    }
+    let mut values = missing(enums, &default(rows), tys)?;
+    // Name a variant that no row starts with, unless no row names any, in
+    // which case anything at all is missing.
+    let head = match (ty, variants(enums, ty)) {
+        (Type::Enum(name), Some(variants))
+            if rows.iter().any(|r| !matches!(r[0], Pattern::Wild)) =>
+        {
+            let (variant, fields) = variants
+                .iter()
+                .find(|(v, _)| !rows.iter().any(|r| starts_with(r, *v)))
+                .expect("the column is not complete");
+            Pattern::Variant(*name, *variant, vec![Pattern::Wild; fields.len()])
+        }
+        _ => Pattern::Wild,
+    };
+    values.insert(0, head);
+    Some(values)
+}
+
+fn variants<'a>(enums: &'a [(Symbol, Variants)], ty: &Type) -> Option<&'a Variants> {
+    let Type::Enum(name) = ty else {
+        return None;
+    };
+    enums.iter().find(|(n, _)| n == name).map(|(_, v)| v)
+}
+
+fn fields<'a>(enums: &'a [(Symbol, Variants)], ty: &Type, variant: Symbol) -> &'a [Type] {
+    let variants = variants(enums, ty).expect("the checker unified the pattern with an enum");
+    let (_, fields) = variants
+        .iter()
+        .find(|(v, _)| *v == variant)
+        .expect("the checker found the variant");
+    fields
+}
+
+/// The variants of `ty` if the first column of `rows` names every one of
+/// them, so that a wildcard there can be split into each.
+fn complete<'a>(
+    enums: &'a [(Symbol, Variants)],
+    rows: &[Vec<Pattern>],
+    ty: &Type,
+) -> Option<&'a Variants> {
+    let variants = variants(enums, ty)?;
+    variants
+        .iter()
+        .all(|(v, _)| rows.iter().any(|r| starts_with(r, *v)))
+        .then_some(variants)
+}
+
+fn starts_with(row: &[Pattern], variant: Symbol) -> bool {
+    matches!(&row[0], Pattern::Variant(_, v, _) if *v == variant)
+}
+
+/// The rows that can match a value built with `ctor`, with the patterns for
+/// its `arity` values in place of the first column.
+fn specialize(rows: &[Vec<Pattern>], ctor: Ctor, arity: usize) -> Vec<Vec<Pattern>> {
+    rows.iter()
+        .filter_map(|row| {
+            let (head, rest) = row.split_first()?;
+            let args = match (head, ctor) {
+                (Pattern::Wild, _) => vec![Pattern::Wild; arity],
+                (Pattern::Int(i), Ctor::Int(j)) if *i == j => vec![],
+                (Pattern::Variant(_, v, args), Ctor::Variant(w)) if *v == w => args.clone(),
+                _ => return None,
+            };
+            Some([args, rest.to_vec()].concat())
+        })
+        .collect()
+}
+
+/// The rows that match whatever is in the first column, without it.
+fn default(rows: &[Vec<Pattern>]) -> Vec<Vec<Pattern>> {
+    rows.iter()
+        .filter(|row| matches!(row[0], Pattern::Wild))
+        .map(|row| row[1..].to_vec())
+        .collect()
+}
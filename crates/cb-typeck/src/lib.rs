@@ -0,0 +1,1449 @@
+//! Type inference for C Flat.
+//!
+//! Every expression is an `i32`, a `String`, a function, an array, a struct,
+//! an enum or `()`, the type of calls that only have an effect. Parameters
+//! and return types may be left out of a lambda, in which case they are
+//! inferred by unification from how the lambda is used. Array lengths are
+//! types too, so that `len` accepts arrays of any length.
+//!
+//! Arithmetic and comparisons are on `i32`s, except that `+` also joins two
+//! strings and `==` and `!=` also compare them. An operand whose type is not
+//! known yet is taken to be an `i32`.
+
+mod exhaustive;
+
+use crate::exhaustive::Pattern;
+use cb_lexer::Span;
+use cb_parse::{
+    Ast, Atom, Expr, ExprId, Loop, LoopKind, MatchId, Op, Pat, PatId, SideTable, Stmt, Symbol,
+    TypeExpr,
+};
+use cb_prelude::Intrinsic;
+use std::fmt;
+
+type CResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Type {
+    Int,
+    String,
+    /// The type of calls like `print(x)` that produce no value.
+    Unit,
+    Fn(Vec<Type>, Box<Type>),
+    /// An array of elements of the first type, whose length is the second.
+    Array(Box<Type>, Box<Type>),
+    /// A struct, named by its declaration.
+    Struct(Symbol),
+    /// An enum, named by its declaration.
+    Enum(Symbol),
+    /// The length of an array, which only appears inside [`Type::Array`].
+    Size(u32),
+    /// A type that nothing in the program pins down, such as the parameter
+    /// of a lambda that is never called.
+    Var(u32),
+}
+
+impl From<&TypeExpr> for Type {
+    fn from(ty: &TypeExpr) -> Self {
+        match ty {
+            TypeExpr::Int => Self::Int,
+            TypeExpr::String => Self::String,
+            TypeExpr::Fn(params, ret) => Self::Fn(
+                params.iter().map(Self::from).collect(),
+                Box::new(Self::from(&**ret)),
+            ),
+            TypeExpr::Array(elem, len) => {
+                Self::Array(Box::new(Self::from(&**elem)), Box::new(Self::Size(*len)))
+            }
+            TypeExpr::Struct(name) => Self::Struct(*name),
+            TypeExpr::Enum(name) => Self::Enum(*name),
+        }
+    }
+}
+
+impl Type {
+    /// Formats the type the way it is written, with the names that structs
+    /// and enums have in `ast`.
+    pub fn display<'a>(&'a self, ast: &'a Ast) -> impl fmt::Display + 'a {
+        DisplayType { ast, ty: self }
+    }
+}
+
+struct DisplayType<'a> {
+    ast: &'a Ast,
+    ty: &'a Type,
+}
+
+impl fmt::Display for DisplayType<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let d = |ty: &'_ Type| DisplayType { ast: self.ast, ty }.to_string();
+        match self.ty {
+            Type::Int => write!(f, "i32"),
+            Type::String => write!(f, "String"),
+            Type::Unit => write!(f, "()"),
+            Type::Fn(params, ret) => {
+                write!(f, "fn(")?;
+                for (i, param) in params.iter().enumerate() {
+                    match i {
+                        0 => write!(f, "{}", d(param))?,
+                        _ => write!(f, ", {}", d(param))?,
+                    }
+                }
+                write!(f, ") -> {}", d(ret))
+            }
+            Type::Array(elem, len) => write!(f, "[{}; {}]", d(elem), d(len)),
+            Type::Struct(name) | Type::Enum(name) => write!(f, "{}", &self.ast[*name]),
+            Type::Size(n) => write!(f, "{n}"),
+            Type::Var(n) => write!(f, "?{n}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeError {
+    pub span: Span,
+    pub message: String,
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{} {}", self.span.start, self.span.end, self.message)
+    }
+}
+
+impl std::error::Error for TypeError {}
+
+/// Infers the type of every expression that produces a value. Top level
+/// statements are printed, so their values must be `i32`, `String` or `()`,
+/// which prints nothing.
+pub fn check(ast: &Ast) -> CResult<SideTable<ExprId, Type>> {
+    let checked = analyze(ast);
+    match checked.error {
+        Some(error) => Err(Box::new(error)),
+        None => Ok(checked.types),
+    }
+}
+
+/// What [`analyze`] found out about a program.
+#[derive(Debug, Clone, Default)]
+pub struct Checked {
+    /// The type of every expression checked before the first error.
+    pub types: SideTable<ExprId, Type>,
+    /// What each name that refers to a variable refers to.
+    pub bindings: SideTable<ExprId, Binding>,
+    /// The first error in the program, if it has one.
+    pub error: Option<TypeError>,
+}
+
+/// Where a variable is bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binding {
+    /// By the `let` at this expression.
+    Let(ExprId),
+    /// As the parameter at this index of the lambda at the expression.
+    Param(ExprId, usize),
+    /// As the counter of the `for` loop at this expression.
+    For(ExprId),
+    /// By this pattern, which is a name.
+    Pat(PatId),
+}
+
+impl Binding {
+    /// The span of the node that binds the variable, which starts with or
+    /// contains its name.
+    pub fn span(self, ast: &Ast) -> Span {
+        match self {
+            Self::Let(expr) | Self::Param(expr, _) | Self::For(expr) => ast.span(expr),
+            Self::Pat(pat) => ast.pat_span(pat),
+        }
+    }
+}
+
+/// Like [`check`], but for tools such as the language server that want to
+/// know as much as can be learned about a program that may have an error.
+pub fn analyze(ast: &Ast) -> Checked {
+    let mut checker = Checker {
+        ast,
+        vars: vec![],
+        scope: vec![],
+        loops: vec![],
+        structs: vec![],
+        enums: vec![],
+        types: SideTable::new(),
+        bindings: SideTable::new(),
+        printed: vec![],
+        measured: vec![],
+    };
+    let error = checker
+        .program()
+        .err()
+        .map(|e| match e.downcast::<TypeError>() {
+            Ok(e) => *e,
+            Err(e) => unreachable!("the checker only fails with type errors: {e}"),
+        });
+    let mut types = SideTable::new();
+    for (expr, ty) in checker.types.iter() {
+        types.insert(expr, checker.resolve(ty));
+    }
+    Checked {
+        types,
+        bindings: checker.bindings,
+        error,
+    }
+}
+
+struct Checker<'a> {
+    ast: &'a Ast,
+    /// What each type variable has been unified with so far.
+    vars: Vec<Option<Type>>,
+    /// Parameters and variables in scope, innermost last.
+    scope: Vec<(Symbol, Type, Binding)>,
+    /// Loops around the statement being checked, innermost last.
+    loops: Vec<Target>,
+    /// The structs declared so far and the types of their fields.
+    structs: Vec<(Symbol, Vec<(Symbol, Type)>)>,
+    /// The enums declared so far and the types of each variant's values.
+    enums: Vec<(Symbol, Variants)>,
+    types: SideTable<ExprId, Type>,
+    bindings: SideTable<ExprId, Binding>,
+    /// The uses of `print` and `println` and the type of what they print,
+    /// which is checked once the whole program has been.
+    printed: Vec<(ExprId, Type)>,
+    /// Likewise for `len`, which takes an array or a string.
+    measured: Vec<(ExprId, Type)>,
+}
+
+/// The variants of an enum in the order they were declared.
+type Variants = Vec<(Symbol, Vec<Type>)>;
+
+/// Whether `expr` is a name, possibly indexed or accessed one or more
+/// times, the only kind of expression that can be assigned to.
+fn is_place(ast: &Ast, expr: ExprId) -> bool {
+    match ast[expr] {
+        Expr::Atom(Atom::Id(_)) => true,
+        Expr::Index(base, _) | Expr::Field(base, _) => is_place(ast, base),
+        _ => false,
+    }
+}
+
+/// A loop that `break` and `continue` can leave.
+struct Target {
+    label: Option<Symbol>,
+    /// `loop`, `while` or `for`.
+    keyword: &'static str,
+    /// The type of the values a `loop` breaks with; other loops have none.
+    value: Option<Type>,
+    valued: bool,
+    bare: bool,
+}
+
+impl Checker<'_> {
+    fn program(&mut self) -> CResult<()> {
+        let ast = self.ast;
+        for &stmt in ast.program() {
+            let expr = match ast[stmt] {
+                Stmt::Expr(expr) => expr,
+                Stmt::Struct(decl) => {
+                    let decl = &ast[decl];
+                    let fields = decl.fields.iter().map(|f| (f.name, (&f.ty).into()));
+                    self.structs.push((decl.name, fields.collect()));
+                    continue;
+                }
+                Stmt::Enum(decl) => {
+                    let decl = &ast[decl];
+                    let variants = decl
+                        .variants
+                        .iter()
+                        .map(|v| (v.name, v.fields.iter().map(Type::from).collect()));
+                    self.enums.push((decl.name, variants.collect()));
+                    continue;
+                }
+                // Linking modules replaces these with what they name, so a
+                // program checked on its own has nothing from other files.
+                Stmt::Mod(_) | Stmt::Use(_) => continue,
+            };
+            if let Some(ty) = self.statement(expr)? {
+                if matches!(self.shallow(&ty), Type::String | Type::Unit) {
+                    continue;
+                }
+                if self.unify(&ty, &Type::Int).is_err() {
+                    let ty = self.resolve(&ty);
+                    let message =
+                        format!("cannot print a value of type '{}'", ty.display(self.ast));
+                    return Err(self.error(expr, message));
+                }
+            }
+        }
+        for (expr, ty) in &self.printed {
+            let ty = self.resolve(ty);
+            if !matches!(ty, Type::Int | Type::String | Type::Var(_)) {
+                let message = format!("cannot print a value of type '{}'", ty.display(self.ast));
+                return Err(self.error(*expr, message));
+            }
+        }
+        for (expr, ty) in &self.measured {
+            let ty = self.resolve(ty);
+            if !matches!(ty, Type::Array(..) | Type::String | Type::Var(_)) {
+                let message = format!(
+                    "expected an array or a string but found '{}'",
+                    ty.display(self.ast)
+                );
+                return Err(self.error(*expr, message));
+            }
+        }
+        Ok(())
+    }
+
+    fn error(&self, expr: ExprId, message: String) -> Box<dyn std::error::Error> {
+        let span = self.ast.span(expr);
+        Box::new(TypeError { span, message })
+    }
+
+    fn pat_error(&self, pat: PatId, message: String) -> Box<dyn std::error::Error> {
+        let span = self.ast.pat_span(pat);
+        Box::new(TypeError { span, message })
+    }
+
+    fn fresh(&mut self) -> Type {
+        self.vars.push(None);
+        Type::Var(self.vars.len() as u32 - 1)
+    }
+
+    /// Replaces every solved variable in `ty` with its solution.
+    fn resolve(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Int
+            | Type::String
+            | Type::Unit
+            | Type::Struct(_)
+            | Type::Enum(_)
+            | Type::Size(_) => ty.clone(),
+            Type::Fn(params, ret) => Type::Fn(
+                params.iter().map(|p| self.resolve(p)).collect(),
+                Box::new(self.resolve(ret)),
+            ),
+            Type::Array(elem, len) => {
+                Type::Array(Box::new(self.resolve(elem)), Box::new(self.resolve(len)))
+            }
+            Type::Var(n) => match &self.vars[*n as usize] {
+                Some(ty) => self.resolve(ty),
+                None => ty.clone(),
+            },
+        }
+    }
+
+    /// Follows solved variables until `ty` is no longer one.
+    fn shallow(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Var(n) => match &self.vars[*n as usize] {
+                Some(ty) => self.shallow(ty),
+                None => ty.clone(),
+            },
+            _ => ty.clone(),
+        }
+    }
+
+    fn occurs(&self, var: u32, ty: &Type) -> bool {
+        match self.shallow(ty) {
+            Type::Int
+            | Type::String
+            | Type::Unit
+            | Type::Struct(_)
+            | Type::Enum(_)
+            | Type::Size(_) => false,
+            Type::Fn(params, ret) => {
+                params.iter().any(|p| self.occurs(var, p)) || self.occurs(var, &ret)
+            }
+            Type::Array(elem, len) => self.occurs(var, &elem) || self.occurs(var, &len),
+            Type::Var(n) => n == var,
+        }
+    }
+
+    /// Makes `a` and `b` the same type, or fails without saying why so that
+    /// the caller can report both types in full.
+    fn unify(&mut self, a: &Type, b: &Type) -> Result<(), ()> {
+        match (self.shallow(a), self.shallow(b)) {
+            (Type::Int, Type::Int) | (Type::String, Type::String) | (Type::Unit, Type::Unit) => {
+                Ok(())
+            }
+            (Type::Struct(x), Type::Struct(y)) | (Type::Enum(x), Type::Enum(y)) if x == y => Ok(()),
+            (Type::Size(x), Type::Size(y)) if x == y => Ok(()),
+            (Type::Var(x), Type::Var(y)) if x == y => Ok(()),
+            (Type::Var(x), ty) | (ty, Type::Var(x)) => {
+                if self.occurs(x, &ty) {
+                    return Err(());
+                }
+                self.vars[x as usize] = Some(ty);
+                Ok(())
+            }
+            (Type::Fn(p1, r1), Type::Fn(p2, r2)) if p1.len() == p2.len() => {
+                for (p1, p2) in p1.iter().zip(&p2) {
+                    self.unify(p1, p2)?;
+                }
+                self.unify(&r1, &r2)
+            }
+            (Type::Array(e1, l1), Type::Array(e2, l2)) => {
+                self.unify(&e1, &e2)?;
+                self.unify(&l1, &l2)
+            }
+            _ => Err(()),
+        }
+    }
+
+    /// Checks that `expr`, whose type is `found`, has type `expected`.
+    fn expect(&mut self, expr: ExprId, found: &Type, expected: &Type) -> CResult<()> {
+        if self.unify(found, expected).is_ok() {
+            return Ok(());
+        }
+        let (found, expected) = (self.resolve(found), self.resolve(expected));
+        let message = match (&found, &expected) {
+            (Type::Var(_), ty) | (ty, Type::Var(_)) => {
+                let var = if ty == &found { &expected } else { &found };
+                let (var, ty) = (var.display(self.ast), ty.display(self.ast));
+                format!("'{var}' cannot be '{ty}', which contains it")
+            }
+            _ => format!(
+                "expected '{}' but found '{}'",
+                expected.display(self.ast),
+                found.display(self.ast)
+            ),
+        };
+        Err(self.error(expr, message))
+    }
+
+    /// The type of a statement's value, or `None` if it may not produce one.
+    fn statement(&mut self, expr: ExprId) -> CResult<Option<Type>> {
+        match self.ast[expr] {
+            Expr::If(c, b) => {
+                self.condition(c)?;
+                self.statement(b)?;
+                Ok(None)
+            }
+            Expr::IfElse(c, b1, b2) => {
+                self.condition(c)?;
+                let t1 = self.statement(b1)?;
+                let t2 = self.statement(b2)?;
+                let (Some(t1), Some(t2)) = (t1, t2) else {
+                    return Ok(None);
+                };
+                self.expect(b2, &t2, &t1)?;
+                self.types.insert(expr, t1.clone());
+                Ok(Some(t1))
+            }
+            Expr::Assign(target, _) if !is_place(self.ast, target) => {
+                let message = "only variables, array elements and fields can be assigned to";
+                Err(self.error(target, message.to_string()))
+            }
+            Expr::Assign(target, value) => {
+                let expected = self.expression(target)?;
+                let found = self.expression(value)?;
+                self.expect(value, &found, &expected)?;
+                Ok(None)
+            }
+            Expr::Block(block) => {
+                let depth = self.scope.len();
+                let mut ty = None;
+                for &stmt in &self.ast[block] {
+                    ty = self.statement(stmt)?;
+                }
+                self.scope.truncate(depth);
+                if let Some(ty) = &ty {
+                    self.types.insert(expr, ty.clone());
+                }
+                Ok(ty)
+            }
+            Expr::Let(name, value) => {
+                let ty = self.expression(value)?;
+                self.scope.push((name, ty, Binding::Let(expr)));
+                Ok(None)
+            }
+            Expr::Loop(id) => self.loop_statement(expr, self.ast[id]),
+            Expr::Break(label, value) => {
+                let target = self.target(expr, label)?;
+                match value {
+                    Some(value) => {
+                        let found = self.expression(value)?;
+                        let Some(expected) = self.loops[target].value.clone() else {
+                            let keyword = self.loops[target].keyword;
+                            let message = format!("'break' with a value in a '{keyword}' loop");
+                            return Err(self.error(expr, message));
+                        };
+                        self.expect(value, &found, &expected)?;
+                        self.loops[target].valued = true;
+                    }
+                    None => self.loops[target].bare = true,
+                }
+                Ok(None)
+            }
+            Expr::Continue(label) => {
+                self.target(expr, label)?;
+                Ok(None)
+            }
+            Expr::Match(m) => self.match_statement(expr, m),
+            _ => self.expression(expr).map(Some),
+        }
+    }
+
+    /// Like an `if`, a `match` has a value when every arm does. The arms are
+    /// checked for reachability and coverage once their types are known.
+    fn match_statement(&mut self, expr: ExprId, id: MatchId) -> CResult<Option<Type>> {
+        let m = &self.ast[id];
+        let ty = self.expression(m.scrutinee)?;
+        let mut value = Some(None);
+        for arm in &m.arms {
+            let depth = self.scope.len();
+            let body = self.arm(arm.pat, arm.guard, arm.body, &ty);
+            self.scope.truncate(depth);
+            value = match (value, body?) {
+                (Some(None), Some(ty)) => Some(Some(ty)),
+                (Some(Some(first)), Some(ty)) => {
+                    self.expect(arm.body, &ty, &first)?;
+                    Some(Some(first))
+                }
+                _ => None,
+            };
+        }
+        self.exhaustive(expr, id, &ty)?;
+        let Some(Some(ty)) = value else {
+            return Ok(None);
+        };
+        self.types.insert(expr, ty.clone());
+        Ok(Some(ty))
+    }
+
+    /// Checks one arm with the names its pattern binds in scope, returning
+    /// the type of its body's value.
+    fn arm(
+        &mut self,
+        pat: PatId,
+        guard: Option<ExprId>,
+        body: ExprId,
+        ty: &Type,
+    ) -> CResult<Option<Type>> {
+        self.pattern(pat, ty)?;
+        if let Some(guard) = guard {
+            self.condition(guard)?;
+        }
+        self.statement(body)
+    }
+
+    /// Checks that `pat` can match a value of type `ty`, binding its names.
+    fn pattern(&mut self, pat: PatId, ty: &Type) -> CResult<()> {
+        let (found, args) = match &self.ast[pat] {
+            Pat::Wild => return Ok(()),
+            Pat::Bind(name) => {
+                self.scope.push((*name, ty.clone(), Binding::Pat(pat)));
+                return Ok(());
+            }
+            Pat::Int(_) => (Type::Int, vec![]),
+            Pat::Variant(name, variant, args) => {
+                let fields = self
+                    .variant(*name, *variant, args.len())
+                    .map_err(|message| self.pat_error(pat, message))?;
+                (
+                    Type::Enum(*name),
+                    args.iter().copied().zip(fields).collect(),
+                )
+            }
+        };
+        if self.unify(ty, &found).is_err() {
+            let ty = self.resolve(ty);
+            let message = format!(
+                "expected '{}' but found '{}'",
+                ty.display(self.ast),
+                found.display(self.ast)
+            );
+            return Err(self.pat_error(pat, message));
+        }
+        for (arg, ty) in args {
+            self.pattern(arg, &ty)?;
+        }
+        Ok(())
+    }
+
+    /// The types of the values of `name::variant`, which must be `count`
+    /// long, or the message to report if they are not.
+    fn variant(&self, name: Symbol, variant: Symbol, count: usize) -> Result<Vec<Type>, String> {
+        let Some((_, variants)) = self.enums.iter().find(|(n, _)| *n == name) else {
+            return Err(format!("unknown enum '{}'", &self.ast[name]));
+        };
+        let Some((_, fields)) = variants.iter().find(|(v, _)| *v == variant) else {
+            let (name, variant) = (&self.ast[name], &self.ast[variant]);
+            return Err(format!("enum '{name}' has no variant '{variant}'"));
+        };
+        if fields.len() != count {
+            let n = fields.len();
+            return Err(format!("expected {n} values but found {count}"));
+        }
+        Ok(fields.clone())
+    }
+
+    /// Reports the first arm that no value can reach, or else a value that
+    /// no arm matches. An arm with a guard may not match, so it covers
+    /// nothing for the arms after it.
+    fn exhaustive(&self, expr: ExprId, id: MatchId, ty: &Type) -> CResult<()> {
+        let m = &self.ast[id];
+        let ty = self.resolve(ty);
+        let mut rows = vec![];
+        for arm in &m.arms {
+            let row = vec![Pattern::new(self.ast, arm.pat)];
+            if !exhaustive::useful(&self.enums, &rows, &row, std::slice::from_ref(&ty)) {
+                return Err(self.pat_error(arm.pat, "unreachable pattern".to_string()));
+            }
+            if arm.guard.is_none() {
+                rows.push(row);
+            }
+        }
+        if let Some(missing) = exhaustive::missing(&self.enums, &rows, &[ty]) {
+            let message = format!("pattern '{}' is not covered", missing[0].display(self.ast));
+            return Err(self.error(expr, message));
+        }
+        Ok(())
+    }
+
+    /// A `loop` has a value when every `break` that leaves it has one.
+    fn loop_statement(&mut self, expr: ExprId, l: Loop) -> CResult<Option<Type>> {
+        let depth = self.scope.len();
+        let (keyword, value) = match l.kind {
+            LoopKind::Loop => ("loop", Some(self.fresh())),
+            LoopKind::While(cond) => {
+                self.condition(cond)?;
+                ("while", None)
+            }
+            LoopKind::For(name, from, to) => {
+                self.condition(from)?;
+                self.condition(to)?;
+                self.scope.push((name, Type::Int, Binding::For(expr)));
+                ("for", None)
+            }
+        };
+        self.loops.push(Target {
+            label: l.label,
+            keyword,
+            value,
+            valued: false,
+            bare: false,
+        });
+        let body = self.statement(l.body);
+        self.scope.truncate(depth);
+        let target = self.loops.pop().expect("pushed above");
+        body?;
+        match target {
+            Target {
+                value: Some(ty),
+                valued: true,
+                bare: false,
+                ..
+            } => {
+                self.types.insert(expr, ty.clone());
+                Ok(Some(ty))
+            }
+            Target {
+                valued: true,
+                bare: true,
+                ..
+            } => {
+                let message = "'loop' breaks both with and without a value".to_string();
+                Err(self.error(expr, message))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// The loop that the `break` or `continue` at `expr` leaves. The parser
+    /// only allows jumps to enclosing loops, but a tree read from JSON may
+    /// have any.
+    fn target(&self, expr: ExprId, label: Option<Symbol>) -> CResult<usize> {
+        let target = self
+            .loops
+            .iter()
+            .rposition(|t| label.is_none() || t.label == label);
+        target.ok_or_else(|| {
+            let message = match (label, self.ast[expr]) {
+                (Some(label), _) => format!("no loop is labelled {}", &self.ast[label]),
+                (None, Expr::Break(..)) => "'break' outside of a loop".to_string(),
+                (None, _) => "'continue' outside of a loop".to_string(),
+            };
+            self.error(expr, message)
+        })
+    }
+
+    /// The type of a use of a function from the prelude. Each use gets fresh
+    /// variables, so `len` takes arrays of any length.
+    fn intrinsic(&mut self, expr: ExprId, intrinsic: Intrinsic) -> Type {
+        let (params, ret) = match intrinsic {
+            Intrinsic::Len => {
+                let value = self.fresh();
+                self.measured.push((expr, value.clone()));
+                (vec![value], Type::Int)
+            }
+            Intrinsic::Print | Intrinsic::Println => {
+                let value = self.fresh();
+                self.printed.push((expr, value.clone()));
+                (vec![value], Type::Unit)
+            }
+            Intrinsic::ReadLine => (vec![], Type::String),
+            Intrinsic::Exit | Intrinsic::Assert => (vec![Type::Int], Type::Unit),
+            Intrinsic::ToString => (vec![Type::Int], Type::String),
+            Intrinsic::ParseInt => (vec![Type::String], Type::Int),
+            Intrinsic::Slice => (vec![Type::String, Type::Int, Type::Int], Type::String),
+        };
+        Type::Fn(params, Box::new(ret))
+    }
+
+    /// The fields of the struct `name`, or the message to report if no such
+    /// struct has been declared. The parser only allows declared structs,
+    /// but a tree read from JSON may name any.
+    fn fields(&self, name: Symbol) -> Result<Vec<(Symbol, Type)>, String> {
+        match self.structs.iter().find(|(n, _)| *n == name) {
+            Some((_, fields)) => Ok(fields.clone()),
+            None => Err(format!("unknown struct '{}'", &self.ast[name])),
+        }
+    }
+
+    fn condition(&mut self, expr: ExprId) -> CResult<()> {
+        let ty = self.expression(expr)?;
+        self.expect(expr, &ty, &Type::Int)
+    }
+
+    fn expression(&mut self, expr: ExprId) -> CResult<Type> {
+        let ty = match self.ast[expr] {
+            Expr::Atom(Atom::Int(_)) => Type::Int,
+            Expr::Atom(Atom::Str(_)) => Type::String,
+            Expr::Atom(Atom::Id(id)) => match self.scope.iter().rev().find(|(n, ..)| *n == id) {
+                Some((_, ty, binding)) => {
+                    self.bindings.insert(expr, *binding);
+                    ty.clone()
+                }
+                None => match Intrinsic::lookup(&self.ast[id]) {
+                    Some(intrinsic) => self.intrinsic(expr, intrinsic),
+                    None => {
+                        let message = format!("unbound identifier '{}'", &self.ast[id]);
+                        return Err(self.error(expr, message));
+                    }
+                },
+            },
+            Expr::Unary(_, rhs) => {
+                self.condition(rhs)?;
+                Type::Int
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                let ty = self.expression(lhs)?;
+                match (op, self.shallow(&ty)) {
+                    (Op::Plus | Op::Eq | Op::Ne, Type::String) => {
+                        let found = self.expression(rhs)?;
+                        self.expect(rhs, &found, &Type::String)?;
+                        match op {
+                            Op::Plus => Type::String,
+                            _ => Type::Int,
+                        }
+                    }
+                    _ => {
+                        self.expect(lhs, &ty, &Type::Int)?;
+                        self.condition(rhs)?;
+                        Type::Int
+                    }
+                }
+            }
+            Expr::If(..) | Expr::IfElse(..) => match self.statement(expr)? {
+                Some(ty) => ty,
+                None => {
+                    let message = "'if' without 'else' used as a value".to_string();
+                    return Err(self.error(expr, message));
+                }
+            },
+            Expr::Assign(..) => {
+                self.statement(expr)?;
+                let message = "assignment used as a value".to_string();
+                return Err(self.error(expr, message));
+            }
+            Expr::Block(_)
+            | Expr::Let(..)
+            | Expr::Match(_)
+            | Expr::Loop(_)
+            | Expr::Break(..)
+            | Expr::Continue(_) => match self.statement(expr)? {
+                Some(ty) => ty,
+                None => {
+                    let message = "statement without a value used as a value".to_string();
+                    return Err(self.error(expr, message));
+                }
+            },
+            Expr::Lambda(lambda) => {
+                let lambda = &self.ast[lambda];
+                let params: Vec<_> = lambda
+                    .params
+                    .iter()
+                    .map(|p| match &p.ty {
+                        Some(ty) => ty.into(),
+                        None => self.fresh(),
+                    })
+                    .collect();
+                let depth = self.scope.len();
+                let names = lambda.params.iter().map(|p| p.name);
+                let bound = names
+                    .zip(params.iter().cloned())
+                    .enumerate()
+                    .map(|(i, (name, ty))| (name, ty, Binding::Param(expr, i)));
+                self.scope.extend(bound);
+                // A jump cannot leave the function it is in.
+                let loops = std::mem::take(&mut self.loops);
+                let body = self.expression(lambda.body);
+                self.loops = loops;
+                self.scope.truncate(depth);
+                let body = body?;
+                if let Some(ret) = &lambda.ret {
+                    self.expect(lambda.body, &body, &ret.into())?;
+                }
+                Type::Fn(params, Box::new(body))
+            }
+            Expr::Call(call) => {
+                let call = &self.ast[call];
+                let callee = self.expression(call.callee)?;
+                let (params, ret) = match self.shallow(&callee) {
+                    Type::Fn(params, ret) => (params, *ret),
+                    Type::Var(_) => {
+                        let params: Vec<_> = call.args.iter().map(|_| self.fresh()).collect();
+                        let ret = self.fresh();
+                        let ty = Type::Fn(params.clone(), Box::new(ret.clone()));
+                        self.expect(call.callee, &callee, &ty)?;
+                        (params, ret)
+                    }
+                    ty => {
+                        let ty = self.resolve(&ty);
+                        let message =
+                            format!("expected a function but found '{}'", ty.display(self.ast));
+                        return Err(self.error(call.callee, message));
+                    }
+                };
+                if params.len() != call.args.len() {
+                    let (p, a) = (params.len(), call.args.len());
+                    let message = format!("expected {p} arguments but found {a}");
+                    return Err(self.error(expr, message));
+                }
+                for (&arg, param) in call.args.iter().zip(&params) {
+                    let ty = self.expression(arg)?;
+                    self.expect(arg, &ty, param)?;
+                }
+                ret
+            }
+            Expr::Array(array) => {
+                let elem = self.fresh();
+                for &e in &self.ast[array] {
+                    let ty = self.expression(e)?;
+                    self.expect(e, &ty, &elem)?;
+                }
+                let len = self.ast[array].len() as u32;
+                Type::Array(Box::new(elem), Box::new(Type::Size(len)))
+            }
+            Expr::Repeat(elem, len) => {
+                let elem = self.expression(elem)?;
+                Type::Array(Box::new(elem), Box::new(Type::Size(len)))
+            }
+            Expr::Index(array, index) => {
+                let ty = self.expression(array)?;
+                let elem = match self.shallow(&ty) {
+                    Type::Array(elem, _) => *elem,
+                    Type::Var(_) => {
+                        let elem = self.fresh();
+                        let len = self.fresh();
+                        let array_ty = Type::Array(Box::new(elem.clone()), Box::new(len));
+                        self.expect(array, &ty, &array_ty)?;
+                        elem
+                    }
+                    ty => {
+                        let ty = self.resolve(&ty);
+                        let message =
+                            format!("expected an array but found '{}'", ty.display(self.ast));
+                        return Err(self.error(array, message));
+                    }
+                };
+                self.condition(index)?;
+                elem
+            }
+            Expr::StructLit(lit) => {
+                let lit = &self.ast[lit];
+                let fields = self
+                    .fields(lit.name)
+                    .map_err(|message| self.error(expr, message))?;
+                for &(name, value) in &lit.fields {
+                    let Some((_, expected)) = fields.iter().find(|(n, _)| *n == name) else {
+                        let (ty, name) = (&self.ast[lit.name], &self.ast[name]);
+                        let message = format!("struct '{ty}' has no field '{name}'");
+                        return Err(self.error(value, message));
+                    };
+                    let found = self.expression(value)?;
+                    self.expect(value, &found, expected)?;
+                }
+                if let Some((missing, _)) = fields
+                    .iter()
+                    .find(|(n, _)| lit.fields.iter().all(|(m, _)| m != n))
+                {
+                    let (missing, ty) = (&self.ast[*missing], &self.ast[lit.name]);
+                    let message = format!("missing field '{missing}' in '{ty}'");
+                    return Err(self.error(expr, message));
+                }
+                Type::Struct(lit.name)
+            }
+            Expr::Field(value, name) => {
+                let ty = self.expression(value)?;
+                let ty = match self.shallow(&ty) {
+                    Type::Struct(ty) => ty,
+                    // Field names are not unique to one struct, so the struct
+                    // has to be known already.
+                    Type::Var(_) => {
+                        let name = &self.ast[name];
+                        let message = format!("type must be known to access field '{name}'");
+                        return Err(self.error(value, message));
+                    }
+                    ty => {
+                        let ty = self.resolve(&ty);
+                        let message =
+                            format!("expected a struct but found '{}'", ty.display(self.ast));
+                        return Err(self.error(value, message));
+                    }
+                };
+                let fields = self
+                    .fields(ty)
+                    .map_err(|message| self.error(value, message))?;
+                match fields.into_iter().find(|(n, _)| *n == name) {
+                    Some((_, ty)) => ty,
+                    None => {
+                        let (ty, name) = (&self.ast[ty], &self.ast[name]);
+                        let message = format!("struct '{ty}' has no field '{name}'");
+                        return Err(self.error(expr, message));
+                    }
+                }
+            }
+            Expr::VariantLit(lit) => {
+                let lit = &self.ast[lit];
+                let fields = self
+                    .variant(lit.ty, lit.variant, lit.args.len())
+                    .map_err(|message| self.error(expr, message))?;
+                for (&arg, expected) in lit.args.iter().zip(&fields) {
+                    let found = self.expression(arg)?;
+                    self.expect(arg, &found, expected)?;
+                }
+                Type::Enum(lit.ty)
+            }
+        };
+        self.types.insert(expr, ty.clone());
+        Ok(ty)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cb_parse::{parse, Call, Id, Lambda, StructLit};
+
+    /// The type of the first statement that is an expression.
+    fn ty(src: &str) -> String {
+        let ast = parse(src).unwrap();
+        let types = check(&ast).unwrap();
+        let expr = ast.program().iter().find_map(|&stmt| match ast[stmt] {
+            Stmt::Expr(expr) => Some(expr),
+            _ => None,
+        });
+        let ty = types[expr.unwrap()].display(&ast).to_string();
+        ty
+    }
+
+    fn error(src: &str) -> String {
+        check(&parse(src).unwrap()).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn integers() {
+        assert_eq!(ty("1 + 2 * 3"), "i32");
+        assert_eq!(ty("if 1 { 2 } else { -3 }"), "i32");
+        assert!(check(&parse("if 1 > 2 { 3 }").unwrap()).is_ok());
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(ty("λ(x: i32) -> i32 { x + 1 }(2)"), "i32");
+        assert_eq!(ty("fn(f, x) { f(f(x)) }(fn(y) { y }, 1)"), "i32");
+        assert_eq!(ty("fn(x) { fn(y) { x(y) } }(fn(z: i32) { z })(1)"), "i32");
+    }
+
+    #[test]
+    fn inferred_types() {
+        let ast = parse("fn(f, x) { f(x) + 1 }(fn(y) { y }, 2)").unwrap();
+        let types = check(&ast).unwrap();
+        let lambda = ast
+            .expr_ids()
+            .find(|&id| matches!(ast[id], Expr::Lambda(_)));
+        assert_eq!(
+            types[lambda.unwrap()].display(&ast).to_string(),
+            "fn(fn(i32) -> i32, i32) -> i32"
+        );
+    }
+
+    #[test]
+    fn bindings() {
+        let src = "let x = 1 fn(y) { x + y }(2) for i in 0..x { match i { n => n } } x";
+        let ast = parse(src).unwrap();
+        let checked = analyze(&ast);
+        assert_eq!(checked.error, None);
+        let bound: Vec<_> = ast
+            .expr_ids()
+            .filter_map(|id| {
+                let binding = checked.bindings.get(id)?;
+                Some((&src[ast.span(id)], &src[binding.span(&ast)]))
+            })
+            .collect();
+        assert_eq!(
+            bound,
+            [
+                ("x", "let x = 1"),
+                ("y", "fn(y) { x + y }"),
+                ("x", "let x = 1"),
+                ("i", "for i in 0..x { match i { n => n } }"),
+                ("n", "n"),
+                ("x", "let x = 1"),
+            ]
+        );
+        let checked = analyze(&parse("let s = \"a\" s + 1").unwrap());
+        assert_eq!(checked.types[ExprId::new(0)], Type::String);
+        assert_eq!(
+            checked.error.unwrap().to_string(),
+            "16:17 expected 'String' but found 'i32'"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(error("a + 1"), "0:1 unbound identifier 'a'");
+        assert_eq!(error("1(2)"), "0:1 expected a function but found 'i32'");
+        assert_eq!(
+            error("fn(x) { x }(1, 2)"),
+            "0:17 expected 1 arguments but found 2"
+        );
+        assert_eq!(
+            error("fn(x: i32) { x }(fn(y) { y })"),
+            "17:28 expected 'i32' but found 'fn(?0) -> ?0'"
+        );
+        assert_eq!(
+            error("fn(x) -> fn(i32) -> i32 { x + 1 }(1)"),
+            "26:31 expected 'fn(i32) -> i32' but found 'i32'"
+        );
+        assert_eq!(
+            error("fn(x) { x }"),
+            "0:11 cannot print a value of type 'fn(?0) -> ?0'"
+        );
+        assert_eq!(
+            error("fn(f) { f(f) }"),
+            "10:11 '?1' cannot be 'fn(?1) -> ?2', which contains it"
+        );
+        assert_eq!(
+            error("1 + fn() { if 1 { 2 } }()"),
+            "11:21 'if' without 'else' used as a value"
+        );
+    }
+
+    #[test]
+    fn arrays() {
+        assert_eq!(ty("[1, 2][0]"), "i32");
+        assert_eq!(ty("len([[1; 2], [3, 4]])"), "i32");
+        assert_eq!(ty("fn(a: [i32; 2]) { a[0] + len(a) }([1, 2])"), "i32");
+        assert_eq!(ty("fn(a) { a[0][1] }([[1, 2]])"), "i32");
+        assert!(check(&parse("fn(len) { len }(1)").unwrap()).is_ok());
+    }
+
+    #[test]
+    fn array_errors() {
+        assert_eq!(
+            error("[1, fn() { 2 }]"),
+            "4:14 expected 'i32' but found 'fn() -> i32'"
+        );
+        assert_eq!(
+            error("[1, 2]"),
+            "0:6 cannot print a value of type '[i32; 2]'"
+        );
+        assert_eq!(error("1[0]"), "0:1 expected an array but found 'i32'");
+        assert_eq!(error("[1][[0]]"), "4:7 expected 'i32' but found '[i32; 1]'");
+        assert_eq!(
+            error("fn(a: [i32; 2]) { 1 }([1, 2, 3])"),
+            "22:31 expected '[i32; 2]' but found '[i32; 3]'"
+        );
+        assert_eq!(
+            error("fn(a) { a[0] = 1 }([2])"),
+            "8:16 assignment used as a value"
+        );
+        assert_eq!(
+            error("fn(a: [i32; 1]) { if 1 { a[0] = [1] } }"),
+            "32:35 expected 'i32' but found '[i32; 1]'"
+        );
+        assert_eq!(
+            error("[1](0)"),
+            "0:3 expected a function but found '[i32; 1]'"
+        );
+    }
+
+    #[test]
+    fn loops() {
+        assert_eq!(ty("loop { break 1 }"), "i32");
+        assert_eq!(
+            ty("fn(n) { let a = [0; 3] for i in 0..n { a[i] = i } a }(2)[1]"),
+            "i32"
+        );
+        assert_eq!(
+            ty("fn() { 'a: loop { loop { break 'a fn() { 1 } } } }()()"),
+            "i32"
+        );
+        let ast = parse("let x = 1 while x < 10 { x = x * 2 } for i in 0..x { i }").unwrap();
+        assert!(check(&ast).is_ok());
+    }
+
+    #[test]
+    fn loop_errors() {
+        assert_eq!(
+            error("let x = 1 x = [x]"),
+            "14:17 expected 'i32' but found '[i32; 1]'"
+        );
+        assert_eq!(
+            error("if 1 { let y = 1 } y"),
+            "19:20 unbound identifier 'y'"
+        );
+        assert_eq!(
+            error("loop { break [1] }"),
+            "0:18 cannot print a value of type '[i32; 1]'"
+        );
+        assert_eq!(
+            error("loop { if 1 { break } break 2 }"),
+            "0:31 'loop' breaks both with and without a value"
+        );
+        assert_eq!(
+            error("fn() { while 1 {} }() + 1"),
+            "7:17 statement without a value used as a value"
+        );
+        assert_eq!(
+            error("for i in 0..fn() { 1 } {}"),
+            "12:22 expected 'i32' but found 'fn() -> i32'"
+        );
+    }
+
+    /// The error for a program of one statement, built by `build` rather
+    /// than parsed, as a tree read from JSON would be.
+    fn built_error(build: impl FnOnce(&mut Ast) -> Expr) -> String {
+        let mut ast = Ast::new();
+        let expr = build(&mut ast);
+        let expr = ast.alloc_expr(expr, 0..1);
+        let stmt = ast.alloc_stmt(Stmt::Expr(expr));
+        ast.push(stmt);
+        check(&ast).unwrap_err().to_string()
+    }
+
+    /// Wraps `body` in a loop of `kind` with `label`.
+    fn in_loop(ast: &mut Ast, label: Option<&str>, kind: LoopKind, body: Expr) -> Expr {
+        let body = ast.alloc_expr(body, 0..1);
+        let label = label.map(|label| ast.intern(label));
+        Expr::Loop(ast.alloc_loop(Loop { label, kind, body }))
+    }
+
+    #[test]
+    fn built_errors() {
+        assert_eq!(
+            built_error(|_| Expr::Break(None, None)),
+            "0:1 'break' outside of a loop"
+        );
+        assert_eq!(
+            built_error(|ast| {
+                let body = Expr::Continue(Some(ast.intern("'b")));
+                in_loop(ast, Some("'a"), LoopKind::Loop, body)
+            }),
+            "0:1 no loop is labelled 'b"
+        );
+        assert_eq!(
+            built_error(|ast| {
+                let one = ast.alloc_expr(Expr::Atom(Atom::Int(1)), 0..1);
+                in_loop(
+                    ast,
+                    None,
+                    LoopKind::While(one),
+                    Expr::Break(None, Some(one)),
+                )
+            }),
+            "0:1 'break' with a value in a 'while' loop"
+        );
+        assert_eq!(
+            built_error(|ast| {
+                let body = ast.alloc_expr(Expr::Break(None, None), 0..1);
+                let lambda = ast.alloc_lambda(Lambda {
+                    params: vec![],
+                    ret: None,
+                    body,
+                });
+                let callee = ast.alloc_expr(Expr::Lambda(lambda), 0..1);
+                let args = vec![];
+                let call = Expr::Call(ast.alloc_call(Call { callee, args }));
+                in_loop(ast, None, LoopKind::Loop, call)
+            }),
+            "0:1 'break' outside of a loop"
+        );
+        assert_eq!(
+            built_error(|ast| {
+                let one = ast.alloc_expr(Expr::Atom(Atom::Int(1)), 0..1);
+                Expr::Assign(one, one)
+            }),
+            "0:1 only variables, array elements and fields can be assigned to"
+        );
+        assert_eq!(
+            built_error(|ast| {
+                let name = ast.intern("P");
+                Expr::StructLit(ast.alloc_struct_lit(StructLit {
+                    name,
+                    fields: vec![],
+                }))
+            }),
+            "0:1 unknown struct 'P'"
+        );
+    }
+
+    #[test]
+    fn structs() {
+        let point = "struct P { x: i32, y: [i32; 2] }";
+        assert_eq!(ty(&format!("{point} P {{ y: [1, 2], x: 3 }}.y[1]")), "i32");
+        assert_eq!(
+            ty(&format!("{point} fn(p) {{ p }}(P {{ x: 1, y: [2; 2] }}).x")),
+            "i32"
+        );
+        let src = format!("{point} struct L {{ p: P }} fn(l: L) {{ l.p.y[0] = l.p.x l }}(L {{ p: P {{ x: 1, y: [2, 3] }} }}).p.x");
+        let ast = parse(&src).unwrap();
+        let types = check(&ast).unwrap();
+        let lambda = ast
+            .expr_ids()
+            .find(|&id| matches!(ast[id], Expr::Lambda(_)));
+        assert_eq!(
+            types[lambda.unwrap()].display(&ast).to_string(),
+            "fn(L) -> L"
+        );
+    }
+
+    #[test]
+    fn struct_errors() {
+        let point = "struct P { x: i32, y: i32 } ";
+        assert_eq!(
+            error(&format!("{point}P {{ x: 1, z: 2 }}")),
+            "41:42 struct 'P' has no field 'z'"
+        );
+        assert_eq!(
+            error(&format!("{point}P {{ x: 1 }}")),
+            "28:38 missing field 'y' in 'P'"
+        );
+        assert_eq!(
+            error(&format!("{point}P {{ x: 1, y: [2] }}")),
+            "41:44 expected 'i32' but found '[i32; 1]'"
+        );
+        assert_eq!(
+            error(&format!("{point}P {{ x: 1, y: 2 }}.z")),
+            "28:46 struct 'P' has no field 'z'"
+        );
+        assert_eq!(
+            error(&format!("{point}fn(p) {{ p.x }}")),
+            "36:37 type must be known to access field 'x'"
+        );
+        assert_eq!(error("[1].x"), "0:3 expected a struct but found '[i32; 1]'");
+        assert_eq!(
+            error(&format!("{point}let p = P {{ x: 1, y: 2 }} p.x = p")),
+            "59:60 expected 'i32' but found 'P'"
+        );
+        assert_eq!(
+            error(&format!("{point}P {{ x: 1, y: 2 }}")),
+            "28:44 cannot print a value of type 'P'"
+        );
+    }
+
+    #[test]
+    fn enums() {
+        let shape = "enum S { C(i32), R(i32, i32), E } ";
+        assert_eq!(
+            ty(&format!("{shape}fn(s) {{ match s {{ S::C(r) => r, S::R(w, h) => w * h, S::E => 0 }} }}(S::E)")),
+            "i32"
+        );
+        let src = format!("{shape}fn(s) {{ match s {{ S::R(w, _) if w > 1 => w, _ => 0 }} }}");
+        let ast = parse(&src).unwrap();
+        let types = check(&ast).unwrap_err();
+        assert_eq!(
+            types.to_string(),
+            "34:88 cannot print a value of type 'fn(S) -> i32'"
+        );
+        assert!(check(
+            &parse(&format!(
+                "{shape}match S::C(1) {{ S::C(1) => {{}}, _ => {{}} }}"
+            ))
+            .unwrap()
+        )
+        .is_ok());
+        assert_eq!(ty("match 1 { 0 => 1, n => n * 2 }"), "i32");
+        assert_eq!(
+            ty("enum O { N, S(i32) } enum L2 { X } enum L { E, C(O, L2) } match L::E { L::C(O::S(x), _) => x, L::C(O::N, L2::X) => 0, L::E => 1 }"),
+            "i32"
+        );
+        assert!(check(
+            &parse(&format!(
+                "{shape}let x = match S::E {{ S::C(r) => r, _ => 0 }} + 1 x"
+            ))
+            .unwrap()
+        )
+        .is_ok());
+        assert_eq!(
+            error(&format!(
+                "{shape}let x = match S::E {{ S::C(r) => r, _ => {{}} }}"
+            )),
+            "42:78 statement without a value used as a value"
+        );
+    }
+
+    #[test]
+    fn enum_errors() {
+        let shape = "enum S { C(i32), R(i32, i32), E } ";
+        assert_eq!(error("T::A"), "0:4 unknown enum 'T'");
+        assert_eq!(
+            error(&format!("{shape}S::Q")),
+            "34:38 enum 'S' has no variant 'Q'"
+        );
+        assert_eq!(
+            error(&format!("{shape}S::R(1)")),
+            "34:41 expected 2 values but found 1"
+        );
+        assert_eq!(
+            error(&format!("{shape}S::C([1])")),
+            "39:42 expected 'i32' but found '[i32; 1]'"
+        );
+        assert_eq!(
+            error(&format!("{shape}S::E")),
+            "34:38 cannot print a value of type 'S'"
+        );
+        assert_eq!(
+            error(&format!("{shape}match S::E {{ 1 => 1, _ => 2 }}")),
+            "47:48 expected 'S' but found 'i32'"
+        );
+        assert_eq!(
+            error(&format!("{shape}match 1 {{ S::E => 1, _ => 2 }}")),
+            "44:48 expected 'i32' but found 'S'"
+        );
+        assert_eq!(
+            error(&format!("{shape}match S::E {{ S::R(x) => x, _ => 2 }}")),
+            "47:54 expected 2 values but found 1"
+        );
+        assert_eq!(
+            error(&format!(
+                "{shape}fn(s: S) {{ match s {{ S::C(x) => x, S::E => s }} }}(S::E)"
+            )),
+            "77:78 expected 'i32' but found 'S'"
+        );
+    }
+
+    #[test]
+    fn exhaustiveness() {
+        let shape = "enum S { C(i32), R(i32, i32), E } ";
+        let arms = |arms: &str| error(&format!("{shape}fn(s: S) {{ match s {{ {arms} }} }}(S::E)"));
+        assert_eq!(
+            arms("S::C(r) => r, S::E => 0"),
+            "45:80 pattern 'S::R(_, _)' is not covered"
+        );
+        assert_eq!(
+            arms("S::C(r) => r, S::R(w, h) if w > h => w, S::E => 0"),
+            "45:106 pattern 'S::R(_, _)' is not covered"
+        );
+        assert_eq!(
+            arms("S::C(0) => 0, S::R(_, _) => 1, S::E => 2"),
+            "45:97 pattern 'S::C(_)' is not covered"
+        );
+        assert_eq!(arms("x => 0, S::E => 1"), "63:67 unreachable pattern");
+        assert_eq!(
+            arms("S::C(_) => 0, S::R(1, _) => 1, _ => 2, S::R(_, 2) => 3"),
+            "94:104 unreachable pattern"
+        );
+        assert_eq!(
+            arms("S::C(1) => 0, S::C(1) if 1 => 1, _ => 2"),
+            "69:76 unreachable pattern"
+        );
+        assert_eq!(
+            arms("S::C(_) => 0, S::R(_, _) => 1, S::E => 2, _ => 3"),
+            "97:98 unreachable pattern"
+        );
+        assert_eq!(
+            error("match 1 { 0 => 1, 1 => 2 }"),
+            "0:26 pattern '_' is not covered"
+        );
+        assert_eq!(error("match 1 {}"), "0:10 pattern '_' is not covered");
+        assert_eq!(
+            error("enum O { N, S(i32) } enum P { A(O, O) } match P::A(O::N, O::N) { P::A(O::N, _) => 1, P::A(_, O::N) => 2 }"),
+            "40:105 pattern 'P::A(O::S(_), O::S(_))' is not covered"
+        );
+        assert_eq!(
+            error("enum O { N, S(i32) } fn(o: O) { match o { O::S(1) => 1 } }(O::N)"),
+            "32:56 pattern 'O::N' is not covered"
+        );
+        let empty = parse("enum V {} let f = fn(v: V) { match v {} 1 }").unwrap();
+        assert!(check(&empty).is_ok());
+    }
+
+    #[test]
+    fn prelude() {
+        assert_eq!(ty("\"hi\""), "String");
+        assert_eq!(ty("println(\"hi\")"), "()");
+        assert_eq!(ty("parse_int(read_line()) + len([1])"), "i32");
+        assert_eq!(ty("to_string(1)"), "String");
+        assert_eq!(ty("fn(s: String) -> String { s }(\"a\")"), "String");
+        assert_eq!(ty("fn(x) { println(x) }(\"a\")"), "()");
+        assert!(
+            check(&parse("exit(0) assert(1) if 1 { print(2) } else { print(3) }").unwrap()).is_ok()
+        );
+        assert!(check(&parse("let print = fn(x) { x } print([1])[0]").unwrap()).is_ok());
+    }
+
+    #[test]
+    fn prelude_errors() {
+        assert_eq!(
+            error("println([1])"),
+            "0:7 cannot print a value of type '[i32; 1]'"
+        );
+        assert_eq!(
+            error("let p = fn(x) { print(x) } p(fn() { 1 })"),
+            "16:21 cannot print a value of type 'fn() -> i32'"
+        );
+        assert_eq!(error("\"a\" - 1"), "0:3 expected 'i32' but found 'String'");
+        assert_eq!(
+            error("parse_int(1)"),
+            "10:11 expected 'String' but found 'i32'"
+        );
+        assert_eq!(error("1 + exit(1)"), "4:11 expected 'i32' but found '()'");
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(ty("\"a\" + to_string(1) + read_line()"), "String");
+        assert_eq!(ty("\"a\" == \"b\""), "i32");
+        assert_eq!(ty("(\"a\" + \"b\") != \"ab\""), "i32");
+        assert_eq!(ty("len(\"abc\") + len([1])"), "i32");
+        assert_eq!(ty("slice(\"abc\", 1, 2)"), "String");
+        assert_eq!(ty("fn(s: String, t) { s + t }(\"a\", \"b\")"), "String");
+        assert_eq!(ty("fn(x, y) { x == y }(1, 2)"), "i32");
+    }
+
+    #[test]
+    fn string_errors() {
+        assert_eq!(error("1 + \"a\""), "4:7 expected 'i32' but found 'String'");
+        assert_eq!(error("\"a\" == 1"), "7:8 expected 'String' but found 'i32'");
+        assert_eq!(
+            error("\"a\" * \"b\""),
+            "0:3 expected 'i32' but found 'String'"
+        );
+        assert_eq!(
+            error("fn(x, y) { x + y }(\"a\", \"b\")"),
+            "19:22 expected 'i32' but found 'String'"
+        );
+        assert_eq!(
+            error("len(1)"),
+            "0:3 expected an array or a string but found 'i32'"
+        );
+        assert_eq!(
+            error("slice(\"a\", \"b\", 1)"),
+            "11:14 expected 'i32' but found 'String'"
+        );
+    }
+}
@@ -0,0 +1,824 @@
+mod runtime;
+#[cfg(test)]
+mod test;
+
+use cb_parse::{
+    Ast, Atom, Call, CallId, Expr, ExprId, Loop, LoopId, LoopKind, Op, SideTable, Stmt, StmtId,
+    Symbol,
+};
+use cb_prelude::Intrinsic;
+use cb_typeck::Type;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+type CResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+const INDENT: &str = "  ";
+
+/// The size of a page of linear memory.
+const PAGE: usize = 65536;
+
+/// Compiles a program into a binary WebAssembly module.
+pub fn compile(ast: &Ast, types: &SideTable<ExprId, Type>) -> CResult<Vec<u8>> {
+    Ok(wat::parse_str(compile_wat(ast, types)?)?)
+}
+
+/// Compiles a program into the WebAssembly text format, given the types
+/// that [`cb_typeck::check`] found for its expressions. The module imports
+/// `env.print` and exports `main`, which passes the value of every top level
+/// expression to `print`.
+///
+/// The prelude needs more from the host, and only what a program uses is
+/// imported: `env.write(ptr, len)` writes UTF-8 text from the exported
+/// `memory`, `env.write_int(i)` writes an integer without a newline and
+/// `env.exit(code)` stops the program without returning. `read_line` asks
+/// `env.read_line()` for the length of the next line, which is empty at the
+/// end of the input, then `env.take_line(ptr)` to copy the line to memory.
+/// A failed `assert` traps.
+///
+/// Strings are laid out in the exported memory as the [`runtime`] module
+/// describes. Variables are locals of `main`, numbered like the variables
+/// of the C backend, and a loop is a `loop` inside the `block` that `break`
+/// leaves.
+///
+/// A function bound by a top level `let` becomes a function of the module,
+/// as long as it only reads its parameters and other such functions. The
+/// last one bound to each name is exported under it, unless the name is
+/// `main` or `memory`.
+pub fn compile_wat(ast: &Ast, types: &SideTable<ExprId, Type>) -> CResult<String> {
+    let mut w = CodeGen {
+        ast,
+        types,
+        out: String::new(),
+        depth: 2,
+        divides: false,
+        writes_ints: false,
+        exits: false,
+        writes: false,
+        data: vec![],
+        helpers: BTreeSet::new(),
+        locals: vec![],
+        vars: vec![],
+        declared: HashMap::new(),
+        loops: vec![],
+        loop_count: 0,
+        functions: String::new(),
+        boundary: 0,
+    };
+    let mut exports = HashMap::new();
+    for &stmt in ast.program() {
+        if let Some((name, value)) = function(ast, stmt) {
+            exports.insert(name, value);
+        }
+    }
+    for &stmt in ast.program() {
+        // A struct or enum declaration only names a type, so it generates no
+        // code.
+        match (function(ast, stmt), ast[stmt]) {
+            (Some((name, value)), _) => {
+                let export = exports[&name] == value && !["main", "memory"].contains(&&ast[name]);
+                w.function(name, value, export)?;
+            }
+            (None, Stmt::Expr(expr)) => w.statement(expr, Sink::Print)?,
+            (None, _) => {}
+        }
+    }
+    let body = std::mem::take(&mut w.out);
+    w.depth = 0;
+    w.line("(module");
+    w.depth += 1;
+    w.line("(import \"env\" \"print\" (func $print (param i32)))");
+    if w.writes {
+        w.line("(import \"env\" \"write\" (func $write (param i32 i32)))");
+    }
+    if w.writes_ints {
+        w.line("(import \"env\" \"write_int\" (func $write_int (param i32)))");
+    }
+    if w.exits {
+        w.line("(import \"env\" \"exit\" (func $exit (param i32)))");
+    }
+    if w.helpers.contains("read_line") {
+        w.line("(import \"env\" \"read_line\" (func $next_line (result i32)))");
+        w.line("(import \"env\" \"take_line\" (func $take_line (param i32)))");
+    }
+    if !w.data.is_empty() || !w.helpers.is_empty() {
+        let len: usize = w.data.iter().map(String::len).sum();
+        w.line(&format!(
+            "(memory (export \"memory\") {})",
+            len.div_ceil(PAGE).max(1)
+        ));
+        if w.helpers.contains("alloc") {
+            w.line(&format!("(global $heap (mut i32) (i32.const {len}))"));
+        }
+        let mut offset = 0;
+        for text in std::mem::take(&mut w.data) {
+            w.line(&format!(
+                "(data (i32.const {offset}) {})",
+                wat_string(&text)
+            ));
+            offset += text.len();
+        }
+    }
+    w.line("(func $main (export \"main\")");
+    w.depth += 1;
+    for (local, ty) in std::mem::take(&mut w.locals) {
+        w.line(&format!("(local {local} {ty})"));
+    }
+    w.depth -= 1;
+    w.out.push_str(&body);
+    w.line(")");
+    let functions = std::mem::take(&mut w.functions);
+    w.out.push_str(&functions);
+    if w.divides {
+        w.divide();
+    }
+    for name in std::mem::take(&mut w.helpers) {
+        for line in runtime::helper(name).wat.lines() {
+            w.line(line);
+        }
+    }
+    w.depth -= 1;
+    w.line(")");
+    Ok(w.out)
+}
+
+#[derive(Debug)]
+enum CodeGenError {
+    Unit,
+    /// A feature only the interpreter runs so far, named in the plural.
+    Unsupported(&'static str),
+}
+
+impl fmt::Display for CodeGenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unit => write!(f, "statement without a value used as a value"),
+            Self::Unsupported(what) => write!(f, "{what} are only supported by the interpreter"),
+        }
+    }
+}
+
+impl std::error::Error for CodeGenError {}
+
+struct CodeGen<'a> {
+    ast: &'a Ast,
+    /// The type the checker gave each expression.
+    types: &'a SideTable<ExprId, Type>,
+    out: String,
+    depth: usize,
+    /// Whether the program divides, and so needs the `$div` helper.
+    divides: bool,
+    /// Whether the program prints integers with `print` or `println`.
+    writes_ints: bool,
+    exits: bool,
+    /// Whether the program writes strings, with `env.write`.
+    writes: bool,
+    /// The string literals, laid out in memory in order.
+    data: Vec<String>,
+    /// The [`runtime`] functions the program calls.
+    helpers: BTreeSet<&'static str>,
+    /// The locals of `main` and their types.
+    locals: Vec<(String, &'static str)>,
+    /// Variables in scope, innermost last.
+    vars: Vec<Var>,
+    /// How many variables of each name have been declared so far.
+    declared: HashMap<Symbol, usize>,
+    /// The numbers of the loops being generated, which name their labels,
+    /// with their own labels, innermost last.
+    loops: Vec<(Option<Symbol>, usize)>,
+    /// How many loops have been generated so far.
+    loop_count: usize,
+    /// The functions of the program generated so far, which follow `main`.
+    functions: String,
+    /// How many of `vars` are outside the function being generated, which
+    /// can only call those that are functions themselves.
+    boundary: usize,
+}
+
+/// What happens to the value a statement leaves on the stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sink {
+    /// It is printed, as the value of a top level expression is.
+    Print,
+    /// It is dropped, as the value of a statement in a block other than the
+    /// last is.
+    Drop,
+    /// It is left as the result of a function, as the value of its body is.
+    Return,
+}
+
+struct Var {
+    name: Symbol,
+    /// The local, as the name is not unique once shadowed.
+    local: String,
+    /// Whether `local` names a function rather than a local.
+    function: bool,
+}
+
+impl CodeGen<'_> {
+    fn line(&mut self, line: &str) {
+        self.out.push_str(&INDENT.repeat(self.depth));
+        self.out.push_str(line);
+        self.out.push('\n');
+    }
+
+    fn statement(&mut self, expr: ExprId, sink: Sink) -> CResult<()> {
+        match self.ast[expr] {
+            Expr::If(c, b) => {
+                self.expression(c)?;
+                self.line("if");
+                self.block(b, sink)?;
+                self.line("end");
+            }
+            Expr::IfElse(c, b1, b2) if sink == Sink::Drop || !self.has_value(expr) => {
+                self.expression(c)?;
+                match sink {
+                    Sink::Return => self.line(&format!("if (result {})", self.wasm_type(expr)?)),
+                    _ => self.line("if"),
+                }
+                self.block(b1, sink)?;
+                self.line("else");
+                self.block(b2, sink)?;
+                self.line("end");
+            }
+            Expr::Block(block) => {
+                let depth = self.vars.len();
+                let stmts = &self.ast[block];
+                for (i, &stmt) in stmts.iter().enumerate() {
+                    let sink = if i + 1 == stmts.len() {
+                        sink
+                    } else {
+                        Sink::Drop
+                    };
+                    self.statement(stmt, sink)?;
+                }
+                self.vars.truncate(depth);
+            }
+            Expr::Let(name, value) => {
+                let ty = self.wasm_type(value)?;
+                self.expression(value)?;
+                let local = self.declare(name, ty);
+                self.line(&format!("local.set {local}"));
+            }
+            Expr::Assign(target, value) => self.assign(target, value)?,
+            Expr::Loop(id) => self.run_loop(id)?,
+            Expr::Break(_, Some(_)) => {
+                return Err(Box::new(CodeGenError::Unsupported("loop values")))
+            }
+            Expr::Break(label, None) => {
+                let index = self.target(label);
+                self.line(&format!("br $break.{index}"));
+            }
+            Expr::Continue(label) => {
+                let index = self.target(label);
+                self.line(&format!("br $continue.{index}"));
+            }
+            Expr::Call(id) if self.is_unit(expr) => {
+                let call = &self.ast[id];
+                match self.intrinsic(call.callee) {
+                    Some(intrinsic) => self.intrinsic_call(intrinsic, &call.args)?,
+                    None => self.call_function(id)?,
+                }
+            }
+            _ if sink == Sink::Drop => {
+                self.expression(expr)?;
+                self.line("drop");
+            }
+            _ if sink == Sink::Return => self.expression(expr)?,
+            Expr::Atom(Atom::Str(text)) => self.write(&format!("{}\n", &self.ast[text])),
+            _ if self.types.get(expr) == Some(&Type::String) => {
+                self.expression(expr)?;
+                self.write_string();
+                self.write("\n");
+            }
+            _ => {
+                self.expression(expr)?;
+                self.line("call $print");
+            }
+        }
+        Ok(())
+    }
+
+    /// Declares a variable of type `ty`, returning its local.
+    fn declare(&mut self, name: Symbol, ty: &'static str) -> String {
+        let local = self.bind(name, false);
+        self.locals.push((local.clone(), ty));
+        local
+    }
+
+    /// Brings a parameter or a function into scope, returning its name in
+    /// the module.
+    fn bind(&mut self, name: Symbol, function: bool) -> String {
+        let count = self.declared.entry(name).or_default();
+        let local = format!("${}_{count}", &self.ast[name]);
+        *count += 1;
+        self.vars.push(Var {
+            name,
+            local: local.clone(),
+            function,
+        });
+        local
+    }
+
+    /// Writes the lambda `value`, bound to `name` at the top level, as a
+    /// function of the module with locals of its own. Its body leaves the
+    /// value of its last statement as the result.
+    fn function(&mut self, name: Symbol, value: ExprId, export: bool) -> CResult<()> {
+        let Expr::Lambda(lambda) = self.ast[value] else {
+            unreachable!("only lambdas become functions");
+        };
+        let Some(Type::Fn(params, ret)) = self.types.get(value) else {
+            unreachable!("the type checker gives a lambda a function type");
+        };
+        let mut signature = match export {
+            true => format!(" (export \"{}\")", &self.ast[name]),
+            false => String::new(),
+        };
+        let out = std::mem::take(&mut self.out);
+        let locals = std::mem::take(&mut self.locals);
+        let loops = std::mem::take(&mut self.loops);
+        let depth = self.vars.len();
+        self.boundary = depth;
+        for (param, ty) in self.ast[lambda].params.iter().zip(params) {
+            let ty = wasm_type(ty)?;
+            let local = self.bind(param.name, false);
+            signature.push_str(&format!(" (param {local} {ty})"));
+        }
+        let sink = match **ret {
+            Type::Unit => Sink::Drop,
+            ref ty => {
+                signature.push_str(&format!(" (result {})", wasm_type(ty)?));
+                Sink::Return
+            }
+        };
+        let indent = std::mem::replace(&mut self.depth, 1);
+        let body = self.block(self.ast[lambda].body, sink);
+        self.depth = indent;
+        self.boundary = 0;
+        self.loops = loops;
+        body?;
+        self.vars.truncate(depth);
+        let body = std::mem::replace(&mut self.out, out);
+        let locals = std::mem::replace(&mut self.locals, locals);
+        let local = self.bind(name, true);
+        let function = &mut self.functions;
+        function.push_str(&format!("{INDENT}(func {local}{signature}\n"));
+        for (local, ty) in locals {
+            function.push_str(&format!("{INDENT}{INDENT}(local {local} {ty})\n"));
+        }
+        function.push_str(&body);
+        function.push_str(&format!("{INDENT})\n"));
+        Ok(())
+    }
+
+    fn lookup(&self, name: Symbol) -> Option<&Var> {
+        self.vars.iter().rev().find(|var| var.name == name)
+    }
+
+    /// The variable `name` names, which a function can only read if it is
+    /// its own or another function.
+    fn var(&self, name: Symbol) -> CResult<Option<&Var>> {
+        let Some(i) = self.vars.iter().rposition(|var| var.name == name) else {
+            return Ok(None);
+        };
+        let var = &self.vars[i];
+        if i < self.boundary && !var.function {
+            return Err(Box::new(CodeGenError::Unsupported("closures")));
+        }
+        Ok(Some(var))
+    }
+
+    fn assign(&mut self, target: ExprId, value: ExprId) -> CResult<()> {
+        let name = match self.ast[target] {
+            Expr::Atom(Atom::Id(name)) => name,
+            Expr::Index(..) => return Err(Box::new(CodeGenError::Unsupported("arrays"))),
+            Expr::Field(..) => return Err(Box::new(CodeGenError::Unsupported("structs"))),
+            _ => unreachable!("the parser only allows places to be assigned to"),
+        };
+        self.expression(value)?;
+        let Some(var) = self.var(name)? else {
+            unreachable!("the type checker binds '{}'", &self.ast[name]);
+        };
+        let local = var.local.clone();
+        self.line(&format!("local.set {local}"));
+        Ok(())
+    }
+
+    /// Writes a loop. Its body is a block of its own, which `continue`
+    /// leaves. The bounds of a `for` are kept in locals of their own, and
+    /// its variable is a copy of the counter so that assigning to it only
+    /// changes the current iteration, as in the interpreter.
+    fn run_loop(&mut self, id: LoopId) -> CResult<()> {
+        let Loop { label, kind, body } = self.ast[id];
+        let index = self.loop_count;
+        self.loop_count += 1;
+        let (counter, end) = (format!("$counter.{index}"), format!("$end.{index}"));
+        if let LoopKind::For(_, from, to) = kind {
+            self.locals.push((counter.clone(), "i32"));
+            self.locals.push((end.clone(), "i32"));
+            self.expression(from)?;
+            self.line(&format!("local.set {counter}"));
+            self.expression(to)?;
+            self.line(&format!("local.set {end}"));
+        }
+        self.line(&format!("block $break.{index}"));
+        self.depth += 1;
+        self.line(&format!("loop $loop.{index}"));
+        self.depth += 1;
+        let depth = self.vars.len();
+        match kind {
+            LoopKind::Loop => {}
+            LoopKind::While(c) => {
+                self.expression(c)?;
+                self.line("i32.eqz");
+                self.line(&format!("br_if $break.{index}"));
+            }
+            LoopKind::For(name, ..) => {
+                self.line(&format!("local.get {counter}"));
+                self.line(&format!("local.get {end}"));
+                self.line("i32.ge_s");
+                self.line(&format!("br_if $break.{index}"));
+                self.line(&format!("local.get {counter}"));
+                let local = self.declare(name, "i32");
+                self.line(&format!("local.set {local}"));
+            }
+        }
+        self.line(&format!("block $continue.{index}"));
+        self.loops.push((label, index));
+        let body = self.block(body, Sink::Drop);
+        self.loops.pop();
+        body?;
+        self.line("end");
+        self.vars.truncate(depth);
+        if let LoopKind::For(..) = kind {
+            self.line(&format!("local.get {counter}"));
+            self.line("i32.const 1");
+            self.line("i32.add");
+            self.line(&format!("local.set {counter}"));
+        }
+        self.line(&format!("br $loop.{index}"));
+        self.depth -= 1;
+        self.line("end");
+        self.depth -= 1;
+        self.line("end");
+        Ok(())
+    }
+
+    /// The number of the innermost loop, or of the one with `label`, which
+    /// the parser made sure exists.
+    fn target(&self, label: Option<Symbol>) -> usize {
+        let frame = match label {
+            Some(label) => self.loops.iter().rev().find(|(l, _)| *l == Some(label)),
+            None => self.loops.last(),
+        };
+        frame.expect("the parser only allows jumps inside loops").1
+    }
+
+    /// Writes a call to `print`, `println`, `exit` or `assert`.
+    fn intrinsic_call(&mut self, intrinsic: Intrinsic, args: &[ExprId]) -> CResult<()> {
+        let &[arg] = args else {
+            return Err(Box::new(CodeGenError::Unsupported("functions")));
+        };
+        match (intrinsic, self.ast[arg]) {
+            (Intrinsic::Print, Expr::Atom(Atom::Str(text))) => self.write(&self.ast[text]),
+            (Intrinsic::Println, Expr::Atom(Atom::Str(text))) => {
+                self.write(&format!("{}\n", &self.ast[text]))
+            }
+            (Intrinsic::Print | Intrinsic::Println, _) => {
+                self.expression(arg)?;
+                if self.types.get(arg) == Some(&Type::String) {
+                    self.write_string();
+                } else {
+                    self.writes_ints = true;
+                    self.line("call $write_int");
+                }
+                if intrinsic == Intrinsic::Println {
+                    self.write("\n");
+                }
+            }
+            (Intrinsic::Exit, _) => {
+                self.expression(arg)?;
+                self.exits = true;
+                self.line("call $exit");
+                self.line("unreachable");
+            }
+            (Intrinsic::Assert, _) => {
+                self.expression(arg)?;
+                self.line("i32.eqz");
+                self.line("if");
+                self.line("  unreachable");
+                self.line("end");
+            }
+            _ => unreachable!("'{intrinsic}' has a value"),
+        }
+        Ok(())
+    }
+
+    /// Writes `text`.
+    fn write(&mut self, text: &str) {
+        self.writes = true;
+        let offset = self.data(text);
+        self.line(&format!("i32.const {offset}"));
+        self.line(&format!("i32.const {}", text.len()));
+        self.line("call $write");
+    }
+
+    /// Writes the string on the stack.
+    fn write_string(&mut self) {
+        self.writes = true;
+        self.call("write_string");
+    }
+
+    /// The address of `text` in memory, where it is stored once however
+    /// often it is used.
+    fn data(&mut self, text: &str) -> usize {
+        let index = match self.data.iter().position(|data| data == text) {
+            Some(index) => index,
+            None => {
+                self.data.push(text.to_string());
+                self.data.len() - 1
+            }
+        };
+        self.data[..index].iter().map(String::len).sum()
+    }
+
+    /// Calls a [`runtime`] function, which is then part of the module.
+    fn call(&mut self, name: &'static str) {
+        self.line(&format!("call ${name}"));
+        let mut names = vec![name];
+        while let Some(name) = names.pop() {
+            if self.helpers.insert(name) {
+                names.extend(runtime::helper(name).needs);
+            }
+        }
+    }
+
+    fn block(&mut self, expr: ExprId, sink: Sink) -> CResult<()> {
+        self.depth += 1;
+        self.statement(expr, sink)?;
+        self.depth -= 1;
+        Ok(())
+    }
+
+    fn expression(&mut self, expr: ExprId) -> CResult<()> {
+        let ast = self.ast;
+        match ast[expr] {
+            Expr::Atom(Atom::Int(i)) => self.line(&format!("i32.const {i}")),
+            Expr::Atom(Atom::Id(id)) => match self.var(id)? {
+                Some(var) if var.function => {
+                    return Err(Box::new(CodeGenError::Unsupported("functions")))
+                }
+                Some(var) => {
+                    let local = var.local.clone();
+                    self.line(&format!("local.get {local}"));
+                }
+                None if Intrinsic::lookup(&ast[id]).is_some() => {
+                    return Err(Box::new(CodeGenError::Unsupported("functions")))
+                }
+                None => unreachable!("the type checker binds '{}'", &ast[id]),
+            },
+            Expr::Atom(Atom::Str(text)) => {
+                let offset = self.data(&ast[text]) as i64;
+                let len = ast[text].len() as i64;
+                self.line(&format!("i64.const {}", offset << 32 | len));
+            }
+            Expr::Call(call) => {
+                let (callee, args) = (ast[call].callee, &ast[call].args);
+                let error = match (self.intrinsic(callee), ast[callee]) {
+                    (Some(Intrinsic::Len), _)
+                        if args.first().and_then(|&arg| self.types.get(arg))
+                            != Some(&Type::String) =>
+                    {
+                        CodeGenError::Unsupported("arrays")
+                    }
+                    (Some(i), _) if i.is_unit() => CodeGenError::Unit,
+                    (Some(i), _) if args.len() != i.arity() => {
+                        CodeGenError::Unsupported("functions")
+                    }
+                    (Some(i), _) => {
+                        for &arg in args {
+                            self.expression(arg)?;
+                        }
+                        match i {
+                            Intrinsic::Len => self.line("i32.wrap_i64"),
+                            Intrinsic::ToString => self.call("to_string"),
+                            Intrinsic::ParseInt => self.call("parse_int"),
+                            Intrinsic::ReadLine => self.call("read_line"),
+                            Intrinsic::Slice => self.call("slice"),
+                            _ => unreachable!("'{i}' has no value"),
+                        }
+                        return Ok(());
+                    }
+                    (None, _) if self.is_unit(expr) => CodeGenError::Unit,
+                    (None, _) => return self.call_function(call),
+                };
+                return Err(Box::new(error));
+            }
+            Expr::Unary(op, rhs) => {
+                self.line("i32.const 0");
+                self.expression(rhs)?;
+                self.line(instruction(op));
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                let strings = self.types.get(lhs) == Some(&Type::String);
+                self.expression(lhs)?;
+                self.expression(rhs)?;
+                match (strings, op) {
+                    (false, _) => {
+                        self.divides |= op == Op::Div;
+                        self.line(instruction(op));
+                    }
+                    (true, Op::Plus) => self.call("concat"),
+                    (true, _) => {
+                        self.call("equal");
+                        if op == Op::Ne {
+                            self.line("i32.eqz");
+                        }
+                    }
+                }
+            }
+            Expr::IfElse(c, b1, b2) => {
+                self.expression(c)?;
+                match self.types.get(expr) == Some(&Type::String) {
+                    true => self.line("if (result i64)"),
+                    false => self.line("if (result i32)"),
+                }
+                self.depth += 1;
+                self.expression(b1)?;
+                self.depth -= 1;
+                self.line("else");
+                self.depth += 1;
+                self.expression(b2)?;
+                self.depth -= 1;
+                self.line("end");
+            }
+            Expr::If(..) => {
+                unreachable!("the type checker rejects an 'if' without 'else' as a value")
+            }
+            Expr::Lambda(_) => return Err(Box::new(CodeGenError::Unsupported("functions"))),
+            Expr::Array(_) | Expr::Repeat(..) | Expr::Index(..) => {
+                return Err(Box::new(CodeGenError::Unsupported("arrays")))
+            }
+            Expr::Block(_) => return Err(Box::new(CodeGenError::Unsupported("block values"))),
+            Expr::Loop(_) => return Err(Box::new(CodeGenError::Unsupported("loop values"))),
+            Expr::Assign(..) | Expr::Let(..) | Expr::Break(..) | Expr::Continue(_) => {
+                return Err(Box::new(CodeGenError::Unit))
+            }
+            Expr::StructLit(_) | Expr::Field(..) => {
+                return Err(Box::new(CodeGenError::Unsupported("structs")))
+            }
+            Expr::VariantLit(_) | Expr::Match(_) => {
+                return Err(Box::new(CodeGenError::Unsupported("enums")))
+            }
+        }
+        Ok(())
+    }
+
+    /// Calls a function bound by a top level `let`.
+    fn call_function(&mut self, id: CallId) -> CResult<()> {
+        let ast = self.ast;
+        let Call { callee, ref args } = ast[id];
+        let function = match ast[callee] {
+            Expr::Atom(Atom::Id(name)) => self.var(name)?,
+            _ => None,
+        };
+        let function = match function {
+            Some(var) if var.function => var.local.clone(),
+            _ => return Err(Box::new(CodeGenError::Unsupported("functions"))),
+        };
+        for &arg in args {
+            self.expression(arg)?;
+        }
+        self.line(&format!("call {function}"));
+        Ok(())
+    }
+
+    /// The type of the value of `expr` in the module.
+    fn wasm_type(&self, expr: ExprId) -> CResult<&'static str> {
+        self.types.get(expr).map_or(Ok("i32"), wasm_type)
+    }
+
+    /// Whether every branch of an `if`/`else` chain produces a value, in
+    /// which case it becomes a single `if` block with a result.
+    fn has_value(&self, expr: ExprId) -> bool {
+        match self.ast[expr] {
+            Expr::If(..) => false,
+            Expr::IfElse(_, b1, b2) => self.has_value(b1) && self.has_value(b2),
+            _ => !is_statement(self.ast, expr) && !self.is_unit(expr),
+        }
+    }
+
+    /// The function from the prelude that `callee` names, unless a variable
+    /// shadows it.
+    fn intrinsic(&self, callee: ExprId) -> Option<Intrinsic> {
+        match self.ast[callee] {
+            Expr::Atom(Atom::Id(id)) if self.lookup(id).is_none() => {
+                Intrinsic::lookup(&self.ast[id])
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether `expr` is a call that only has an effect, which leaves
+    /// nothing on the stack.
+    fn is_unit(&self, expr: ExprId) -> bool {
+        match self.ast[expr] {
+            Expr::Call(call) => match self.intrinsic(self.ast[call].callee) {
+                Some(intrinsic) => intrinsic.is_unit(),
+                None => self.types.get(expr) == Some(&Type::Unit),
+            },
+            _ => false,
+        }
+    }
+
+    /// `i32.div_s` traps on `i32::MIN / -1` as well as on a zero divisor, so
+    /// division by `-1` is done as a wrapping negation instead.
+    fn divide(&mut self) {
+        self.line("(func $div (param $lhs i32) (param $rhs i32) (result i32)");
+        self.depth += 1;
+        for line in [
+            "local.get $rhs",
+            "i32.const -1",
+            "i32.eq",
+            "if (result i32)",
+            "  i32.const 0",
+            "  local.get $lhs",
+            "  i32.sub",
+            "else",
+            "  local.get $lhs",
+            "  local.get $rhs",
+            "  i32.div_s",
+            "end",
+        ] {
+            self.line(line);
+        }
+        self.depth -= 1;
+        self.line(")");
+    }
+}
+
+/// The name and the lambda of a top level `let` that binds a function.
+fn function(ast: &Ast, stmt: StmtId) -> Option<(Symbol, ExprId)> {
+    let Stmt::Expr(expr) = ast[stmt] else {
+        return None;
+    };
+    match ast[expr] {
+        Expr::Let(name, value) if matches!(ast[value], Expr::Lambda(_)) => Some((name, value)),
+        _ => None,
+    }
+}
+
+/// The type in the module of values of type `ty`: strings are `i64`, as
+/// the [`runtime`] module describes. A type nothing pins down is never
+/// looked at, so any will do.
+fn wasm_type(ty: &Type) -> CResult<&'static str> {
+    match ty {
+        Type::Int | Type::Var(_) => Ok("i32"),
+        Type::String => Ok("i64"),
+        Type::Array(..) => Err(Box::new(CodeGenError::Unsupported("arrays"))),
+        Type::Struct(_) => Err(Box::new(CodeGenError::Unsupported("structs"))),
+        Type::Enum(_) => Err(Box::new(CodeGenError::Unsupported("enums"))),
+        Type::Fn(..) => Err(Box::new(CodeGenError::Unsupported("functions"))),
+        Type::Unit | Type::Size(_) => Err(Box::new(CodeGenError::Unit)),
+    }
+}
+
+/// Whether `expr` can only be a statement: it runs for its effect on
+/// variables or on the flow of control.
+fn is_statement(ast: &Ast, expr: ExprId) -> bool {
+    matches!(
+        ast[expr],
+        Expr::Block(_)
+            | Expr::Let(..)
+            | Expr::Assign(..)
+            | Expr::Loop(_)
+            | Expr::Break(..)
+            | Expr::Continue(_)
+    )
+}
+
+/// A string in the text format holding the bytes of `text`.
+fn wat_string(text: &str) -> String {
+    let mut string = String::from('"');
+    for byte in text.bytes() {
+        match byte {
+            b' '..=b'~' if byte != b'"' && byte != b'\\' => string.push(byte as char),
+            _ => string.push_str(&format!("\\{byte:02x}")),
+        }
+    }
+    string.push('"');
+    string
+}
+
+fn instruction(op: Op) -> &'static str {
+    match op {
+        Op::Minus => "i32.sub",
+        Op::Plus => "i32.add",
+        Op::Mult => "i32.mul",
+        Op::Div => "call $div",
+        Op::Grt => "i32.gt_s",
+        Op::Les => "i32.lt_s",
+        Op::Eq => "i32.eq",
+        Op::Ne => "i32.ne",
+    }
+}
@@ -0,0 +1,428 @@
+//! Functions in the text format that the generated code calls for strings.
+//!
+//! A string is an `i64` holding a pointer into memory in its high half and
+//! its length in bytes in its low half. New strings are bump allocated from
+//! `$heap`, which starts after the data segments, and are never freed: the
+//! memory goes with the instance. A failed `slice` or `parse_int` traps.
+
+/// A helper and the helpers it calls.
+pub(crate) struct Helper {
+    pub(crate) needs: &'static [&'static str],
+    pub(crate) wat: &'static str,
+}
+
+pub(crate) fn helper(name: &str) -> Helper {
+    let (needs, wat): (&[&str], _) = match name {
+        "alloc" => (&[], ALLOC),
+        "copy" => (&[], COPY),
+        "concat" => (&["alloc", "copy"], CONCAT),
+        "equal" => (&[], EQUAL),
+        "boundary" => (&[], BOUNDARY),
+        "slice" => (&["boundary"], SLICE),
+        "to_string" => (&["alloc"], TO_STRING),
+        "parse_int" => (&[], PARSE_INT),
+        "read_line" => (&["alloc"], READ_LINE),
+        "write_string" => (&[], WRITE_STRING),
+        _ => unreachable!("no helper is called '{name}'"),
+    };
+    Helper { needs, wat }
+}
+
+/// Takes `$len` bytes from the heap, growing the memory if it runs out.
+const ALLOC: &str = r#"(func $alloc (param $len i32) (result i32)
+  (local $ptr i32)
+  global.get $heap
+  local.tee $ptr
+  local.get $len
+  i32.add
+  global.set $heap
+  global.get $heap
+  local.get $ptr
+  i32.lt_u
+  if
+    unreachable
+  end
+  global.get $heap
+  memory.size
+  i32.const 16
+  i32.shl
+  i32.gt_u
+  if
+    global.get $heap
+    memory.size
+    i32.const 16
+    i32.shl
+    i32.sub
+    i32.const 65535
+    i32.add
+    i32.const 16
+    i32.shr_u
+    memory.grow
+    i32.const -1
+    i32.eq
+    if
+      unreachable
+    end
+  end
+  local.get $ptr
+)"#;
+
+const COPY: &str = r#"(func $copy (param $dst i32) (param $src i32) (param $len i32)
+  block
+    loop
+      local.get $len
+      i32.eqz
+      br_if 1
+      local.get $dst
+      local.get $src
+      i32.load8_u
+      i32.store8
+      local.get $dst
+      i32.const 1
+      i32.add
+      local.set $dst
+      local.get $src
+      i32.const 1
+      i32.add
+      local.set $src
+      local.get $len
+      i32.const 1
+      i32.sub
+      local.set $len
+      br 0
+    end
+  end
+)"#;
+
+const CONCAT: &str = r#"(func $concat (param $a i64) (param $b i64) (result i64)
+  (local $a_len i32) (local $b_len i32) (local $ptr i32)
+  local.get $a
+  i32.wrap_i64
+  local.set $a_len
+  local.get $b
+  i32.wrap_i64
+  local.set $b_len
+  local.get $a_len
+  local.get $b_len
+  i32.add
+  call $alloc
+  local.tee $ptr
+  local.get $a
+  i64.const 32
+  i64.shr_u
+  i32.wrap_i64
+  local.get $a_len
+  call $copy
+  local.get $ptr
+  local.get $a_len
+  i32.add
+  local.get $b
+  i64.const 32
+  i64.shr_u
+  i32.wrap_i64
+  local.get $b_len
+  call $copy
+  local.get $ptr
+  i64.extend_i32_u
+  i64.const 32
+  i64.shl
+  local.get $a_len
+  local.get $b_len
+  i32.add
+  i64.extend_i32_u
+  i64.or
+)"#;
+
+const EQUAL: &str = r#"(func $equal (param $a i64) (param $b i64) (result i32)
+  (local $a_ptr i32) (local $b_ptr i32) (local $len i32)
+  local.get $a
+  i32.wrap_i64
+  local.tee $len
+  local.get $b
+  i32.wrap_i64
+  i32.ne
+  if
+    i32.const 0
+    return
+  end
+  local.get $a
+  i64.const 32
+  i64.shr_u
+  i32.wrap_i64
+  local.set $a_ptr
+  local.get $b
+  i64.const 32
+  i64.shr_u
+  i32.wrap_i64
+  local.set $b_ptr
+  block
+    loop
+      local.get $len
+      i32.eqz
+      br_if 1
+      local.get $a_ptr
+      i32.load8_u
+      local.get $b_ptr
+      i32.load8_u
+      i32.ne
+      if
+        i32.const 0
+        return
+      end
+      local.get $a_ptr
+      i32.const 1
+      i32.add
+      local.set $a_ptr
+      local.get $b_ptr
+      i32.const 1
+      i32.add
+      local.set $b_ptr
+      local.get $len
+      i32.const 1
+      i32.sub
+      local.set $len
+      br 0
+    end
+  end
+  i32.const 1
+)"#;
+
+/// Whether byte `$i` of a string starts a character, or is its end.
+const BOUNDARY: &str = r#"(func $boundary (param $ptr i32) (param $i i32) (param $len i32) (result i32)
+  local.get $i
+  local.get $len
+  i32.eq
+  if (result i32)
+    i32.const 1
+  else
+    local.get $ptr
+    local.get $i
+    i32.add
+    i32.load8_u
+    i32.const 192
+    i32.and
+    i32.const 128
+    i32.ne
+  end
+)"#;
+
+/// Strings never change, so a slice shares the bytes of its string.
+const SLICE: &str = r#"(func $slice (param $s i64) (param $from i32) (param $to i32) (result i64)
+  (local $ptr i32) (local $len i32)
+  local.get $s
+  i64.const 32
+  i64.shr_u
+  i32.wrap_i64
+  local.set $ptr
+  local.get $s
+  i32.wrap_i64
+  local.set $len
+  local.get $from
+  local.get $to
+  i32.gt_u
+  local.get $to
+  local.get $len
+  i32.gt_u
+  i32.or
+  if
+    unreachable
+  end
+  local.get $ptr
+  local.get $from
+  local.get $len
+  call $boundary
+  local.get $ptr
+  local.get $to
+  local.get $len
+  call $boundary
+  i32.and
+  i32.eqz
+  if
+    unreachable
+  end
+  local.get $ptr
+  local.get $from
+  i32.add
+  i64.extend_i32_u
+  i64.const 32
+  i64.shl
+  local.get $to
+  local.get $from
+  i32.sub
+  i64.extend_i32_u
+  i64.or
+)"#;
+
+/// Writes the digits backwards from the end of an 11 byte buffer, which
+/// fits `-2147483648`.
+const TO_STRING: &str = r#"(func $to_string (param $i i32) (result i64)
+  (local $n i64) (local $end i32) (local $pos i32)
+  local.get $i
+  i64.extend_i32_s
+  local.set $n
+  local.get $i
+  i32.const 0
+  i32.lt_s
+  if
+    i64.const 0
+    local.get $n
+    i64.sub
+    local.set $n
+  end
+  i32.const 11
+  call $alloc
+  i32.const 11
+  i32.add
+  local.tee $end
+  local.set $pos
+  loop
+    local.get $pos
+    i32.const 1
+    i32.sub
+    local.tee $pos
+    local.get $n
+    i64.const 10
+    i64.rem_u
+    i32.wrap_i64
+    i32.const 48
+    i32.add
+    i32.store8
+    local.get $n
+    i64.const 10
+    i64.div_u
+    local.tee $n
+    i64.const 0
+    i64.ne
+    br_if 0
+  end
+  local.get $i
+  i32.const 0
+  i32.lt_s
+  if
+    local.get $pos
+    i32.const 1
+    i32.sub
+    local.tee $pos
+    i32.const 45
+    i32.store8
+  end
+  local.get $pos
+  i64.extend_i32_u
+  i64.const 32
+  i64.shl
+  local.get $end
+  local.get $pos
+  i32.sub
+  i64.extend_i32_u
+  i64.or
+)"#;
+
+/// Accepts what Rust's `i32::from_str` does: a sign, then at least one
+/// digit.
+const PARSE_INT: &str = r#"(func $parse_int (param $s i64) (result i32)
+  (local $ptr i32) (local $len i32) (local $i i32) (local $negative i32) (local $n i64) (local $digit i32)
+  local.get $s
+  i64.const 32
+  i64.shr_u
+  i32.wrap_i64
+  local.set $ptr
+  local.get $s
+  i32.wrap_i64
+  local.tee $len
+  i32.eqz
+  if
+    unreachable
+  end
+  local.get $ptr
+  i32.load8_u
+  i32.const 45
+  i32.eq
+  local.tee $negative
+  local.get $ptr
+  i32.load8_u
+  i32.const 43
+  i32.eq
+  i32.or
+  local.tee $i
+  local.get $len
+  i32.eq
+  if
+    unreachable
+  end
+  loop
+    local.get $ptr
+    local.get $i
+    i32.add
+    i32.load8_u
+    i32.const 48
+    i32.sub
+    local.tee $digit
+    i32.const 9
+    i32.gt_u
+    if
+      unreachable
+    end
+    local.get $n
+    i64.const 10
+    i64.mul
+    local.get $digit
+    i64.extend_i32_u
+    i64.add
+    local.tee $n
+    i64.const 2147483648
+    i64.gt_u
+    if
+      unreachable
+    end
+    local.get $i
+    i32.const 1
+    i32.add
+    local.tee $i
+    local.get $len
+    i32.lt_u
+    br_if 0
+  end
+  local.get $negative
+  if
+    i64.const 0
+    local.get $n
+    i64.sub
+    local.set $n
+  end
+  local.get $n
+  i64.const 2147483647
+  i64.gt_s
+  if
+    unreachable
+  end
+  local.get $n
+  i32.wrap_i64
+)"#;
+
+/// Asks the host for the length of the next line, then for the line itself
+/// in memory allocated for it.
+const READ_LINE: &str = r#"(func $read_line (result i64)
+  (local $len i32) (local $ptr i32)
+  call $next_line
+  local.tee $len
+  call $alloc
+  local.tee $ptr
+  call $take_line
+  local.get $ptr
+  i64.extend_i32_u
+  i64.const 32
+  i64.shl
+  local.get $len
+  i64.extend_i32_u
+  i64.or
+)"#;
+
+const WRITE_STRING: &str = r#"(func $write_string (param $s i64)
+  local.get $s
+  i64.const 32
+  i64.shr_u
+  i32.wrap_i64
+  local.get $s
+  i32.wrap_i64
+  call $write
+)"#;
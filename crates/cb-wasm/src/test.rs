@@ -0,0 +1,461 @@
+use super::{compile, compile_wat};
+use cb_parse::{parse, Ast, ExprId, SideTable};
+use cb_prelude::{Capture, Exit, Host};
+use cb_typeck::Type;
+use wasmi::{Caller, Engine, Extern, Linker, Module, Store};
+
+/// Parses and type checks a program, which [`compile`] expects.
+fn check(src: &str) -> (Ast, SideTable<ExprId, Type>) {
+    let ast = parse(src).unwrap();
+    let types = cb_typeck::check(&ast).unwrap();
+    (ast, types)
+}
+
+/// Instantiates the module with `wasmi`, calls `main` and returns everything
+/// that was passed to `env.print`.
+fn run_wasm(wasm: &[u8]) -> Vec<i32> {
+    let engine = Engine::default();
+    let module = Module::new(&engine, wasm).unwrap();
+    let mut store = Store::new(&engine, Vec::<i32>::new());
+    let mut linker = <Linker<Vec<i32>>>::new(&engine);
+    linker
+        .func_wrap(
+            "env",
+            "print",
+            |mut caller: Caller<'_, Vec<i32>>, value: i32| {
+                caller.data_mut().push(value);
+            },
+        )
+        .unwrap();
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .unwrap()
+        .start(&mut store)
+        .unwrap();
+    let main = instance.get_typed_func::<(), ()>(&store, "main").unwrap();
+    main.call(&mut store, ()).unwrap();
+    store.into_data()
+}
+
+macro_rules! setup_test {
+    ($name:ident, $input:expr $(,)?) => {
+        #[test]
+        fn $name() {
+            let (ast, types) = check($input);
+            let wasm = compile(&ast, &types).unwrap();
+            let expected = cb_interp::run(&ast).unwrap();
+            assert_eq!(
+                run_wasm(&wasm),
+                expected,
+                "{}",
+                compile_wat(&ast, &types).unwrap()
+            );
+        }
+    };
+}
+
+setup_test!(wasm_atom, "1");
+setup_test!(wasm_unary, "-1 - -(2 * 3)");
+setup_test!(wasm_precedence, "-7 / 2 1 + 2 * 3 (1 + 2) * 3 10 - 4 - 3");
+setup_test!(wasm_divide_overflow, "-2147483648 / -1 7 / -1");
+setup_test!(
+    wasm_wrapping,
+    "2147483647 + 1 (0 - 2147483647) - 2 65536 * 65536 + 7 -(0 - 2147483647 - 1) 7 / -1"
+);
+setup_test!(wasm_comparison, "1 > 2 1 < 2");
+setup_test!(wasm_if, "if 1 > 3 { 1 } if 1 < 3 { 2 }");
+setup_test!(wasm_if_else, "if 1 > 3 { 1 } else { 2 }");
+setup_test!(
+    wasm_if_else_chain,
+    "if 3 < 1 { 1 } else if 1 > 2 { 2 } else if 2 > 1 { if 1 > 0 { 3 } } else { 4 }"
+);
+setup_test!(
+    wasm_variables,
+    "let x = 2 let y = x * 3 x = x + y x let x = x - 1 x if x > 0 { let z = x z } else { x }"
+);
+setup_test!(
+    wasm_while,
+    "let i = 0 let s = 0 while i < 5 { s = s + i i = i + 1 } s"
+);
+setup_test!(
+    wasm_for,
+    "let s = 0 for i in 0..4 { i = i * 10 s = s + i } s\n\
+     'outer: for i in 1..5 { for j in 0..9 { if j > i { continue 'outer } if i == 4 { break 'outer } s = s + 1 } } s\n\
+     loop { s = s - 7 if s < 0 { break } } s for i in 3..1 { s = 0 } s"
+);
+
+#[test]
+fn wasm_divide_by_zero() {
+    let (ast, types) = check("1 2 / (1 - 1) 3");
+    let wasm = compile(&ast, &types).unwrap();
+    let err = run_wasm_output(&wasm, "").unwrap_err();
+    assert_eq!(
+        err.as_trap_code(),
+        Some(wasmi::core::TrapCode::IntegerDivisionByZero)
+    );
+}
+
+#[test]
+fn wasm_if_result_type() {
+    let (ast, types) = check("if 1 > 3 { 1 } else { 2 }");
+    assert_eq!(
+        compile_wat(&ast, &types).unwrap(),
+        r#"(module
+  (import "env" "print" (func $print (param i32)))
+  (func $main (export "main")
+    i32.const 1
+    i32.const 3
+    i32.gt_s
+    if (result i32)
+      i32.const 1
+    else
+      i32.const 2
+    end
+    call $print
+  )
+)
+"#
+    );
+}
+
+#[test]
+fn wasm_functions() {
+    check_output(
+        "let double = fn(x: i32) -> i32 { x * 2 }\n\
+         let greet = fn(name: String) { println(\"hi \" + name) }\n\
+         let sign = fn(x) { if x < 0 { let s = \"-\" s } else { \"+\" } }\n\
+         let unused = fn(x, y) { 0 }\n\
+         double(double(3)) greet(\"you\") sign(-1) + sign(double(1))\n\
+         let quad = fn(x) { let d = double(x) double(d) } quad(read_line() == \"\" + 5)",
+    );
+}
+
+#[test]
+fn wasm_exported_functions() {
+    let (ast, types) = check(
+        "let main = fn() { 0 } let add = fn(x: i32, y: i32) -> i32 { x + y }\n\
+         let add = fn(x: i32, y: i32) -> i32 { add(x, y) + 1 } add(1, 2)",
+    );
+    let wat = compile_wat(&ast, &types).unwrap();
+    assert!(
+        wat.contains(
+            "  (func $add_0 (param $x_0 i32) (param $y_0 i32) (result i32)
+    local.get $x_0
+    local.get $y_0
+    i32.add
+  )
+"
+        ),
+        "{wat}"
+    );
+    let wasm = compile(&ast, &types).unwrap();
+    let engine = Engine::default();
+    let module = Module::new(&engine, &wasm[..]).unwrap();
+    let mut store = Store::new(&engine, ());
+    let instance = <Linker<()>>::new(&engine)
+        .func_wrap("env", "print", |_: i32| {})
+        .unwrap()
+        .instantiate(&mut store, &module)
+        .unwrap()
+        .start(&mut store)
+        .unwrap();
+    let add = instance
+        .get_typed_func::<(i32, i32), i32>(&store, "add")
+        .unwrap();
+    assert_eq!(add.call(&mut store, (40, 1)).unwrap(), 42);
+    assert_eq!(add.call(&mut store, (i32::MAX, 0)).unwrap(), i32::MIN);
+    let main = instance.get_typed_func::<(), ()>(&store, "main").unwrap();
+    main.call(&mut store, ()).unwrap();
+}
+
+#[test]
+fn wasm_unsupported() {
+    for (src, what) in [
+        ("fn(x) { x }(1)", "functions"),
+        ("let f = fn(x) { x } let g = f g(1)", "functions"),
+        ("if 1 { let f = fn(x) { x } f(1) }", "functions"),
+        ("let y = 1 let f = fn(x) { x + y } f(1)", "closures"),
+        ("let p = println 1", "functions"),
+        ("[1, 2][0]", "arrays"),
+        ("let a = [1] a[0]", "arrays"),
+        ("let a = [1] a[0] = 2", "arrays"),
+        ("struct P { x: i32 } P { x: 1 }.x", "structs"),
+        ("enum E { A } match E::A { E::A => 1 }", "enums"),
+        ("loop { break 1 }", "loop values"),
+        ("let x = loop { break 1 } x", "loop values"),
+        ("1 + if 1 { let y = 2 y } else { 3 }", "block values"),
+    ] {
+        let (ast, types) = check(src);
+        let err = compile(&ast, &types).unwrap_err();
+        let message = format!("{what} are only supported by the interpreter");
+        assert_eq!(err.to_string(), message, "{src}");
+    }
+}
+
+/// Runs the module with a host for the whole prelude, reading `input`, and
+/// returns what it wrote and the code it exited with, if it called `exit`.
+/// A trap is an error.
+fn run_wasm_output(wasm: &[u8], input: &str) -> Result<(String, Option<i32>), wasmi::Error> {
+    let engine = Engine::default();
+    let module = Module::new(&engine, wasm).unwrap();
+    // The host and the line `env.read_line` read, until `env.take_line`.
+    let mut store = Store::new(&engine, (Capture::new(input), String::new()));
+    let mut linker = <Linker<(Capture, String)>>::new(&engine);
+    linker
+        .func_wrap(
+            "env",
+            "print",
+            |mut caller: Caller<'_, (Capture, String)>, value: i32| {
+                caller.data_mut().0.value(value);
+            },
+        )
+        .unwrap()
+        .func_wrap(
+            "env",
+            "write_int",
+            |mut caller: Caller<'_, (Capture, String)>, value: i32| {
+                caller.data_mut().0.write(&value.to_string());
+            },
+        )
+        .unwrap()
+        .func_wrap(
+            "env",
+            "write",
+            |mut caller: Caller<'_, (Capture, String)>, ptr: i32, len: i32| {
+                let mut bytes = vec![0; len as usize];
+                memory(&caller)
+                    .read(&caller, ptr as usize, &mut bytes)
+                    .unwrap();
+                caller
+                    .data_mut()
+                    .0
+                    .write(&String::from_utf8(bytes).unwrap());
+            },
+        )
+        .unwrap()
+        .func_wrap(
+            "env",
+            "exit",
+            |_: Caller<'_, (Capture, String)>, code: i32| -> Result<(), wasmi::Error> {
+                Err(wasmi::Error::i32_exit(code))
+            },
+        )
+        .unwrap()
+        .func_wrap(
+            "env",
+            "read_line",
+            |mut caller: Caller<'_, (Capture, String)>| -> i32 {
+                let (host, line) = caller.data_mut();
+                *line = host.read_line().unwrap_or_default();
+                line.len() as i32
+            },
+        )
+        .unwrap()
+        .func_wrap(
+            "env",
+            "take_line",
+            |mut caller: Caller<'_, (Capture, String)>, ptr: i32| {
+                let line = std::mem::take(&mut caller.data_mut().1);
+                let memory = memory(&caller);
+                memory
+                    .write(&mut caller, ptr as usize, line.as_bytes())
+                    .unwrap();
+            },
+        )
+        .unwrap();
+    let instance = linker
+        .instantiate(&mut store, &module)
+        .unwrap()
+        .start(&mut store)
+        .unwrap();
+    let main = instance.get_typed_func::<(), ()>(&store, "main").unwrap();
+    let exit = match main.call(&mut store, ()) {
+        Ok(()) => None,
+        Err(e) => Some(e.i32_exit_status().ok_or(e)?),
+    };
+    Ok((store.into_data().0.output, exit))
+}
+
+fn memory(caller: &Caller<'_, (Capture, String)>) -> wasmi::Memory {
+    match caller.get_export("memory") {
+        Some(Extern::Memory(memory)) => memory,
+        _ => panic!("a module that writes exports its memory"),
+    }
+}
+
+/// Checks that the module writes what the interpreter does.
+fn check_output(src: &str) {
+    check_output_with(src, "");
+}
+
+fn check_output_with(src: &str, input: &str) {
+    let (ast, types) = check(src);
+    let wasm = compile(&ast, &types).unwrap();
+    let mut host = Capture::new(input);
+    let exit = match cb_interp::run_with(&ast, &mut host) {
+        Ok(()) => None,
+        Err(e) => Some(e.downcast_ref::<Exit>().unwrap().0),
+    };
+    assert_eq!(
+        run_wasm_output(&wasm, input).unwrap(),
+        (host.output, exit),
+        "{}",
+        compile_wat(&ast, &types).unwrap()
+    );
+}
+
+#[test]
+fn wasm_loops_output() {
+    let (ast, types) = check("let n = 0 for i in 0..2 { while 1 { n = n + i break } }");
+    let wat = compile_wat(&ast, &types).unwrap();
+    assert!(
+        wat.contains(
+            "  (func $main (export \"main\")
+    (local $n_0 i32)
+    (local $counter.0 i32)
+    (local $end.0 i32)
+    (local $i_0 i32)
+    i32.const 0
+    local.set $n_0
+    i32.const 0
+    local.set $counter.0
+    i32.const 2
+    local.set $end.0
+    block $break.0
+      loop $loop.0
+        local.get $counter.0
+        local.get $end.0
+        i32.ge_s
+        br_if $break.0
+        local.get $counter.0
+        local.set $i_0
+        block $continue.0
+          block $break.1
+            loop $loop.1
+              i32.const 1
+              i32.eqz
+              br_if $break.1
+              block $continue.1
+                local.get $n_0
+                local.get $i_0
+                i32.add
+                local.set $n_0
+                br $break.1
+              end
+              br $loop.1
+            end
+          end
+        end
+        local.get $counter.0
+        i32.const 1
+        i32.add
+        local.set $counter.0
+        br $loop.0
+      end
+    end
+  )
+"
+        ),
+        "{wat}"
+    );
+}
+
+#[test]
+fn wasm_string_variables() {
+    check_output(
+        "let s = \"a\" for i in 0..3 { s = s + to_string(i) } println(s) s let s = len(s) s",
+    );
+    check_output("let i = 0 while 1 { i = i + 1 if i > 2 { exit(i) } }");
+}
+
+#[test]
+fn wasm_prelude() {
+    check_output(
+        "print(1 + 2) println(-4) print(\"a\\t\\\"b\\\" \") println(\"é\") \"s\" 5\n\
+         if 1 > 2 { println(1) } else { print(\"\\0x\\n\") } assert(1) print(\"a\\t\\\"b\\\" \")",
+    );
+    check_output("1 if 2 > 1 { exit(2) } 3");
+    let (ast, types) = check("assert(0 > 1)");
+    let wasm = compile(&ast, &types).unwrap();
+    let engine = Engine::default();
+    let module = Module::new(&engine, &wasm[..]).unwrap();
+    let mut store = Store::new(&engine, ());
+    let instance = <Linker<()>>::new(&engine)
+        .func_wrap("env", "print", |_: i32| {})
+        .unwrap()
+        .instantiate(&mut store, &module)
+        .unwrap()
+        .start(&mut store)
+        .unwrap();
+    let main = instance.get_typed_func::<(), ()>(&store, "main").unwrap();
+    assert!(main.call(&mut store, ()).is_err());
+}
+
+#[test]
+fn wasm_prelude_imports() {
+    let (ast, types) = check("println(\"hi\") println(\"hi\") exit(1)");
+    assert_eq!(
+        compile_wat(&ast, &types).unwrap(),
+        r#"(module
+  (import "env" "print" (func $print (param i32)))
+  (import "env" "write" (func $write (param i32 i32)))
+  (import "env" "exit" (func $exit (param i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "hi\0a")
+  (func $main (export "main")
+    i32.const 0
+    i32.const 3
+    call $write
+    i32.const 0
+    i32.const 3
+    call $write
+    i32.const 1
+    call $exit
+    unreachable
+  )
+)
+"#
+    );
+}
+
+setup_test!(
+    wasm_equality,
+    "(1 == 1) (1 != 1) (\"ab\" == (\"a\" + \"b\")) (\"a\" != \"b\") len(\"hé\")"
+);
+
+#[test]
+fn wasm_strings() {
+    check_output(
+        "println(\"n = \" + to_string(parse_int(\"-42\") * 2)) slice(\"héllo\", 1, 3)\n\
+         if len(\"abc\") > 2 { \"long\" } else { \"short\" } print(slice(\"ab\", 1, 2) + \"\\n\")\n\
+         println(to_string(0 - 2147483647 - 1) + to_string(0)) parse_int(\"+7\") parse_int(\"-2147483648\")",
+    );
+    check_output_with(
+        "println(to_string(parse_int(read_line()) + 1)) read_line() + \"!\" read_line()",
+        "7\nmore\n",
+    );
+    // Enough text to grow the memory a few pages past the data.
+    let text = format!("\"{}\"", "é".repeat(20000));
+    check_output(&format!(
+        "len(({text} + {text}) + ({text} + {text})) slice({text} + \"!\", 39998, 40001)"
+    ));
+}
+
+#[test]
+fn wasm_string_errors() {
+    for src in [
+        "slice(\"é\", 0, 1)",
+        "slice(\"abc\", 2, 1)",
+        "slice(\"abc\", 0 - 1, 1)",
+        "slice(\"abc\", 1, 4)",
+        "parse_int(\"\")",
+        "parse_int(\"-\")",
+        "parse_int(\"1x\")",
+        "parse_int(\"2147483648\")",
+    ] {
+        let (ast, types) = check(src);
+        let wasm = compile(&ast, &types).unwrap();
+        let err = run_wasm_output(&wasm, "").unwrap_err();
+        assert!(err.as_trap_code().is_some(), "{src}: {err}");
+    }
+}
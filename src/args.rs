@@ -1,55 +1,253 @@
-use clap::{crate_description, crate_name, crate_version, Arg, ColorChoice, Command};
+use crate::emit::{Artifact, Emit};
+use clap::{crate_description, crate_name, crate_version, Arg, ArgAction, ColorChoice, Command};
+use clap_complete::Shell;
 
-#[derive(Debug, Default)]
-pub struct Settings {
-    pub filename: Option<String>,
-    pub debug_token: bool,
-    pub debug_ast: bool,
-    pub debug_graph: bool,
+pub use cflat::project::Target;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Interp,
+    Jit,
 }
 
-pub fn cargs() -> Settings {
-    let matches = Command::new(crate_name!())
+impl Backend {
+    fn lookup(name: &str) -> Option<Self> {
+        match name {
+            "interp" => Some(Self::Interp),
+            "jit" => Some(Self::Jit),
+            _ => None,
+        }
+    }
+}
+
+/// The subcommand picked on the command line along with its options.
+#[derive(Debug)]
+pub enum Settings {
+    Check {
+        filename: String,
+        emit: Vec<Artifact>,
+    },
+    Run {
+        filename: String,
+        backend: Backend,
+    },
+    /// Without a file, builds the project the current directory is in.
+    Build {
+        filename: Option<String>,
+        target: Option<Target>,
+        output: Option<String>,
+        emit: Vec<Artifact>,
+    },
+    New {
+        path: String,
+    },
+    Fmt {
+        filenames: Vec<String>,
+        check: bool,
+    },
+    Repl,
+    Test {
+        paths: Vec<String>,
+        backend: Backend,
+    },
+    Lsp,
+    Completions {
+        shell: Shell,
+    },
+}
+
+const EXIT_CODES: &str = "\
+Exit codes:
+  0  success
+  1  the program has errors, or a check or test failed
+  2  invalid command line
+  3  the program failed while running
+  4  a file could not be read or written
+
+'cbc run' exits with the code the program passes to 'exit', if it calls it,
+when that is 0 or from 5 to 255. Other codes fail with 3, since 1 to 4 are
+cbc's own and the rest do not fit in an exit status.";
+
+fn filename_arg() -> Arg {
+    Arg::new("filename")
+        .required(true)
+        .help("C Flat source file, or a .json AST as written by --emit=ast-json")
+}
+
+fn emit_arg() -> Arg {
+    Arg::new("emit")
+        .long("emit")
+        .action(ArgAction::Append)
+        .value_delimiter(',')
+        .value_parser(Artifact::parse)
+        .value_name("KIND[=PATH]")
+        .help(format!(
+            "Write pipeline artifacts, any of: {} ({})",
+            Emit::NAMES.join(", "),
+            Emit::ALIASES
+                .map(|(alias, name)| format!("{alias} is an alias of {name}"))
+                .join(", ")
+        ))
+}
+
+fn backend_arg() -> Arg {
+    Arg::new("backend")
+        .long("backend")
+        .default_value("interp")
+        .value_parser(["interp", "jit"])
+        .help("Run with the tree walking interpreter or the Cranelift JIT")
+}
+
+pub fn command() -> Command {
+    Command::new(crate_name!())
         .color(ColorChoice::Always)
         .version(crate_version!())
         .author("Cowboy8625")
         .about(crate_description!())
-        .arg(Arg::new("filename"))
-        .arg(
-            Arg::new("debug-token")
-                .long("debug-token")
-                .required(false)
-                .action(clap::ArgAction::SetTrue)
-                .help("Show Tokens as they are created"),
+        .after_help(EXIT_CODES)
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("check")
+                .about("Check a file for errors without generating code")
+                .arg(filename_arg())
+                .arg(emit_arg()),
+        )
+        .subcommand(
+            Command::new("run")
+                .about("Run a file and print the value of each expression")
+                .arg(filename_arg())
+                .arg(backend_arg()),
         )
-        .arg(
-            Arg::new("debug-ast")
-                .long("debug-ast")
-                .required(false)
-                .action(clap::ArgAction::SetTrue)
-                .help("Show Ast"),
+        .subcommand(
+            Command::new("build")
+                .about("Compile a file, or the current project into its target directory")
+                .arg(
+                    filename_arg().required(false).requires("target").help(
+                        "File to build, or the project found through cflat.toml when omitted",
+                    ),
+                )
+                .arg(
+                    Arg::new("target")
+                        .long("target")
+                        .value_parser(["c", "wasm32"])
+                        .help("Backend to generate code for, taken from cflat.toml by default"),
+                )
+                .arg(emit_arg())
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .help("Where to write the output when a single artifact is emitted"),
+                ),
         )
-        .arg(
-            Arg::new("debug-graph")
-                .long("debug-graph")
-                .required(false)
-                .action(clap::ArgAction::SetTrue)
-                .help("Turns AST into a visual graph"),
+        .subcommand(
+            Command::new("fmt")
+                .about("Format source files in place")
+                .arg(
+                    Arg::new("filename")
+                        .action(ArgAction::Append)
+                        .help("Files to format, reads stdin and writes stdout when omitted"),
+                )
+                .arg(
+                    Arg::new("check")
+                        .long("check")
+                        .action(ArgAction::SetTrue)
+                        .help("Fail instead of rewriting files that are not formatted"),
+                ),
         )
-        .get_matches();
+        .subcommand(
+            Command::new("new")
+                .about("Create a project with a cflat.toml manifest and a src/main.cb")
+                .arg(
+                    Arg::new("path")
+                        .required(true)
+                        .help("Directory to create, whose name is the project's name"),
+                ),
+        )
+        .subcommand(Command::new("repl").about("Evaluate expressions interactively"))
+        .subcommand(
+            Command::new("test")
+                .about("Run every .cb file and compare its output with a sibling .out file")
+                .arg(
+                    Arg::new("path")
+                        .action(ArgAction::Append)
+                        .required(true)
+                        .help("Files or directories to test"),
+                )
+                .arg(backend_arg()),
+        )
+        .subcommand(Command::new("lsp").about("Start a language server on stdin and stdout"))
+        .subcommand(
+            Command::new("completions")
+                .about("Print a shell completion script")
+                .arg(
+                    Arg::new("shell")
+                        .required(true)
+                        .value_parser(clap::value_parser!(Shell)),
+                ),
+        )
+}
 
-    let mut setting = Settings::default();
-    if let Some(filename) = matches.get_one::<String>("filename") {
-        setting.filename = Some(filename.to_string());
+pub fn cargs() -> Settings {
+    let matches = command().get_matches();
+    let filename = |m: &clap::ArgMatches| {
+        m.get_one::<String>("filename")
+            .cloned()
+            .expect("filename is required")
+    };
+    let emit = |m: &clap::ArgMatches| {
+        m.get_many::<Artifact>("emit")
+            .map(|a| a.cloned().collect())
+            .unwrap_or_default()
+    };
+    let backend = |m: &clap::ArgMatches| {
+        m.get_one::<String>("backend")
+            .and_then(|b| Backend::lookup(b))
+            .expect("backend has a default")
+    };
+    match matches.subcommand() {
+        Some(("check", m)) => Settings::Check {
+            filename: filename(m),
+            emit: emit(m),
+        },
+        Some(("run", m)) => Settings::Run {
+            filename: filename(m),
+            backend: backend(m),
+        },
+        Some(("build", m)) => Settings::Build {
+            filename: m.get_one::<String>("filename").cloned(),
+            target: m
+                .get_one::<String>("target")
+                .map(|t| Target::lookup(t).expect("targets are checked by clap")),
+            output: m.get_one::<String>("output").cloned(),
+            emit: emit(m),
+        },
+        Some(("new", m)) => Settings::New {
+            path: m
+                .get_one::<String>("path")
+                .cloned()
+                .expect("path is required"),
+        },
+        Some(("fmt", m)) => Settings::Fmt {
+            filenames: m
+                .get_many::<String>("filename")
+                .map(|f| f.cloned().collect())
+                .unwrap_or_default(),
+            check: m.get_flag("check"),
+        },
+        Some(("repl", _)) => Settings::Repl,
+        Some(("test", m)) => Settings::Test {
+            paths: m
+                .get_many::<String>("path")
+                .map(|p| p.cloned().collect())
+                .unwrap_or_default(),
+            backend: backend(m),
+        },
+        Some(("lsp", _)) => Settings::Lsp,
+        Some(("completions", m)) => Settings::Completions {
+            shell: *m.get_one::<Shell>("shell").expect("shell is required"),
+        },
+        _ => unreachable!("a subcommand is required"),
     }
-    setting.debug_token = *matches
-        .get_one::<bool>("debug-token")
-        .expect("debug-token failed");
-    setting.debug_ast = *matches
-        .get_one::<bool>("debug-ast")
-        .expect("debug-ast failed");
-    setting.debug_graph = *matches
-        .get_one::<bool>("debug-graph")
-        .expect("debug-graph failed");
-    setting
 }
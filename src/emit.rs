@@ -0,0 +1,163 @@
+use crate::args::Target;
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+
+type CResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+/// An artifact of the pipeline that can be requested with `--emit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emit {
+    Tokens,
+    Cst,
+    Ast,
+    AstJson,
+    Dot,
+    Asm,
+    Obj,
+    Exe,
+}
+
+impl Emit {
+    pub const NAMES: [&'static str; 8] = [
+        "tokens", "cst", "ast", "ast-json", "dot", "asm", "obj", "exe",
+    ];
+
+    /// Names that [`Emit::lookup`] accepts besides [`Emit::NAMES`].
+    pub const ALIASES: [(&'static str, &'static str); 1] = [("wat", "asm")];
+
+    pub fn lookup(name: &str) -> Option<Self> {
+        match name {
+            "tokens" => Some(Self::Tokens),
+            "cst" => Some(Self::Cst),
+            "ast" => Some(Self::Ast),
+            "ast-json" => Some(Self::AstJson),
+            "dot" => Some(Self::Dot),
+            // The wasm target writes its asm in the text format, so `wat`
+            // names the same artifact.
+            "asm" | "wat" => Some(Self::Asm),
+            "obj" => Some(Self::Obj),
+            "exe" => Some(Self::Exe),
+            _ => None,
+        }
+    }
+
+    /// Whether the artifact only needs the source, so that it can be written
+    /// for a file that does not parse.
+    pub fn is_source(self) -> bool {
+        matches!(self, Self::Tokens | Self::Cst)
+    }
+
+    fn extension(self, target: Option<Target>) -> &'static str {
+        match (self, target) {
+            (Self::Tokens, _) => "tokens",
+            (Self::Cst, _) => "cst",
+            (Self::Ast, _) => "ast",
+            (Self::AstJson, _) => "json",
+            (Self::Dot, _) => "dot",
+            (Self::Asm, Some(Target::C)) => "c",
+            (Self::Asm, _) => "wat",
+            (Self::Obj, Some(Target::C)) => "o",
+            (Self::Obj | Self::Exe, Some(Target::Wasm32)) => "wasm",
+            (Self::Obj, None) => "o",
+            (Self::Exe, _) => "",
+        }
+    }
+}
+
+impl fmt::Display for Emit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Tokens => "tokens",
+            Self::Cst => "cst",
+            Self::Ast => "ast",
+            Self::AstJson => "ast-json",
+            Self::Dot => "dot",
+            Self::Asm => "asm",
+            Self::Obj => "obj",
+            Self::Exe => "exe",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// One `--emit=kind[=path]` request. A path of `-` writes to stdout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Artifact {
+    pub kind: Emit,
+    pub path: Option<String>,
+}
+
+impl Artifact {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let (kind, path) = match value.split_once('=') {
+            Some((kind, path)) => (kind, Some(path.to_string())),
+            None => (value, None),
+        };
+        let Some(kind) = Emit::lookup(kind) else {
+            return Err(format!(
+                "unknown artifact '{kind}', expected one of: {}",
+                Emit::NAMES.join(", ")
+            ));
+        };
+        Ok(Self { kind, path })
+    }
+
+    /// Where this artifact goes when no path was given: next to the input,
+    /// with an extension that depends on the artifact and the target. A
+    /// native executable drops the input's extension, or gets `.out` when
+    /// there is none to drop.
+    pub fn path(&self, filename: &str, target: Option<Target>) -> String {
+        let filename = Path::new(filename);
+        let extension = match self.kind.extension(target) {
+            "" if filename.extension().is_none() => "out",
+            extension => extension,
+        };
+        match &self.path {
+            Some(path) => path.clone(),
+            None => filename.with_extension(extension).display().to_string(),
+        }
+    }
+}
+
+/// Refuses to write an artifact over the file it was built from, which
+/// `prog --emit=exe` or `a.json --emit=ast-json` would otherwise do.
+pub fn check_output(path: &str, input: &str) -> Result<(), String> {
+    let output = Path::new(path).canonicalize();
+    match (output, Path::new(input).canonicalize()) {
+        (Ok(a), Ok(b)) if path != "-" && a == b => Err(format!(
+            "refusing to overwrite the input '{input}' with an artifact"
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Writes an artifact to `path`, or to stdout when `path` is `-`.
+pub fn write(path: &str, bytes: &[u8]) -> CResult<()> {
+    if path == "-" {
+        std::io::stdout().write_all(bytes)?;
+        return Ok(());
+    }
+    std::fs::write(path, bytes).map_err(|e| format!("failed to write '{path}': {e}"))?;
+    Ok(())
+}
+
+/// Hands generated C to the host compiler, producing an object file when
+/// `object` is set and an executable otherwise.
+pub fn cc(code: &str, path: &str, object: bool, opt_level: u8) -> CResult<()> {
+    let src = std::env::temp_dir().join(format!("cbc-{}.c", std::process::id()));
+    std::fs::write(&src, code)?;
+    let mut cc = Command::new("cc");
+    cc.arg("-std=c99").arg(format!("-O{opt_level}"));
+    if object {
+        cc.arg("-c");
+    }
+    let status = cc.arg("-o").arg(path).arg(&src).status();
+    let _ = std::fs::remove_file(&src);
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("cc failed with {status}").into()),
+        Err(e) => Err(format!("failed to run cc: {e}").into()),
+    }
+}
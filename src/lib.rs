@@ -1,2 +1,17 @@
-pub use cb_lexer::{Token, TokenDebug};
-pub use cb_parse::{parse, Atom, Expr, ParseDebug};
+pub use cb_c as c;
+pub use cb_fmt as fmt;
+pub use cb_interp as interp;
+pub use cb_jit as jit;
+pub use cb_lexer::{Scanner, SourceMap, Token, TokenKind};
+pub use cb_lsp as lsp;
+pub use cb_module as module;
+pub use cb_parse::{
+    dot, parse, print, syntax, visit, Arm, ArrayId, Ast, Atom, BlockId, Call, CallId, Enum, EnumId,
+    Expr, ExprId, Field, Lambda, LambdaId, Loop, LoopId, LoopKind, Match, MatchId, Param, Pat,
+    PatId, SideTable, Stmt, StmtId, Struct, StructId, StructLit, StructLitId, SyntaxError,
+    TypeExpr, Variant, VariantLit, VariantLitId,
+};
+pub use cb_prelude as prelude;
+pub use cb_project as project;
+pub use cb_typeck as typeck;
+pub use cb_wasm as wasm;
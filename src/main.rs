@@ -1,21 +1,661 @@
-use cflat;
 mod args;
-fn main() {
-    let settings = args::cargs();
-    if settings.debug_graph {
-        eprintln!("not implemented yet!");
-        std::process::exit(1);
+mod emit;
+use args::{Backend, Settings, Target};
+use cflat::module::Program;
+use cflat::prelude::{Capture, Exit, Host, Stdio};
+use cflat::project::{Project, MANIFEST};
+use cflat::typeck::Type;
+use cflat::{ExprId, SideTable};
+use emit::{Artifact, Emit};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+/// Why a subcommand failed, which decides the exit code documented in
+/// `cbc --help`.
+enum Failure {
+    /// The program has errors.
+    Errors(String),
+    /// A check or a test did not pass and has already been reported.
+    Failed,
+    /// The program failed while running.
+    Runtime(String),
+    /// The program called `exit` with this code, which `cbc run` passes on.
+    Exit(u8),
+    /// A file could not be read or written.
+    Io(String),
+}
+
+impl Failure {
+    fn code(&self) -> u8 {
+        match self {
+            Self::Errors(_) | Self::Failed => 1,
+            Self::Runtime(_) => 3,
+            Self::Exit(code) => *code,
+            Self::Io(_) => 4,
+        }
     }
-    let Some(filename) = settings.filename else {
-        eprintln!("No file given");
-        return;
+
+    /// The failure for a program that called `exit(code)`. Only 0 and 5 to
+    /// 255 are passed on: 1 to 4 are cbc's own codes and the rest do not fit
+    /// in an exit status, so those are reported as failing while running.
+    fn exit(filename: &str, code: i32) -> Self {
+        match u8::try_from(code) {
+            Ok(code) if !(1..=4).contains(&code) => Self::Exit(code),
+            _ => Self::Runtime(format!(
+                "{filename}: the program exited with {code}, but 'cbc run' can only pass on 0 and 5 to 255"
+            )),
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let result = match args::cargs() {
+        Settings::Check { filename, emit } => check(&filename, &emit),
+        Settings::Run { filename, backend } => run(&filename, backend),
+        Settings::Build {
+            filename,
+            target,
+            output,
+            emit,
+        } => build(filename.as_deref(), target, output, &emit),
+        Settings::New { path } => new(&path),
+        Settings::Fmt { filenames, check } => fmt(&filenames, check),
+        Settings::Repl => repl(),
+        Settings::Test { paths, backend } => test(&paths, backend),
+        Settings::Lsp => cflat::lsp::stdio().map_err(|e| Failure::Io(e.to_string())),
+        Settings::Completions { shell } => {
+            let mut cmd = args::command();
+            clap_complete::generate(shell, &mut cmd, "cbc", &mut std::io::stdout());
+            Ok(())
+        }
     };
-    let Ok(src) = std::fs::read_to_string(&filename) else {
-        eprintln!("failed to open '{filename}'");
-        return;
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(failure) => {
+            match &failure {
+                Failure::Errors(e) | Failure::Runtime(e) | Failure::Io(e) => eprintln!("{e}"),
+                Failure::Failed | Failure::Exit(_) => {}
+            }
+            ExitCode::from(failure.code())
+        }
+    }
+}
+
+fn read_source(filename: &str) -> Result<String, Failure> {
+    std::fs::read_to_string(filename)
+        .map_err(|e| Failure::Io(format!("failed to open '{filename}': {e}")))
+}
+
+/// Whether `filename` holds an AST as JSON rather than source code.
+fn is_json(filename: &str) -> bool {
+    Path::new(filename).extension().is_some_and(|e| e == "json")
+}
+
+/// A program that type checks, with the types of its expressions, which the
+/// compiled backends generate code from.
+struct Checked {
+    program: Program,
+    types: SideTable<ExprId, Type>,
+}
+
+/// Loads a program and the modules it declares, or reads an AST written as
+/// JSON by another tool, and type checks it. Every backend goes through
+/// here, so none of them sees a program with type errors, such as a `match`
+/// that misses a case or has an arm that can never be reached.
+fn load(filename: &str, src: &str, dependencies: &[(String, PathBuf)]) -> Result<Checked, Failure> {
+    let program = if is_json(filename) {
+        let mut sources = cflat::SourceMap::new();
+        sources.add(filename, src);
+        let ast = read_json(filename, src)?;
+        Program { ast, sources }
+    } else {
+        cflat::module::load_with(filename, src.to_string(), dependencies)
+            .map_err(|e| Failure::Errors(e.to_string()))?
     };
+    let types = typeck(&program)?;
+    Ok(Checked { program, types })
+}
+
+/// Type checks a program, reporting errors in the file they are in.
+fn typeck(program: &Program) -> Result<SideTable<ExprId, Type>, Failure> {
+    cflat::typeck::check(&program.ast).map_err(|e| {
+        let e = match e.downcast_ref::<cflat::typeck::TypeError>() {
+            Some(e) => program.error(&e.span, &e.message),
+            None => program.error(&(0..0), e.to_string()),
+        };
+        Failure::Errors(e.to_string())
+    })
+}
 
-    let debug_token = cflat::TokenDebug::from(settings.debug_token);
-    let debug_ast = cflat::ParseDebug::from(settings.debug_ast);
-    let _ast = cflat::parse(&src, debug_token, debug_ast);
+#[cfg(feature = "serde")]
+fn read_json(filename: &str, src: &str) -> Result<cflat::Ast, Failure> {
+    serde_json::from_str(src).map_err(|e| Failure::Errors(format!("{filename}: {e}")))
+}
+
+#[cfg(not(feature = "serde"))]
+fn read_json(filename: &str, _src: &str) -> Result<cflat::Ast, Failure> {
+    let e = format!("{filename}: reading an AST from JSON needs the 'serde' feature");
+    Err(Failure::Errors(e))
+}
+
+/// Runs a program against `host`, returning the code it passed to `exit`,
+/// if it called it. An error is reported in the file it happened in when the
+/// backend knows where that was, and otherwise in the file the program
+/// starts in.
+fn execute(
+    checked: &Checked,
+    backend: Backend,
+    host: &mut dyn Host,
+) -> Result<Option<i32>, String> {
+    let Checked { program, types } = checked;
+    let result = match backend {
+        Backend::Interp => cflat::interp::run_with(&program.ast, host),
+        Backend::Jit => cflat::jit::run_with(&program.ast, types, host),
+    };
+    let Err(e) = result else {
+        return Ok(None);
+    };
+    if let Some(Exit(code)) = e.downcast_ref::<Exit>() {
+        return Ok(Some(*code));
+    }
+    match e.downcast_ref::<cflat::interp::RuntimeError>() {
+        Some(e) => Err(program.error(&e.span, &e.message).to_string()),
+        None => {
+            let (_, entry) = program
+                .sources
+                .files()
+                .next()
+                .expect("a program has a file");
+            Err(format!("{}: {e}", entry.name))
+        }
+    }
+}
+
+fn check(filename: &str, emit: &[Artifact]) -> Result<(), Failure> {
+    let src = read_source(filename)?;
+    let (early, late): (Vec<_>, Vec<_>) = emit.iter().partition(|a| a.kind.is_source());
+    let path = |artifact: &Artifact| {
+        let path = artifact.path(filename, None);
+        emit::check_output(&path, filename).map_err(Failure::Io)?;
+        Ok(path)
+    };
+    for artifact in early {
+        emit_source(artifact, &path(artifact)?, filename, &src)?;
+    }
+    let program = load(filename, &src, &[])?;
+    for artifact in late {
+        let path = path(artifact)?;
+        emit_artifact(artifact, &path, None, 0, filename, &src, &program)?;
+    }
+    Ok(())
+}
+
+fn run(filename: &str, backend: Backend) -> Result<(), Failure> {
+    let src = read_source(filename)?;
+    let program = load(filename, &src, &[])?;
+    match execute(&program, backend, &mut Stdio) {
+        Ok(None) | Ok(Some(0)) => Ok(()),
+        Ok(Some(code)) => Err(Failure::exit(filename, code)),
+        Err(e) => Err(Failure::Runtime(e)),
+    }
+}
+
+/// Builds a file, or without one the project that the current directory is
+/// in, whose artifacts go to its target directory.
+fn build(
+    filename: Option<&str>,
+    target: Option<Target>,
+    output: Option<String>,
+    emit: &[Artifact],
+) -> Result<(), Failure> {
+    let (filename, base, target, opt_level, dependencies) = match filename {
+        Some(filename) => {
+            let target = target.expect("clap requires a target with a file");
+            (
+                filename.to_string(),
+                filename.to_string(),
+                target,
+                0,
+                vec![],
+            )
+        }
+        None => {
+            let project = find_project()?;
+            let package = &project.manifest.package;
+            let dependencies = project
+                .dependencies()
+                .map_err(|e| Failure::Errors(e.to_string()))?;
+            let target_dir = project.target_dir();
+            std::fs::create_dir_all(&target_dir).map_err(|e| {
+                Failure::Io(format!("failed to create '{}': {e}", target_dir.display()))
+            })?;
+            let base = target_dir.join(project.name()).display().to_string();
+            let target = target.unwrap_or(package.target);
+            let entry = project.entry().display().to_string();
+            (entry, base, target, package.opt_level, dependencies)
+        }
+    };
+    let src = read_source(&filename)?;
+    let default = Artifact {
+        kind: match target {
+            Target::C => Emit::Asm,
+            Target::Wasm32 => Emit::Obj,
+        },
+        path: None,
+    };
+    let emit = match emit {
+        [] => std::slice::from_ref(&default),
+        emit => emit,
+    };
+    let path = |artifact: &Artifact| {
+        let path = match (&output, emit.len()) {
+            (Some(output), 1) if artifact.path.is_none() => output.clone(),
+            _ => artifact.path(&base, Some(target)),
+        };
+        emit::check_output(&path, &filename).map_err(Failure::Io)?;
+        Ok(path)
+    };
+    let (early, late): (Vec<_>, Vec<_>) = emit.iter().partition(|a| a.kind.is_source());
+    for artifact in early {
+        emit_source(artifact, &path(artifact)?, &filename, &src)?;
+    }
+    let program = load(&filename, &src, &dependencies)?;
+    for artifact in late {
+        let path = path(artifact)?;
+        emit_artifact(
+            artifact,
+            &path,
+            Some(target),
+            opt_level,
+            &filename,
+            &src,
+            &program,
+        )?;
+    }
+    Ok(())
+}
+
+/// The project that the current directory is in.
+fn find_project() -> Result<Project, Failure> {
+    let dir = std::env::current_dir().map_err(|e| Failure::Io(e.to_string()))?;
+    match Project::find(&dir) {
+        Ok(Some(project)) => Ok(project),
+        Ok(None) => Err(Failure::Errors(format!(
+            "could not find '{MANIFEST}' in '{}' or any parent directory",
+            dir.display()
+        ))),
+        Err(e) => Err(Failure::Errors(e.to_string())),
+    }
+}
+
+/// Creates a project in a new directory named after it.
+fn new(path: &str) -> Result<(), Failure> {
+    let dir = Path::new(path);
+    let name = dir
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    cflat::project::scaffold(dir, &name).map_err(|e| match e.downcast::<std::io::Error>() {
+        Ok(e) => Failure::Io(format!("failed to create '{path}': {e}")),
+        Err(e) => Failure::Errors(e.to_string()),
+    })?;
+    println!("created project '{name}' in '{path}'");
+    Ok(())
+}
+
+/// Writes an artifact that only needs the source, which happens before the
+/// source is parsed.
+fn emit_source(artifact: &Artifact, path: &str, filename: &str, src: &str) -> Result<(), Failure> {
+    if is_json(filename) {
+        let e = format!("'--emit={}' needs C Flat source", artifact.kind);
+        return Err(Failure::Errors(e));
+    }
+    let bytes = match artifact.kind {
+        Emit::Tokens => {
+            let mut out = String::new();
+            for token in cflat::Scanner::new(src) {
+                let span = &token.span;
+                let text = token.text(src);
+                out.push_str(&format!(
+                    "{}..{} {:?} {text:?}\n",
+                    span.start, span.end, token.kind
+                ));
+                if token.is_eof() {
+                    break;
+                }
+            }
+            out.into_bytes()
+        }
+        Emit::Cst => format!("{:#?}", cflat::syntax::parse(src).syntax()).into_bytes(),
+        kind => unreachable!("'{kind}' needs the AST"),
+    };
+    emit::write(path, &bytes).map_err(|e| Failure::Io(e.to_string()))
+}
+
+fn emit_artifact(
+    artifact: &Artifact,
+    path: &str,
+    target: Option<Target>,
+    opt_level: u8,
+    filename: &str,
+    src: &str,
+    checked: &Checked,
+) -> Result<(), Failure> {
+    let (ast, types) = (&checked.program.ast, &checked.types);
+    let errors = |e: Box<dyn std::error::Error>| Failure::Errors(format!("{filename}: {e}"));
+    let bytes = match (artifact.kind, target) {
+        (Emit::Tokens | Emit::Cst, _) => return emit_source(artifact, path, filename, src),
+        (Emit::Ast, _) => format!("{ast:#?}\n").into_bytes(),
+        (Emit::Dot, _) => cflat::dot(ast).into_bytes(),
+        #[cfg(feature = "serde")]
+        (Emit::AstJson, _) => {
+            let mut json = serde_json::to_string_pretty(ast).map_err(|e| errors(e.into()))?;
+            json.push('\n');
+            json.into_bytes()
+        }
+        #[cfg(not(feature = "serde"))]
+        (Emit::AstJson, _) => {
+            let e = "'--emit=ast-json' needs the 'serde' feature";
+            return Err(Failure::Errors(e.into()));
+        }
+        (Emit::Asm | Emit::Obj | Emit::Exe, None) => {
+            let e = format!(
+                "'--emit={}' is only available with 'cbc build'",
+                artifact.kind
+            );
+            return Err(Failure::Errors(e));
+        }
+        (Emit::Asm, Some(Target::C)) => cflat::c::compile(ast, types).map_err(errors)?.into_bytes(),
+        (Emit::Obj | Emit::Exe, Some(Target::C)) => {
+            let code = cflat::c::compile(ast, types).map_err(errors)?;
+            let object = artifact.kind == Emit::Obj;
+            return emit::cc(&code, path, object, opt_level).map_err(errors);
+        }
+        (Emit::Asm, Some(Target::Wasm32)) => cflat::wasm::compile_wat(ast, types)
+            .map_err(errors)?
+            .into_bytes(),
+        (Emit::Obj | Emit::Exe, Some(Target::Wasm32)) => {
+            cflat::wasm::compile(ast, types).map_err(errors)?
+        }
+    };
+    emit::write(path, &bytes).map_err(|e| Failure::Io(e.to_string()))
+}
+
+/// Formats each file in place, or stdin to stdout when no files are given.
+/// With `check` nothing is written and unformatted files are reported.
+fn fmt(filenames: &[String], check: bool) -> Result<(), Failure> {
+    if filenames.is_empty() {
+        let mut src = String::new();
+        std::io::Read::read_to_string(&mut std::io::stdin(), &mut src)
+            .map_err(|e| Failure::Io(format!("failed to read stdin: {e}")))?;
+        let formatted =
+            cflat::fmt::format(&src).map_err(|e| Failure::Errors(format!("<stdin>: {e}")))?;
+        if check {
+            return match formatted == src {
+                true => Ok(()),
+                false => {
+                    println!("<stdin> is not formatted");
+                    Err(Failure::Failed)
+                }
+            };
+        }
+        print!("{formatted}");
+        return Ok(());
+    }
+    let mut unformatted = 0;
+    for filename in filenames {
+        let src = read_source(filename)?;
+        let formatted =
+            cflat::fmt::format(&src).map_err(|e| Failure::Errors(format!("{filename}: {e}")))?;
+        if formatted == src {
+            continue;
+        }
+        if check {
+            println!("{filename} is not formatted");
+            unformatted += 1;
+            continue;
+        }
+        std::fs::write(filename, formatted)
+            .map_err(|e| Failure::Io(format!("failed to write '{filename}': {e}")))?;
+    }
+    match unformatted {
+        0 => Ok(()),
+        _ => Err(Failure::Failed),
+    }
+}
+
+/// Runs each line after the lines before it, which stay part of the
+/// program: their variables stay in scope, and each line is type checked
+/// along with them. A line that fails to parse, type check or run is left
+/// out, and its errors are located in the line.
+fn repl() -> Result<(), Failure> {
+    let stdin = std::io::stdin();
+    let mut stdout = std::io::stdout();
+    let mut session = cflat::interp::Session::new();
+    let mut history = String::new();
+    loop {
+        print!(">> ");
+        stdout.flush().map_err(|e| Failure::Io(e.to_string()))?;
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) => return Ok(()),
+            Ok(_) => {}
+            Err(e) => return Err(Failure::Io(e.to_string())),
+        }
+        let src = format!("{history}{line}");
+        let result = cflat::parse(&src).and_then(|ast| {
+            cflat::typeck::check(&ast)?;
+            session.run(&ast, &mut Stdio)
+        });
+        match result {
+            Ok(()) => history = src,
+            Err(e) => match e.downcast_ref::<Exit>() {
+                Some(Exit(0)) => return Ok(()),
+                Some(Exit(code)) => return Err(Failure::exit("<stdin>", *code)),
+                None => eprintln!("{}", in_line(&*e, history.len())),
+            },
+        }
+    }
+}
+
+/// An error of the REPL, with its span counted from the start of the line
+/// that was entered rather than of the program so far, which starts
+/// `offset` bytes earlier.
+fn in_line(e: &(dyn std::error::Error + 'static), offset: usize) -> String {
+    let (span, message) = if let Some(e) = e.downcast_ref::<cflat::SyntaxError>() {
+        (e.span.clone(), e.message.clone())
+    } else if let Some(e) = e.downcast_ref::<cflat::typeck::TypeError>() {
+        (e.span.clone(), e.message.clone())
+    } else if let Some(e) = e.downcast_ref::<cflat::interp::RuntimeError>() {
+        (e.span.clone(), e.message.clone())
+    } else {
+        return e.to_string();
+    };
+    let (start, end) = (
+        span.start.saturating_sub(offset),
+        span.end.saturating_sub(offset),
+    );
+    format!("{start}:{end} {message}")
+}
+
+fn test(paths: &[String], backend: Backend) -> Result<(), Failure> {
+    let mut files = vec![];
+    for path in paths {
+        collect_sources(Path::new(path), &mut files)
+            .map_err(|e| Failure::Io(format!("failed to read '{path}': {e}")))?;
+    }
+    let (mut passed, mut failed) = (0, 0);
+    for file in &files {
+        match test_file(file, backend) {
+            Ok(()) => {
+                println!("test {} ... ok", file.display());
+                passed += 1;
+            }
+            Err(e) => {
+                println!("test {} ... FAILED\n    {e}", file.display());
+                failed += 1;
+            }
+        }
+    }
+    let result = if failed == 0 { "ok" } else { "FAILED" };
+    println!("\ntest result: {result}. {passed} passed; {failed} failed");
+    match failed {
+        0 => Ok(()),
+        _ => Err(Failure::Failed),
+    }
+}
+
+fn collect_sources(path: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+    let mut entries = std::fs::read_dir(path)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() || entry.extension().is_some_and(|e| e == "cb") {
+            collect_sources(&entry, files)?;
+        }
+    }
+    Ok(())
+}
+
+/// A file passes when it runs without errors, or exits with code 0, and, if
+/// there is a `.out` file next to it, prints exactly what is there. The
+/// program reads no input.
+fn test_file(file: &Path, backend: Backend) -> Result<(), String> {
+    let src = std::fs::read_to_string(file).map_err(|e| e.to_string())?;
+    let program = load(&file.to_string_lossy(), &src, &[]).map_err(|e| match e {
+        Failure::Errors(e) => e,
+        _ => unreachable!("loading source only fails with errors"),
+    })?;
+    let mut host = Capture::default();
+    match execute(&program, backend, &mut host)? {
+        None | Some(0) => {}
+        Some(code) => return Err(format!("exited with code {code}")),
+    }
+    let Ok(expected) = std::fs::read_to_string(file.with_extension("out")) else {
+        return Ok(());
+    };
+    let output = host.output;
+    if output.trim_end() != expected.trim_end() {
+        return Err(format!(
+            "expected:\n{}\n    found:\n{}",
+            expected.trim_end(),
+            output.trim_end()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(src: &str) -> String {
+        match load("a.cb", src, &[]) {
+            Err(Failure::Errors(e)) => e,
+            Ok(_) => panic!("'{src}' loaded"),
+            Err(_) => panic!("'{src}' failed without errors"),
+        }
+    }
+
+    #[test]
+    fn load_checks_matches() {
+        let decl = "enum S { A, B(i32) } ";
+        let missing = format!("{decl}match S::A {{ S::A => println(1) }}");
+        assert_eq!(
+            errors(&missing),
+            "a.cb: 21:54 pattern 'S::B(_)' is not covered"
+        );
+        let unreachable = format!("{decl}match S::A {{ _ => 1, S::A => 2 }}");
+        assert_eq!(errors(&unreachable), "a.cb: 42:46 unreachable pattern");
+        assert_eq!(
+            errors("-\"a\""),
+            "a.cb: 1:4 expected 'i32' but found 'String'"
+        );
+    }
+
+    /// Programs are type checked before they run, so a name that is never
+    /// bound is an error even in code that never runs. This program ran
+    /// before there was a type checker.
+    #[test]
+    fn unbound_names_are_errors_where_they_never_run() {
+        assert_eq!(
+            errors("if (1 > 3) {\n    a + b\n}\n"),
+            "a.cb: 17:18 unbound identifier 'a'"
+        );
+    }
+
+    #[test]
+    fn runtime_errors_name_their_file() {
+        let dir = std::env::temp_dir().join(format!("cbc-runtime-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let main = "mod m;\nuse m::f;\nf(0)\n";
+        std::fs::write(
+            dir.join("m.cb"),
+            "pub let f = fn(x: i32) -> i32 {\n    10 / x\n}\n",
+        )
+        .unwrap();
+        let filename = dir.join("main.cb").display().to_string();
+        let program = load(&filename, main, &[]).ok().unwrap();
+        let m = dir.join("m.cb").display().to_string();
+        let e = execute(&program, Backend::Interp, &mut Capture::default()).unwrap_err();
+        assert_eq!(e, format!("{m}: 36:42 attempt to divide by zero"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn artifacts_do_not_overwrite_their_input() {
+        let exe = Artifact::parse("exe").unwrap();
+        assert_eq!(exe.path("prog.cb", Some(Target::C)), "prog");
+        assert_eq!(exe.path("prog", Some(Target::C)), "prog.out");
+        let json = Artifact::parse("ast-json").unwrap();
+        assert_eq!(json.path("a.json", None), "a.json");
+
+        let dir = std::env::temp_dir().join(format!("cbc-emit-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let filename = dir.join("a.cb").display().to_string();
+        std::fs::write(&filename, "1\n").unwrap();
+        let tokens = Artifact::parse(&format!("tokens={filename}")).unwrap();
+        let Err(Failure::Io(e)) = check(&filename, &[tokens]) else {
+            panic!("'{filename}' was overwritten");
+        };
+        assert_eq!(
+            e,
+            format!("refusing to overwrite the input '{filename}' with an artifact")
+        );
+        assert_eq!(std::fs::read_to_string(&filename).unwrap(), "1\n");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn repl_errors_are_located_in_their_line() {
+        let history = "let x = 1\n";
+        let e = cflat::parse(&format!("{history}x +")).unwrap_err();
+        assert_eq!(
+            in_line(&*e, history.len()),
+            "3:3 expected an expression but found end of input"
+        );
+        let ast = cflat::parse(&format!("{history}x + \"a\"")).unwrap();
+        let e = cflat::typeck::check(&ast).unwrap_err();
+        assert_eq!(
+            in_line(&*e, history.len()),
+            "4:7 expected 'i32' but found 'String'"
+        );
+    }
+
+    #[test]
+    fn exit_codes() {
+        assert_eq!(Failure::exit("a.cb", 5).code(), 5);
+        assert_eq!(Failure::exit("a.cb", 255).code(), 255);
+        for code in [1, 2, 3, 4, 256, -1, i32::MIN] {
+            let failure = Failure::exit("a.cb", code);
+            assert!(matches!(failure, Failure::Runtime(_)), "{code}");
+            assert_eq!(failure.code(), 3);
+        }
+    }
 }